@@ -22,6 +22,24 @@ pub enum AppError {
 
     #[error("The build did not pass after the maximum number of attempts.")]
     MaxAttemptsReached,
+
+    #[error("Spending budget exceeded, stopping before the next LLM call: {0}")]
+    BudgetExceeded(String),
+
+    #[error("Prompt too large for the model's context window: {0}")]
+    PromptTooLarge(String),
+
+    #[error("No recorded LLM response to replay: {0}")]
+    ReplayMiss(String),
+
+    #[error("LLM call deadline exceeded: {0}")]
+    DeadlineExceeded(String),
+
+    #[error("Interrupted by Ctrl-C: {0}")]
+    Interrupted(String),
+
+    #[error("Tool call limit reached: {0}")]
+    ToolCallLimit(String),
 }
 
 #[derive(Error, Debug)]
@@ -1,9 +1,9 @@
 use crate::app_error::AppError;
 use crate::auto_workflow::graph;
 use crate::auto_workflow::types::{Stage, Task};
-use ignore::WalkBuilder;
+use crate::specifications::find_all_user_specifications;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
 pub fn find_next_task(root: &Path) -> Result<Option<Task>, AppError> {
     let specs = find_all_user_specifications(root)?;
@@ -33,24 +33,6 @@ pub fn find_next_task(root: &Path) -> Result<Option<Task>, AppError> {
     Ok(Some(Task { spec_path, stage }))
 }
 
-pub(crate) fn find_all_user_specifications(root: &Path) -> Result<Vec<PathBuf>, AppError> {
-    let mut specs = Vec::new();
-    let walker = WalkBuilder::new(root)
-        .hidden(false)
-        .git_ignore(true)
-        .build();
-
-    for result in walker {
-        let entry = result.map_err(|e| AppError::Config(format!("Failed to walk dir: {e}")))?;
-        if entry.file_type().map(|ft| ft.is_file()).unwrap_or(false)
-            && entry.file_name() == "UserSpecification.md"
-        {
-            specs.push(entry.path().to_path_buf());
-        }
-    }
-    Ok(specs)
-}
-
 fn get_next_stage(root: &Path, spec_path: &Path) -> Result<Option<Stage>, AppError> {
     let module_dir = spec_path.parent().unwrap_or(root);
     let relative_module_dir = module_dir.strip_prefix(root).unwrap_or(module_dir);
@@ -5,8 +5,13 @@ use crate::auto_workflow::types::{Stage, Task};
 use crate::committing_code;
 use crate::config::Config;
 use crate::llm;
+use crate::llm::generation::CallRole;
+use crate::llm::schema::{self, ResponseSchema};
+use crate::llm::tokens::{estimate_tokens, input_token_limit};
+use crate::llm::Conversation;
 use crate::logger::Logger;
 use crate::system_prompts;
+use serde_json::json;
 use std::fs;
 use std::path::Path;
 
@@ -49,20 +54,41 @@ pub async fn execute_task(
     );
 
     let response = if task.stage == Stage::SelfConsistent {
-        llm::query(
+        // This stage never edits files, so its status and comment can be
+        // requested as JSON. Replies that ignore the schema still go through
+        // the sentinel checks below unchanged.
+        let limit = input_token_limit(config.model);
+        let user_limit = limit.saturating_sub(estimate_tokens(&prompt.system));
+        let fitted = committing_code::fit_prompt(&prompt.user, user_limit)?;
+        committing_code::report_trimmed(logger, &log_name, limit, &fitted.trimmed)?;
+        let mut conversation = Conversation::with_system(&prompt.system, fitted.prompt);
+        conversation.set_response_schema(status_schema());
+        conversation.set_call_role(CallRole::SelfConsistent);
+        let reply = llm::converse(
             config.model,
             config.api_key.clone(),
-            &prompt,
+            &config.session,
+            &mut conversation,
             logger,
             &log_name,
         )
-        .await?
+        .await?;
+        structured_status_to_text(&reply).unwrap_or(reply)
     } else {
         let task_config = Config {
             model: config.model,
             api_key: config.api_key.clone(),
-            query: prompt,
-            system_prompts: system_prompts::COMMITTING_CODE_INITIAL_QUERY.to_string(),
+            query: prompt.user,
+            system_prompts: format!(
+                "{}\n{}",
+                system_prompts::COMMITTING_CODE_INITIAL_QUERY,
+                prompt.system
+            ),
+            session: config.session.clone(),
+            context_mode: config.context_mode.clone(),
+            context_budget: config.context_budget,
+            git_history: config.git_history.clone(),
+            attachments: config.attachments.clone(),
         };
 
         committing_code::run_with_codebase(logger, &task_config, String::new()).await?
@@ -97,6 +123,50 @@ pub async fn execute_task(
     }
 }
 
+const STATUSES: [&str; 3] = ["task-success", "changes-requested", "changes-attempted"];
+
+/// `{"status": ..., "comment": ...}`, the JSON form of a stage reply.
+pub(crate) fn status_schema() -> ResponseSchema {
+    ResponseSchema {
+        name: "stage_status",
+        schema: json!({
+            "type": "object",
+            "properties": {
+                "status": {
+                    "type": "string",
+                    "enum": STATUSES,
+                    "description": "The status string, without the @@@@ markers."
+                },
+                "comment": {
+                    "type": "string",
+                    "description": "The comment for the supervisor, without the %%%% tags. Empty for no comment."
+                }
+            },
+            "required": ["status", "comment"],
+            "additionalProperties": false
+        }),
+    }
+}
+
+/// Rewrites a JSON stage reply into the sentinel form that the rest of the
+/// workflow reads. Returns `None` when the reply does not follow the schema.
+pub(crate) fn structured_status_to_text(reply: &str) -> Option<String> {
+    let value = schema::parse_json_reply(reply)?;
+    let status = value.get("status")?.as_str()?;
+    if !STATUSES.contains(&status) {
+        return None;
+    }
+    let mut text = format!("@@@@{status}@@@@\n");
+    let comment = value.get("comment").and_then(|c| c.as_str()).unwrap_or("");
+    if !comment.trim().is_empty() {
+        text.push_str(&format!(
+            "%%%%comment%%%%\n{}\n%%%%end%%%%\n",
+            comment.trim()
+        ));
+    }
+    Some(text)
+}
+
 pub(crate) fn validate_response_format(response: &str) -> Result<(), AppError> {
     let success = response.contains("@@@@task-success@@@@");
     let requested = response.contains("@@@@changes-requested@@@@");
@@ -66,3 +66,33 @@ fn test_mark_stage_complete() {
     assert!(state_path.exists());
     assert_eq!(fs::read_to_string(state_path).unwrap(), content);
 }
+
+#[test]
+fn test_structured_status_is_rewritten_to_sentinels() {
+    let text = executor::structured_status_to_text(
+        r#"{"status": "changes-requested", "comment": " Section 2 contradicts section 3. "}"#,
+    )
+    .unwrap();
+    assert!(executor::validate_response_format(&text).is_ok());
+    assert!(text.contains("@@@@changes-requested@@@@"));
+    assert_eq!(
+        executor::extract_comment(&text).map(str::trim),
+        Some("Section 2 contradicts section 3.")
+    );
+
+    let text = executor::structured_status_to_text(r#"{"status": "task-success", "comment": ""}"#)
+        .unwrap();
+    assert_eq!(text, "@@@@task-success@@@@\n");
+}
+
+#[test]
+fn test_unstructured_status_is_left_to_sentinel_parsing() {
+    assert_eq!(
+        executor::structured_status_to_text("@@@@task-success@@@@"),
+        None
+    );
+    assert_eq!(
+        executor::structured_status_to_text(r#"{"status": "done", "comment": ""}"#),
+        None
+    );
+}
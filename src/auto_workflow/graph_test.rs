@@ -79,3 +79,11 @@ fn test_cycle_detection() {
     let err = result.err().unwrap().to_string();
     assert!(err.contains("Dependency cycle"));
 }
+
+#[test]
+fn test_this_project_has_no_dependency_cycle() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let specs = crate::specifications::find_all_user_specifications(root).unwrap();
+    let nodes = graph::build_dependency_graph(root, &specs).unwrap();
+    assert!(nodes.len() > 1);
+}
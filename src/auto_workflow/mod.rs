@@ -42,7 +42,7 @@ pub async fn run(logger: &Logger, cli_args: CliArgs) -> Result<(), AppError> {
             }
         };
 
-        let result = executor::execute_task(root, &task, &config, logger).await?;
+        let result = execute_within_budget(root, &task, &config, logger).await?;
 
         match result {
             executor::ExecutionResult::Success => {
@@ -51,7 +51,7 @@ pub async fn run(logger: &Logger, cli_args: CliArgs) -> Result<(), AppError> {
             }
             executor::ExecutionResult::ChangesAttempted => {
                 println!("Changes attempted. Retrying task...");
-                let retry_result = executor::execute_task(root, &task, &config, logger).await?;
+                let retry_result = execute_within_budget(root, &task, &config, logger).await?;
                 match retry_result {
                     executor::ExecutionResult::Success => {
                         println!("Retry successful. Continuing workflow.");
@@ -78,3 +78,21 @@ pub async fn run(logger: &Logger, cli_args: CliArgs) -> Result<(), AppError> {
 
     Ok(())
 }
+
+async fn execute_within_budget(
+    root: &Path,
+    task: &types::Task,
+    config: &Config,
+    logger: &Logger,
+) -> Result<executor::ExecutionResult, AppError> {
+    match executor::execute_task(root, task, config, logger).await {
+        Err(e @ AppError::BudgetExceeded(_)) => {
+            println!(
+                "Spending budget reached after ${:.4} this run. Stopping Auto Workflow; completed stages are recorded in agent-state/ and logs are in agent-config/logs/.",
+                config.session.budget.total_cost()
+            );
+            Err(e)
+        }
+        other => other,
+    }
+}
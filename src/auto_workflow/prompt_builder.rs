@@ -8,12 +8,19 @@ use ignore::WalkBuilder;
 use std::fs;
 use std::path::Path;
 
+/// A stage prompt split into the instructions, which are sent as the system
+/// prompt, and the specifications and code the instructions apply to.
+pub struct StagePrompt {
+    pub system: String,
+    pub user: String,
+}
+
 pub fn build_prompt(
     root: &Path,
     spec_path: &Path,
     stage: Stage,
     spec_content: &str,
-) -> Result<String, AppError> {
+) -> Result<StagePrompt, AppError> {
     match stage {
         Stage::SelfConsistent => build_self_consistent_prompt(root, spec_path, spec_content),
         Stage::Implemented => build_implemented_prompt(root, spec_path, spec_content),
@@ -22,11 +29,17 @@ pub fn build_prompt(
     }
 }
 
+fn stage_system_prompt(label: &str, prompt_name: &str, prompt: &str) -> String {
+    format!(
+        "{label}\n[response format instructions]\n{RESPONSE_FORMAT_INSTRUCTIONS}\n[{prompt_name}]\n{prompt}"
+    )
+}
+
 fn build_self_consistent_prompt(
     root: &Path,
     spec_path: &Path,
     spec_content: &str,
-) -> Result<String, AppError> {
+) -> Result<StagePrompt, AppError> {
     let top_spec = get_top_level_spec(root)?;
     let target_spec_section = if is_top_level_spec(root, spec_path) {
         String::new()
@@ -34,45 +47,48 @@ fn build_self_consistent_prompt(
         format!("[target user specification]\n{spec_content}\n")
     };
 
-    Ok(format!(
-        "{}\n[response format instructions]\n{}\n[self consistent prompt]\n{}\n[top level UserSpecification.md]\n{}\n{}",
-        "1. self-consistent",
-        RESPONSE_FORMAT_INSTRUCTIONS,
-        SELF_CONSISTENT,
-        top_spec,
-        target_spec_section
-    ))
+    Ok(StagePrompt {
+        system: stage_system_prompt(
+            "1. self-consistent",
+            "self consistent prompt",
+            SELF_CONSISTENT,
+        ),
+        user: format!("[top level UserSpecification.md]\n{top_spec}\n{target_spec_section}"),
+    })
 }
 
 fn build_implemented_prompt(
     root: &Path,
     spec_path: &Path,
     spec_content: &str,
-) -> Result<String, AppError> {
+) -> Result<StagePrompt, AppError> {
     let cached_content = get_cached_spec(root, spec_path, Stage::Implemented)?;
     let codebase = build_codebase_context(root, spec_path.parent().unwrap_or(root))?;
 
     let target_spec_section = format!("[target user specification]\n{spec_content}\n");
 
     if let Some(cached) = cached_content {
-        Ok(format!(
-            "{}\n[response format instructions]\n{}\n[implementation-with-cache prompt]\n{}\n[cached target user specification]\n{}\n{}[codebase, including dependency files and top level UserSpecification]\n{}",
-            "2. implemented - cached UserSpecification",
-            RESPONSE_FORMAT_INSTRUCTIONS,
-            IMPLEMENTED_WITH_CACHE,
-            cached,
-            target_spec_section,
-            codebase
-        ))
+        Ok(StagePrompt {
+            system: stage_system_prompt(
+                "2. implemented - cached UserSpecification",
+                "implementation-with-cache prompt",
+                IMPLEMENTED_WITH_CACHE,
+            ),
+            user: format!(
+                "[cached target user specification]\n{cached}\n{target_spec_section}[codebase, including dependency files and top level UserSpecification]\n{codebase}"
+            ),
+        })
     } else {
-        Ok(format!(
-            "{}\n[response format instructions]\n{}\n[implementation-no-cache prompt]\n{}\n{}[codebase, including dependency files and top level UserSpecification]\n{}",
-            "2. implemented - no cached UserSpecification",
-            RESPONSE_FORMAT_INSTRUCTIONS,
-            IMPLEMENTED_NO_CACHE,
-            target_spec_section,
-            codebase
-        ))
+        Ok(StagePrompt {
+            system: stage_system_prompt(
+                "2. implemented - no cached UserSpecification",
+                "implementation-no-cache prompt",
+                IMPLEMENTED_NO_CACHE,
+            ),
+            user: format!(
+                "{target_spec_section}[codebase, including dependency files and top level UserSpecification]\n{codebase}"
+            ),
+        })
     }
 }
 
@@ -80,48 +96,47 @@ fn build_documented_prompt(
     root: &Path,
     spec_path: &Path,
     spec_content: &str,
-) -> Result<String, AppError> {
+) -> Result<StagePrompt, AppError> {
     // For documentation, we look at the module itself
     let module_dir = spec_path.parent().unwrap_or(Path::new("."));
     let codebase = build_module_only_context(root, module_dir)?;
 
-    Ok(format!(
-        "{}\n[response format instructions]\n{}\n[documented prompt]\n{}\n[target user specification]\n{}\n[codebase]\n{}",
-        "3. documented",
-        RESPONSE_FORMAT_INSTRUCTIONS,
-        DOCUMENTED,
-        spec_content,
-        codebase
-    ))
+    Ok(StagePrompt {
+        system: stage_system_prompt("3. documented", "documented prompt", DOCUMENTED),
+        user: format!("[target user specification]\n{spec_content}\n[codebase]\n{codebase}"),
+    })
 }
 
 fn build_happy_path_tested_prompt(
     root: &Path,
     spec_path: &Path,
     spec_content: &str,
-) -> Result<String, AppError> {
+) -> Result<StagePrompt, AppError> {
     let cached_content = get_cached_spec(root, spec_path, Stage::HappyPathTested)?;
     let codebase = build_codebase_context(root, spec_path.parent().unwrap_or(root))?;
 
     if let Some(cached) = cached_content {
-        Ok(format!(
-            "{}\n[response format instructions]\n{}\n[happy-path-tested prompt]\n{}\n[cached target user specification]\n{}\n[target user specification]\n{}\n[codebase, including dependency files and top level UserSpecification]\n{}",
-            "4. happy-path-tested - cached UserSpecification",
-            RESPONSE_FORMAT_INSTRUCTIONS,
-            HAPPY_PATH_TESTED_WITH_CACHE,
-            cached,
-            spec_content,
-            codebase
-        ))
+        Ok(StagePrompt {
+            system: stage_system_prompt(
+                "4. happy-path-tested - cached UserSpecification",
+                "happy-path-tested prompt",
+                HAPPY_PATH_TESTED_WITH_CACHE,
+            ),
+            user: format!(
+                "[cached target user specification]\n{cached}\n[target user specification]\n{spec_content}\n[codebase, including dependency files and top level UserSpecification]\n{codebase}"
+            ),
+        })
     } else {
-        Ok(format!(
-            "{}\n[response format instructions]\n{}\n[happy-path-tested prompt]\n{}\n[target user specification]\n{}\n[codebase, including dependency files and top level UserSpecification]\n{}",
-            "4. happy-path-tested - no cached UserSpecification",
-            RESPONSE_FORMAT_INSTRUCTIONS,
-            HAPPY_PATH_TESTED_NO_CACHE,
-            spec_content,
-            codebase
-        ))
+        Ok(StagePrompt {
+            system: stage_system_prompt(
+                "4. happy-path-tested - no cached UserSpecification",
+                "happy-path-tested prompt",
+                HAPPY_PATH_TESTED_NO_CACHE,
+            ),
+            user: format!(
+                "[target user specification]\n{spec_content}\n[codebase, including dependency files and top level UserSpecification]\n{codebase}"
+            ),
+        })
     }
 }
 
@@ -22,10 +22,10 @@ fn test_build_prompt_includes_files() {
     let prompt =
         prompt_builder::build_prompt(root, &spec_path, Stage::Implemented, "MODULE_SPEC").unwrap();
 
-    assert!(prompt.contains("TOP_LEVEL_SPEC"));
-    assert!(prompt.contains("CARGO_TOML"));
-    assert!(prompt.contains("MODULE_SPEC"));
-    assert!(prompt.contains("fn test() {}"));
+    assert!(prompt.user.contains("TOP_LEVEL_SPEC"));
+    assert!(prompt.user.contains("CARGO_TOML"));
+    assert!(prompt.user.contains("MODULE_SPEC"));
+    assert!(prompt.user.contains("fn test() {}"));
 }
 
 #[test]
@@ -53,8 +53,8 @@ fn test_build_prompt_includes_dependencies() {
     let prompt =
         prompt_builder::build_prompt(root, &spec_path, Stage::Implemented, "MAIN_SPEC").unwrap();
 
-    assert!(prompt.contains("DEP_SPEC"));
-    assert!(prompt.contains("DEP_SIG"));
+    assert!(prompt.user.contains("DEP_SPEC"));
+    assert!(prompt.user.contains("DEP_SIG"));
 }
 
 #[test]
@@ -72,9 +72,9 @@ fn test_build_prompt_documented_stage() {
         prompt_builder::build_prompt(root, &spec_path, Stage::Documented, "DOC_SPEC").unwrap();
 
     // Documented stage should focus on module internals
-    assert!(prompt.contains("3. documented"));
-    assert!(prompt.contains("DOC_SPEC"));
-    assert!(prompt.contains("struct Internal;"));
+    assert!(prompt.system.contains("3. documented"));
+    assert!(prompt.user.contains("DOC_SPEC"));
+    assert!(prompt.user.contains("struct Internal;"));
 }
 
 #[test]
@@ -100,10 +100,10 @@ fn test_build_prompt_with_cache() {
     let prompt =
         prompt_builder::build_prompt(root, &spec_path, Stage::Implemented, spec_content).unwrap();
 
-    assert!(prompt.contains("implementation-with-cache prompt"));
-    assert!(prompt.contains("cached target user specification"));
-    assert!(prompt.contains(cached_content));
-    assert!(prompt.contains(spec_content));
+    assert!(prompt.system.contains("implementation-with-cache prompt"));
+    assert!(prompt.user.contains("cached target user specification"));
+    assert!(prompt.user.contains(cached_content));
+    assert!(prompt.user.contains(spec_content));
 }
 
 #[test]
@@ -132,13 +132,13 @@ fn test_build_prompt_root_module() {
             .unwrap();
 
     // Should include root files
-    assert!(prompt.contains("TOP_LEVEL_SPEC"));
-    assert!(prompt.contains("CARGO_TOML"));
-    assert!(prompt.contains("BUILD_SCRIPT"));
+    assert!(prompt.user.contains("TOP_LEVEL_SPEC"));
+    assert!(prompt.user.contains("CARGO_TOML"));
+    assert!(prompt.user.contains("BUILD_SCRIPT"));
 
     // Should include src/main.rs
-    assert!(prompt.contains("fn main() {}"));
+    assert!(prompt.user.contains("fn main() {}"));
 
     // Should NOT include submodule files (unless deps, but here no deps)
-    assert!(!prompt.contains("fn sub() {}"));
+    assert!(!prompt.user.contains("fn sub() {}"));
 }
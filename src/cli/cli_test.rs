@@ -1,4 +1,5 @@
-use crate::cli::{parse_args, CliArgs, Model, Workflow};
+use crate::cli::{parse_args, CliArgs, ContextMode, DiffSpec, Model, QuerySource, Workflow};
+use std::path::PathBuf;
 
 fn to_string_vec(args: &[&str]) -> Vec<String> {
     args.iter().map(|s| s.to_string()).collect()
@@ -11,10 +12,11 @@ fn test_no_args() {
     assert_eq!(
         result,
         CliArgs {
-            model: Model::Gemini3Pro,
+            model: Model::from_str("gemini-3-pro-preview").unwrap(),
             workflow: Workflow::CommitCode,
             force: false,
             rollup_full: false,
+            ..Default::default()
         }
     );
 }
@@ -33,10 +35,11 @@ fn test_model_arg() {
     assert_eq!(
         result_gpt5,
         CliArgs {
-            model: Model::Gpt5,
+            model: Model::from_str("gpt-5.2").unwrap(),
             workflow: Workflow::CommitCode,
             force: false,
             rollup_full: false,
+            ..Default::default()
         }
     );
 
@@ -45,10 +48,11 @@ fn test_model_arg() {
     assert_eq!(
         result_gpt5_2,
         CliArgs {
-            model: Model::Gpt5,
+            model: Model::from_str("gpt-5.2").unwrap(),
             workflow: Workflow::CommitCode,
             force: false,
             rollup_full: false,
+            ..Default::default()
         }
     );
 
@@ -57,10 +61,11 @@ fn test_model_arg() {
     assert_eq!(
         result_gemini25,
         CliArgs {
-            model: Model::Gemini2_5Pro,
+            model: Model::from_str("gemini-2.5-pro").unwrap(),
             workflow: Workflow::CommitCode,
             force: false,
             rollup_full: false,
+            ..Default::default()
         }
     );
 
@@ -69,10 +74,11 @@ fn test_model_arg() {
     assert_eq!(
         result_gemini3,
         CliArgs {
-            model: Model::Gemini3Pro,
+            model: Model::from_str("gemini-3-pro-preview").unwrap(),
             workflow: Workflow::CommitCode,
             force: false,
             rollup_full: false,
+            ..Default::default()
         }
     );
 }
@@ -192,10 +198,11 @@ fn test_model_and_force() {
     assert_eq!(
         result,
         CliArgs {
-            model: Model::Gpt5,
+            model: Model::from_str("gpt-5.2").unwrap(),
             workflow: Workflow::CommitCode,
             force: true,
             rollup_full: false,
+            ..Default::default()
         }
     );
 }
@@ -263,3 +270,345 @@ fn test_init_with_force_is_error() {
     let result = parse_args(args.into_iter());
     assert!(result.is_err());
 }
+
+#[test]
+fn test_budget_flags() {
+    let args = to_string_vec(&["--aw", "--max-cost", "2.5", "--max-tokens", "500000"]);
+    let result = parse_args(args.into_iter()).unwrap();
+    assert_eq!(result.workflow, Workflow::Auto);
+    assert_eq!(result.max_cost, Some(2.5));
+    assert_eq!(result.max_tokens, Some(500_000));
+
+    let args = to_string_vec(&["--max-cost", "-1"]);
+    assert!(parse_args(args.into_iter()).is_err());
+
+    let args = to_string_vec(&["--max-tokens", "lots"]);
+    assert!(parse_args(args.into_iter()).is_err());
+
+    let args = to_string_vec(&["--max-tokens"]);
+    assert!(parse_args(args.into_iter()).is_err());
+
+    let args = to_string_vec(&["--rollup", "--max-cost", "1"]);
+    assert!(parse_args(args.into_iter()).is_err());
+}
+
+#[test]
+fn test_stream_flags() {
+    let args = to_string_vec(&["--commit", "--stream"]);
+    assert_eq!(parse_args(args.into_iter()).unwrap().stream, Some(true));
+
+    let args = to_string_vec(&["--aw", "--no-stream"]);
+    assert_eq!(parse_args(args.into_iter()).unwrap().stream, Some(false));
+
+    let args = to_string_vec(&["--cc"]);
+    assert_eq!(parse_args(args.into_iter()).unwrap().stream, None);
+
+    let args = to_string_vec(&["--rollup", "--stream"]);
+    assert!(parse_args(args.into_iter()).is_err());
+}
+
+#[test]
+fn test_record_and_replay_flags() {
+    let args = to_string_vec(&["--commit", "--record", "cassettes/bug-42"]);
+    let parsed = parse_args(args.into_iter()).unwrap();
+    assert_eq!(parsed.record, Some(PathBuf::from("cassettes/bug-42")));
+    assert_eq!(parsed.replay, None);
+
+    let args = to_string_vec(&["--cc", "--replay", "agent-config/logs/run"]);
+    let parsed = parse_args(args.into_iter()).unwrap();
+    assert_eq!(parsed.replay, Some(PathBuf::from("agent-config/logs/run")));
+
+    let args = to_string_vec(&["--commit", "--record", "a", "--replay", "b"]);
+    assert!(parse_args(args.into_iter()).is_err());
+
+    let args = to_string_vec(&["--commit", "--replay"]);
+    assert!(parse_args(args.into_iter()).is_err());
+
+    let args = to_string_vec(&["--rollup", "--replay", "b"]);
+    assert!(parse_args(args.into_iter()).is_err());
+}
+
+#[test]
+fn test_call_timeout_flag() {
+    let args = to_string_vec(&["--commit", "--call-timeout", "600"]);
+    assert_eq!(
+        parse_args(args.into_iter()).unwrap().call_timeout,
+        Some(600)
+    );
+
+    for bad in ["0", "-5", "ten"] {
+        let args = to_string_vec(&["--commit", "--call-timeout", bad]);
+        assert!(parse_args(args.into_iter()).is_err());
+    }
+
+    let args = to_string_vec(&["--rollup", "--call-timeout", "60"]);
+    assert!(parse_args(args.into_iter()).is_err());
+}
+
+#[test]
+fn test_tools_flag() {
+    let args = to_string_vec(&["--commit", "--tools"]);
+    assert!(parse_args(args.into_iter()).unwrap().tools);
+    let args = to_string_vec(&["--commit"]);
+    assert!(!parse_args(args.into_iter()).unwrap().tools);
+
+    for workflow in ["--cc", "--aw", "--rollup"] {
+        let args = to_string_vec(&[workflow, "--tools"]);
+        assert!(parse_args(args.into_iter()).is_err());
+    }
+}
+
+#[test]
+fn test_models_command() {
+    let args = to_string_vec(&["models"]);
+    let result = parse_args(args.into_iter()).unwrap();
+    assert_eq!(result.workflow, Workflow::Models);
+
+    let args = to_string_vec(&["models", "--cc"]);
+    assert!(parse_args(args.into_iter()).is_err());
+
+    let args = to_string_vec(&["models", "--stream"]);
+    assert!(parse_args(args.into_iter()).is_err());
+}
+
+#[test]
+fn test_context_flag() {
+    let args = to_string_vec(&["--context", "modules:llm,config"]);
+    let result = parse_args(args.into_iter()).unwrap();
+    assert_eq!(
+        result.context,
+        Some(ContextMode::Modules(vec![
+            "llm".to_string(),
+            "config".to_string()
+        ]))
+    );
+
+    let args = to_string_vec(&["--cc", "--context", "auto"]);
+    let result = parse_args(args.into_iter()).unwrap();
+    assert_eq!(result.context, Some(ContextMode::Auto));
+
+    for bad in [
+        &["--context"][..],
+        &["--context", "some"],
+        &["--aw", "--context", "all"],
+        &["--rollup", "--context", "all"],
+        &["--tools", "--context", "all"],
+    ] {
+        let args = to_string_vec(bad);
+        assert!(parse_args(args.into_iter()).is_err(), "{bad:?}");
+    }
+}
+
+#[test]
+fn test_git_history_flags() {
+    let args = to_string_vec(&["--with-log", "5", "--with-diff", "HEAD~3..HEAD"]);
+    let result = parse_args(args.into_iter()).unwrap();
+    assert_eq!(result.with_log, Some(5));
+    assert_eq!(
+        result.with_diff,
+        Some(DiffSpec::Range("HEAD~3..HEAD".to_string()))
+    );
+
+    let args = to_string_vec(&["--cc", "--with-diff", "worktree"]);
+    let result = parse_args(args.into_iter()).unwrap();
+    assert_eq!(result.with_diff, Some(DiffSpec::WorkTree));
+    assert_eq!(result.with_log, None);
+
+    for bad in [
+        &["--with-log"][..],
+        &["--with-log", "0"],
+        &["--with-log", "some"],
+        &["--with-diff"],
+        &["--with-diff", "--output=x"],
+        &["--aw", "--with-log", "3"],
+        &["--rollup", "--with-diff", "HEAD"],
+    ] {
+        let args = to_string_vec(bad);
+        assert!(parse_args(args.into_iter()).is_err(), "{bad:?}");
+    }
+}
+
+#[test]
+fn test_attach_flags() {
+    let args = to_string_vec(&[
+        "--consistency",
+        "--attach",
+        "docs/design.md",
+        "--attach",
+        "-",
+        "--allow-external-attachments",
+    ]);
+    let result = parse_args(args.into_iter()).unwrap();
+    assert_eq!(
+        result.attach,
+        vec![PathBuf::from("docs/design.md"), PathBuf::from("-")]
+    );
+    assert!(result.allow_external_attachments);
+
+    let args = to_string_vec(&["--attach", "error.log"]);
+    let result = parse_args(args.into_iter()).unwrap();
+    assert_eq!(result.attach, vec![PathBuf::from("error.log")]);
+    assert!(!result.allow_external_attachments);
+
+    for bad in [
+        &["--attach"][..],
+        &["--attach", "-", "--attach", "-"],
+        &["--aw", "--attach", "notes.md"],
+        &["--rollup", "--allow-external-attachments"],
+    ] {
+        let args = to_string_vec(bad);
+        assert!(parse_args(args.into_iter()).is_err(), "{bad:?}");
+    }
+}
+
+#[test]
+fn test_query_flags() {
+    let cases = [
+        (
+            &["--query", "Fix the parser"][..],
+            QuerySource::Text("Fix the parser".to_string()),
+        ),
+        (
+            &["--cc", "--query-file", "query.md"],
+            QuerySource::File(PathBuf::from("query.md")),
+        ),
+        (&["--query", "-"], QuerySource::Stdin),
+        (&["--query-file", "-"], QuerySource::Stdin),
+    ];
+    for (args, expected) in cases {
+        let result = parse_args(to_string_vec(args).into_iter()).unwrap();
+        assert_eq!(result.query, Some(expected), "{args:?}");
+    }
+
+    for bad in [
+        &["--query"][..],
+        &["--query-file"],
+        &["--query", "a", "--query-file", "b.md"],
+        &["--query", "-", "--attach", "-"],
+        &["--aw", "--query", "a"],
+        &["--rollup", "--query-file", "q.md"],
+    ] {
+        let args = to_string_vec(bad);
+        assert!(parse_args(args.into_iter()).is_err(), "{bad:?}");
+    }
+}
+
+#[test]
+fn test_history_and_rerun_commands() {
+    let args = to_string_vec(&["history"]);
+    assert_eq!(
+        parse_args(args.into_iter()).unwrap().workflow,
+        Workflow::History
+    );
+
+    let args = to_string_vec(&["rerun", "2026-10-18-09-00-00-committing-code", "--f"]);
+    let result = parse_args(args.into_iter()).unwrap();
+    assert_eq!(
+        result.workflow,
+        Workflow::Rerun {
+            run_id: "2026-10-18-09-00-00-committing-code".to_string(),
+            model: None,
+        }
+    );
+    assert!(result.force);
+
+    let args = to_string_vec(&["--model", "gpt-5.2", "rerun", "2026-10-18"]);
+    let result = parse_args(args.into_iter()).unwrap();
+    assert_eq!(
+        result.workflow,
+        Workflow::Rerun {
+            run_id: "2026-10-18".to_string(),
+            model: Some(Model::from_str("gpt-5.2").unwrap()),
+        }
+    );
+
+    for bad in [
+        &["rerun"][..],
+        &["history", "--cc"],
+        &["rerun", "x", "models"],
+        &["history", "--max-cost", "1"],
+        &["history", "--f"],
+        &["rerun", "x", "--query", "Fix it"],
+    ] {
+        let args = to_string_vec(bad);
+        assert!(parse_args(args.into_iter()).is_err(), "{bad:?}");
+    }
+
+    // Options for the rerun itself are checked once its workflow is known.
+    let args = to_string_vec(&["rerun", "x", "--context", "auto", "--with-log", "2"]);
+    let result = parse_args(args.into_iter()).unwrap();
+    assert_eq!(result.context, Some(ContextMode::Auto));
+}
+
+#[test]
+fn test_run_options_parse_back_to_the_same_flags() {
+    let args = to_string_vec(&[
+        "--commit",
+        "--max-cost",
+        "2.5",
+        "--max-tokens",
+        "1000",
+        "--no-stream",
+        "--call-timeout",
+        "60",
+        "--context",
+        "modules:llm,config",
+        "--with-log",
+        "3",
+        "--with-diff",
+        "HEAD~1",
+        "--attach",
+        "notes.md",
+        "--allow-external-attachments",
+        "--record",
+        "cassettes",
+        "--query",
+        "Fix it",
+    ]);
+    let original = parse_args(args.into_iter()).unwrap();
+    let mut argv = vec!["--commit".to_string()];
+    argv.extend(original.run_options());
+    let restored = parse_args(argv.into_iter()).unwrap();
+    assert_eq!(
+        restored,
+        CliArgs {
+            record: None,
+            query: None,
+            ..original
+        }
+    );
+}
+
+#[test]
+fn test_parse_context_modes() {
+    assert_eq!(ContextMode::parse("llm").unwrap(), ContextMode::Llm);
+    assert_eq!(ContextMode::parse("all").unwrap(), ContextMode::All);
+    assert_eq!(ContextMode::parse("auto").unwrap(), ContextMode::Auto);
+    assert_eq!(ContextMode::parse("ranked").unwrap(), ContextMode::Ranked);
+    assert_eq!(
+        ContextMode::parse("modules:llm, src/config/, crates/core/src/db").unwrap(),
+        ContextMode::Modules(vec![
+            "llm".to_string(),
+            "src/config".to_string(),
+            "crates/core/src/db".to_string()
+        ])
+    );
+    assert!(ContextMode::parse("modules:").is_err());
+    assert!(ContextMode::parse("everything").is_err());
+}
+
+#[test]
+fn test_diff_spec_parse() {
+    assert_eq!(DiffSpec::parse("worktree").unwrap(), DiffSpec::WorkTree);
+    assert_eq!(
+        DiffSpec::parse("HEAD~1").unwrap(),
+        DiffSpec::Commit("HEAD~1".to_string())
+    );
+    assert_eq!(
+        DiffSpec::parse("main...feature").unwrap(),
+        DiffSpec::Range("main...feature".to_string())
+    );
+    for bad in ["", " ", "-p", "--output=x", "HEAD~1 HEAD"] {
+        assert!(DiffSpec::parse(bad).is_err(), "{bad:?}");
+    }
+}
@@ -0,0 +1,112 @@
+use crate::app_error::AppError;
+use std::fmt;
+
+/// Git history to add to a codebase context, from `--with-log` and
+/// `--with-diff`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GitHistory {
+    /// How many recent commits to list with their messages and changed files.
+    pub log: Option<usize>,
+    pub diff: Option<DiffSpec>,
+}
+
+/// What `--with-diff` shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffSpec {
+    /// Uncommitted changes to tracked files, against `HEAD`.
+    WorkTree,
+    /// The changes made by one commit, such as `HEAD~1`.
+    Commit(String),
+    /// The changes between two commits, such as `HEAD~3..HEAD`.
+    Range(String),
+}
+
+impl DiffSpec {
+    /// Parses `worktree`, a commit, or a range containing `..`.
+    pub(crate) fn parse(s: &str) -> Result<Self, AppError> {
+        let s = s.trim();
+        if s.is_empty() || s.starts_with('-') || s.contains(char::is_whitespace) {
+            return Err(AppError::Config(format!(
+                "Invalid value for --with-diff: '{s}'. Expected worktree, a commit such as HEAD~1, or a range such as HEAD~3..HEAD"
+            )));
+        }
+        Ok(match s {
+            "worktree" => DiffSpec::WorkTree,
+            _ if s.contains("..") => DiffSpec::Range(s.to_string()),
+            _ => DiffSpec::Commit(s.to_string()),
+        })
+    }
+}
+
+/// Writes the `--with-diff` value that parses back to the same spec.
+impl fmt::Display for DiffSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffSpec::WorkTree => write!(f, "worktree"),
+            DiffSpec::Commit(revision) | DiffSpec::Range(revision) => write!(f, "{revision}"),
+        }
+    }
+}
+
+/// How the files for a codebase context are chosen.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ContextMode {
+    /// A preprocessing LLM call chooses the files from the codebase summary.
+    #[default]
+    Llm,
+    /// Every file the rollup would include.
+    All,
+    /// The named modules in full, plus the documentation of the modules they
+    /// depend on.
+    Modules(Vec<String>),
+    /// Like `Modules`, with the modules the query mentions, plus the files
+    /// changed by recent commits.
+    Auto,
+    /// The files the retrieval index ranks highest for the query.
+    Ranked,
+}
+
+impl ContextMode {
+    /// Parses `llm`, `all`, `auto`, `ranked` or `modules:<name>,<name>,...`.
+    pub(crate) fn parse(s: &str) -> Result<Self, AppError> {
+        match s {
+            "llm" => Ok(ContextMode::Llm),
+            "all" => Ok(ContextMode::All),
+            "auto" => Ok(ContextMode::Auto),
+            "ranked" => Ok(ContextMode::Ranked),
+            _ => {
+                let list = s.strip_prefix("modules:").ok_or_else(|| {
+                    AppError::Config(format!(
+                        "Unknown context mode '{s}'. Expected llm, all, auto, ranked or modules:<list>"
+                    ))
+                })?;
+                let names: Vec<String> = list
+                    .split(',')
+                    .map(|name| name.trim().trim_end_matches('/'))
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                if names.is_empty() {
+                    return Err(AppError::Config(
+                        "The modules context mode needs at least one module, for example modules:llm,config"
+                            .to_string(),
+                    ));
+                }
+                Ok(ContextMode::Modules(names))
+            }
+        }
+    }
+}
+
+/// Writes the `--context` value that parses back to the same mode.
+impl fmt::Display for ContextMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContextMode::Llm => write!(f, "llm"),
+            ContextMode::All => write!(f, "all"),
+            ContextMode::Modules(names) => write!(f, "modules:{}", names.join(",")),
+            ContextMode::Auto => write!(f, "auto"),
+            ContextMode::Ranked => write!(f, "ranked"),
+        }
+    }
+}
@@ -1,26 +1,13 @@
 use crate::app_error::AppError;
+use std::path::PathBuf;
+
+mod context_options;
 
 #[cfg(test)]
 mod cli_test;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
-pub enum Model {
-    #[default]
-    Gemini3Pro,
-    Gemini2_5Pro,
-    Gpt5,
-}
-
-impl Model {
-    fn from_str(s: &str) -> Result<Self, AppError> {
-        match s {
-            "gemini-3-pro-preview" => Ok(Model::Gemini3Pro),
-            "gemini-2.5-pro" => Ok(Model::Gemini2_5Pro),
-            "gpt-5" | "gpt-5.2" => Ok(Model::Gpt5),
-            _ => Err(AppError::Config(format!("Unsupported model: {s}"))),
-        }
-    }
-}
+pub use crate::llm::models::Model;
+pub use context_options::{ContextMode, DiffSpec, GitHistory};
 
 #[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub enum Workflow {
@@ -30,6 +17,22 @@ pub enum Workflow {
     Rollup,
     Auto,
     Init(String),
+    Models,
+    History,
+    /// Runs an earlier run's query again, with `model` in place of the
+    /// recorded model when `--model` is given.
+    Rerun {
+        run_id: String,
+        model: Option<Model>,
+    },
+}
+
+/// Where the supervisor query comes from when it is not typed in the editor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuerySource {
+    Text(String),
+    File(PathBuf),
+    Stdin,
 }
 
 #[derive(Debug, PartialEq, Default)]
@@ -38,6 +41,66 @@ pub struct CliArgs {
     pub workflow: Workflow,
     pub force: bool,
     pub rollup_full: bool,
+    pub max_cost: Option<f64>,
+    pub max_tokens: Option<u64>,
+    pub stream: Option<bool>,
+    pub call_timeout: Option<u64>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub tools: bool,
+    pub context: Option<ContextMode>,
+    pub with_log: Option<usize>,
+    pub with_diff: Option<DiffSpec>,
+    pub attach: Vec<PathBuf>,
+    pub allow_external_attachments: bool,
+    pub query: Option<QuerySource>,
+}
+
+impl CliArgs {
+    /// The flags that shape how this run queries the LLM, written back as
+    /// command-line arguments so that `rerun` can restore them. The
+    /// workflow, model, query, `--force`, `--record` and `--replay` are left
+    /// out.
+    pub(crate) fn run_options(&self) -> Vec<String> {
+        let mut options = Vec::new();
+        let mut flag = |name: &str, value: Option<String>| {
+            options.push(name.to_string());
+            options.extend(value);
+        };
+        if let Some(cost) = self.max_cost {
+            flag("--max-cost", Some(cost.to_string()));
+        }
+        if let Some(tokens) = self.max_tokens {
+            flag("--max-tokens", Some(tokens.to_string()));
+        }
+        match self.stream {
+            Some(true) => flag("--stream", None),
+            Some(false) => flag("--no-stream", None),
+            None => {}
+        }
+        if let Some(seconds) = self.call_timeout {
+            flag("--call-timeout", Some(seconds.to_string()));
+        }
+        if self.tools {
+            flag("--tools", None);
+        }
+        if let Some(context) = &self.context {
+            flag("--context", Some(context.to_string()));
+        }
+        if let Some(count) = self.with_log {
+            flag("--with-log", Some(count.to_string()));
+        }
+        if let Some(diff) = &self.with_diff {
+            flag("--with-diff", Some(diff.to_string()));
+        }
+        for path in &self.attach {
+            flag("--attach", Some(path.display().to_string()));
+        }
+        if self.allow_external_attachments {
+            flag("--allow-external-attachments", None);
+        }
+        options
+    }
 }
 
 pub fn parse_cli_args() -> Result<CliArgs, AppError> {
@@ -46,9 +109,23 @@ pub fn parse_cli_args() -> Result<CliArgs, AppError> {
 
 pub(crate) fn parse_args<T: Iterator<Item = String>>(mut args: T) -> Result<CliArgs, AppError> {
     let mut model = Model::default();
+    let mut model_given = false;
     let mut workflow: Option<Workflow> = None;
     let mut force = false;
     let mut rollup_full = false;
+    let mut max_cost: Option<f64> = None;
+    let mut max_tokens: Option<u64> = None;
+    let mut stream: Option<bool> = None;
+    let mut call_timeout: Option<u64> = None;
+    let mut record: Option<PathBuf> = None;
+    let mut replay: Option<PathBuf> = None;
+    let mut tools = false;
+    let mut context: Option<ContextMode> = None;
+    let mut with_log: Option<usize> = None;
+    let mut with_diff: Option<DiffSpec> = None;
+    let mut attach: Vec<PathBuf> = Vec::new();
+    let mut allow_external_attachments = false;
+    let mut query: Option<QuerySource> = None;
 
     while let Some(arg) = args.next() {
         match arg.as_str() {
@@ -57,6 +134,7 @@ pub(crate) fn parse_args<T: Iterator<Item = String>>(mut args: T) -> Result<CliA
                     AppError::Config("Missing value for --model argument".to_string())
                 })?;
                 model = Model::from_str(&model_str)?;
+                model_given = true;
             }
             "--commit" => {
                 if workflow.is_some() {
@@ -99,6 +177,111 @@ pub(crate) fn parse_args<T: Iterator<Item = String>>(mut args: T) -> Result<CliA
                 workflow = Some(Workflow::Rollup);
                 rollup_full = true;
             }
+            "--max-cost" => {
+                let value = args.next().ok_or_else(|| {
+                    AppError::Config("Missing value for --max-cost argument".to_string())
+                })?;
+                let cost = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|c| c.is_finite() && *c >= 0.0);
+                max_cost = Some(cost.ok_or_else(|| {
+                    AppError::Config(format!(
+                        "Invalid value for --max-cost: '{value}'. Expected a dollar amount such as 2.50"
+                    ))
+                })?);
+            }
+            "--max-tokens" => {
+                let value = args.next().ok_or_else(|| {
+                    AppError::Config("Missing value for --max-tokens argument".to_string())
+                })?;
+                max_tokens = Some(value.parse::<u64>().map_err(|_| {
+                    AppError::Config(format!(
+                        "Invalid value for --max-tokens: '{value}'. Expected a whole number"
+                    ))
+                })?);
+            }
+            "--stream" => {
+                stream = Some(true);
+            }
+            "--no-stream" => {
+                stream = Some(false);
+            }
+            "--call-timeout" => {
+                let value = args.next().ok_or_else(|| {
+                    AppError::Config("Missing value for --call-timeout argument".to_string())
+                })?;
+                call_timeout = Some(value.parse::<u64>().ok().filter(|s| *s > 0).ok_or_else(
+                    || {
+                        AppError::Config(format!(
+                            "Invalid value for --call-timeout: '{value}'. Expected a whole number of seconds"
+                        ))
+                    },
+                )?);
+            }
+            "--record" => {
+                let dir = args.next().ok_or_else(|| {
+                    AppError::Config("Missing directory for --record argument".to_string())
+                })?;
+                record = Some(PathBuf::from(dir));
+            }
+            "--replay" => {
+                let dir = args.next().ok_or_else(|| {
+                    AppError::Config("Missing directory for --replay argument".to_string())
+                })?;
+                replay = Some(PathBuf::from(dir));
+            }
+            "--tools" => {
+                tools = true;
+            }
+            "--context" => {
+                let value = args.next().ok_or_else(|| {
+                    AppError::Config("Missing value for --context argument".to_string())
+                })?;
+                context = Some(ContextMode::parse(&value)?);
+            }
+            "--with-log" => {
+                let value = args.next().ok_or_else(|| {
+                    AppError::Config("Missing value for --with-log argument".to_string())
+                })?;
+                with_log = Some(value.parse::<usize>().ok().filter(|n| *n > 0).ok_or_else(
+                    || {
+                        AppError::Config(format!(
+                            "Invalid value for --with-log: '{value}'. Expected a number of commits"
+                        ))
+                    },
+                )?);
+            }
+            "--with-diff" => {
+                let value = args.next().ok_or_else(|| {
+                    AppError::Config("Missing value for --with-diff argument".to_string())
+                })?;
+                with_diff = Some(DiffSpec::parse(&value)?);
+            }
+            "--attach" => {
+                let path = args.next().ok_or_else(|| {
+                    AppError::Config("Missing path for --attach argument".to_string())
+                })?;
+                attach.push(PathBuf::from(path));
+            }
+            "--allow-external-attachments" => {
+                allow_external_attachments = true;
+            }
+            "--query" | "--query-file" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| AppError::Config(format!("Missing value for {arg} argument")))?;
+                if query.is_some() {
+                    return Err(AppError::Config(
+                        "Only one of --query and --query-file can be given.".to_string(),
+                    ));
+                }
+                query = Some(match (arg.as_str(), value.as_str()) {
+                    (_, "-") => QuerySource::Stdin,
+                    ("--query", _) => QuerySource::Text(value),
+                    _ => QuerySource::File(PathBuf::from(value)),
+                });
+            }
             "--force" | "--f" => {
                 force = true;
             }
@@ -113,25 +296,212 @@ pub(crate) fn parse_args<T: Iterator<Item = String>>(mut args: T) -> Result<CliA
                 })?;
                 workflow = Some(Workflow::Init(project_name));
             }
+            "models" => {
+                if workflow.is_some() {
+                    return Err(AppError::Config(
+                        "It is an error to trigger more than one workflow at a time.".to_string(),
+                    ));
+                }
+                workflow = Some(Workflow::Models);
+            }
+            "history" => {
+                if workflow.is_some() {
+                    return Err(AppError::Config(
+                        "It is an error to trigger more than one workflow at a time.".to_string(),
+                    ));
+                }
+                workflow = Some(Workflow::History);
+            }
+            "rerun" => {
+                if workflow.is_some() {
+                    return Err(AppError::Config(
+                        "It is an error to trigger more than one workflow at a time.".to_string(),
+                    ));
+                }
+                let run_id = args.next().ok_or_else(|| {
+                    AppError::Config("Missing run id for rerun command".to_string())
+                })?;
+                workflow = Some(Workflow::Rerun {
+                    run_id,
+                    model: None,
+                });
+            }
             _ => {
                 return Err(AppError::Config(format!("Unknown argument: {arg}")));
             }
         }
     }
 
-    let final_workflow = workflow.unwrap_or_default();
+    let final_workflow = match workflow.unwrap_or_default() {
+        Workflow::Rerun { run_id, .. } => Workflow::Rerun {
+            run_id,
+            model: model_given.then_some(model),
+        },
+        workflow => workflow,
+    };
 
-    if force && final_workflow != Workflow::CommitCode {
+    if force
+        && !matches!(
+            final_workflow,
+            Workflow::CommitCode | Workflow::Rerun { .. }
+        )
+    {
         return Err(AppError::Config(
             "The --force or --f flag can only be used with the 'committing-code' workflow."
                 .to_string(),
         ));
     }
 
+    if (max_cost.is_some() || max_tokens.is_some())
+        && matches!(
+            final_workflow,
+            Workflow::Rollup | Workflow::Init(_) | Workflow::Models | Workflow::History
+        )
+    {
+        return Err(AppError::Config(
+            "The --max-cost and --max-tokens flags can only be used with workflows that call an LLM."
+                .to_string(),
+        ));
+    }
+
+    if stream.is_some()
+        && matches!(
+            final_workflow,
+            Workflow::Rollup | Workflow::Init(_) | Workflow::Models | Workflow::History
+        )
+    {
+        return Err(AppError::Config(
+            "The --stream and --no-stream flags can only be used with workflows that call an LLM."
+                .to_string(),
+        ));
+    }
+
+    if call_timeout.is_some()
+        && matches!(
+            final_workflow,
+            Workflow::Rollup | Workflow::Init(_) | Workflow::Models | Workflow::History
+        )
+    {
+        return Err(AppError::Config(
+            "The --call-timeout flag can only be used with workflows that call an LLM.".to_string(),
+        ));
+    }
+
+    if record.is_some() && replay.is_some() {
+        return Err(AppError::Config(
+            "The --record and --replay flags cannot be used together.".to_string(),
+        ));
+    }
+
+    if (record.is_some() || replay.is_some())
+        && matches!(
+            final_workflow,
+            Workflow::Rollup | Workflow::Init(_) | Workflow::Models | Workflow::History
+        )
+    {
+        return Err(AppError::Config(
+            "The --record and --replay flags can only be used with workflows that call an LLM."
+                .to_string(),
+        ));
+    }
+
+    // A rerun is checked again once its original workflow and options are
+    // known.
+    if tools
+        && !matches!(
+            final_workflow,
+            Workflow::CommitCode | Workflow::Rerun { .. }
+        )
+    {
+        return Err(AppError::Config(
+            "The --tools flag can only be used with the 'committing-code' workflow.".to_string(),
+        ));
+    }
+
+    if context.is_some()
+        && !matches!(
+            final_workflow,
+            Workflow::CommitCode | Workflow::ConsistencyCheck | Workflow::Rerun { .. }
+        )
+    {
+        return Err(AppError::Config(
+            "The --context flag can only be used with the 'committing-code' and 'consistency' workflows."
+                .to_string(),
+        ));
+    }
+
+    if context.is_some() && tools {
+        return Err(AppError::Config(
+            "The --context flag cannot be used with --tools, which lets the LLM read files itself."
+                .to_string(),
+        ));
+    }
+
+    if (with_log.is_some() || with_diff.is_some())
+        && !matches!(
+            final_workflow,
+            Workflow::CommitCode | Workflow::ConsistencyCheck | Workflow::Rerun { .. }
+        )
+    {
+        return Err(AppError::Config(
+            "The --with-log and --with-diff flags can only be used with the 'committing-code' and 'consistency' workflows."
+                .to_string(),
+        ));
+    }
+
+    if (!attach.is_empty() || allow_external_attachments)
+        && !matches!(
+            final_workflow,
+            Workflow::CommitCode | Workflow::ConsistencyCheck | Workflow::Rerun { .. }
+        )
+    {
+        return Err(AppError::Config(
+            "The --attach and --allow-external-attachments flags can only be used with the 'committing-code' and 'consistency' workflows."
+                .to_string(),
+        ));
+    }
+
+    if attach.iter().filter(|path| path.as_os_str() == "-").count() > 1 {
+        return Err(AppError::Config(
+            "Standard input can only be attached once with --attach -.".to_string(),
+        ));
+    }
+
+    if query.is_some()
+        && !matches!(
+            final_workflow,
+            Workflow::CommitCode | Workflow::ConsistencyCheck
+        )
+    {
+        return Err(AppError::Config(
+            "The --query and --query-file flags can only be used with the 'committing-code' and 'consistency' workflows."
+                .to_string(),
+        ));
+    }
+
+    if query == Some(QuerySource::Stdin) && attach.iter().any(|path| path.as_os_str() == "-") {
+        return Err(AppError::Config(
+            "Standard input cannot be both the query and an attachment.".to_string(),
+        ));
+    }
+
     Ok(CliArgs {
         model,
         workflow: final_workflow,
         force,
         rollup_full,
+        max_cost,
+        max_tokens,
+        stream,
+        call_timeout,
+        record,
+        replay,
+        tools,
+        context,
+        with_log,
+        with_diff,
+        attach,
+        allow_external_attachments,
+        query,
     })
 }
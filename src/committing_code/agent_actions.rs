@@ -1,7 +1,8 @@
 use super::build_runner;
 use crate::app_error::{AppError, BuildFailure};
 use crate::cli::Model;
-use crate::llm;
+use crate::context_builder::LocalTools;
+use crate::llm::{self, Conversation, Session};
 use crate::logger::Logger;
 use std::future::Future;
 use std::path::Path;
@@ -12,29 +13,62 @@ pub(crate) trait AgentActions {
         &'a self,
         model: Model,
         api_key: String,
-        prompt: String,
+        session: &'a Session,
+        conversation: &'a mut Conversation,
         logger: &'a Logger,
         log_prefix: String,
     ) -> Pin<Box<dyn Future<Output = Result<String, AppError>> + Send + 'a>>;
 
     fn run_build(&self) -> Result<String, BuildFailure>;
+
+    /// Whether `query_llm` lets the model read files with tools, in which case
+    /// the workflow sends a summary instead of the chosen files and skips the
+    /// extra code query.
+    fn uses_tools(&self) -> bool {
+        false
+    }
 }
 
-pub(crate) struct RealAgentActions;
+pub(crate) struct RealAgentActions {
+    pub(crate) tools: Option<LocalTools>,
+}
 
 impl AgentActions for RealAgentActions {
     fn query_llm<'a>(
         &'a self,
         model: Model,
         api_key: String,
-        prompt: String,
+        session: &'a Session,
+        conversation: &'a mut Conversation,
         logger: &'a Logger,
         log_prefix: String,
     ) -> Pin<Box<dyn Future<Output = Result<String, AppError>> + Send + 'a>> {
-        Box::pin(async move { llm::query(model, api_key, &prompt, logger, &log_prefix).await })
+        Box::pin(async move {
+            match &self.tools {
+                Some(tools) => {
+                    llm::converse_with_tools(
+                        model,
+                        api_key,
+                        session,
+                        conversation,
+                        tools,
+                        logger,
+                        &log_prefix,
+                    )
+                    .await
+                }
+                None => {
+                    llm::converse(model, api_key, session, conversation, logger, &log_prefix).await
+                }
+            }
+        })
     }
 
     fn run_build(&self) -> Result<String, BuildFailure> {
         build_runner::run(Path::new("."))
     }
+
+    fn uses_tools(&self) -> bool {
+        self.tools.is_some()
+    }
 }
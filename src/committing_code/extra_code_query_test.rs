@@ -3,7 +3,9 @@ use super::run_with_actions;
 use crate::app_error::{AppError, BuildFailure};
 use crate::cli::Model;
 use crate::config::Config;
+use crate::llm::{Conversation, Session};
 use crate::logger::Logger;
+use crate::system_prompts::COMMITTING_CODE_TOOLS;
 use std::collections::VecDeque;
 use std::fs;
 use std::future::Future;
@@ -15,6 +17,7 @@ struct MockAgentActions {
     llm_responses: Mutex<VecDeque<Result<String, AppError>>>,
     build_results: Mutex<VecDeque<Result<String, BuildFailure>>>,
     captured_prompts: Mutex<Vec<String>>,
+    uses_tools: bool,
 }
 
 impl MockAgentActions {
@@ -26,6 +29,7 @@ impl MockAgentActions {
             llm_responses: Mutex::new(llm_responses.into()),
             build_results: Mutex::new(build_results.into()),
             captured_prompts: Mutex::new(Vec::new()),
+            uses_tools: false,
         }
     }
 
@@ -39,18 +43,22 @@ impl AgentActions for MockAgentActions {
         &'a self,
         _model: Model,
         _api_key: String,
-        prompt: String,
+        _session: &'a Session,
+        conversation: &'a mut Conversation,
         _logger: &'a Logger,
         _log_prefix: String,
     ) -> Pin<Box<dyn Future<Output = Result<String, AppError>> + Send + 'a>> {
         let mut prompts = self.captured_prompts.lock().unwrap();
-        prompts.push(prompt);
+        prompts.push(conversation.transcript());
 
         let mut responses = self.llm_responses.lock().unwrap();
         if responses.is_empty() {
             panic!("Mock query_llm called more times than expected. Log prefix: {_log_prefix}");
         }
         let res = responses.pop_front().unwrap();
+        if let Ok(text) = &res {
+            conversation.push_assistant(text.clone());
+        }
         Box::pin(async move { res })
     }
 
@@ -61,14 +69,19 @@ impl AgentActions for MockAgentActions {
         }
         results.pop_front().unwrap()
     }
+
+    fn uses_tools(&self) -> bool {
+        self.uses_tools
+    }
 }
 
 fn create_test_config() -> Config {
     Config {
-        model: Model::Gpt5,
+        model: Model::from_str("gpt-5.2").unwrap(),
         api_key: "test-key".to_string(),
         query: "Fix the bug".to_string(),
         system_prompts: "".to_string(),
+        ..Default::default()
     }
 }
 
@@ -172,3 +185,33 @@ async fn test_extra_code_query_skips_missing_files() {
     // Should NOT contain the missing file header
     assert!(!repair_prompt.contains("--- src/does_not_exist.rs ---"));
 }
+
+#[tokio::test]
+async fn test_tools_replace_the_extra_code_query() {
+    let dir = tempdir().unwrap();
+    let logger = Logger::new_with_root(dir.path(), "test").unwrap();
+    let config = create_test_config();
+
+    let response_1 = "^^^src/main.rs\nfn main() { error }\n^^^end".to_string();
+    let response_2 = "^^^src/main.rs\nfn main() {}\n^^^end".to_string();
+    let mut actions = MockAgentActions::new(
+        vec![Ok(response_1), Ok(response_2)],
+        vec![
+            Err(BuildFailure {
+                output: "error".to_string(),
+            }),
+            Ok("EXIT CODE: 0".to_string()),
+        ],
+    );
+    actions.uses_tools = true;
+
+    let codebase = "--- FILENAMES ---\nsrc/main.rs\n--- END FILENAMES ---".to_string();
+    let result = run_with_actions(&logger, &config, codebase, &actions, dir.path()).await;
+    assert!(result.is_ok());
+
+    // The initial query and one repair; no extra code query in between.
+    let prompts = actions.get_captured_prompts();
+    assert_eq!(prompts.len(), 2);
+    assert!(prompts[0].contains(COMMITTING_CODE_TOOLS));
+    assert!(prompts[1].contains("[build.sh output]"));
+}
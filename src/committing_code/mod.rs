@@ -2,6 +2,7 @@ mod agent_actions;
 mod build_runner;
 mod file_updater;
 mod git_status;
+mod prompt_fit;
 mod response_parser;
 
 #[cfg(test)]
@@ -15,6 +16,8 @@ mod file_updater_test;
 #[cfg(test)]
 mod git_status_test;
 #[cfg(test)]
+mod prompt_fit_test;
+#[cfg(test)]
 mod response_parser_adversarial_test;
 #[cfg(test)]
 mod response_parser_edge_test;
@@ -31,9 +34,14 @@ use crate::app_error::AppError;
 use crate::cli::CliArgs;
 use crate::config::Config;
 use crate::context_builder;
+use crate::llm::generation::CallRole;
+use crate::llm::schema::ResponseSchema;
+use crate::llm::tokens::{estimate_tokens, input_token_limit};
+use crate::llm::Conversation;
 use crate::logger;
 use crate::system_prompts::{
-    CODE_MODIFICATION_INSTRUCTIONS, COMMITTING_CODE_EXTRA_CODE_QUERY, COMMITTING_CODE_REPAIR_QUERY,
+    CODE_MODIFICATION_INSTRUCTIONS, COMMITTING_CODE_EXTRA_CODE_QUERY,
+    COMMITTING_CODE_REPAIR_FOLLOW_UP, COMMITTING_CODE_REPAIR_QUERY, COMMITTING_CODE_TOOLS,
     PROJECT_STRUCTURE,
 };
 use agent_actions::{AgentActions, RealAgentActions};
@@ -44,6 +52,8 @@ use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+pub(crate) use prompt_fit::{fit_initial_prompt, fit_prompt, report_trimmed};
+
 const MAX_ATTEMPTS: u32 = 4;
 
 pub async fn run(logger: &logger::Logger, cli_args: CliArgs) -> Result<(), AppError> {
@@ -56,14 +66,35 @@ pub async fn run(logger: &logger::Logger, cli_args: CliArgs) -> Result<(), AppEr
     }
 
     let config = Config::load(&cli_args)?;
+    // Saved with the logs so that the run can be replayed with --replay.
+    logger.log_text(logger::QUERY_FILE, &config.query)?;
+
+    if cli_args.tools {
+        if !config.model.features().tools {
+            return Err(AppError::Config(format!(
+                "--tools needs a model that can call tools, and {} cannot.",
+                config.model.name()
+            )));
+        }
+        println!("Building codebase summary; the LLM will read files with tools...");
+        let codebase = format!(
+            "{}{}",
+            context_builder::build_history_context(&config.git_history)?,
+            context_builder::build_codebase_summary()?
+        );
+        logger.log_text("codebase.txt", &codebase)?;
+        let actions = RealAgentActions {
+            tools: Some(context_builder::LocalTools::new(Path::new("."))?),
+        };
+        run_with_actions(logger, &config, codebase, &actions, Path::new(".")).await?;
+        return Ok(());
+    }
 
     println!("Building codebase context for LLM...");
-    let initial_query_prompt = &config.system_prompts;
-    let system_prompt_part =
-        format!("{PROJECT_STRUCTURE}\n{CODE_MODIFICATION_INSTRUCTIONS}\n{initial_query_prompt}");
     let next_agent_prompt = format!(
-        "{}\n[supervisor query]\n{}",
-        system_prompt_part, config.query
+        "{}\n{}",
+        build_initial_system_prompt(&config),
+        build_supervisor_query(&config)
     );
 
     let codebase = context_builder::build_codebase_context(
@@ -85,7 +116,7 @@ pub async fn run_with_codebase(
     config: &Config,
     codebase: String,
 ) -> Result<String, AppError> {
-    let actions = RealAgentActions;
+    let actions = RealAgentActions { tools: None };
     run_with_actions(logger, config, codebase, &actions, Path::new(".")).await
 }
 
@@ -96,45 +127,92 @@ async fn run_with_actions<A: AgentActions>(
     actions: &A,
     base_dir: &Path,
 ) -> Result<String, AppError> {
-    let initial_query_prompt = &config.system_prompts;
-    let system_prompt_part =
-        format!("{PROJECT_STRUCTURE}\n{CODE_MODIFICATION_INSTRUCTIONS}\n{initial_query_prompt}");
-    let next_agent_prompt = format!(
-        "{}\n[supervisor query]\n{}",
-        system_prompt_part, config.query
-    );
+    let mut initial_system_prompt = build_initial_system_prompt(config);
+    let mut repair_system_prompt = build_repair_system_prompt();
+    if actions.uses_tools() {
+        initial_system_prompt = format!("{initial_system_prompt}\n{COMMITTING_CODE_TOOLS}");
+        repair_system_prompt = format!("{repair_system_prompt}\n{COMMITTING_CODE_TOOLS}");
+    }
+    let supervisor_query = build_supervisor_query(config);
 
     let mut last_build_output: Option<String> = None;
     let mut cumulative_updates: HashMap<PathBuf, Option<String>> = HashMap::new();
+    let mut conversation = Conversation::new();
+    // How much of `codebase` the conversation has already seen. Files added by
+    // the extra code query after this point go into the next follow-up turn.
+    let mut codebase_sent = 0;
 
     for attempt in 1..=MAX_ATTEMPTS {
         println!("Starting attempt {attempt}/{MAX_ATTEMPTS}...");
 
-        let (prompt, name_part) = if attempt == 1 {
-            (
-                build_initial_prompt(&next_agent_prompt, &codebase),
-                "initial-query",
-            )
+        let limit = input_token_limit(config.model);
+        let name_part = if attempt == 1 {
+            "initial-query"
+        } else {
+            "repair"
+        };
+        let log_prefix = format!("{attempt}-{name_part}");
+
+        // Repairs continue the conversation with just the new build output.
+        // When the conversation has grown too large for the model, fall back
+        // to a fresh, self-contained repair prompt.
+        let fitted = if attempt == 1 {
+            let user_limit = limit.saturating_sub(estimate_tokens(&initial_system_prompt));
+            let fitted = prompt_fit::fit_initial_prompt(&supervisor_query, &codebase, user_limit)?;
+            Some((initial_system_prompt.as_str(), fitted))
         } else {
             let build_output = last_build_output
                 .as_ref()
                 .expect("Build output should exist for repair attempts");
-            (
-                build_repair_prompt(config, build_output, &cumulative_updates, &codebase),
-                "repair",
-            )
+            let follow_up = build_repair_follow_up(build_output, &codebase[codebase_sent..]);
+            if conversation.estimated_tokens() + estimate_tokens(&follow_up) <= limit {
+                conversation.push_user(follow_up);
+                None
+            } else {
+                println!(
+                    "Conversation no longer fits the model's input limit; sending a full repair prompt instead."
+                );
+                let user_limit = limit.saturating_sub(estimate_tokens(&repair_system_prompt));
+                let fitted = prompt_fit::fit_repair_prompt(
+                    config,
+                    build_output,
+                    &cumulative_updates,
+                    &codebase,
+                    user_limit,
+                )?;
+                Some((repair_system_prompt.as_str(), fitted))
+            }
         };
-        let log_prefix = format!("{attempt}-{name_part}");
+        if let Some((system_prompt, fitted)) = fitted {
+            prompt_fit::report_trimmed(logger, &log_prefix, limit, &fitted.trimmed)?;
+            // The codebase goes in its own turn ahead of the build output so
+            // that providers can cache it across fresh repair prompts.
+            let (stable, volatile) = split_repair_prompt(&fitted.prompt);
+            conversation = Conversation::with_system(system_prompt, stable);
+            if !volatile.is_empty() {
+                conversation.push_user(volatile);
+            }
+            conversation.set_call_role(CallRole::Commit);
+        }
+        codebase_sent = codebase.len();
 
         let response_text = actions
             .query_llm(
                 config.model,
                 config.api_key.clone(),
-                prompt,
+                &config.session,
+                &mut conversation,
                 logger,
                 log_prefix.clone(),
             )
-            .await?;
+            .await
+            .inspect_err(|e| {
+                if matches!(e, AppError::BudgetExceeded(_)) {
+                    println!(
+                        "Spending budget reached on attempt {attempt}. File changes applied so far remain on disk and logs are in agent-config/logs/."
+                    );
+                }
+            })?;
 
         println!("Parsing LLM response and applying file updates...");
         let updates = response_parser_impl::parse_llm_response(&response_text)?;
@@ -157,7 +235,8 @@ async fn run_with_actions<A: AgentActions>(
                 println!("Build failed. Preparing for repair attempt...");
                 last_build_output = Some(build_failure.output);
 
-                if attempt < MAX_ATTEMPTS {
+                // With tools the model reads whatever the repair needs itself.
+                if attempt < MAX_ATTEMPTS && !actions.uses_tools() {
                     let build_output = last_build_output.as_ref().unwrap();
                     run_extra_code_query(
                         config,
@@ -191,15 +270,26 @@ async fn run_extra_code_query<A: AgentActions>(
     let existing_files = extract_filenames_from_codebase(codebase);
     let existing_files_list = existing_files.join("\n");
 
-    let extra_code_prompt = format!(
-        "{COMMITTING_CODE_EXTRA_CODE_QUERY}\n[codebase file list]\n{existing_files_list}\n[build.sh output]\n{build_output}"
-    );
+    let mut extra_code_prompt =
+        format!("[codebase file list]\n{existing_files_list}\n[build.sh output]\n{build_output}");
+    let user_limit = input_token_limit(config.model)
+        .saturating_sub(estimate_tokens(COMMITTING_CODE_EXTRA_CODE_QUERY));
+    if estimate_tokens(&extra_code_prompt) > user_limit {
+        let summary = prompt_fit::summarize_build_log(build_output);
+        extra_code_prompt =
+            format!("[codebase file list]\n{existing_files_list}\n[build.sh output]\n{summary}");
+    }
 
+    let mut conversation =
+        Conversation::with_system(COMMITTING_CODE_EXTRA_CODE_QUERY, extra_code_prompt);
+    conversation.set_response_schema(ResponseSchema::file_list());
+    conversation.set_call_role(CallRole::ExtraCode);
     let response = actions
         .query_llm(
             config.model,
             config.api_key.clone(),
-            extra_code_prompt,
+            &config.session,
+            &mut conversation,
             logger,
             format!("{attempt}-extra-code"),
         )
@@ -263,8 +353,23 @@ fn extract_filenames_from_codebase(codebase: &str) -> Vec<String> {
         .collect()
 }
 
-fn build_initial_prompt(next_agent_prompt: &str, codebase: &str) -> String {
-    format!("{next_agent_prompt}\n[codebase]\n{codebase}")
+fn build_initial_system_prompt(config: &Config) -> String {
+    format!(
+        "{PROJECT_STRUCTURE}\n{CODE_MODIFICATION_INSTRUCTIONS}\n{}",
+        config.system_prompts
+    )
+}
+
+fn build_repair_system_prompt() -> String {
+    format!("{PROJECT_STRUCTURE}\n{CODE_MODIFICATION_INSTRUCTIONS}\n{COMMITTING_CODE_REPAIR_QUERY}")
+}
+
+fn build_supervisor_query(config: &Config) -> String {
+    config.supervisor_query()
+}
+
+fn build_initial_prompt(supervisor_query: &str, codebase: &str) -> String {
+    format!("{supervisor_query}\n[codebase]\n{codebase}")
 }
 
 fn build_repair_prompt(
@@ -273,16 +378,43 @@ fn build_repair_prompt(
     file_replacements: &std::collections::HashMap<std::path::PathBuf, Option<String>>,
     codebase: &str,
 ) -> String {
+    // Stable content comes first so that repeated repair prompts share a
+    // cacheable prefix with each other and with the initial prompt.
     let replacements_str = format_file_replacements(file_replacements);
-    let system_prompt = format!(
-        "{PROJECT_STRUCTURE}\n{CODE_MODIFICATION_INSTRUCTIONS}\n{COMMITTING_CODE_REPAIR_QUERY}"
-    );
     format!(
-        "{}\n[build.sh output]\n{}\n[supervisor query]\n{}\n[codebase]\n{}\n[file replacements]\n{}",
-        system_prompt, build_output, config.query, codebase, replacements_str
+        "{}\n[codebase]\n{}\n[file replacements]\n{}\n[build.sh output]\n{}",
+        config.supervisor_query(),
+        codebase,
+        replacements_str,
+        build_output
     )
 }
 
+/// Splits a prompt into the part that every repair prompt shares, the query
+/// and the codebase, and the file replacements and build output that follow
+/// it. A prompt without file replacements is all shared. A codebase file that
+/// itself contains the section header only makes the shared part shorter.
+fn split_repair_prompt(prompt: &str) -> (&str, &str) {
+    match prompt.find("\n[file replacements]\n") {
+        Some(at) => (&prompt[..at], &prompt[at + 1..]),
+        None => (prompt, ""),
+    }
+}
+
+/// The follow-up turn for a repair attempt that continues the conversation.
+/// The earlier turns already hold the codebase and every change the model made,
+/// so only the build output and any newly added files are sent.
+fn build_repair_follow_up(build_output: &str, new_codebase_files: &str) -> String {
+    let mut prompt = format!("{COMMITTING_CODE_REPAIR_FOLLOW_UP}\n");
+    if !new_codebase_files.trim().is_empty() {
+        prompt.push_str(&format!(
+            "[additional codebase files]\n{new_codebase_files}\n"
+        ));
+    }
+    prompt.push_str(&format!("[build.sh output]\n{build_output}"));
+    prompt
+}
+
 fn format_file_replacements(
     replacements: &std::collections::HashMap<std::path::PathBuf, Option<String>>,
 ) -> String {
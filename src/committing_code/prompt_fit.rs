@@ -0,0 +1,319 @@
+use super::{build_initial_prompt, build_repair_prompt};
+use crate::app_error::AppError;
+use crate::config::Config;
+use crate::llm::tokens::estimate_tokens;
+use crate::logger::Logger;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+const BUILD_LOG_HEAD_LINES: usize = 20;
+const BUILD_LOG_TAIL_LINES: usize = 20;
+const BUILD_LOG_CONTEXT_LINES: usize = 3;
+
+/// A prompt that fits the model's input limit, along with a description of
+/// everything that had to be removed to get it there.
+pub(crate) struct FittedPrompt {
+    pub(crate) prompt: String,
+    pub(crate) trimmed: Vec<String>,
+}
+
+/// Text split into the part before the first `--- [filepath] ---` header and
+/// the file sections that follow it.
+struct Sections {
+    preamble: String,
+    files: Vec<(String, String)>,
+}
+
+impl Sections {
+    fn parse(text: &str) -> Self {
+        let mut preamble = String::new();
+        let mut files: Vec<(String, String)> = Vec::new();
+        for line in text.split_inclusive('\n') {
+            if let Some(path) = section_header_path(line) {
+                files.push((path.to_string(), String::new()));
+            }
+            match files.last_mut() {
+                Some((_, body)) => body.push_str(line),
+                None => preamble.push_str(line),
+            }
+        }
+        Self { preamble, files }
+    }
+
+    fn render(&self) -> String {
+        let mut out = self.preamble.clone();
+        for (_, body) in &self.files {
+            out.push_str(body);
+        }
+        out
+    }
+
+    fn content_of(&self, path: &str) -> Option<&str> {
+        self.files
+            .iter()
+            .find(|(p, _)| p == path)
+            .map(|(_, body)| body.split_once('\n').map(|(_, c)| c).unwrap_or(""))
+    }
+
+    fn remove(&mut self, path: &str) -> bool {
+        let before = self.files.len();
+        self.files.retain(|(p, _)| p != path);
+        self.files.len() != before
+    }
+
+    /// Removes the least relevant file section, largest first among equally
+    /// relevant files. Returns the removed path and its estimated size.
+    fn drop_least_relevant(&mut self, hints: &str) -> Option<(String, u64)> {
+        let (index, _) = self
+            .files
+            .iter()
+            .enumerate()
+            .map(|(i, (path, body))| (i, (relevance(path, hints), estimate_tokens(body))))
+            .min_by(|(_, (ra, sa)), (_, (rb, sb))| ra.cmp(rb).then(sb.cmp(sa)))?;
+        let (path, body) = self.files.remove(index);
+        Some((path, estimate_tokens(&body)))
+    }
+}
+
+fn section_header_path(line: &str) -> Option<&str> {
+    let line = line.trim_end_matches('\n');
+    let name = line.strip_prefix("--- ")?.strip_suffix(" ---")?;
+    if name.is_empty()
+        || name == "FILENAMES"
+        || name == "END FILENAMES"
+        || name.starts_with("FILE REPLACEMENT")
+        || name.starts_with("FILE REMOVED")
+    {
+        return None;
+    }
+    Some(name)
+}
+
+/// Scores how strongly `hints` point at `path`. A full path mention beats a
+/// file or directory name mention, which beats no mention at all.
+fn relevance(path: &str, hints: &str) -> u8 {
+    if hints.contains(path) {
+        return 2;
+    }
+    let p = Path::new(path);
+    let stem = p.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    let name = if matches!(stem, "mod" | "lib" | "main") {
+        p.parent()
+            .and_then(|d| d.file_name())
+            .and_then(|s| s.to_str())
+            .unwrap_or(stem)
+    } else {
+        stem
+    };
+    if name.len() > 2 && hints.contains(name) {
+        1
+    } else {
+        0
+    }
+}
+
+/// Keeps the head and tail of a build log plus every line that mentions an
+/// error or warning, with a few lines of context after each.
+pub(crate) fn summarize_build_log(output: &str) -> String {
+    let lines: Vec<&str> = output.lines().collect();
+    if lines.len() <= BUILD_LOG_HEAD_LINES + BUILD_LOG_TAIL_LINES {
+        return output.to_string();
+    }
+
+    let mut keep = vec![false; lines.len()];
+    for flag in keep.iter_mut().take(BUILD_LOG_HEAD_LINES) {
+        *flag = true;
+    }
+    for flag in keep.iter_mut().skip(lines.len() - BUILD_LOG_TAIL_LINES) {
+        *flag = true;
+    }
+    for (i, line) in lines.iter().enumerate() {
+        let lower = line.to_lowercase();
+        if lower.contains("error") || lower.contains("warning") || lower.contains("panicked") {
+            let end = (i + BUILD_LOG_CONTEXT_LINES + 1).min(lines.len());
+            for flag in &mut keep[i..end] {
+                *flag = true;
+            }
+        }
+    }
+
+    let mut out = String::new();
+    let mut omitted = 0;
+    for (line, kept) in lines.iter().zip(keep) {
+        if kept {
+            if omitted > 0 {
+                out.push_str(&format!("[... {omitted} lines omitted ...]\n"));
+                omitted = 0;
+            }
+            out.push_str(line);
+            out.push('\n');
+        } else {
+            omitted += 1;
+        }
+    }
+    if omitted > 0 {
+        out.push_str(&format!("[... {omitted} lines omitted ...]\n"));
+    }
+    out
+}
+
+/// Drops the least relevant file sections from `codebase` and then from
+/// `supervisor_query` until the initial prompt fits in `limit` tokens. File
+/// sections only appear in the query when a caller such as auto-workflow
+/// embeds its own codebase in it. `limit` covers the user prompt only; the
+/// caller subtracts the system prompt.
+pub(crate) fn fit_initial_prompt(
+    supervisor_query: &str,
+    codebase: &str,
+    limit: u64,
+) -> Result<FittedPrompt, AppError> {
+    fit_file_sections(supervisor_query, codebase, limit, build_initial_prompt)
+}
+
+/// Like `fit_initial_prompt`, for a user prompt that is sent as it is, such
+/// as an auto-workflow stage prompt with its own codebase sections.
+pub(crate) fn fit_prompt(prompt: &str, limit: u64) -> Result<FittedPrompt, AppError> {
+    fit_file_sections(prompt, "", limit, |query, _| query.to_string())
+}
+
+fn fit_file_sections(
+    query: &str,
+    codebase: &str,
+    limit: u64,
+    build: impl Fn(&str, &str) -> String,
+) -> Result<FittedPrompt, AppError> {
+    let mut trimmed = Vec::new();
+    let mut prompt = build(query, codebase);
+    if estimate_tokens(&prompt) <= limit {
+        return Ok(FittedPrompt { prompt, trimmed });
+    }
+
+    let mut query_sections = Sections::parse(query);
+    let mut codebase_sections = Sections::parse(codebase);
+    let hints = query_sections.preamble.clone();
+
+    while estimate_tokens(&prompt) > limit {
+        let dropped = codebase_sections
+            .drop_least_relevant(&hints)
+            .or_else(|| query_sections.drop_least_relevant(&hints));
+        let Some((path, tokens)) = dropped else {
+            return Err(too_large(&prompt, limit, &trimmed));
+        };
+        trimmed.push(format!(
+            "dropped least relevant file {path} (~{tokens} tokens)"
+        ));
+        prompt = build(&query_sections.render(), &codebase_sections.render());
+    }
+
+    Ok(FittedPrompt { prompt, trimmed })
+}
+
+/// Prints a summary of what was trimmed to fit `limit` and logs the details
+/// to `[log_prefix]-trimmed.txt`. Does nothing when nothing was trimmed.
+pub(crate) fn report_trimmed(
+    logger: &Logger,
+    log_prefix: &str,
+    limit: u64,
+    trimmed: &[String],
+) -> Result<(), AppError> {
+    if trimmed.is_empty() {
+        return Ok(());
+    }
+    println!(
+        "Prompt exceeded the model's input limit of {limit} tokens; trimmed {} item(s) to fit.",
+        trimmed.len()
+    );
+    logger.log_text(&format!("{log_prefix}-trimmed.txt"), &trimmed.join("\n"))
+}
+
+/// Shrinks the repair prompt until it fits in `limit` tokens, which covers the
+/// user prompt only. The steps are applied in order, and each one only runs if
+/// the previous steps were not enough:
+///
+/// 1. Drop duplication between file replacements and the codebase. A
+///    replacement identical to the codebase copy is dropped; otherwise the
+///    stale codebase copy is dropped in favor of the replacement.
+/// 2. Summarize the build output down to its errors and warnings.
+/// 3. Drop codebase files, least relevant to the build output and query first.
+pub(crate) fn fit_repair_prompt(
+    config: &Config,
+    build_output: &str,
+    file_replacements: &HashMap<PathBuf, Option<String>>,
+    codebase: &str,
+    limit: u64,
+) -> Result<FittedPrompt, AppError> {
+    let mut trimmed = Vec::new();
+    let mut prompt = build_repair_prompt(config, build_output, file_replacements, codebase);
+    if estimate_tokens(&prompt) <= limit {
+        return Ok(FittedPrompt { prompt, trimmed });
+    }
+
+    // Step 1: deduplicate replacements against the codebase.
+    let mut sections = Sections::parse(codebase);
+    let mut replacements = file_replacements.clone();
+    let mut paths: Vec<PathBuf> = replacements.keys().cloned().collect();
+    paths.sort();
+    for path in paths {
+        let path_str = path.to_string_lossy().to_string();
+        let Some(existing) = sections.content_of(&path_str) else {
+            continue;
+        };
+        let identical = match replacements.get(&path) {
+            Some(Some(content)) => existing.trim_end() == content.trim_end(),
+            _ => false,
+        };
+        if identical {
+            replacements.remove(&path);
+            trimmed.push(format!(
+                "dropped file replacement for {path_str} (identical to the codebase copy)"
+            ));
+        } else if sections.remove(&path_str) {
+            trimmed.push(format!(
+                "dropped stale codebase copy of {path_str} (superseded by its file replacement)"
+            ));
+        }
+    }
+    prompt = build_repair_prompt(config, build_output, &replacements, &sections.render());
+    if estimate_tokens(&prompt) <= limit {
+        return Ok(FittedPrompt { prompt, trimmed });
+    }
+
+    // Step 2: summarize the build output.
+    let summary = summarize_build_log(build_output);
+    if summary.len() < build_output.len() {
+        trimmed.push(format!(
+            "summarized build output from {} to {} lines",
+            build_output.lines().count(),
+            summary.lines().count()
+        ));
+    }
+    prompt = build_repair_prompt(config, &summary, &replacements, &sections.render());
+
+    // Step 3: drop the least relevant codebase files.
+    let mut hints = format!("{summary}\n{}", config.query);
+    for path in replacements.keys() {
+        hints.push_str(&format!("\n{}", path.display()));
+    }
+    while estimate_tokens(&prompt) > limit {
+        let Some((path, tokens)) = sections.drop_least_relevant(&hints) else {
+            return Err(too_large(&prompt, limit, &trimmed));
+        };
+        trimmed.push(format!(
+            "dropped least relevant file {path} (~{tokens} tokens)"
+        ));
+        prompt = build_repair_prompt(config, &summary, &replacements, &sections.render());
+    }
+
+    Ok(FittedPrompt { prompt, trimmed })
+}
+
+fn too_large(prompt: &str, limit: u64, trimmed: &[String]) -> AppError {
+    let mut msg = format!(
+        "the prompt is still estimated at {} tokens after trimming everything that can be trimmed (limit {limit}).",
+        estimate_tokens(prompt)
+    );
+    if !trimmed.is_empty() {
+        msg.push_str(&format!("\nTrimmed:\n- {}", trimmed.join("\n- ")));
+    }
+    AppError::PromptTooLarge(msg)
+}
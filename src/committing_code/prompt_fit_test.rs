@@ -0,0 +1,177 @@
+use super::prompt_fit::{fit_initial_prompt, fit_prompt, fit_repair_prompt, summarize_build_log};
+use super::{build_initial_prompt, build_repair_prompt};
+use crate::app_error::AppError;
+use crate::config::Config;
+use crate::llm::tokens::estimate_tokens;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+fn file_section(path: &str, lines: usize) -> String {
+    let mut s = format!("--- {path} ---\n");
+    for i in 0..lines {
+        s.push_str(&format!("let value_{i} = compute_something({i});\n"));
+    }
+    s.push('\n');
+    s
+}
+
+fn test_config(query: &str) -> Config {
+    Config {
+        query: query.to_string(),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_initial_prompt_untouched_when_it_fits() {
+    let codebase = file_section("src/a.rs", 5);
+    let fitted = fit_initial_prompt("query", &codebase, 1_000_000).unwrap();
+    assert!(fitted.trimmed.is_empty());
+    assert!(fitted.prompt.contains("--- src/a.rs ---"));
+}
+
+#[test]
+fn test_initial_prompt_drops_unmentioned_files_first() {
+    let parser = file_section("src/parser/mod.rs", 200);
+    let other = file_section("src/other.rs", 50);
+    let codebase = format!("{parser}{}{other}", file_section("src/unrelated.rs", 200));
+    let query = "Please fix the parser";
+    let limit = estimate_tokens(&build_initial_prompt(query, &format!("{parser}{other}"))) + 5;
+    let fitted = fit_initial_prompt(query, &codebase, limit).unwrap();
+
+    assert!(fitted.prompt.contains("--- src/parser/mod.rs ---"));
+    assert!(!fitted.prompt.contains("--- src/unrelated.rs ---"));
+    // The larger unrelated file goes first, and that alone was enough.
+    assert!(fitted.prompt.contains("--- src/other.rs ---"));
+    assert_eq!(fitted.trimmed.len(), 1);
+    assert!(fitted.trimmed[0].contains("src/unrelated.rs"));
+}
+
+#[test]
+fn test_initial_prompt_trims_sections_embedded_in_query() {
+    let query = format!("[codebase]\n{}", file_section("src/big.rs", 400));
+    let fitted = fit_initial_prompt(&query, "", 200).unwrap();
+    assert!(!fitted.prompt.contains("src/big.rs"));
+    assert!(fitted.prompt.starts_with("[codebase]\n"));
+}
+
+#[test]
+fn test_prompt_sent_as_is_drops_its_own_sections() {
+    let kept = format!(
+        "[target user specification]\nmention src/kept.rs\n[codebase]\n{}",
+        file_section("src/kept.rs", 20)
+    );
+    let prompt = format!("{kept}{}", file_section("src/big.rs", 400));
+    let fitted = fit_prompt(&prompt, estimate_tokens(&kept) + 5).unwrap();
+    assert!(fitted.prompt.contains("--- src/kept.rs ---"));
+    assert!(!fitted.prompt.contains("src/big.rs"));
+    assert!(fitted.prompt.starts_with("[target user specification]\n"));
+    assert_eq!(fitted.trimmed.len(), 1);
+}
+
+#[test]
+fn test_initial_prompt_too_large_even_after_trimming() {
+    let query = "word ".repeat(1_000);
+    let result = fit_initial_prompt(&query, &file_section("src/a.rs", 10), 100);
+    match result {
+        Err(AppError::PromptTooLarge(msg)) => assert!(msg.contains("src/a.rs")),
+        _ => panic!("expected PromptTooLarge"),
+    }
+}
+
+#[test]
+fn test_repair_prompt_deduplicates_before_anything_else() {
+    let codebase = format!(
+        "{}{}",
+        file_section("src/a.rs", 100),
+        file_section("src/b.rs", 100)
+    );
+    let a_content = codebase
+        .split("--- src/b.rs ---")
+        .next()
+        .unwrap()
+        .trim_start_matches("--- src/a.rs ---\n")
+        .to_string();
+    let mut replacements: HashMap<PathBuf, Option<String>> = HashMap::new();
+    replacements.insert(PathBuf::from("src/a.rs"), Some(a_content));
+    replacements.insert(PathBuf::from("src/b.rs"), Some("fn b() {}".to_string()));
+
+    let config = test_config("query");
+    let build_output = "error: something\n";
+    let full = fit_repair_prompt(&config, build_output, &replacements, &codebase, u64::MAX)
+        .unwrap()
+        .prompt;
+    let fitted = fit_repair_prompt(
+        &config,
+        build_output,
+        &replacements,
+        &codebase,
+        estimate_tokens(&full) - 10,
+    )
+    .unwrap();
+
+    assert_eq!(fitted.trimmed.len(), 2);
+    assert!(fitted.trimmed[0].contains("file replacement for src/a.rs"));
+    assert!(fitted.trimmed[1].contains("stale codebase copy of src/b.rs"));
+    assert!(fitted.prompt.contains("--- src/a.rs ---"));
+    assert!(!fitted.prompt.contains("--- FILE REPLACEMENT src/a.rs ---"));
+    assert!(!fitted.prompt.contains("--- src/b.rs ---"));
+    assert!(fitted.prompt.contains("--- FILE REPLACEMENT src/b.rs ---"));
+}
+
+#[test]
+fn test_repair_prompt_summarizes_build_log_then_drops_files() {
+    let mut build_output = String::from("EXIT CODE: 101\n");
+    for i in 0..500 {
+        build_output.push_str(&format!("   Compiling crate_{i} v1.0.0\n"));
+    }
+    build_output.push_str("error[E0425]: cannot find value `x` in src/keep.rs\n");
+    for i in 0..100 {
+        build_output.push_str(&format!("note line {i}\n"));
+    }
+
+    let codebase = format!(
+        "{}{}",
+        file_section("src/keep.rs", 50),
+        file_section("src/drop.rs", 300)
+    );
+    let replacements = HashMap::new();
+    let config = test_config("query");
+    let limit = estimate_tokens(&build_repair_prompt(
+        &config,
+        &summarize_build_log(&build_output),
+        &replacements,
+        &file_section("src/keep.rs", 50),
+    )) + 5;
+
+    let fitted =
+        fit_repair_prompt(&config, &build_output, &replacements, &codebase, limit).unwrap();
+    assert_eq!(fitted.trimmed.len(), 2);
+    assert!(fitted.trimmed[0].starts_with("summarized build output"));
+    assert!(fitted.trimmed[1].contains("src/drop.rs"));
+    assert!(fitted.prompt.contains("error[E0425]"));
+    assert!(fitted.prompt.contains("--- src/keep.rs ---"));
+    assert!(estimate_tokens(&fitted.prompt) <= limit);
+}
+
+#[test]
+fn test_summarize_build_log_keeps_errors_and_edges() {
+    let mut log = String::new();
+    for i in 0..100 {
+        log.push_str(&format!("line {i}\n"));
+    }
+    log.push_str("warning: unused variable\n");
+    for i in 100..200 {
+        log.push_str(&format!("line {i}\n"));
+    }
+
+    let summary = summarize_build_log(&log);
+    assert!(summary.contains("line 0\n"));
+    assert!(summary.contains("line 199\n"));
+    assert!(summary.contains("warning: unused variable\nline 100\nline 101\nline 102\n"));
+    assert!(summary.contains("lines omitted"));
+    assert!(!summary.contains("line 50\n"));
+
+    let short = "EXIT CODE: 1\nerror\n";
+    assert_eq!(summarize_build_log(short), short);
+}
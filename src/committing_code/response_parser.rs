@@ -1,4 +1,5 @@
 use crate::app_error::AppError;
+use crate::llm::schema;
 use std::path::PathBuf;
 
 #[derive(Debug, PartialEq, Clone)]
@@ -41,7 +42,12 @@ pub(crate) fn parse_llm_response(text: &str) -> Result<Vec<FileUpdate>, AppError
     Ok(updates)
 }
 
+/// Reads the file list from a JSON `{"files": [...]}` reply, falling back to
+/// `%%%files ... %%%end` blocks when the reply is free text.
 pub(crate) fn parse_extra_files_response(text: &str) -> Result<Vec<PathBuf>, AppError> {
+    if let Some(files) = schema::parse_file_list(text) {
+        return Ok(files);
+    }
     let mut files = Vec::new();
     let lines = text.lines();
     let mut inside_block = false;
@@ -31,3 +31,9 @@ fn ignores_whitespace() {
     assert_eq!(files.len(), 1);
     assert_eq!(files[0], PathBuf::from("src/a.rs"));
 }
+
+#[test]
+fn parses_json_file_list() {
+    let files = parse_extra_files_response(r#"{"files": ["src/a.rs", ""]}"#).expect("should parse");
+    assert_eq!(files, vec![PathBuf::from("src/a.rs")]);
+}
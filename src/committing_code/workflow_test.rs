@@ -1,12 +1,14 @@
 use super::agent_actions::AgentActions;
-use super::run_with_actions;
+use super::{build_repair_prompt, run_with_actions};
 use crate::app_error::{AppError, BuildFailure};
 use crate::cli::Model;
 use crate::config::Config;
+use crate::llm::{Conversation, Session};
 use crate::logger::Logger;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::fs;
 use std::future::Future;
+use std::path::PathBuf;
 use std::pin::Pin;
 use std::sync::Mutex;
 use tempfile::tempdir;
@@ -39,18 +41,22 @@ impl AgentActions for MockAgentActions {
         &'a self,
         _model: Model,
         _api_key: String,
-        prompt: String,
+        _session: &'a Session,
+        conversation: &'a mut Conversation,
         _logger: &'a Logger,
         _log_prefix: String,
     ) -> Pin<Box<dyn Future<Output = Result<String, AppError>> + Send + 'a>> {
         let mut prompts = self.captured_prompts.lock().unwrap();
-        prompts.push(prompt);
+        prompts.push(conversation.transcript());
 
         let mut responses = self.llm_responses.lock().unwrap();
         if responses.is_empty() {
             panic!("Mock query_llm called more times than expected. Log prefix: {_log_prefix}");
         }
         let res = responses.pop_front().unwrap();
+        if let Ok(text) = &res {
+            conversation.push_assistant(text.clone());
+        }
         Box::pin(async move { res })
     }
 
@@ -65,10 +71,11 @@ impl AgentActions for MockAgentActions {
 
 fn create_test_config() -> Config {
     Config {
-        model: Model::Gpt5,
+        model: Model::from_str("gpt-5.2").unwrap(),
         api_key: "test-key".to_string(),
         query: "Fix the bug".to_string(),
         system_prompts: "".to_string(),
+        ..Default::default()
     }
 }
 
@@ -365,7 +372,11 @@ async fn test_repair_propt_shows_deletion() {
     assert!(prompts.len() >= 3);
     let repair_prompt = &prompts[2];
 
-    assert!(repair_prompt.contains("--- FILE REMOVED src/delete_me.rs ---"));
+    // The repair continues the conversation: the deletion is visible in the
+    // previous assistant turn and the codebase is not sent a second time.
+    assert!(repair_prompt.contains("=== assistant ===\n^^^src/delete_me.rs\n^^^delete"));
+    assert!(repair_prompt.contains("[build.sh output]\nerror"));
+    assert_eq!(repair_prompt.matches("fn old() {}").count(), 1);
 }
 
 #[tokio::test]
@@ -424,12 +435,15 @@ async fn test_repair_prompt_accumulates_updates() {
     assert!(prompts.len() >= 5);
 
     let repair_1 = &prompts[2];
-    assert!(repair_1.contains("--- FILE REPLACEMENT src/file1.rs ---"));
+    assert!(repair_1.contains("^^^src/file1.rs"));
     assert!(!repair_1.contains("src/file2.rs"));
 
     let repair_2 = &prompts[4];
-    assert!(repair_2.contains("--- FILE REPLACEMENT src/file1.rs ---"));
-    assert!(repair_2.contains("--- FILE REPLACEMENT src/file2.rs ---"));
+    assert!(repair_2.contains("^^^src/file1.rs"));
+    assert!(repair_2.contains("^^^src/file2.rs"));
+    assert!(repair_2.contains("[build.sh output]\ne1"));
+    assert!(repair_2.contains("[build.sh output]\ne2"));
+    assert_eq!(repair_2.matches("=== user ===").count(), 3);
 }
 
 #[tokio::test]
@@ -559,23 +573,125 @@ async fn test_repair_prompt_latest_version_only() {
     let prompts = actions.get_captured_prompts();
     // Prompt 0: Initial
     // Prompt 1: Extra
-    // Prompt 2: Repair 1 (contains Version 1)
+    // Prompt 2: Repair 1 (follows Version 1)
     // Prompt 3: Extra
-    // Prompt 4: Repair 2 (contains Version 2, should NOT contain Version 1)
+    // Prompt 4: Repair 2 (follows Version 2)
 
     assert!(prompts.len() >= 5);
     let repair_prompt = &prompts[4];
 
-    // It should contain the latest replacement
-    assert!(repair_prompt.contains("--- FILE REPLACEMENT src/foo.rs ---"));
-    assert!(repair_prompt.contains("Version 2"));
+    // The latest turn only carries the newest build output.
+    let latest_turn = repair_prompt.rsplit("=== user ===").next().unwrap();
+    assert!(latest_turn.contains("fail 2"));
+    assert!(!latest_turn.contains("fail 1"));
+    assert!(!latest_turn.contains("Version"));
+}
+
+#[test]
+fn test_full_repair_prompt_shows_latest_replacements_and_removals() {
+    // The self-contained repair prompt is used when the conversation no longer
+    // fits the model's input limit.
+    let config = create_test_config();
+    let mut replacements = HashMap::new();
+    replacements.insert(
+        PathBuf::from("src/foo.rs"),
+        Some("fn foo() { \"Version 2\" }".to_string()),
+    );
+    replacements.insert(PathBuf::from("src/delete_me.rs"), None);
+
+    let prompt = build_repair_prompt(&config, "fail 2", &replacements, "--- src/main.rs ---\n");
+
+    assert!(prompt.contains("--- FILE REMOVED src/delete_me.rs ---"));
+    assert!(prompt.contains("--- FILE REPLACEMENT src/foo.rs ---"));
+    assert!(prompt.contains("Version 2"));
+    assert_eq!(
+        prompt
+            .match_indices("--- FILE REPLACEMENT src/foo.rs ---")
+            .count(),
+        1
+    );
+    assert!(prompt.starts_with("[supervisor query]\nFix the bug"));
+    assert!(prompt.ends_with("[build.sh output]\nfail 2"));
+}
 
-    // It should NOT contain the old replacement
-    assert!(!repair_prompt.contains("Version 1"));
+#[tokio::test]
+async fn test_repair_falls_back_to_full_prompt_when_conversation_is_too_large() {
+    let dir = tempdir().unwrap();
+    let logger = Logger::new_with_root(dir.path(), "test").unwrap();
+    let config = create_test_config();
 
-    // Verify no duplicate headers
-    let matches: Vec<_> = repair_prompt
-        .match_indices("--- FILE REPLACEMENT src/foo.rs ---")
-        .collect();
-    assert_eq!(matches.len(), 1);
+    // Two large files that together nearly fill GPT 5.2's input limit, so the
+    // initial prompt fits but a follow-up turn on top of it does not.
+    let big_file = "word ".repeat(133_000);
+    let codebase = format!("--- src/a.rs ---\n{big_file}\n--- src/b.rs ---\n{big_file}\n");
+
+    let caret_block = "^^^";
+    let end_block = "^^^end";
+    let response_1 = format!("{caret_block}src/main.rs\nfn main() {{ broken }}\n{end_block}");
+    let response_2 = format!("{caret_block}src/main.rs\nfn main() {{}}\n{end_block}");
+
+    let actions = MockAgentActions::new(
+        vec![Ok(response_1), Ok("".to_string()), Ok(response_2)],
+        vec![
+            Err(BuildFailure {
+                output: "err ".repeat(4_000),
+            }),
+            Ok("EXIT CODE: 0".to_string()),
+        ],
+    );
+
+    let result = run_with_actions(&logger, &config, codebase, &actions, dir.path()).await;
+    assert!(result.is_ok());
+
+    let prompts = actions.get_captured_prompts();
+    let repair_prompt = &prompts[2];
+    assert!(!repair_prompt.contains("=== assistant ==="));
+    assert!(repair_prompt.contains("--- FILE REPLACEMENT src/main.rs ---"));
+    // The codebase and the build output are separate turns, so the first turn
+    // can be cached across repair prompts.
+    let turns: Vec<&str> = repair_prompt.split("=== user ===\n").skip(1).collect();
+    assert_eq!(turns.len(), 2);
+    assert!(turns[0].contains("[codebase]\n"));
+    assert!(!turns[0].contains("[build.sh output]"));
+    assert!(turns[1].starts_with("[file replacements]\n"));
+}
+
+#[tokio::test]
+async fn test_instructions_are_sent_as_system_prompt() {
+    let dir = tempdir().unwrap();
+    let logger = Logger::new_with_root(dir.path(), "test").unwrap();
+    let config = create_test_config();
+    let codebase = "--- src/main.rs ---\nfn main() {}\n".to_string();
+
+    let actions = MockAgentActions::new(
+        vec![
+            Ok("^^^src/main.rs\nbroken\n^^^end".to_string()),
+            Ok("".to_string()),
+            Ok("^^^src/main.rs\nfn main() {}\n^^^end".to_string()),
+        ],
+        vec![
+            Err(BuildFailure {
+                output: "error".to_string(),
+            }),
+            Ok("EXIT CODE: 0".to_string()),
+        ],
+    );
+
+    run_with_actions(&logger, &config, codebase, &actions, dir.path())
+        .await
+        .unwrap();
+
+    let prompts = actions.get_captured_prompts();
+    let initial = &prompts[0];
+    let (system, user) = initial.split_once("=== user ===\n").unwrap();
+    assert!(system.starts_with("=== system ===\n"));
+    assert!(system.contains(crate::system_prompts::PROJECT_STRUCTURE.trim()));
+    assert!(system.contains(crate::system_prompts::CODE_MODIFICATION_INSTRUCTIONS.trim()));
+    assert!(user.starts_with("[supervisor query]\nFix the bug\n[codebase]\n"));
+    assert!(!user.contains(crate::system_prompts::PROJECT_STRUCTURE.trim()));
+
+    let extra = &prompts[1];
+    let (system, user) = extra.split_once("=== user ===\n").unwrap();
+    assert!(system.contains(crate::system_prompts::COMMITTING_CODE_EXTRA_CODE_QUERY.trim()));
+    assert!(user.starts_with("[codebase file list]\nsrc/main.rs\n"));
 }
@@ -0,0 +1,182 @@
+use super::settings::KeyCommands;
+use crate::app_error::AppError;
+use crate::llm::api::censor_api_key;
+use crate::llm::models::Model;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Looks up an environment variable. Tests pass their own so that they do not
+/// depend on, or change, the real environment.
+pub(crate) type EnvLookup<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+/// Where a model's API key comes from, in order of precedence.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum KeySource {
+    /// A non-empty environment variable, such as `GEMINI_API_KEY`.
+    Env(&'static str),
+    /// A shell command from `[key_command]` in the settings file; the key is
+    /// the first line of its output.
+    Command {
+        provider: &'static str,
+        command: String,
+    },
+    /// The model's key file, relative to the project directory.
+    File(PathBuf),
+}
+
+impl KeySource {
+    pub(crate) fn describe(&self) -> String {
+        match self {
+            KeySource::Env(name) => format!("the {name} environment variable"),
+            KeySource::Command { provider, .. } => {
+                format!("key_command.{provider} in agent-config/config.toml")
+            }
+            KeySource::File(path) => path.display().to_string(),
+        }
+    }
+}
+
+/// The first source that has a key for `model`. Nothing is read or run yet,
+/// so this is safe for listing models.
+pub(crate) fn find_key_source(
+    base_dir: &Path,
+    model: Model,
+    commands: &KeyCommands,
+    env: EnvLookup,
+) -> Option<KeySource> {
+    let spec = model.spec();
+    if env(spec.key_env).is_some_and(|value| !value.trim().is_empty()) {
+        return Some(KeySource::Env(spec.key_env));
+    }
+    let provider = spec.provider.name();
+    if let Some(command) = commands.for_provider(spec.provider) {
+        return Some(KeySource::Command {
+            provider,
+            command: command.to_string(),
+        });
+    }
+    let file = key_file(model);
+    base_dir
+        .join(&file)
+        .is_file()
+        .then_some(KeySource::File(file))
+}
+
+fn key_file(model: Model) -> PathBuf {
+    PathBuf::from("agent-config").join(model.spec().key_file)
+}
+
+/// Reads API keys for a run. A key command runs at most once, even when the
+/// requested model and a fallback share it.
+pub(crate) struct KeyReader<'a> {
+    base_dir: &'a Path,
+    commands: &'a KeyCommands,
+    env: EnvLookup<'a>,
+    /// A replay never contacts the provider, so a missing key is allowed.
+    replay: bool,
+    read: HashMap<KeySource, String>,
+}
+
+impl<'a> KeyReader<'a> {
+    pub(crate) fn new(
+        base_dir: &'a Path,
+        commands: &'a KeyCommands,
+        env: EnvLookup<'a>,
+        replay: bool,
+    ) -> Self {
+        Self {
+            base_dir,
+            commands,
+            env,
+            replay,
+            read: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn read(&mut self, model: Model) -> Result<String, AppError> {
+        let Some(source) = find_key_source(self.base_dir, model, self.commands, self.env) else {
+            if self.replay {
+                return Ok(String::new());
+            }
+            return Err(not_found_error(model));
+        };
+        if let Some(key) = self.read.get(&source) {
+            return Ok(key.clone());
+        }
+        let key = match &source {
+            KeySource::Env(name) => (self.env)(name).unwrap_or_default().trim().to_string(),
+            KeySource::Command { provider, command } => run_key_command(provider, command)?,
+            KeySource::File(path) => read_key_file(self.base_dir, path)?,
+        };
+        self.read.insert(source, key.clone());
+        Ok(key)
+    }
+}
+
+fn not_found_error(model: Model) -> AppError {
+    let spec = model.spec();
+    AppError::Config(format!(
+        "No API key found for {}. Keys are looked up in this order: the {} environment variable, key_command.{} in agent-config/config.toml, then {}.",
+        spec.id,
+        spec.key_env,
+        spec.provider.name(),
+        key_file(model).display()
+    ))
+}
+
+fn run_key_command(provider: &str, command: &str) -> Result<String, AppError> {
+    let setting = format!("key_command.{provider} in agent-config/config.toml");
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .output()
+        .map_err(|e| AppError::Config(format!("Failed to run {setting}: {e}")))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let key = stdout.lines().next().unwrap_or("").trim().to_string();
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(AppError::Config(format!(
+            "{setting} failed with {}: {}",
+            output.status,
+            censor_api_key(stderr.trim(), &key)
+        )));
+    }
+    if key.is_empty() {
+        return Err(AppError::Config(format!(
+            "{setting} printed no key on its first line."
+        )));
+    }
+    Ok(key)
+}
+
+fn read_key_file(base_dir: &Path, rel_path: &Path) -> Result<String, AppError> {
+    let full_path = base_dir.join(rel_path);
+    if is_readable_by_others(&full_path) {
+        eprintln!(
+            "Warning: {0} can be read by other users; run `chmod 600 {0}` to restrict it.",
+            rel_path.display()
+        );
+    }
+    let key = fs::read_to_string(&full_path).map_err(|e| {
+        AppError::Config(format!(
+            "Failed to read file '{}': {}",
+            rel_path.display(),
+            e
+        ))
+    })?;
+    Ok(key.trim().to_string())
+}
+
+/// Whether the group or other users may read the file.
+#[cfg(unix)]
+pub(crate) fn is_readable_by_others(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.permissions().mode() & 0o044 != 0)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn is_readable_by_others(_path: &Path) -> bool {
+    false
+}
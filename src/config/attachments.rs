@@ -0,0 +1,127 @@
+use crate::app_error::AppError;
+use crate::path_filter::PathFilter;
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+/// Attachments larger than this are refused rather than sent.
+const MAX_ATTACHMENT_BYTES: u64 = 1_000_000;
+
+/// The `--attach` value that reads standard input.
+pub(crate) const STDIN_ATTACHMENT: &str = "-";
+
+/// A file or piped input sent along with the supervisor query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    /// The path relative to the project root, the path as given for a file
+    /// outside the project, or `stdin`.
+    pub label: String,
+    pub content: String,
+}
+
+/// Reads each `--attach` path, in order, relative to `base_dir`. Files inside
+/// the project must pass `PathFilter`; files outside it are refused unless
+/// `allow_external` is set. `-` reads `stdin`, which is `None` when nothing
+/// was piped in.
+pub(crate) fn load_attachments(
+    paths: &[PathBuf],
+    base_dir: &Path,
+    allow_external: bool,
+    mut stdin: Option<&mut dyn Read>,
+) -> Result<Vec<Attachment>, AppError> {
+    let mut attachments = Vec::new();
+    for path in paths {
+        if path == Path::new(STDIN_ATTACHMENT) {
+            let input = stdin.take().ok_or_else(|| {
+                AppError::Config(
+                    "--attach - reads standard input, but nothing was piped in.".to_string(),
+                )
+            })?;
+            let mut bytes = Vec::new();
+            input
+                .take(MAX_ATTACHMENT_BYTES + 1)
+                .read_to_end(&mut bytes)
+                .map_err(AppError::Io)?;
+            attachments.push(Attachment {
+                label: "stdin".to_string(),
+                content: text("standard input", bytes)?,
+            });
+            continue;
+        }
+        attachments.push(load_file(path, base_dir, allow_external)?);
+    }
+    Ok(attachments)
+}
+
+fn load_file(path: &Path, base_dir: &Path, allow_external: bool) -> Result<Attachment, AppError> {
+    let shown = path.display().to_string();
+    let full_path = base_dir.join(path);
+    let resolved = full_path
+        .canonicalize()
+        .ok()
+        .filter(|p| p.is_file())
+        .ok_or_else(|| AppError::Config(format!("Attachment '{shown}' is not a file.")))?;
+    let root = base_dir.canonicalize().map_err(AppError::Io)?;
+
+    let label = match resolved.strip_prefix(&root) {
+        Ok(relative) => {
+            // `.gitignore` does not cover `.git`, which can hold credentials.
+            let reason = if relative.components().next() == Some(Component::Normal(".git".as_ref())) {
+                Some("files in .git are never sent.".to_string())
+            } else {
+                match PathFilter::new_for_base_dir(base_dir)?.validate(relative) {
+                    Ok(()) => None,
+                    Err(AppError::FileUpdate(msg)) => Some(msg),
+                    Err(e) => Some(e.to_string()),
+                }
+            };
+            if let Some(reason) = reason {
+                return Err(AppError::Config(format!(
+                    "Attachment '{shown}' may not be sent: {reason}"
+                )));
+            }
+            relative.display().to_string()
+        }
+        Err(_) if allow_external => shown.clone(),
+        Err(_) => {
+            return Err(AppError::Config(format!(
+                "Attachment '{shown}' is outside the project. Pass --allow-external-attachments to send files from outside it."
+            )))
+        }
+    };
+
+    let bytes = fs::metadata(&resolved).map_err(AppError::Io)?.len();
+    if bytes > MAX_ATTACHMENT_BYTES {
+        return Err(AppError::Config(format!(
+            "Attachment '{shown}' is {bytes} bytes, over the {MAX_ATTACHMENT_BYTES} byte limit."
+        )));
+    }
+    let content = text(&format!("Attachment '{shown}'"), fs::read(&resolved)?)?;
+    Ok(Attachment { label, content })
+}
+
+fn text(source: &str, bytes: Vec<u8>) -> Result<String, AppError> {
+    if bytes.len() as u64 > MAX_ATTACHMENT_BYTES {
+        return Err(AppError::Config(format!(
+            "{source} is over the {MAX_ATTACHMENT_BYTES} byte limit for attachments."
+        )));
+    }
+    String::from_utf8(bytes).map_err(|_| AppError::Config(format!("{source} is not UTF-8 text.")))
+}
+
+/// The `[supervisor attachments]` section, or an empty string when there are
+/// no attachments.
+pub(crate) fn render_attachments(attachments: &[Attachment]) -> String {
+    if attachments.is_empty() {
+        return String::new();
+    }
+    let mut section = String::from("[supervisor attachments]\n");
+    for attachment in attachments {
+        section.push_str(&format!("=== Attachment: {} ===\n", attachment.label));
+        section.push_str(&attachment.content);
+        if !attachment.content.ends_with('\n') {
+            section.push('\n');
+        }
+    }
+    section
+}
@@ -1,6 +1,9 @@
 use crate::app_error::AppError;
-use crate::cli::{CliArgs, Model, Workflow};
-use crate::config::Config;
+use crate::cli::{CliArgs, ContextMode, Model, QuerySource, Workflow};
+use crate::config::{is_readable_by_others, Config};
+use crate::llm::generation::{CallRole, ReasoningEffort};
+use crate::llm::models::Provider;
+use crate::logger::PastQuery;
 use crate::system_prompts::COMMITTING_CODE_INITIAL_QUERY;
 use std::fs::File;
 use std::io::Write;
@@ -14,10 +17,11 @@ fn test_load_from_dir_commit_code_success() {
     setup_valid_environment(base_path, "gemini-key.txt", "secret-key");
 
     let args = CliArgs {
-        model: Model::Gemini3Pro,
+        model: Model::from_str("gemini-3-pro-preview").unwrap(),
         workflow: Workflow::CommitCode,
         force: false,
         rollup_full: false,
+        ..Default::default()
     };
 
     let query = "my query".to_string();
@@ -38,10 +42,11 @@ fn test_load_from_dir_gemini_2_5_pro_success() {
     setup_valid_environment(base_path, "gemini-key.txt", "secret-2.5");
 
     let args = CliArgs {
-        model: Model::Gemini2_5Pro,
+        model: Model::from_str("gemini-2.5-pro").unwrap(),
         workflow: Workflow::CommitCode,
         force: false,
         rollup_full: false,
+        ..Default::default()
     };
 
     let query = "query".to_string();
@@ -58,10 +63,11 @@ fn test_load_from_dir_consistency_check_success() {
     setup_valid_environment(base_path, "gemini-key.txt", "secret-key");
 
     let args = CliArgs {
-        model: Model::Gemini3Pro,
+        model: Model::from_str("gemini-3-pro-preview").unwrap(),
         workflow: Workflow::ConsistencyCheck,
         force: false,
         rollup_full: false,
+        ..Default::default()
     };
 
     let query = "consistency query".to_string();
@@ -78,10 +84,11 @@ fn test_load_from_dir_auto_workflow_success() {
     setup_valid_environment(base_path, "gemini-key.txt", "gemini-secret-auto");
 
     let args = CliArgs {
-        model: Model::Gemini3Pro,
+        model: Model::from_str("gemini-3-pro-preview").unwrap(),
         workflow: Workflow::Auto,
         force: false,
         rollup_full: false,
+        ..Default::default()
     };
 
     let query = "".to_string();
@@ -99,10 +106,11 @@ fn test_load_from_dir_gpt5_success() {
     setup_valid_environment(base_path, "openai-key.txt", "openai-secret");
 
     let args = CliArgs {
-        model: Model::Gpt5,
+        model: Model::from_str("gpt-5.2").unwrap(),
         workflow: Workflow::CommitCode,
         force: false,
         rollup_full: false,
+        ..Default::default()
     };
 
     let query = "gpt query".to_string();
@@ -118,10 +126,11 @@ fn test_load_from_dir_rollup_returns_error() {
     setup_valid_environment(base_path, "gemini-key.txt", "key");
 
     let args = CliArgs {
-        model: Model::Gemini3Pro,
+        model: Model::from_str("gemini-3-pro-preview").unwrap(),
         workflow: Workflow::Rollup,
         force: false,
         rollup_full: false,
+        ..Default::default()
     };
 
     let result = Config::load_from_dir(&args, base_path, "".to_string());
@@ -135,10 +144,11 @@ fn test_load_from_dir_init_returns_error() {
     setup_valid_environment(base_path, "gemini-key.txt", "key");
 
     let args = CliArgs {
-        model: Model::Gemini3Pro,
+        model: Model::from_str("gemini-3-pro-preview").unwrap(),
         workflow: Workflow::Init("proj".to_string()),
         force: false,
         rollup_full: false,
+        ..Default::default()
     };
 
     let result = Config::load_from_dir(&args, base_path, "".to_string());
@@ -151,10 +161,11 @@ fn test_load_from_dir_missing_gitignore() {
     let base_path = temp_dir.path();
 
     let args = CliArgs {
-        model: Model::Gemini3Pro,
+        model: Model::from_str("gemini-3-pro-preview").unwrap(),
         workflow: Workflow::CommitCode,
         force: false,
         rollup_full: false,
+        ..Default::default()
     };
 
     let result = Config::load_from_dir(&args, base_path, "query".to_string());
@@ -171,16 +182,638 @@ fn test_load_from_dir_insecure_gitignore() {
     writeln!(gitignore_file, "target/").unwrap();
 
     let args = CliArgs {
-        model: Model::Gemini3Pro,
+        model: Model::from_str("gemini-3-pro-preview").unwrap(),
         workflow: Workflow::CommitCode,
         force: false,
         rollup_full: false,
+        ..Default::default()
     };
 
     let result = Config::load_from_dir(&args, base_path, "query".to_string());
     assert!(matches!(result, Err(AppError::Config(_))));
 }
 
+#[test]
+fn test_load_from_dir_budget_from_settings_and_flags() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path();
+    setup_valid_environment(base_path, "gemini-key.txt", "secret-key");
+    std::fs::write(
+        base_path.join("agent-config/config.toml"),
+        "max_cost = 3.5\nmax_tokens = 100000\n",
+    )
+    .unwrap();
+
+    let args = CliArgs {
+        model: Model::from_str("gemini-3-pro-preview").unwrap(),
+        workflow: Workflow::Auto,
+        ..Default::default()
+    };
+    let config = Config::load_from_dir(&args, base_path, String::new()).unwrap();
+    let limits = config.session.budget.limits();
+    assert_eq!(limits.max_cost, Some(3.5));
+    assert_eq!(limits.max_tokens, Some(100_000));
+
+    // Command line flags override the settings file.
+    let args = CliArgs {
+        model: Model::from_str("gemini-3-pro-preview").unwrap(),
+        workflow: Workflow::Auto,
+        max_cost: Some(1.0),
+        ..Default::default()
+    };
+    let config = Config::load_from_dir(&args, base_path, String::new()).unwrap();
+    let limits = config.session.budget.limits();
+    assert_eq!(limits.max_cost, Some(1.0));
+    assert_eq!(limits.max_tokens, Some(100_000));
+}
+
+#[test]
+fn test_load_from_dir_stream_from_settings_and_flags() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path();
+    setup_valid_environment(base_path, "gemini-key.txt", "secret-key");
+
+    let args = CliArgs {
+        model: Model::from_str("gemini-3-pro-preview").unwrap(),
+        workflow: Workflow::Auto,
+        ..Default::default()
+    };
+    let config = Config::load_from_dir(&args, base_path, String::new()).unwrap();
+    assert!(!config.session.stream);
+
+    std::fs::write(
+        base_path.join("agent-config/config.toml"),
+        "stream = true\n",
+    )
+    .unwrap();
+    let config = Config::load_from_dir(&args, base_path, String::new()).unwrap();
+    assert!(config.session.stream);
+
+    // --no-stream overrides the settings file.
+    let args = CliArgs {
+        model: Model::from_str("gemini-3-pro-preview").unwrap(),
+        workflow: Workflow::Auto,
+        stream: Some(false),
+        ..Default::default()
+    };
+    let config = Config::load_from_dir(&args, base_path, String::new()).unwrap();
+    assert!(!config.session.stream);
+}
+
+#[test]
+fn test_load_from_dir_call_timeout() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path();
+    setup_valid_environment(base_path, "gemini-key.txt", "secret-key");
+    let args = CliArgs {
+        workflow: Workflow::Auto,
+        ..Default::default()
+    };
+    let config = Config::load_from_dir(&args, base_path, String::new()).unwrap();
+    assert_eq!(
+        config.session.call_timeout,
+        Some(std::time::Duration::from_secs(30 * 60))
+    );
+
+    std::fs::write(
+        base_path.join("agent-config/config.toml"),
+        "call_timeout = 120\n",
+    )
+    .unwrap();
+    let config = Config::load_from_dir(&args, base_path, String::new()).unwrap();
+    assert_eq!(
+        config.session.call_timeout,
+        Some(std::time::Duration::from_secs(120))
+    );
+
+    let args = CliArgs {
+        workflow: Workflow::Auto,
+        call_timeout: Some(45),
+        ..Default::default()
+    };
+    let config = Config::load_from_dir(&args, base_path, String::new()).unwrap();
+    assert_eq!(
+        config.session.call_timeout,
+        Some(std::time::Duration::from_secs(45))
+    );
+
+    std::fs::write(
+        base_path.join("agent-config/config.toml"),
+        "call_timeout = 0\n",
+    )
+    .unwrap();
+    let args = CliArgs {
+        workflow: Workflow::Auto,
+        ..Default::default()
+    };
+    assert!(Config::load_from_dir(&args, base_path, String::new()).is_err());
+}
+
+#[test]
+fn test_load_from_dir_max_tool_calls() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path();
+    setup_valid_environment(base_path, "gemini-key.txt", "secret-key");
+    let args = CliArgs {
+        workflow: Workflow::CommitCode,
+        tools: true,
+        ..Default::default()
+    };
+    let config = Config::load_from_dir(&args, base_path, String::new()).unwrap();
+    assert_eq!(config.session.max_tool_calls, 40);
+
+    std::fs::write(
+        base_path.join("agent-config/config.toml"),
+        "max_tool_calls = 12\n",
+    )
+    .unwrap();
+    let config = Config::load_from_dir(&args, base_path, String::new()).unwrap();
+    assert_eq!(config.session.max_tool_calls, 12);
+}
+
+#[test]
+fn test_load_from_dir_generation() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path();
+    setup_valid_environment(base_path, "gemini-key.txt", "secret-key");
+    let args = CliArgs {
+        workflow: Workflow::Auto,
+        ..Default::default()
+    };
+    let config = Config::load_from_dir(&args, base_path, String::new()).unwrap();
+    let generation = &config.session.generation;
+    assert_eq!(
+        generation
+            .resolve(Some(CallRole::Context), Some(Provider::Gemini))
+            .reasoning_effort,
+        Some(ReasoningEffort::Low)
+    );
+    assert_eq!(
+        generation
+            .resolve(Some(CallRole::SelfConsistent), Some(Provider::Gpt))
+            .reasoning_effort,
+        Some(ReasoningEffort::High)
+    );
+    assert_eq!(
+        generation
+            .resolve(Some(CallRole::Commit), None)
+            .reasoning_effort,
+        None
+    );
+
+    std::fs::write(
+        base_path.join("agent-config/config.toml"),
+        r#"
+[generation]
+max_output_tokens = 32000
+
+[generation.gpt]
+reasoning_effort = "medium"
+
+[generation.context]
+reasoning_effort = "minimal"
+
+[generation.commit.gemini]
+temperature = 0.4
+"#,
+    )
+    .unwrap();
+    let config = Config::load_from_dir(&args, base_path, String::new()).unwrap();
+    let generation = &config.session.generation;
+    let params = generation.resolve(Some(CallRole::Commit), Some(Provider::Gemini));
+    assert_eq!(params.temperature, Some(0.4));
+    assert_eq!(params.max_output_tokens, Some(32000));
+    assert_eq!(params.reasoning_effort, None);
+    let params = generation.resolve(Some(CallRole::Commit), Some(Provider::Gpt));
+    assert_eq!(params.temperature, None);
+    assert_eq!(params.reasoning_effort, Some(ReasoningEffort::Medium));
+    assert_eq!(
+        generation
+            .resolve(Some(CallRole::Context), Some(Provider::Gpt))
+            .reasoning_effort,
+        Some(ReasoningEffort::Minimal)
+    );
+    // The built-in setting for a role is kept unless the file overrides it.
+    assert_eq!(
+        generation
+            .resolve(Some(CallRole::SelfConsistent), Some(Provider::Gpt))
+            .reasoning_effort,
+        Some(ReasoningEffort::High)
+    );
+}
+
+#[test]
+fn test_load_from_dir_rejects_invalid_generation_settings() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path();
+    setup_valid_environment(base_path, "gemini-key.txt", "secret-key");
+    let args = CliArgs::default();
+    for (content, expected) in [
+        (
+            "[generation]\ntemperature = 3.0\n",
+            "generation.temperature",
+        ),
+        ("[generation]\nmax_output_tokens = 0\n", "max_output_tokens"),
+        (
+            "[generation.context]\nreasoning_effort = \"extreme\"\n",
+            "generation.context.reasoning_effort",
+        ),
+        (
+            "[generation.gpt.context]\nreasoning_effort = \"low\"\n",
+            "generation.gpt.context",
+        ),
+        ("[generation.planning]\ntemperature = 1.0\n", "planning"),
+    ] {
+        std::fs::write(base_path.join("agent-config/config.toml"), content).unwrap();
+        let result = Config::load_from_dir(&args, base_path, String::new());
+        assert!(
+            matches!(&result, Err(AppError::Config(msg)) if msg.contains(expected)),
+            "{content}: {result:?}"
+        );
+    }
+}
+
+#[test]
+fn test_load_from_dir_record_and_replay() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path();
+    setup_valid_environment(base_path, "gemini-key.txt", "secret-key");
+    let cassette_dir = base_path.join("cassette");
+
+    let args = CliArgs {
+        workflow: Workflow::CommitCode,
+        record: Some(cassette_dir.clone()),
+        ..Default::default()
+    };
+    let config = Config::load_from_dir(&args, base_path, "my query".to_string()).unwrap();
+    assert!(!config.session.cassette.unwrap().is_replay());
+    assert_eq!(
+        std::fs::read_to_string(cassette_dir.join("query.md")).unwrap(),
+        "my query"
+    );
+    std::fs::write(
+        cassette_dir.join("cassette.jsonl"),
+        "{\"key\": \"k\", \"response\": {}}\n",
+    )
+    .unwrap();
+
+    // A replay needs no API key.
+    std::fs::remove_file(base_path.join("agent-config/gemini-key.txt")).unwrap();
+    let args = CliArgs {
+        workflow: Workflow::CommitCode,
+        replay: Some(cassette_dir),
+        ..Default::default()
+    };
+    let config = Config::load_from_dir(&args, base_path, "my query".to_string()).unwrap();
+    assert!(config.session.cassette.unwrap().is_replay());
+    assert_eq!(config.api_key, "");
+}
+
+#[test]
+fn test_load_from_dir_fallback_models() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path();
+    setup_valid_environment(base_path, "gemini-key.txt", "gemini-secret");
+    std::fs::write(
+        base_path.join("agent-config/config.toml"),
+        "fallback_models = [\"gpt-5.2\"]\n",
+    )
+    .unwrap();
+    let args = CliArgs {
+        workflow: Workflow::Auto,
+        ..Default::default()
+    };
+
+    // A fallback model without its provider's key is skipped.
+    let config = Config::load_from_dir(&args, base_path, String::new()).unwrap();
+    assert!(config.session.fallbacks.is_empty());
+
+    std::fs::write(
+        base_path.join("agent-config/openai-key.txt"),
+        "openai-secret\n",
+    )
+    .unwrap();
+    let config = Config::load_from_dir(&args, base_path, String::new()).unwrap();
+    assert_eq!(config.session.fallbacks.len(), 1);
+    assert_eq!(
+        config.session.fallbacks[0].model,
+        Model::from_str("gpt-5.2").unwrap()
+    );
+    assert_eq!(config.session.fallbacks[0].api_key, "openai-secret");
+
+    std::fs::write(
+        base_path.join("agent-config/config.toml"),
+        "fallback_models = [\"gpt-4\"]\n",
+    )
+    .unwrap();
+    let result = Config::load_from_dir(&args, base_path, String::new());
+    assert!(matches!(result, Err(AppError::Config(msg)) if msg.contains("fallback_models")));
+}
+
+#[test]
+fn test_load_from_dir_key_sources_in_order() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path();
+    setup_valid_environment(base_path, "gemini-key.txt", "file-key");
+    let args = CliArgs::default();
+    let no_env = |_: &str| None;
+    let load = |env: &dyn Fn(&str) -> Option<String>| {
+        Config::load_from_dir_with_env(&args, base_path, String::new(), env)
+    };
+
+    assert_eq!(load(&no_env).unwrap().api_key, "file-key");
+
+    std::fs::write(
+        base_path.join("agent-config/config.toml"),
+        "[key_command]\ngemini = \"printf 'command-key\\nsecond line\\n'\"\n",
+    )
+    .unwrap();
+    assert_eq!(load(&no_env).unwrap().api_key, "command-key");
+
+    // A blank variable does not count.
+    let blank = |_: &str| Some("  ".to_string());
+    assert_eq!(load(&blank).unwrap().api_key, "command-key");
+
+    let env = |name: &str| (name == "GEMINI_API_KEY").then(|| "env-key\n".to_string());
+    assert_eq!(load(&env).unwrap().api_key, "env-key");
+}
+
+#[test]
+fn test_load_from_dir_missing_key_lists_sources() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path();
+    setup_valid_environment(base_path, "openai-key.txt", "unused");
+    let args = CliArgs::default();
+
+    let result = Config::load_from_dir_with_env(&args, base_path, String::new(), &|_| None);
+    let Err(AppError::Config(msg)) = result else {
+        panic!("expected a missing key error");
+    };
+    assert!(msg.contains("gemini-3-pro-preview"));
+    let env = msg.find("GEMINI_API_KEY").unwrap();
+    let command = msg.find("key_command.gemini").unwrap();
+    let file = msg.find("agent-config/gemini-key.txt").unwrap();
+    assert!(env < command && command < file);
+}
+
+#[test]
+fn test_load_from_dir_failing_key_command() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path();
+    setup_valid_environment(base_path, "gemini-key.txt", "file-key");
+    let args = CliArgs::default();
+
+    // A configured command that fails is an error, not a reason to fall back.
+    std::fs::write(
+        base_path.join("agent-config/config.toml"),
+        "[key_command]\ngemini = \"echo locked >&2; exit 3\"\n",
+    )
+    .unwrap();
+    let result = Config::load_from_dir_with_env(&args, base_path, String::new(), &|_| None);
+    assert!(matches!(result, Err(AppError::Config(msg))
+        if msg.contains("key_command.gemini") && msg.contains("locked")));
+
+    std::fs::write(
+        base_path.join("agent-config/config.toml"),
+        "[key_command]\ngemini = \"true\"\n",
+    )
+    .unwrap();
+    let result = Config::load_from_dir_with_env(&args, base_path, String::new(), &|_| None);
+    assert!(matches!(result, Err(AppError::Config(msg)) if msg.contains("printed no key")));
+}
+
+#[test]
+fn test_key_file_permissions() {
+    let temp_dir = TempDir::new().unwrap();
+    let key_path = temp_dir.path().join("gemini-key.txt");
+    std::fs::write(&key_path, "secret").unwrap();
+
+    std::fs::set_permissions(&key_path, std::fs::Permissions::from_mode(0o600)).unwrap();
+    assert!(!is_readable_by_others(&key_path));
+    std::fs::set_permissions(&key_path, std::fs::Permissions::from_mode(0o640)).unwrap();
+    assert!(is_readable_by_others(&key_path));
+    std::fs::set_permissions(&key_path, std::fs::Permissions::from_mode(0o604)).unwrap();
+    assert!(is_readable_by_others(&key_path));
+}
+
+#[test]
+fn test_load_from_dir_context_mode() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path();
+    setup_valid_environment(base_path, "gemini-key.txt", "secret-key");
+    let mut args = CliArgs::default();
+
+    let config = Config::load_from_dir(&args, base_path, String::new()).unwrap();
+    assert_eq!(config.context_mode, ContextMode::Llm);
+
+    std::fs::write(
+        base_path.join("agent-config/config.toml"),
+        "context = \"all\"\n",
+    )
+    .unwrap();
+    let config = Config::load_from_dir(&args, base_path, String::new()).unwrap();
+    assert_eq!(config.context_mode, ContextMode::All);
+
+    // The flag wins over the settings file.
+    args.context = Some(ContextMode::Auto);
+    let config = Config::load_from_dir(&args, base_path, String::new()).unwrap();
+    assert_eq!(config.context_mode, ContextMode::Auto);
+
+    std::fs::write(
+        base_path.join("agent-config/config.toml"),
+        "context = \"most\"\n",
+    )
+    .unwrap();
+    args.context = None;
+    let result = Config::load_from_dir(&args, base_path, String::new());
+    assert!(
+        matches!(result, Err(AppError::Config(msg)) if msg.contains("context in agent-config"))
+    );
+}
+
+#[test]
+fn test_load_from_dir_context_budget() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path();
+    setup_valid_environment(base_path, "gemini-key.txt", "secret-key");
+    let args = CliArgs::default();
+
+    let config = Config::load_from_dir(&args, base_path, String::new()).unwrap();
+    assert_eq!(config.context_budget, Some(200_000));
+
+    std::fs::write(
+        base_path.join("agent-config/config.toml"),
+        "context_budget = 50000\n",
+    )
+    .unwrap();
+    let config = Config::load_from_dir(&args, base_path, String::new()).unwrap();
+    assert_eq!(config.context_budget, Some(50_000));
+
+    std::fs::write(
+        base_path.join("agent-config/config.toml"),
+        "context_budget = 0\n",
+    )
+    .unwrap();
+    let result = Config::load_from_dir(&args, base_path, String::new());
+    assert!(
+        matches!(result, Err(AppError::Config(msg)) if msg.contains("context_budget in agent-config"))
+    );
+}
+
+#[test]
+fn test_load_from_dir_attachments() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path();
+    setup_valid_environment(base_path, "gemini-key.txt", "secret-key");
+    std::fs::create_dir_all(base_path.join("docs")).unwrap();
+    std::fs::write(base_path.join("docs/design.md"), "# Design\n").unwrap();
+    let outside = TempDir::new().unwrap();
+    let log = outside.path().join("failure.log");
+    std::fs::write(&log, "test failed").unwrap();
+
+    let mut args = CliArgs {
+        attach: vec!["docs/design.md".into(), log.clone()],
+        ..CliArgs::default()
+    };
+    let result = Config::load_from_dir(&args, base_path, "Fix it".to_string());
+    assert!(
+        matches!(result, Err(AppError::Config(msg)) if msg.contains("--allow-external-attachments"))
+    );
+
+    args.allow_external_attachments = true;
+    let config = Config::load_from_dir(&args, base_path, "Fix it".to_string()).unwrap();
+    assert_eq!(
+        config.supervisor_query(),
+        format!(
+            "[supervisor query]\nFix it\n[supervisor attachments]\n=== Attachment: docs/design.md ===\n# Design\n=== Attachment: {} ===\ntest failed",
+            log.display()
+        )
+    );
+
+    for blocked in [
+        "agent-config/gemini-key.txt",
+        "docs/missing.md",
+        "docs",
+        ".git/config",
+    ] {
+        std::fs::create_dir_all(base_path.join(".git")).unwrap();
+        std::fs::write(base_path.join(".git/config"), "[remote]\n").unwrap();
+        let args = CliArgs {
+            attach: vec![blocked.into()],
+            allow_external_attachments: true,
+            ..CliArgs::default()
+        };
+        let result = Config::load_from_dir(&args, base_path, String::new());
+        assert!(matches!(result, Err(AppError::Config(_))), "{blocked}");
+    }
+
+    let config =
+        Config::load_from_dir(&CliArgs::default(), base_path, "Fix it".to_string()).unwrap();
+    assert!(config.attachments.is_empty());
+    assert_eq!(config.supervisor_query(), "[supervisor query]\nFix it");
+}
+
+#[test]
+fn test_load_attachments_from_stdin() {
+    let temp_dir = TempDir::new().unwrap();
+    let paths = vec![std::path::PathBuf::from("-")];
+
+    let mut input: &[u8] = b"error: mismatched types\n";
+    let attachments =
+        super::attachments::load_attachments(&paths, temp_dir.path(), false, Some(&mut input))
+            .unwrap();
+    assert_eq!(attachments.len(), 1);
+    assert_eq!(attachments[0].label, "stdin");
+    assert_eq!(attachments[0].content, "error: mismatched types\n");
+
+    let result = super::attachments::load_attachments(&paths, temp_dir.path(), false, None);
+    assert!(matches!(result, Err(AppError::Config(msg)) if msg.contains("nothing was piped")));
+
+    let mut binary: &[u8] = &[0xff, 0xfe];
+    let result =
+        super::attachments::load_attachments(&paths, temp_dir.path(), false, Some(&mut binary));
+    assert!(matches!(result, Err(AppError::Config(msg)) if msg.contains("UTF-8")));
+}
+
+#[test]
+fn test_load_from_dir_rejects_unknown_settings() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path();
+    setup_valid_environment(base_path, "gemini-key.txt", "secret-key");
+    std::fs::write(base_path.join("agent-config/config.toml"), "max_cots = 3\n").unwrap();
+
+    let args = CliArgs::default();
+    let result = Config::load_from_dir(&args, base_path, String::new());
+    assert!(matches!(result, Err(AppError::Config(_))));
+}
+
+#[test]
+fn test_read_query_from_text_file_and_stdin() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("query.md");
+    std::fs::write(&path, "\nFix the parser\n\n").unwrap();
+
+    let read = |source: QuerySource, stdin: Option<&mut dyn std::io::Read>| {
+        super::query::read_query(&source, stdin)
+    };
+    assert_eq!(
+        read(QuerySource::Text(" Add a flag ".to_string()), None).unwrap(),
+        "Add a flag"
+    );
+    assert_eq!(
+        read(QuerySource::File(path), None).unwrap(),
+        "Fix the parser"
+    );
+    let mut input: &[u8] = b"Explain the build\n";
+    assert_eq!(
+        read(QuerySource::Stdin, Some(&mut input)).unwrap(),
+        "Explain the build"
+    );
+    assert!(matches!(
+        read(QuerySource::Stdin, None),
+        Err(AppError::Config(msg)) if msg.contains("nothing was piped")
+    ));
+    assert!(read(QuerySource::File(temp_dir.path().join("missing.md")), None).is_err());
+
+    assert_eq!(
+        super::query::require_query("Fix it".to_string()).unwrap(),
+        "Fix it"
+    );
+    assert!(matches!(
+        super::query::require_query(" \n".to_string()),
+        Err(AppError::Config(msg)) if msg.contains("The query is empty")
+    ));
+}
+
+#[test]
+fn test_editor_template_lists_recent_queries_as_comments() {
+    let history = vec![
+        PastQuery {
+            run_id: "2026-10-17-09-00-00-committing-code".to_string(),
+            query: "Add --query\n<!-- keep --> this".to_string(),
+        },
+        PastQuery {
+            run_id: "2026-10-16-09-00-00-consistency".to_string(),
+            query: "Check the docs".to_string(),
+        },
+    ];
+
+    let template = super::query::editor_template(&history);
+    assert!(template.starts_with("\n\n<!--\nWrite the supervisor query above"));
+    assert!(template.contains(
+        "Recent queries, newest first:\n\n--- 2026-10-17-09-00-00-committing-code ---\nAdd --query\n<!-- keep -- > this\n\n--- 2026-10-16-09-00-00-consistency ---\nCheck the docs\n-->\n"
+    ));
+    assert_eq!(super::query::strip_comments(&template), "");
+    assert!(!super::query::editor_template(&[]).contains("Recent queries"));
+
+    let edited = format!("Fix the <!-- inline --> parser\n\n{template}");
+    assert_eq!(super::query::strip_comments(&edited), "Fix the  parser");
+    assert_eq!(
+        super::query::strip_comments("Keep this\n<!-- unclosed"),
+        "Keep this"
+    );
+}
+
 #[test]
 fn test_get_query_from_editor_env_handling() {
     let temp_dir = TempDir::new().unwrap();
@@ -222,6 +855,14 @@ fn test_get_query_from_editor_env_handling() {
     let query = Config::get_query_from_editor().unwrap();
     assert_eq!(query, "mock query");
 
+    // Case 3: the editor saves the template unchanged, leaving only comments.
+    unsafe {
+        std::env::set_var("EDITOR", "true");
+    }
+    let query = Config::get_query_from_editor().unwrap();
+    assert_eq!(query, "");
+    assert!(super::query::require_query(query).is_err());
+
     // Cleanup
     unsafe {
         std::env::remove_var("VISUAL");
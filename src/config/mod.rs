@@ -1,33 +1,124 @@
 use crate::app_error::AppError;
-use crate::cli::{CliArgs, Model, Workflow};
+use crate::cli::{CliArgs, ContextMode, GitHistory, Model, Workflow};
+use crate::llm::budget::{Budget, BudgetLimits};
+use crate::llm::cassette::Cassette;
+use crate::llm::generation::{CallRole, GenerationConfig, GenerationParams, ReasoningEffort};
+use crate::llm::session::Fallback;
+use crate::llm::Session;
+use crate::logger::{self, Logger};
 use crate::system_prompts::{COMMITTING_CODE_INITIAL_QUERY, CONSISTENCY_CHECK, PROJECT_STRUCTURE};
 use std::fs;
-use std::io::Read;
+use std::io::{IsTerminal, Read};
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::Duration;
+
+mod api_keys;
+mod attachments;
+mod query;
+mod settings;
+
+pub(crate) use api_keys::{is_readable_by_others, EnvLookup, KeySource};
+pub use attachments::Attachment;
+pub(crate) use attachments::STDIN_ATTACHMENT;
+
+/// Deadline for a single LLM call when neither `--call-timeout` nor the
+/// settings file sets one. Long enough for the slowest thinking models.
+const DEFAULT_CALL_TIMEOUT_SECS: u64 = 30 * 60;
+
+/// Tool calls allowed per query with `--tools` when the settings file does
+/// not set `max_tool_calls`.
+const DEFAULT_MAX_TOOL_CALLS: u32 = 40;
+
+/// Estimated tokens of loaded context files when the settings file does not
+/// set `context_budget`. This leaves room for the rest of the prompt in the
+/// smallest supported context window.
+const DEFAULT_CONTEXT_BUDGET: u64 = 200_000;
+
+/// Generation parameters used before the settings file is applied. Choosing
+/// context files is a quick ranking task, while the self-consistent stage
+/// reviews whole specifications and benefits from thinking longer.
+fn default_generation() -> GenerationConfig {
+    let mut generation = GenerationConfig::default();
+    let effort = |effort| GenerationParams {
+        reasoning_effort: Some(effort),
+        ..GenerationParams::default()
+    };
+    generation.set(Some(CallRole::Context), None, effort(ReasoningEffort::Low));
+    generation.set(
+        Some(CallRole::SelfConsistent),
+        None,
+        effort(ReasoningEffort::High),
+    );
+    generation
+}
 
 #[cfg(test)]
 mod config_test;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
 pub struct Config {
     pub model: Model,
     pub api_key: String,
     pub query: String,
     pub system_prompts: String,
+    pub session: Session,
+    pub context_mode: ContextMode,
+    /// Estimated tokens the loaded context files may take up; `None` for no
+    /// limit.
+    pub context_budget: Option<u64>,
+    /// Git log and diff sections to add to the codebase context.
+    pub git_history: GitHistory,
+    /// Files and piped input from `--attach`, sent with the query.
+    pub attachments: Vec<Attachment>,
 }
 
 impl Config {
+    /// The `[supervisor query]` section, followed by the
+    /// `[supervisor attachments]` section when there are attachments.
+    pub fn supervisor_query(&self) -> String {
+        let mut section = format!("[supervisor query]\n{}", self.query);
+        let attachments = attachments::render_attachments(&self.attachments);
+        if !attachments.is_empty() {
+            section.push('\n');
+            section.push_str(attachments.trim_end_matches('\n'));
+        }
+        section
+    }
+
     pub fn load(args: &CliArgs) -> Result<Self, AppError> {
         let query = match args.workflow {
-            Workflow::CommitCode | Workflow::ConsistencyCheck => Self::get_query_from_editor()?,
+            Workflow::CommitCode | Workflow::ConsistencyCheck => {
+                let recorded = args.replay.as_deref().and_then(Cassette::recorded_query);
+                let query = match (&args.query, recorded) {
+                    (Some(source), _) => {
+                        let stdin = std::io::stdin();
+                        let piped = !stdin.is_terminal();
+                        let mut stdin = stdin.lock();
+                        query::read_query(source, piped.then_some(&mut stdin as &mut dyn Read))?
+                    }
+                    (None, Some(query)) => {
+                        println!("Using the recorded query from the replay directory.");
+                        query
+                    }
+                    (None, None) => Self::get_query_from_editor()?,
+                };
+                query::require_query(query)?
+            }
             Workflow::Rollup | Workflow::Auto => String::new(),
-            Workflow::Init(_) => unreachable!("Init workflow does not use Config"),
+            Workflow::Init(_) | Workflow::Models | Workflow::History => {
+                unreachable!("Init, models and history workflows do not use Config")
+            }
+            Workflow::Rerun { .. } => {
+                unreachable!("A rerun is resolved to the workflow it repeats first")
+            }
         };
 
         Self::load_from_dir(args, Path::new("."), query)
     }
 
+    /// Opens `$VISUAL` or `$EDITOR` on a template listing the most recent
+    /// queries, and returns what was written with the comments removed.
     pub(crate) fn get_query_from_editor() -> Result<String, AppError> {
         let editor = std::env::var("VISUAL")
             .or_else(|_| std::env::var("EDITOR"))
@@ -39,6 +130,8 @@ impl Config {
             .map_err(AppError::Io)?;
 
         let file_path = file.path();
+        let history = logger::recent_queries(&Logger::default_root(), query::QUERY_HISTORY);
+        fs::write(file_path, query::editor_template(&history)).map_err(AppError::Io)?;
 
         let status = Command::new(&editor)
             .arg(file_path)
@@ -56,22 +149,35 @@ impl Config {
         let mut f = fs::File::open(file_path).map_err(AppError::Io)?;
         f.read_to_string(&mut buffer).map_err(AppError::Io)?;
 
-        let query = buffer.trim().to_string();
-        Ok(query)
+        Ok(query::strip_comments(&buffer))
     }
 
     pub fn load_from_dir(args: &CliArgs, base_dir: &Path, query: String) -> Result<Self, AppError> {
+        Self::load_from_dir_with_env(args, base_dir, query, &|name| std::env::var(name).ok())
+    }
+
+    pub(crate) fn load_from_dir_with_env(
+        args: &CliArgs,
+        base_dir: &Path,
+        query: String,
+        env: EnvLookup,
+    ) -> Result<Self, AppError> {
         check_gitignore_in_dir(base_dir)?;
 
         match args.workflow {
             Workflow::CommitCode | Workflow::ConsistencyCheck | Workflow::Auto => {
-                let api_key_rel = match args.model {
-                    Model::Gemini3Pro | Model::Gemini2_5Pro => {
-                        PathBuf::from("agent-config/gemini-key.txt")
-                    }
-                    Model::Gpt5 => PathBuf::from("agent-config/openai-key.txt"),
+                let settings = settings::load_settings(base_dir)?;
+                let mut keys = api_keys::KeyReader::new(
+                    base_dir,
+                    &settings.key_command,
+                    env,
+                    args.replay.is_some(),
+                );
+                let api_key = keys.read(args.model)?;
+                let limits = BudgetLimits {
+                    max_cost: args.max_cost.or(settings.max_cost),
+                    max_tokens: args.max_tokens.or(settings.max_tokens),
                 };
-                let api_key = read_file_to_string_at(base_dir, &api_key_rel)?;
 
                 let system_prompts = match args.workflow {
                     Workflow::CommitCode => COMMITTING_CODE_INITIAL_QUERY.to_string(),
@@ -80,14 +186,96 @@ impl Config {
                     }
                     Workflow::Auto => String::new(), // Prompts handled internally
                     Workflow::Rollup => unreachable!(),
-                    Workflow::Init(_) => unreachable!(),
+                    Workflow::Init(_)
+                    | Workflow::Models
+                    | Workflow::History
+                    | Workflow::Rerun { .. } => unreachable!(),
                 };
 
+                let mut session = Session::new(
+                    Budget::new(limits),
+                    args.stream.or(settings.stream).unwrap_or(false),
+                );
+                let call_timeout = args
+                    .call_timeout
+                    .or(settings.call_timeout)
+                    .unwrap_or(DEFAULT_CALL_TIMEOUT_SECS);
+                if call_timeout == 0 {
+                    return Err(AppError::Config(
+                        "call_timeout in agent-config/config.toml must be at least 1 second."
+                            .to_string(),
+                    ));
+                }
+                session.call_timeout = Some(Duration::from_secs(call_timeout));
+                session.max_tool_calls = settings.max_tool_calls.unwrap_or(DEFAULT_MAX_TOOL_CALLS);
+                session.generation = default_generation();
+                if let Some(generation) = &settings.generation {
+                    generation.apply_to(&mut session.generation)?;
+                }
+                for name in settings.fallback_models.iter().flatten() {
+                    let model = Model::from_str(name).map_err(|_| {
+                        AppError::Config(format!(
+                            "Unsupported model '{name}' in fallback_models in agent-config/config.toml"
+                        ))
+                    })?;
+                    // A fallback without a key is left out rather than stopping a
+                    // run that may never need it.
+                    match keys.read(model) {
+                        Ok(api_key) => session.fallbacks.push(Fallback { model, api_key }),
+                        Err(e) => eprintln!(
+                            "Warning: skipping fallback model '{name}' because its API key is not available: {e}"
+                        ),
+                    }
+                }
+                if let Some(dir) = &args.record {
+                    let cassette = Cassette::record(dir)?;
+                    if !query.is_empty() {
+                        cassette.save_query(&query)?;
+                    }
+                    session.cassette = Some(cassette);
+                } else if let Some(dir) = &args.replay {
+                    session.cassette = Some(Cassette::replay(dir)?);
+                }
+
+                let context_mode = match (&args.context, &settings.context) {
+                    (Some(mode), _) => mode.clone(),
+                    (None, Some(name)) => ContextMode::parse(name).map_err(|e| {
+                        AppError::Config(format!("context in agent-config/config.toml: {e}"))
+                    })?,
+                    (None, None) => ContextMode::default(),
+                };
+
+                let stdin = std::io::stdin();
+                let piped = !stdin.is_terminal();
+                let mut stdin = stdin.lock();
+                let attachments = attachments::load_attachments(
+                    &args.attach,
+                    base_dir,
+                    args.allow_external_attachments,
+                    piped.then_some(&mut stdin as &mut dyn Read),
+                )?;
+
+                let context_budget = settings.context_budget.unwrap_or(DEFAULT_CONTEXT_BUDGET);
+                if context_budget == 0 {
+                    return Err(AppError::Config(
+                        "context_budget in agent-config/config.toml must be at least 1 token."
+                            .to_string(),
+                    ));
+                }
+
                 Ok(Self {
                     model: args.model,
-                    api_key: api_key.trim().to_string(),
+                    api_key,
                     query,
                     system_prompts,
+                    session,
+                    context_mode,
+                    context_budget: Some(context_budget),
+                    git_history: GitHistory {
+                        log: args.with_log,
+                        diff: args.with_diff.clone(),
+                    },
+                    attachments,
                 })
             }
             Workflow::Rollup => Err(AppError::Config(
@@ -96,19 +284,34 @@ impl Config {
             Workflow::Init(_) => Err(AppError::Config(
                 "The init workflow does not require configuration.".to_string(),
             )),
+            Workflow::Models => Err(AppError::Config(
+                "The models command does not require configuration.".to_string(),
+            )),
+            Workflow::History => Err(AppError::Config(
+                "The history command does not require configuration.".to_string(),
+            )),
+            Workflow::Rerun { .. } => Err(AppError::Config(
+                "A rerun must be resolved to the workflow it repeats before loading configuration."
+                    .to_string(),
+            )),
         }
     }
 }
 
-fn read_file_to_string_at(base_dir: &Path, rel_path: &Path) -> Result<String, AppError> {
-    let full_path = base_dir.join(rel_path);
-    fs::read_to_string(&full_path).map_err(|e| {
-        AppError::Config(format!(
-            "Failed to read file '{}': {}",
-            rel_path.display(),
-            e
-        ))
-    })
+/// Where the API key for `model` would be read from, without reading it or
+/// running a key command.
+pub(crate) fn key_source(
+    base_dir: &Path,
+    model: Model,
+    env: EnvLookup,
+) -> Result<Option<KeySource>, AppError> {
+    let settings = settings::load_settings(base_dir)?;
+    Ok(api_keys::find_key_source(
+        base_dir,
+        model,
+        &settings.key_command,
+        env,
+    ))
 }
 
 fn check_gitignore_in_dir(base_dir: &Path) -> Result<(), AppError> {
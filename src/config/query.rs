@@ -0,0 +1,85 @@
+use crate::app_error::AppError;
+use crate::cli::QuerySource;
+use crate::logger::PastQuery;
+use std::fs;
+use std::io::Read;
+
+/// How many earlier queries the editor template lists.
+pub(crate) const QUERY_HISTORY: usize = 5;
+
+const COMMENT_START: &str = "<!--";
+const COMMENT_END: &str = "-->";
+
+/// Reads the query from `--query` or `--query-file`. `stdin` is `None` when
+/// nothing was piped in.
+pub(crate) fn read_query(
+    source: &QuerySource,
+    stdin: Option<&mut dyn Read>,
+) -> Result<String, AppError> {
+    let query = match source {
+        QuerySource::Text(text) => text.clone(),
+        QuerySource::File(path) => fs::read_to_string(path).map_err(|e| {
+            AppError::Config(format!(
+                "Failed to read the query file '{}': {e}",
+                path.display()
+            ))
+        })?,
+        QuerySource::Stdin => {
+            let input = stdin.ok_or_else(|| {
+                AppError::Config(
+                    "The query is read from standard input, but nothing was piped in.".to_string(),
+                )
+            })?;
+            let mut query = String::new();
+            input.read_to_string(&mut query).map_err(AppError::Io)?;
+            query
+        }
+    };
+    Ok(query.trim().to_string())
+}
+
+/// The text the editor opens with: room for the query, a comment explaining
+/// what to do, and the `history` of earlier queries as a comment.
+pub(crate) fn editor_template(history: &[PastQuery]) -> String {
+    let mut template = format!(
+        "\n\n{COMMENT_START}\nWrite the supervisor query above, then save and quit. Everything inside\ncomments like this one is ignored, and an empty query aborts the run.\n{COMMENT_END}\n"
+    );
+    if !history.is_empty() {
+        template.push_str(&format!("{COMMENT_START}\nRecent queries, newest first:\n"));
+        for past in history {
+            // A past query must not end the comment early.
+            let query = past.query.replace(COMMENT_END, "-- >");
+            template.push_str(&format!("\n--- {} ---\n{query}\n", past.run_id));
+        }
+        template.push_str(&format!("{COMMENT_END}\n"));
+    }
+    template
+}
+
+/// `text` without its `<!-- ... -->` comments, trimmed. An unclosed comment
+/// runs to the end.
+pub(crate) fn strip_comments(text: &str) -> String {
+    let mut kept = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(COMMENT_START) {
+        kept.push_str(&rest[..start]);
+        let after = &rest[start + COMMENT_START.len()..];
+        rest = match after.find(COMMENT_END) {
+            Some(end) => &after[end + COMMENT_END.len()..],
+            None => "",
+        };
+    }
+    kept.push_str(rest);
+    kept.trim().to_string()
+}
+
+/// Stops the run when there is no query to act on.
+pub(crate) fn require_query(query: String) -> Result<String, AppError> {
+    if query.trim().is_empty() {
+        return Err(AppError::Config(
+            "The query is empty, so there is nothing to do. Write a query in the editor, or pass --query or --query-file."
+                .to_string(),
+        ));
+    }
+    Ok(query)
+}
@@ -0,0 +1,161 @@
+use crate::app_error::AppError;
+use crate::llm::generation::{CallRole, GenerationConfig, GenerationParams, ReasoningEffort};
+use crate::llm::models::Provider;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Optional defaults read from `agent-config/config.toml`. Command line flags
+/// take precedence over anything set here.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct Settings {
+    pub(crate) max_cost: Option<f64>,
+    pub(crate) max_tokens: Option<u64>,
+    pub(crate) stream: Option<bool>,
+    pub(crate) fallback_models: Option<Vec<String>>,
+    pub(crate) call_timeout: Option<u64>,
+    pub(crate) max_tool_calls: Option<u32>,
+    pub(crate) context: Option<String>,
+    pub(crate) context_budget: Option<u64>,
+    pub(crate) generation: Option<GenerationSettings>,
+    pub(crate) key_command: KeyCommands,
+}
+
+/// The `[key_command]` table: a shell command per provider that prints the
+/// provider's API key.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct KeyCommands {
+    pub(crate) gemini: Option<String>,
+    pub(crate) gpt: Option<String>,
+}
+
+impl KeyCommands {
+    pub(crate) fn for_provider(&self, provider: Provider) -> Option<&str> {
+        match provider {
+            Provider::Gemini => self.gemini.as_deref(),
+            Provider::Gpt => self.gpt.as_deref(),
+        }
+    }
+}
+
+/// The `[generation]` table. Its own fields apply to every call; `gemini` and
+/// `gpt` subtables apply to one provider, and a subtable named after a call
+/// role applies to that role and may hold provider subtables of its own.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct GenerationSettings {
+    pub(crate) temperature: Option<f64>,
+    pub(crate) max_output_tokens: Option<u64>,
+    pub(crate) reasoning_effort: Option<String>,
+    pub(crate) gemini: Option<Box<GenerationSettings>>,
+    pub(crate) gpt: Option<Box<GenerationSettings>>,
+    pub(crate) context: Option<Box<GenerationSettings>>,
+    pub(crate) extra_code: Option<Box<GenerationSettings>>,
+    pub(crate) commit: Option<Box<GenerationSettings>>,
+    pub(crate) consistency: Option<Box<GenerationSettings>>,
+    pub(crate) self_consistent: Option<Box<GenerationSettings>>,
+}
+
+impl GenerationSettings {
+    /// Adds every layer of the table to `config`, on top of what it holds.
+    pub(crate) fn apply_to(&self, config: &mut GenerationConfig) -> Result<(), AppError> {
+        self.apply_layer(config, None, None, "generation")
+    }
+
+    fn apply_layer(
+        &self,
+        config: &mut GenerationConfig,
+        role: Option<CallRole>,
+        provider: Option<Provider>,
+        table: &str,
+    ) -> Result<(), AppError> {
+        config.set(role, provider, self.params(table)?);
+        for (p, sub) in self.providers() {
+            let Some(sub) = sub else { continue };
+            let name = format!("{table}.{}", p.name());
+            if provider.is_some() {
+                return Err(nesting_error(&name));
+            }
+            sub.apply_layer(config, role, Some(p), &name)?;
+        }
+        for (r, sub) in self.roles() {
+            let Some(sub) = sub else { continue };
+            let name = format!("{table}.{}", r.name());
+            if role.is_some() || provider.is_some() {
+                return Err(nesting_error(&name));
+            }
+            sub.apply_layer(config, Some(r), None, &name)?;
+        }
+        Ok(())
+    }
+
+    fn params(&self, table: &str) -> Result<GenerationParams, AppError> {
+        if let Some(t) = self.temperature {
+            if !(0.0..=2.0).contains(&t) {
+                return Err(AppError::Config(format!(
+                    "{table}.temperature in agent-config/config.toml must be between 0 and 2."
+                )));
+            }
+        }
+        if self.max_output_tokens == Some(0) {
+            return Err(AppError::Config(format!(
+                "{table}.max_output_tokens in agent-config/config.toml must be at least 1."
+            )));
+        }
+        let reasoning_effort = match &self.reasoning_effort {
+            Some(name) => Some(ReasoningEffort::parse(name).ok_or_else(|| {
+                AppError::Config(format!(
+                    "{table}.reasoning_effort in agent-config/config.toml must be one of minimal, low, medium or high, not '{name}'."
+                ))
+            })?),
+            None => None,
+        };
+        Ok(GenerationParams {
+            temperature: self.temperature,
+            max_output_tokens: self.max_output_tokens,
+            reasoning_effort,
+        })
+    }
+
+    fn providers(&self) -> [(Provider, Option<&GenerationSettings>); 2] {
+        [
+            (Provider::Gemini, self.gemini.as_deref()),
+            (Provider::Gpt, self.gpt.as_deref()),
+        ]
+    }
+
+    fn roles(&self) -> [(CallRole, Option<&GenerationSettings>); 5] {
+        [
+            (CallRole::Context, self.context.as_deref()),
+            (CallRole::ExtraCode, self.extra_code.as_deref()),
+            (CallRole::Commit, self.commit.as_deref()),
+            (CallRole::Consistency, self.consistency.as_deref()),
+            (CallRole::SelfConsistent, self.self_consistent.as_deref()),
+        ]
+    }
+}
+
+fn nesting_error(table: &str) -> AppError {
+    AppError::Config(format!(
+        "[{table}] in agent-config/config.toml is nested too deeply; provider tables go inside role tables, not the other way round."
+    ))
+}
+
+pub(crate) fn load_settings(base_dir: &Path) -> Result<Settings, AppError> {
+    let rel_path = PathBuf::from("agent-config/config.toml");
+    let content = match fs::read_to_string(base_dir.join(&rel_path)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Settings::default()),
+        Err(e) => {
+            return Err(AppError::Config(format!(
+                "Failed to read file '{}': {}",
+                rel_path.display(),
+                e
+            )))
+        }
+    };
+    toml::from_str(&content)
+        .map_err(|e| AppError::Config(format!("Failed to parse '{}': {}", rel_path.display(), e)))
+}
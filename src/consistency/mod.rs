@@ -1,9 +1,12 @@
 use crate::app_error::AppError;
 use crate::cli::{CliArgs, Model};
+use crate::committing_code;
 use crate::config::Config;
 use crate::context_builder;
-use crate::llm;
-use crate::logger::Logger;
+use crate::llm::generation::CallRole;
+use crate::llm::tokens::{estimate_tokens, input_token_limit};
+use crate::llm::{self, Conversation, Session};
+use crate::logger::{self, Logger};
 use crate::system_prompts;
 use std::future::Future;
 use std::pin::Pin;
@@ -21,7 +24,8 @@ pub trait ConsistencyDeps {
         &'a self,
         model: Model,
         api_key: String,
-        prompt: &'a str,
+        session: &'a Session,
+        conversation: &'a mut Conversation,
         logger: &'a Logger,
         prefix: &'a str,
     ) -> Pin<Box<dyn Future<Output = Result<String, AppError>> + Send + 'a>>;
@@ -46,16 +50,26 @@ impl ConsistencyDeps for RealDeps {
         &'a self,
         model: Model,
         api_key: String,
-        prompt: &'a str,
+        session: &'a Session,
+        conversation: &'a mut Conversation,
         logger: &'a Logger,
         prefix: &'a str,
     ) -> Pin<Box<dyn Future<Output = Result<String, AppError>> + Send + 'a>> {
-        Box::pin(llm::query(model, api_key, prompt, logger, prefix))
+        Box::pin(llm::converse(
+            model,
+            api_key,
+            session,
+            conversation,
+            logger,
+            prefix,
+        ))
     }
 }
 
 pub async fn run(logger: &Logger, cli_args: CliArgs) -> Result<(), AppError> {
     let config = Config::load(&cli_args)?;
+    // Saved with the logs so that the run can be replayed with --replay.
+    logger.log_text(logger::QUERY_FILE, &config.query)?;
     let report = run_internal(logger, config, &RealDeps).await?;
     println!("\n{report}");
     Ok(())
@@ -67,12 +81,14 @@ async fn run_internal(
     deps: &impl ConsistencyDeps,
 ) -> Result<String, AppError> {
     println!("Building codebase context for consistency check...");
-    let next_agent_prompt = format!(
-        "{}\n{}\n[supervisor query]\n{}",
+    let system_prompt = format!(
+        "{}\n{}",
         system_prompts::PROJECT_STRUCTURE,
-        system_prompts::CONSISTENCY_CHECK,
-        config.query
+        system_prompts::CONSISTENCY_CHECK
     );
+    let supervisor_query = config.supervisor_query();
+    // The context builder sees everything the consistency check will see.
+    let next_agent_prompt = format!("{system_prompt}\n{supervisor_query}");
 
     let codebase = deps
         .build_context(&next_agent_prompt, &config, logger, "1-consistency-context")
@@ -80,13 +96,19 @@ async fn run_internal(
     logger.log_text("codebase_for_consistency.txt", &codebase)?;
 
     println!("Running consistency check...");
-    let prompt = format!("{next_agent_prompt}\n[codebase]\n{codebase}");
+    let limit = input_token_limit(config.model);
+    let user_limit = limit.saturating_sub(estimate_tokens(&system_prompt));
+    let fitted = committing_code::fit_initial_prompt(&supervisor_query, &codebase, user_limit)?;
+    committing_code::report_trimmed(logger, "2-consistency", limit, &fitted.trimmed)?;
+    let mut conversation = Conversation::with_system(system_prompt, fitted.prompt);
+    conversation.set_call_role(CallRole::Consistency);
 
     let report = deps
         .query_llm(
             config.model,
             config.api_key.clone(),
-            &prompt,
+            &config.session,
+            &mut conversation,
             logger,
             "2-consistency",
         )
@@ -2,6 +2,8 @@ use super::{run_internal, ConsistencyDeps};
 use crate::app_error::AppError;
 use crate::cli::Model;
 use crate::config::Config;
+use crate::llm::conversation::Role;
+use crate::llm::{Conversation, Session};
 use crate::logger::Logger;
 use crate::system_prompts;
 use std::fs;
@@ -13,6 +15,7 @@ struct MockDeps {
     expected_context: String,
     expected_report: String,
     captured_build_context_prompt: Arc<Mutex<Option<String>>>,
+    captured_query_llm_system: Arc<Mutex<Option<String>>>,
     captured_query_llm_prompt: Arc<Mutex<Option<String>>>,
     captured_query_llm_prefix: Arc<Mutex<Option<String>>>,
 }
@@ -23,6 +26,7 @@ impl MockDeps {
             expected_context: context.to_string(),
             expected_report: report.to_string(),
             captured_build_context_prompt: Arc::new(Mutex::new(None)),
+            captured_query_llm_system: Arc::new(Mutex::new(None)),
             captured_query_llm_prompt: Arc::new(Mutex::new(None)),
             captured_query_llm_prefix: Arc::new(Mutex::new(None)),
         }
@@ -43,7 +47,7 @@ impl ConsistencyDeps for MockDeps {
 
         // Assertions for build_context
         assert_eq!(prefix, "1-consistency-context");
-        assert!(matches!(config.model, Model::Gemini3Pro));
+        assert_eq!(config.model.name(), "gemini-3-pro-preview");
 
         Box::pin(async move {
             *captured.lock().unwrap() = Some(prompt_str);
@@ -55,20 +59,28 @@ impl ConsistencyDeps for MockDeps {
         &'a self,
         model: Model,
         api_key: String,
-        prompt: &'a str,
+        _session: &'a Session,
+        conversation: &'a mut Conversation,
         _logger: &'a Logger,
         prefix: &'a str,
     ) -> Pin<Box<dyn Future<Output = Result<String, AppError>> + Send + 'a>> {
         // Assertions for query_llm arguments
-        assert!(matches!(model, Model::Gemini3Pro));
+        assert_eq!(model.name(), "gemini-3-pro-preview");
         assert_eq!(api_key, "key");
 
+        let captured_system = self.captured_query_llm_system.clone();
         let captured_prompt = self.captured_query_llm_prompt.clone();
         let captured_prefix = self.captured_query_llm_prefix.clone();
-        let prompt_str = prompt.to_string();
+        let messages = conversation.messages();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].role, Role::System);
+        assert_eq!(messages[1].role, Role::User);
+        let system_str = messages[0].content.clone();
+        let prompt_str = messages[1].content.clone();
         let prefix_str = prefix.to_string();
         let ret = self.expected_report.clone();
         Box::pin(async move {
+            *captured_system.lock().unwrap() = Some(system_str);
             *captured_prompt.lock().unwrap() = Some(prompt_str);
             *captured_prefix.lock().unwrap() = Some(prefix_str);
             Ok(ret)
@@ -82,10 +94,11 @@ async fn test_run_internal_happy_path() {
     let logger = Logger::new_with_root(temp_dir.path(), "test").expect("logger");
 
     let config = Config {
-        model: Model::Gemini3Pro,
+        model: Model::from_str("gemini-3-pro-preview").unwrap(),
         api_key: "key".to_string(),
         query: "my query".to_string(),
         system_prompts: "prompts".to_string(),
+        ..Default::default()
     };
 
     let deps = MockDeps::new("mock codebase context", "mock report");
@@ -110,15 +123,30 @@ async fn test_run_internal_happy_path() {
     );
     assert_eq!(build_prompt, expected_prompt_start);
 
-    // Verify query_llm inputs
+    // Verify query_llm inputs: the instructions go in the system prompt and
+    // only the query and codebase in the user prompt.
+    let query_system = deps
+        .captured_query_llm_system
+        .lock()
+        .unwrap()
+        .take()
+        .expect("query_llm should be called");
+    assert_eq!(
+        query_system,
+        format!(
+            "{}\n{}",
+            system_prompts::PROJECT_STRUCTURE,
+            system_prompts::CONSISTENCY_CHECK
+        )
+    );
+
     let query_prompt = deps
         .captured_query_llm_prompt
         .lock()
         .unwrap()
         .take()
         .expect("query_llm should be called");
-    let expected_query_prompt =
-        format!("{expected_prompt_start}\n[codebase]\nmock codebase context");
+    let expected_query_prompt = "[supervisor query]\nmy query\n[codebase]\nmock codebase context";
     assert_eq!(query_prompt, expected_query_prompt);
 
     let query_prefix = deps
@@ -141,3 +169,42 @@ async fn test_run_internal_happy_path() {
     let content = fs::read_to_string(log_file_path).expect("read log file");
     assert_eq!(content, "mock codebase context");
 }
+
+#[tokio::test]
+async fn test_run_internal_trims_an_oversized_codebase() {
+    let temp_dir = tempfile::tempdir().expect("tempdir");
+    let logger = Logger::new_with_root(temp_dir.path(), "test").expect("logger");
+    let config = Config {
+        model: Model::from_str("gemini-3-pro-preview").unwrap(),
+        api_key: "key".to_string(),
+        query: "check src/small.rs".to_string(),
+        ..Default::default()
+    };
+    // Punctuation is estimated at a token per character, so this section
+    // alone is over the model's input limit.
+    let context = format!(
+        "--- src/big.rs ---\n{}\n--- src/small.rs ---\nfn small() {{}}\n",
+        ".".repeat(1_100_000)
+    );
+    let deps = MockDeps::new(&context, "mock report");
+
+    run_internal(&logger, config, &deps).await.unwrap();
+
+    let query_prompt = deps
+        .captured_query_llm_prompt
+        .lock()
+        .unwrap()
+        .take()
+        .expect("query_llm should be called");
+    assert!(query_prompt.contains("--- src/small.rs ---"));
+    assert!(!query_prompt.contains("--- src/big.rs ---"));
+    let log_dir = fs::read_dir(temp_dir.path())
+        .expect("read dir")
+        .next()
+        .expect("should be one entry")
+        .expect("entry")
+        .path();
+    let trimmed =
+        fs::read_to_string(log_dir.join("2-consistency-trimmed.txt")).expect("trimmed log");
+    assert!(trimmed.contains("src/big.rs"));
+}
@@ -1,6 +1,6 @@
-use super::load_files_with_root;
+use super::loading::{load_files_with_root, similar_paths, FileOutcome, MAX_FILE_BYTES};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tempfile::TempDir;
 
 #[test]
@@ -21,7 +21,7 @@ fn test_load_files_happy_path() {
 
     let paths = vec![PathBuf::from("src/main.rs"), PathBuf::from("README.md")];
 
-    let result = load_files_with_root(paths, root).expect("should succeed");
+    let (result, report) = load_files_with_root(paths, root, None).expect("should succeed");
 
     // Check content
     // Note: The function appends a newline if missing
@@ -30,25 +30,168 @@ fn test_load_files_happy_path() {
 
     assert!(result.contains(expected_part1));
     assert!(result.contains(expected_part2));
+    assert_eq!(
+        report.used_tokens(),
+        report
+            .files
+            .iter()
+            .map(|(_, o)| match o {
+                FileOutcome::Included { tokens } => *tokens,
+                _ => panic!("{o:?}"),
+            })
+            .sum::<u64>()
+    );
 }
 
 #[test]
-fn test_load_files_respects_gitignore() {
+fn test_unloadable_files_are_reported_instead_of_failing() {
     let dir = TempDir::new().unwrap();
     let root = dir.path();
-
-    // Ignore secret.txt
     fs::write(root.join(".gitignore"), "secret.txt").unwrap();
+    fs::write(root.join("secret.txt"), "hidden").unwrap();
+    fs::create_dir_all(root.join("src/config")).unwrap();
+    fs::write(
+        root.join("src/config/settings.rs"),
+        "pub struct Settings;\n",
+    )
+    .unwrap();
+    fs::write(root.join("src/config/mod.rs"), "mod settings;\n").unwrap();
+    fs::write(root.join("logo.png"), [0x89, 0x50, 0xff, 0x00]).unwrap();
+    fs::write(
+        root.join("dump.sql"),
+        vec![b'x'; MAX_FILE_BYTES as usize + 1],
+    )
+    .unwrap();
+
+    let paths = vec![
+        PathBuf::from("secret.txt"),
+        PathBuf::from("src/config/setings.rs"),
+        PathBuf::from("src/settings.rs"),
+        PathBuf::from("src/nowhere/at_all.rs"),
+        PathBuf::from("logo.png"),
+        PathBuf::from("dump.sql"),
+        PathBuf::from("src/config/mod.rs"),
+    ];
+    let (codebase, report) = load_files_with_root(paths, root, None).unwrap();
+
+    assert_eq!(codebase, "--- src/config/mod.rs ---\nmod settings;\n\n");
+    let outcomes: Vec<&FileOutcome> = report.files.iter().map(|(_, o)| o).collect();
+    assert!(
+        matches!(outcomes[0], FileOutcome::Blocked { reason } if reason.contains("matches a rule in .gitignore"))
+    );
+    let settings = vec![PathBuf::from("src/config/settings.rs")];
+    assert_eq!(
+        outcomes[1],
+        &FileOutcome::Missing {
+            suggestions: settings.clone()
+        }
+    );
+    // The same file name counts even when the directory is wrong.
+    assert_eq!(
+        outcomes[2],
+        &FileOutcome::Missing {
+            suggestions: settings
+        }
+    );
+    assert_eq!(
+        outcomes[3],
+        &FileOutcome::Missing {
+            suggestions: vec![]
+        }
+    );
+    assert_eq!(outcomes[4], &FileOutcome::Binary);
+    assert_eq!(
+        outcomes[5],
+        &FileOutcome::TooLarge {
+            bytes: MAX_FILE_BYTES + 1
+        }
+    );
+    assert!(report.render(true).starts_with(
+        "Context: 1 files included, 0 truncated to an outline, 0 skipped, 6 could not be loaded"
+    ));
+
+    let request = report.correction_request().unwrap();
+    assert!(request.starts_with("Some of the files you selected could not be loaded:\n\n"));
+    assert!(request.contains(
+        "\n- src/config/setings.rs does not exist; similar paths: src/config/settings.rs\n"
+    ));
+    assert!(request.contains("\n- src/nowhere/at_all.rs does not exist\n"));
+    assert!(request.contains("\n- logo.png is not a UTF-8 text file\n"));
+    assert!(request.contains("\n- dump.sql is 1000001 bytes, over the 1000000 byte limit\n"));
+    assert!(request.contains("- secret.txt may not be loaded: "));
+    assert!(!request.contains("src/config/mod.rs"));
+}
+
+#[test]
+fn test_similar_paths_prefer_same_name_then_fewest_edits() {
+    let candidates = vec![
+        PathBuf::from("src/llm/api.rs"),
+        PathBuf::from("src/llm/apis.rs"),
+        PathBuf::from("src/cli/mod.rs"),
+        PathBuf::from("src/app/api.rs"),
+    ];
+    assert_eq!(
+        similar_paths(Path::new("src/lm/api.rs"), &candidates),
+        vec![
+            PathBuf::from("src/llm/api.rs"),
+            PathBuf::from("src/app/api.rs"),
+            PathBuf::from("src/llm/apis.rs"),
+        ]
+    );
+    assert!(similar_paths(Path::new("README.md"), &candidates).is_empty());
+}
+
+#[test]
+fn test_budget_outlines_then_skips_lower_priority_files() {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path();
+    fs::write(root.join(".gitignore"), "").unwrap();
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+    let generated: String = (0..400)
+        .map(|i| format!("pub const VALUE_{i}: u32 = {i};\n"))
+        .collect();
+    fs::write(
+        root.join("src/table.rs"),
+        format!("pub fn lookup(key: u32) -> u32 {{\n{generated}    key\n}}\n"),
+    )
+    .unwrap();
+    fs::write(
+        root.join("data.json"),
+        "[1, 2, 3, 4, 5, 6, 7, 8, 9]\n".repeat(200),
+    )
+    .unwrap();
+    fs::write(root.join("notes.md"), "short\n").unwrap();
 
-    let secret = root.join("secret.txt");
-    fs::write(&secret, "hidden").unwrap();
+    let paths = vec![
+        PathBuf::from("src/main.rs"),
+        PathBuf::from("src/table.rs"),
+        PathBuf::from("data.json"),
+        PathBuf::from("notes.md"),
+    ];
+    let (codebase, report) = load_files_with_root(paths, root, Some(200)).unwrap();
 
-    let paths = vec![PathBuf::from("secret.txt")];
+    assert!(codebase.contains("--- src/main.rs ---\nfn main() {}\n"));
+    assert!(codebase.contains("--- src/table.rs ---\n// Outline only: the full file (~"));
+    assert!(codebase.contains("pub fn lookup(key: u32) -> u32;\n"));
+    assert!(!codebase.contains("VALUE_1"));
+    assert!(!codebase.contains("data.json"));
+    assert!(codebase.contains("--- notes.md ---\nshort\n"));
+    assert!(report.used_tokens() <= 200);
 
-    let result = load_files_with_root(paths, root);
+    let outcomes: Vec<&FileOutcome> = report.files.iter().map(|(_, o)| o).collect();
+    assert!(matches!(outcomes[0], FileOutcome::Included { .. }));
+    assert!(matches!(outcomes[1], FileOutcome::Outlined { .. }));
+    assert!(matches!(outcomes[2], FileOutcome::Skipped { .. }));
+    assert!(matches!(outcomes[3], FileOutcome::Included { .. }));
 
-    // Should fail because validation fails for ignored file
-    assert!(result.is_err());
-    let err = result.unwrap_err();
-    assert!(err.to_string().contains("matches a rule in .gitignore"));
+    let shown = report.render(true);
+    assert!(shown.starts_with(
+        "Context: 2 files included, 1 truncated to an outline, 1 skipped, 0 could not be loaded (~"
+    ));
+    assert!(shown.contains("of a 200 token budget).\n"));
+    assert!(shown.contains("\n  outlined  src/table.rs (~"));
+    assert!(shown.contains("\n  skipped   data.json (~"));
+    assert!(!shown.contains("notes.md"));
+    assert!(report.render(false).contains("\n  included  notes.md (~"));
 }
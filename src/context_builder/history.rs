@@ -0,0 +1,154 @@
+use crate::app_error::AppError;
+use crate::cli::{DiffSpec, GitHistory};
+use crate::path_filter::PathFilter;
+use std::path::Path;
+use std::process::Command;
+
+/// Diffs longer than this are cut at a line boundary.
+const MAX_DIFF_BYTES: usize = 200_000;
+
+/// Separates commits in `git log` output, and each commit's message from its
+/// changed files.
+const RECORD_SEPARATOR: char = '\u{1e}';
+const MESSAGE_END: char = '\u{1f}';
+
+/// How `spec` is named in its section heading.
+fn label(spec: &DiffSpec) -> String {
+    match spec {
+        DiffSpec::WorkTree => "working tree".to_string(),
+        DiffSpec::Commit(commit) => commit.clone(),
+        DiffSpec::Range(range) => range.clone(),
+    }
+}
+
+/// The revisions `git diff` compares for `spec`.
+fn revisions(spec: &DiffSpec) -> Vec<String> {
+    match spec {
+        DiffSpec::WorkTree => vec!["HEAD".to_string()],
+        DiffSpec::Commit(commit) => vec![format!("{commit}^!")],
+        DiffSpec::Range(range) => vec![range.clone()],
+    }
+}
+
+/// The `=== Git Log ... ===` and `=== Git Diff ... ===` sections for
+/// `history`, or an empty string when it asks for neither. Files that
+/// `PathFilter` refuses are left out of both.
+pub(crate) fn history_sections(root: &Path, history: &GitHistory) -> Result<String, AppError> {
+    let filter = PathFilter::new_for_base_dir(root)?;
+    let mut sections = String::new();
+    if let Some(count) = history.log {
+        sections.push_str(&log_section(root, count, &filter)?);
+    }
+    if let Some(spec) = &history.diff {
+        sections.push_str(&diff_section(root, spec, &filter)?);
+    }
+    Ok(sections)
+}
+
+fn log_section(root: &Path, count: usize, filter: &PathFilter) -> Result<String, AppError> {
+    let output = git(
+        root,
+        &[
+            "log".to_string(),
+            format!("-n{count}"),
+            "--no-renames".to_string(),
+            "--name-status".to_string(),
+            "--date=short".to_string(),
+            format!("--format={RECORD_SEPARATOR}%h %ad %an%n%n%B{MESSAGE_END}"),
+        ],
+        "--with-log",
+    )?;
+
+    let mut section = format!("=== Git Log (last {count} commits) ===\n");
+    for record in output
+        .split(RECORD_SEPARATOR)
+        .filter(|r| !r.trim().is_empty())
+    {
+        let (message, changes) = record.split_once(MESSAGE_END).unwrap_or((record, ""));
+        let mut lines = message.trim_end().lines();
+        section.push_str(&format!("commit {}\n", lines.next().unwrap_or("").trim()));
+        for line in lines {
+            if !line.is_empty() {
+                section.push_str("    ");
+            }
+            section.push_str(line);
+            section.push('\n');
+        }
+        for change in changes.lines().filter(|line| !line.trim().is_empty()) {
+            let Some((status, path)) = change.split_once('\t') else {
+                continue;
+            };
+            if filter.validate(Path::new(path)).is_ok() {
+                section.push_str(&format!("  {status} {path}\n"));
+            }
+        }
+        section.push('\n');
+    }
+    Ok(section)
+}
+
+fn diff_section(root: &Path, spec: &DiffSpec, filter: &PathFilter) -> Result<String, AppError> {
+    let mut args = vec![
+        "diff".to_string(),
+        "--no-renames".to_string(),
+        "--name-only".to_string(),
+        "-z".to_string(),
+    ];
+    args.extend(revisions(spec));
+    let names = git(root, &args, "--with-diff")?;
+    let allowed: Vec<&str> = names
+        .split('\0')
+        .filter(|name| !name.is_empty() && filter.validate(Path::new(name)).is_ok())
+        .collect();
+
+    let mut section = format!("=== Git Diff ({}) ===\n", label(spec));
+    if allowed.is_empty() {
+        section.push_str("(no changes to files that can be shown)\n\n");
+        return Ok(section);
+    }
+    let mut args = vec![
+        "diff".to_string(),
+        "--no-renames".to_string(),
+        "--no-color".to_string(),
+        "--no-ext-diff".to_string(),
+    ];
+    args.extend(revisions(spec));
+    args.push("--".to_string());
+    args.extend(allowed.iter().map(|name| name.to_string()));
+    let diff = git(root, &args, "--with-diff")?;
+
+    if diff.len() > MAX_DIFF_BYTES {
+        let mut cut = MAX_DIFF_BYTES;
+        while !diff.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        let cut = diff[..cut].rfind('\n').map_or(cut, |at| at + 1);
+        section.push_str(&diff[..cut]);
+        section.push_str(&format!(
+            "[... diff truncated at {MAX_DIFF_BYTES} bytes ...]\n"
+        ));
+    } else {
+        section.push_str(&diff);
+    }
+    if !section.ends_with('\n') {
+        section.push('\n');
+    }
+    section.push('\n');
+    Ok(section)
+}
+
+fn git(root: &Path, args: &[String], flag: &str) -> Result<String, AppError> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(root)
+        .output()
+        .map_err(|e| AppError::Config(format!("{flag} could not run git: {e}")))?;
+    if !output.status.success() {
+        return Err(AppError::Config(format!(
+            "{flag}: git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,141 @@
+use super::history::history_sections;
+use crate::cli::{DiffSpec, GitHistory};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+
+fn write(root: &Path, rel: &str, content: &str) {
+    let path = root.join(rel);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+fn git(root: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .args([
+            "-c",
+            "user.name=t",
+            "-c",
+            "user.email=t@t",
+            "-c",
+            "commit.gpgsign=false",
+        ])
+        .args(args)
+        .current_dir(root)
+        .output()
+        .unwrap()
+        .status;
+    assert!(status.success(), "git {args:?}");
+}
+
+/// A repository with two commits. The second touches a source file, a
+/// protected file and a file that was committed before `.gitignore` came to
+/// match it, and leaves the source file modified in the working tree.
+fn repository() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path();
+    write(root, "src/main.rs", "fn main() {}\n");
+    write(root, "secrets.env", "TOKEN=1\n");
+    write(root, "agent-config/config.toml", "model = \"a\"\n");
+    git(root, &["init", "-q"]);
+    git(root, &["add", "."]);
+    git(root, &["commit", "-q", "-m", "Initial commit"]);
+
+    write(root, ".gitignore", "secrets.env\n");
+    write(root, "src/main.rs", "fn main() {\n    run();\n}\n");
+    write(root, "secrets.env", "TOKEN=2\n");
+    write(root, "agent-config/config.toml", "model = \"b\"\n");
+    git(root, &["add", "."]);
+    git(
+        root,
+        &[
+            "commit",
+            "-q",
+            "-m",
+            "Call run from main",
+            "-m",
+            "Body line.",
+        ],
+    );
+
+    write(
+        root,
+        "src/main.rs",
+        "fn main() {\n    run();\n    stop();\n}\n",
+    );
+    write(root, "secrets.env", "TOKEN=3\n");
+    dir
+}
+
+#[test]
+fn test_no_history_is_an_empty_string() {
+    let dir = TempDir::new().unwrap();
+    assert_eq!(
+        history_sections(dir.path(), &GitHistory::default()).unwrap(),
+        ""
+    );
+}
+
+#[test]
+fn test_log_lists_commits_without_filtered_paths() {
+    let dir = repository();
+    let history = GitHistory {
+        log: Some(5),
+        diff: None,
+    };
+
+    let sections = history_sections(dir.path(), &history).unwrap();
+    assert!(sections.starts_with("=== Git Log (last 5 commits) ===\ncommit "));
+    assert!(sections.contains("    Call run from main\n\n    Body line.\n"));
+    assert!(sections.contains("    Initial commit\n"));
+    assert!(sections.contains("  A .gitignore\n"));
+    assert!(sections.contains("  M src/main.rs\n"));
+    assert!(sections.contains("  A src/main.rs\n"));
+    assert!(!sections.contains("secrets.env"));
+    assert!(!sections.contains("agent-config"));
+    assert!(sections.find("Call run").unwrap() < sections.find("Initial commit").unwrap());
+}
+
+#[test]
+fn test_diff_of_a_commit_a_range_and_the_working_tree() {
+    let dir = repository();
+    let root = dir.path();
+    let diff = |spec: &str| {
+        let history = GitHistory {
+            log: None,
+            diff: Some(DiffSpec::parse(spec).unwrap()),
+        };
+        history_sections(root, &history).unwrap()
+    };
+
+    let commit = diff("HEAD");
+    assert!(commit.starts_with("=== Git Diff (HEAD) ===\ndiff --git a/.gitignore"));
+    assert!(commit.contains("+    run();\n"));
+    assert!(!commit.contains("stop"));
+    assert!(!commit.contains("TOKEN"));
+    assert!(!commit.contains("model ="));
+
+    assert_eq!(
+        diff("HEAD~1..HEAD"),
+        commit.replace("(HEAD)", "(HEAD~1..HEAD)")
+    );
+
+    let worktree = diff("worktree");
+    assert!(worktree.starts_with("=== Git Diff (working tree) ===\n"));
+    assert!(worktree.contains("+    stop();\n"));
+    assert!(!worktree.contains("TOKEN"));
+
+    write(root, "src/main.rs", "fn main() {\n    run();\n}\n");
+    assert_eq!(
+        diff("worktree"),
+        "=== Git Diff (working tree) ===\n(no changes to files that can be shown)\n\n"
+    );
+
+    let history = GitHistory {
+        log: None,
+        diff: Some(DiffSpec::Commit("no-such-commit".to_string())),
+    };
+    let err = history_sections(root, &history).unwrap_err().to_string();
+    assert!(err.contains("--with-diff"), "{err}");
+}
@@ -0,0 +1,194 @@
+use crate::app_error::AppError;
+use crate::path_filter::PathFilter;
+use crate::specifications::find_all_user_specifications;
+use ignore::WalkBuilder;
+use path_clean::PathClean;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directories of a crate whose sub-directories are each a module.
+const CRATE_TREES: [&str; 4] = ["src", "tests", "benches", "examples"];
+
+/// Where the files of a project are. Every path is relative to the project
+/// root.
+#[derive(Debug, Default)]
+pub(crate) struct Layout {
+    /// Files directly in the root crate's `src/`.
+    pub(crate) src_files: Vec<PathBuf>,
+    /// Cargo workspace members, from the root `Cargo.toml`.
+    pub(crate) members: Vec<PathBuf>,
+    /// Each module directory and the files directly in it.
+    pub(crate) modules: BTreeMap<PathBuf, Vec<PathBuf>>,
+}
+
+/// Finds the modules of the project at `root`: every directory with files
+/// under the `src/`, `tests/`, `benches/` and `examples/` of the root crate
+/// and of each workspace member, each workspace member itself, and every
+/// other directory that has a `UserSpecification.md`. Paths that `filter`
+/// refuses are left out.
+pub(crate) fn discover(root: &Path, filter: &PathFilter) -> Result<Layout, AppError> {
+    let members = workspace_members(root)?;
+    let mut src_files = Vec::new();
+    let mut modules: BTreeMap<PathBuf, BTreeSet<PathBuf>> = BTreeMap::new();
+
+    let crates = std::iter::once(PathBuf::new()).chain(members.iter().cloned());
+    for crate_dir in crates {
+        for tree in CRATE_TREES {
+            let tree_dir = crate_dir.join(tree);
+            for file in walk_files(root, &tree_dir, None, filter)? {
+                let parent = file.parent().map(Path::to_path_buf).unwrap_or_default();
+                if parent == Path::new("src") {
+                    src_files.push(file);
+                } else {
+                    modules.entry(parent).or_default().insert(file);
+                }
+            }
+        }
+    }
+
+    let spec_dirs = find_all_user_specifications(root)?
+        .into_iter()
+        .filter_map(|spec| {
+            let dir = spec.parent()?.strip_prefix(root).ok()?.clean();
+            (dir != Path::new(".")).then_some(dir)
+        });
+    for dir in members.iter().cloned().chain(spec_dirs) {
+        if filter.validate_entry(&dir, true).is_err() {
+            continue;
+        }
+        let files = walk_files(root, &dir, Some(1), filter)?;
+        modules.entry(dir).or_default().extend(files);
+    }
+
+    src_files.sort();
+    Ok(Layout {
+        src_files,
+        members,
+        modules: modules
+            .into_iter()
+            .map(|(dir, files)| (dir, files.into_iter().collect()))
+            .collect(),
+    })
+}
+
+/// The member directories listed under `[workspace] members` in the root
+/// `Cargo.toml`, with `*` and `?` patterns expanded and `exclude` applied.
+/// Only directories with their own `Cargo.toml` count, and the root itself is
+/// left out.
+pub(crate) fn workspace_members(root: &Path) -> Result<Vec<PathBuf>, AppError> {
+    let Ok(manifest) = fs::read_to_string(root.join("Cargo.toml")) else {
+        return Ok(Vec::new());
+    };
+    let manifest: toml::Value = toml::from_str(&manifest)
+        .map_err(|e| AppError::Config(format!("Failed to parse Cargo.toml: {e}")))?;
+    let Some(workspace) = manifest.get("workspace") else {
+        return Ok(Vec::new());
+    };
+    let patterns = |key: &str| -> Vec<String> {
+        workspace
+            .get(key)
+            .and_then(toml::Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(toml::Value::as_str)
+            .map(str::to_string)
+            .collect()
+    };
+    let excluded: Vec<PathBuf> = patterns("exclude")
+        .iter()
+        .map(|path| PathBuf::from(path).clean())
+        .collect();
+
+    let mut members = BTreeSet::new();
+    for pattern in patterns("members") {
+        for dir in expand(root, &pattern) {
+            let dir = dir.clean();
+            if dir != Path::new(".")
+                && !excluded.contains(&dir)
+                && root.join(&dir).join("Cargo.toml").is_file()
+            {
+                members.insert(dir);
+            }
+        }
+    }
+    Ok(members.into_iter().collect())
+}
+
+/// The directories matching a member pattern such as `crates/*`.
+fn expand(root: &Path, pattern: &str) -> Vec<PathBuf> {
+    let mut matches = vec![PathBuf::new()];
+    for component in pattern.split('/').filter(|c| !c.is_empty()) {
+        if !component.contains(['*', '?']) {
+            for path in &mut matches {
+                path.push(component);
+            }
+            continue;
+        }
+        let mut next = Vec::new();
+        for path in matches {
+            let Ok(entries) = fs::read_dir(root.join(&path)) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if entry.path().is_dir() && wildcard_match(component, &name) {
+                    next.push(path.join(name));
+                }
+            }
+        }
+        matches = next;
+    }
+    matches.sort();
+    matches
+}
+
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some(('*', rest)) => (0..=name.len()).any(|skip| matches(rest, &name[skip..])),
+            Some(('?', rest)) => !name.is_empty() && matches(rest, &name[1..]),
+            Some((c, rest)) => name.first() == Some(c) && matches(rest, &name[1..]),
+        }
+    }
+    matches(&pattern, &name)
+}
+
+/// The files under `dir`, down to `max_depth`, that `.gitignore` and `filter`
+/// allow, relative to `root`. A missing directory has none.
+fn walk_files(
+    root: &Path,
+    dir: &Path,
+    max_depth: Option<usize>,
+    filter: &PathFilter,
+) -> Result<Vec<PathBuf>, AppError> {
+    let mut files = Vec::new();
+    if !root.join(dir).is_dir() {
+        return Ok(files);
+    }
+    for result in WalkBuilder::new(root.join(dir))
+        .max_depth(max_depth)
+        .git_ignore(true)
+        .parents(true)
+        .ignore(false)
+        .git_global(false)
+        .build()
+    {
+        let entry =
+            result.map_err(|e| AppError::Config(format!("Error walking directory: {e}")))?;
+        if !entry.file_type().is_some_and(|ft| ft.is_file()) {
+            continue;
+        }
+        let Ok(rel) = entry.path().strip_prefix(root) else {
+            continue;
+        };
+        let rel = rel.clean();
+        if filter.validate(&rel).is_ok() {
+            files.push(rel);
+        }
+    }
+    Ok(files)
+}
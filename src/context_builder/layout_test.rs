@@ -0,0 +1,122 @@
+use super::layout::{discover, workspace_members};
+use crate::path_filter::PathFilter;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+fn write(root: &Path, rel: &str, content: &str) {
+    let path = root.join(rel);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+fn paths(files: &[PathBuf]) -> Vec<String> {
+    files.iter().map(|p| p.display().to_string()).collect()
+}
+
+/// A workspace with a root package, two members under `crates/`, an excluded
+/// crate, and a Python tool documented by its own specification.
+fn workspace() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path();
+    write(root, ".gitignore", "/agent-config\ntarget/\n");
+    write(
+        root,
+        "Cargo.toml",
+        "[package]\nname = \"app\"\n\n[workspace]\nmembers = [\".\", \"crates/*\"]\nexclude = [\"crates/scratch\"]\n",
+    );
+    write(root, "src/main.rs", "fn main() {}\n");
+    write(root, "src/cli/mod.rs", "pub fn parse() {}\n");
+    write(root, "tests/cli.rs", "#[test]\nfn parses() {}\n");
+    write(
+        root,
+        "crates/core/Cargo.toml",
+        "[package]\nname = \"core\"\n",
+    );
+    write(root, "crates/core/README.md", "core\n");
+    write(root, "crates/core/src/lib.rs", "pub mod store;\n");
+    write(root, "crates/core/src/store/mod.rs", "pub struct Store;\n");
+    write(root, "crates/core/benches/store.rs", "fn main() {}\n");
+    write(root, "crates/net/Cargo.toml", "[package]\nname = \"net\"\n");
+    write(root, "crates/net/src/lib.rs", "\n");
+    write(
+        root,
+        "crates/scratch/Cargo.toml",
+        "[package]\nname = \"scratch\"\n",
+    );
+    write(root, "crates/scratch/src/lib.rs", "\n");
+    write(root, "crates/notes/todo.md", "not a crate\n");
+    write(root, "tools/gen/UserSpecification.md", "# Generator\n");
+    write(root, "tools/gen/gen.py", "print('hi')\n");
+    write(root, "tools/gen/templates/a.txt", "a\n");
+    write(root, "agent-state/x/UserSpecification.md", "# state\n");
+    write(
+        root,
+        "target/debug/UserSpecification.md",
+        "# build output\n",
+    );
+    dir
+}
+
+#[test]
+fn test_workspace_members_expand_patterns_and_apply_exclude() {
+    let dir = workspace();
+    let members = paths(&workspace_members(dir.path()).unwrap());
+    assert_eq!(members, vec!["crates/core", "crates/net"]);
+
+    let plain = TempDir::new().unwrap();
+    write(plain.path(), "Cargo.toml", "[package]\nname = \"x\"\n");
+    assert!(workspace_members(plain.path()).unwrap().is_empty());
+    assert!(workspace_members(&plain.path().join("missing"))
+        .unwrap()
+        .is_empty());
+
+    write(plain.path(), "Cargo.toml", "[workspace\n");
+    assert!(workspace_members(plain.path()).is_err());
+}
+
+#[test]
+fn test_discover_covers_members_crate_trees_and_specified_directories() {
+    let dir = workspace();
+    let root = dir.path();
+    let filter = PathFilter::new_for_base_dir(root).unwrap();
+
+    let layout = discover(root, &filter).unwrap();
+
+    assert_eq!(paths(&layout.src_files), vec!["src/main.rs"]);
+    let modules: Vec<(String, Vec<String>)> = layout
+        .modules
+        .iter()
+        .map(|(dir, files)| (dir.display().to_string(), paths(files)))
+        .collect();
+    let expected: Vec<(&str, Vec<&str>)> = vec![
+        (
+            "crates/core",
+            vec!["crates/core/Cargo.toml", "crates/core/README.md"],
+        ),
+        ("crates/core/benches", vec!["crates/core/benches/store.rs"]),
+        ("crates/core/src", vec!["crates/core/src/lib.rs"]),
+        (
+            "crates/core/src/store",
+            vec!["crates/core/src/store/mod.rs"],
+        ),
+        ("crates/net", vec!["crates/net/Cargo.toml"]),
+        ("crates/net/src", vec!["crates/net/src/lib.rs"]),
+        ("src/cli", vec!["src/cli/mod.rs"]),
+        ("tests", vec!["tests/cli.rs"]),
+        (
+            "tools/gen",
+            vec!["tools/gen/UserSpecification.md", "tools/gen/gen.py"],
+        ),
+    ];
+    let expected: Vec<(String, Vec<String>)> = expected
+        .into_iter()
+        .map(|(dir, files)| {
+            (
+                dir.to_string(),
+                files.into_iter().map(str::to_string).collect(),
+            )
+        })
+        .collect();
+    assert_eq!(modules, expected);
+}
@@ -0,0 +1,307 @@
+use super::outline;
+use super::selection;
+use crate::app_error::AppError;
+use crate::llm::tokens::estimate_tokens;
+use crate::path_filter::PathFilter;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Files larger than this are not read at all, whatever the budget.
+pub(crate) const MAX_FILE_BYTES: u64 = 1_000_000;
+
+/// How many similar paths are suggested for a missing file.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// What happened to one requested file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum FileOutcome {
+    /// Loaded in full.
+    Included { tokens: u64 },
+    /// Over the remaining budget, so only its outline was loaded.
+    Outlined { tokens: u64, outline_tokens: u64 },
+    /// Over the remaining budget, with no outline that fits.
+    Skipped { tokens: u64 },
+    /// No such file, with the most similar paths that do exist.
+    Missing { suggestions: Vec<PathBuf> },
+    /// Not UTF-8 text.
+    Binary,
+    /// Over `MAX_FILE_BYTES`.
+    TooLarge { bytes: u64 },
+    /// Refused by `PathFilter`, for example because `.gitignore` matches it.
+    Blocked { reason: String },
+}
+
+impl FileOutcome {
+    /// Whether the file could not be loaded at all, as opposed to being
+    /// loaded or left out for the budget.
+    pub(crate) fn is_rejected(&self) -> bool {
+        matches!(
+            self,
+            FileOutcome::Missing { .. }
+                | FileOutcome::Binary
+                | FileOutcome::TooLarge { .. }
+                | FileOutcome::Blocked { .. }
+        )
+    }
+
+    /// Why a rejected file could not be loaded.
+    fn rejection(&self) -> String {
+        match self {
+            FileOutcome::Missing { suggestions } if suggestions.is_empty() => {
+                "does not exist".to_string()
+            }
+            FileOutcome::Missing { suggestions } => {
+                let similar: Vec<String> = suggestions
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect();
+                format!("does not exist; similar paths: {}", similar.join(", "))
+            }
+            FileOutcome::Binary => "is not a UTF-8 text file".to_string(),
+            FileOutcome::TooLarge { bytes } => {
+                format!("is {bytes} bytes, over the {MAX_FILE_BYTES} byte limit")
+            }
+            FileOutcome::Blocked { reason } => format!("may not be loaded: {reason}"),
+            _ => String::new(),
+        }
+    }
+}
+
+/// The files of a codebase context in the order they were requested, which
+/// is also their priority, and what became of each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LoadReport {
+    pub(crate) budget: Option<u64>,
+    pub(crate) files: Vec<(PathBuf, FileOutcome)>,
+}
+
+impl LoadReport {
+    /// Estimated tokens of everything that was loaded.
+    pub(crate) fn used_tokens(&self) -> u64 {
+        self.files
+            .iter()
+            .map(|(_, outcome)| match outcome {
+                FileOutcome::Included { tokens } => *tokens,
+                FileOutcome::Outlined { outline_tokens, .. } => *outline_tokens,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    fn count(&self, matches: impl Fn(&FileOutcome) -> bool) -> usize {
+        self.files
+            .iter()
+            .filter(|(_, outcome)| matches(outcome))
+            .count()
+    }
+
+    /// One line with the totals, for stdout.
+    fn summary(&self) -> String {
+        let included = self.count(|o| matches!(o, FileOutcome::Included { .. }));
+        let outlined = self.count(|o| matches!(o, FileOutcome::Outlined { .. }));
+        let skipped = self.count(|o| matches!(o, FileOutcome::Skipped { .. }));
+        let rejected = self.count(FileOutcome::is_rejected);
+        let budget = match self.budget {
+            Some(budget) => format!(" of a {budget} token budget"),
+            None => String::new(),
+        };
+        format!(
+            "Context: {included} files included, {outlined} truncated to an outline, {skipped} skipped, {rejected} could not be loaded (~{} tokens{budget}).",
+            self.used_tokens()
+        )
+    }
+
+    /// The summary and a line per file, for the logs. With `only_trimmed`,
+    /// files that were included in full are left out.
+    pub(crate) fn render(&self, only_trimmed: bool) -> String {
+        let mut out = self.summary();
+        out.push('\n');
+        for (path, outcome) in &self.files {
+            let line = match outcome {
+                FileOutcome::Included { tokens } if !only_trimmed => {
+                    format!("included  {} (~{tokens} tokens)", path.display())
+                }
+                FileOutcome::Included { .. } => continue,
+                FileOutcome::Outlined {
+                    tokens,
+                    outline_tokens,
+                } => format!(
+                    "outlined  {} (~{tokens} tokens, outline ~{outline_tokens})",
+                    path.display()
+                ),
+                FileOutcome::Skipped { tokens } => {
+                    format!("skipped   {} (~{tokens} tokens)", path.display())
+                }
+                rejected => format!("rejected  {} {}", path.display(), rejected.rejection()),
+            };
+            out.push_str(&format!("  {line}\n"));
+        }
+        out
+    }
+
+    /// A follow-up asking the context LLM to correct its list, or `None` when
+    /// every file it chose could be loaded.
+    pub(crate) fn correction_request(&self) -> Option<String> {
+        let rejected: Vec<String> = self
+            .files
+            .iter()
+            .filter(|(_, outcome)| outcome.is_rejected())
+            .map(|(path, outcome)| format!("- {} {}", path.display(), outcome.rejection()))
+            .collect();
+        if rejected.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "Some of the files you selected could not be loaded:\n\n{}\n\nReply with the complete corrected list of files, in the same format as before. Replace each path above with the file you meant, or leave it out.",
+            rejected.join("\n")
+        ))
+    }
+}
+
+/// Loads `file_paths` in order as `--- [filepath] ---` sections. A file that
+/// is missing, binary, too large or refused by `PathFilter` is reported
+/// rather than failing the load. With a budget, a file that would take the
+/// total past it is replaced by its outline when that fits, and skipped
+/// otherwise; later, smaller files may still fit.
+pub(crate) fn load_files_with_root(
+    file_paths: Vec<PathBuf>,
+    root: &Path,
+    budget: Option<u64>,
+) -> Result<(String, LoadReport), AppError> {
+    let filter = PathFilter::new_for_base_dir(root)?;
+    let mut codebase = String::new();
+    let mut report = LoadReport {
+        budget,
+        files: Vec::new(),
+    };
+    let mut used: u64 = 0;
+    let mut candidates: Option<Vec<PathBuf>> = None;
+    for path in file_paths {
+        let content = match read_file(root, &path, &filter) {
+            Ok(content) => content,
+            Err(FileOutcome::Missing { .. }) => {
+                let candidates = candidates
+                    .get_or_insert_with(|| selection::all_files(root).unwrap_or_default());
+                let suggestions = similar_paths(&path, candidates);
+                report
+                    .files
+                    .push((path, FileOutcome::Missing { suggestions }));
+                continue;
+            }
+            Err(outcome) => {
+                report.files.push((path, outcome));
+                continue;
+            }
+        };
+        let section = file_section(&path, &content);
+        let tokens = estimate_tokens(&section);
+        let fits = |cost: u64| budget.is_none_or(|budget| used + cost <= budget);
+
+        let outcome = if fits(tokens) {
+            codebase.push_str(&section);
+            used += tokens;
+            FileOutcome::Included { tokens }
+        } else {
+            let outlined = (path.extension().is_some_and(|ext| ext == "rs"))
+                .then(|| outline::file_outline(&content))
+                .flatten()
+                .map(|outline| {
+                    file_section(
+                        &path,
+                        &format!(
+                            "// Outline only: the full file (~{tokens} tokens) is over the context budget.\n{outline}"
+                        ),
+                    )
+                })
+                .filter(|section| fits(estimate_tokens(section)));
+            match outlined {
+                Some(section) => {
+                    let outline_tokens = estimate_tokens(&section);
+                    codebase.push_str(&section);
+                    used += outline_tokens;
+                    FileOutcome::Outlined {
+                        tokens,
+                        outline_tokens,
+                    }
+                }
+                None => FileOutcome::Skipped { tokens },
+            }
+        };
+        report.files.push((path, outcome));
+    }
+    Ok((codebase, report))
+}
+
+/// The text of one requested file, or why it cannot be loaded. A missing
+/// file's suggestions are filled in by the caller.
+fn read_file(root: &Path, path: &Path, filter: &PathFilter) -> Result<String, FileOutcome> {
+    if let Err(e) = filter.validate(path) {
+        let reason = match e {
+            AppError::FileUpdate(msg) => msg,
+            e => e.to_string(),
+        };
+        return Err(FileOutcome::Blocked { reason });
+    }
+    let full_path = root.join(path);
+    let metadata = fs::metadata(&full_path)
+        .ok()
+        .filter(|m| m.is_file())
+        .ok_or(FileOutcome::Missing {
+            suggestions: Vec::new(),
+        })?;
+    if metadata.len() > MAX_FILE_BYTES {
+        return Err(FileOutcome::TooLarge {
+            bytes: metadata.len(),
+        });
+    }
+    let bytes = fs::read(&full_path).map_err(|_| FileOutcome::Missing {
+        suggestions: Vec::new(),
+    })?;
+    String::from_utf8(bytes).map_err(|_| FileOutcome::Binary)
+}
+
+/// The loadable files whose paths are closest to `path`: those with the same
+/// file name, and those within a few edits of the whole path, nearest first.
+pub(crate) fn similar_paths(path: &Path, candidates: &[PathBuf]) -> Vec<PathBuf> {
+    let target = path.to_string_lossy().to_lowercase();
+    let name = path.file_name();
+    let max_distance = (target.chars().count() / 4).max(2);
+    let mut scored: Vec<(bool, usize, &PathBuf)> = candidates
+        .iter()
+        .filter_map(|candidate| {
+            let same_name = name.is_some() && candidate.file_name() == name;
+            let distance = edit_distance(&target, &candidate.to_string_lossy().to_lowercase());
+            (same_name || distance <= max_distance).then_some((!same_name, distance, candidate))
+        })
+        .collect();
+    scored.sort();
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, _, candidate)| candidate.clone())
+        .collect()
+}
+
+/// Levenshtein distance in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn file_section(path: &Path, content: &str) -> String {
+    let mut section = format!("--- {} ---\n{content}", path.display());
+    if !content.ends_with('\n') {
+        section.push('\n');
+    }
+    section.push('\n');
+    section
+}
@@ -1,74 +1,219 @@
-mod path_filter;
+mod history;
+mod layout;
+mod loading;
+mod outline;
 mod response_parser;
+mod retrieval;
+mod selection;
 mod summary_builder;
+mod tools;
 
 #[cfg(test)]
 mod context_assembly_test;
 #[cfg(test)]
-mod path_filter_test;
+mod history_test;
+#[cfg(test)]
+mod layout_test;
+#[cfg(test)]
+mod outline_test;
 #[cfg(test)]
 mod response_parser_test_errors;
 #[cfg(test)]
 mod response_parser_test_happy;
 #[cfg(test)]
+mod retrieval_test;
+#[cfg(test)]
+mod selection_test;
+#[cfg(test)]
 mod summary_builder_test;
+#[cfg(test)]
+mod tools_test;
 
 use crate::app_error::AppError;
+use crate::cli::{ContextMode, GitHistory};
 use crate::config::Config;
 use crate::llm;
+use crate::llm::generation::CallRole;
+use crate::llm::schema::ResponseSchema;
+use crate::llm::Conversation;
 use crate::logger::Logger;
 use crate::system_prompts::CONTEXT_BUILDER_CONTEXT_QUERY;
+use loading::{load_files_with_root, LoadReport};
 use std::path::{Path, PathBuf};
 
+pub use tools::LocalTools;
+
+/// How many ranked files the preprocessing prompt lists.
+const RANKING_SIZE: usize = 20;
+
+/// The git history sections asked for with `--with-log` and `--with-diff`,
+/// followed by the chosen files.
 pub async fn build_codebase_context(
     next_agent_full_prompt: &str,
     config: &Config,
     logger: &Logger,
     log_prefix: &str,
+) -> Result<String, AppError> {
+    let history = build_history_context(&config.git_history)?;
+    let codebase =
+        context_files(next_agent_full_prompt, &history, config, logger, log_prefix).await?;
+    Ok(format!("{history}{codebase}"))
+}
+
+async fn context_files(
+    next_agent_full_prompt: &str,
+    history: &str,
+    config: &Config,
+    logger: &Logger,
+    log_prefix: &str,
+) -> Result<String, AppError> {
+    let root = Path::new(".");
+    let file_paths = match &config.context_mode {
+        ContextMode::Llm => None,
+        ContextMode::All => Some(selection::all_files(root)?),
+        ContextMode::Modules(names) => Some(selection::module_files(root, names)?),
+        ContextMode::Auto => {
+            let files = selection::auto_files(root, &config.query)?;
+            if files.is_none() {
+                println!("The query names no module and git shows no recent changes; choosing context files with the LLM.");
+            }
+            files
+        }
+        ContextMode::Ranked => {
+            let files = selection::ranked_files(root, &config.query)?;
+            if files.is_none() {
+                println!("No file matches the query's terms; choosing context files with the LLM.");
+            }
+            files
+        }
+    };
+    if let Some(file_paths) = file_paths {
+        let list: Vec<String> = file_paths.iter().map(|p| p.display().to_string()).collect();
+        logger.log_text(&format!("{log_prefix}-files.txt"), &list.join("\n"))?;
+        return load_context_files(file_paths, config, logger, log_prefix);
+    }
+
+    choose_files_with_llm(next_agent_full_prompt, history, config, logger, log_prefix).await
+}
+
+/// The preprocessing call: the model picks the files from the codebase
+/// summary.
+async fn choose_files_with_llm(
+    next_agent_full_prompt: &str,
+    history: &str,
+    config: &Config,
+    logger: &Logger,
+    log_prefix: &str,
 ) -> Result<String, AppError> {
     let codebase_summary = summary_builder::build_summary()?;
+    let ranking = retrieval_ranking(Path::new("."), &config.query);
 
+    // The summary rarely changes between runs, so it leads the prompt where a
+    // provider prompt cache can reuse it.
     let prompt = format!(
-        "{CONTEXT_BUILDER_CONTEXT_QUERY}\n\n=== Next Agent Full Prompt ===\n{next_agent_full_prompt}\n\n=== Codebase Summary ===\n{codebase_summary}"
+        "=== Codebase Summary ===\n{codebase_summary}\n\n{ranking}{history}=== Next Agent Full Prompt ===\n{next_agent_full_prompt}"
     );
 
-    let response_text = llm::query(
+    let mut conversation = Conversation::with_system(CONTEXT_BUILDER_CONTEXT_QUERY, prompt);
+    conversation.set_response_schema(ResponseSchema::file_list());
+    conversation.set_call_role(CallRole::Context);
+    let response_text = llm::converse(
         config.model,
         config.api_key.clone(),
-        &prompt,
+        &config.session,
+        &mut conversation,
         logger,
         log_prefix,
     )
     .await?;
 
     let file_paths = response_parser::parse_context_llm_response(&response_text)?;
+    let (mut codebase, mut report) =
+        load_files_with_root(file_paths, Path::new("."), config.context_budget)?;
 
-    load_context_files(file_paths)
-}
+    // One chance to fix hallucinated or unreadable paths. Whatever is still
+    // rejected after that is reported and left out.
+    if let Some(correction) = report.correction_request() {
+        println!(
+            "The context LLM chose files that could not be loaded; asking it once to correct the list."
+        );
+        logger.log_text(&format!("{log_prefix}-rejected.txt"), &report.render(false))?;
+        conversation.push_user(correction);
+        let response_text = llm::converse(
+            config.model,
+            config.api_key.clone(),
+            &config.session,
+            &mut conversation,
+            logger,
+            &format!("{log_prefix}-correction"),
+        )
+        .await?;
+        let file_paths = response_parser::parse_context_llm_response(&response_text)?;
+        (codebase, report) =
+            load_files_with_root(file_paths, Path::new("."), config.context_budget)?;
+    }
 
-fn load_context_files(file_paths: Vec<PathBuf>) -> Result<String, AppError> {
-    load_files_with_root(file_paths, Path::new("."))
+    report_context(&report, logger, log_prefix)?;
+    Ok(codebase)
 }
 
-fn load_files_with_root(file_paths: Vec<PathBuf>, root: &Path) -> Result<String, AppError> {
-    let filter = path_filter::PathFilter::new_for_base_dir(root)?;
-    let mut codebase = String::new();
-    for path in file_paths {
-        filter.validate(&path)?;
-        let full_path = root.join(&path);
-        let content = std::fs::read_to_string(&full_path).map_err(|e| {
-            AppError::FileUpdate(format!(
-                "Failed to read file for codebase {}: {}",
-                full_path.display(),
-                e
-            ))
-        })?;
-        codebase.push_str(&format!("--- {} ---\n", path.display()));
-        codebase.push_str(&content);
-        if !content.ends_with('\n') {
-            codebase.push('\n');
+/// The retrieval index's best files for `query` as a prompt section, or an
+/// empty string when there are none. The index only informs the choice, so a
+/// failure to build it is reported and otherwise ignored.
+fn retrieval_ranking(root: &Path, query: &str) -> String {
+    if query.trim().is_empty() {
+        return String::new();
+    }
+    let index = match retrieval::RetrievalIndex::open(root) {
+        Ok((index, _)) => index,
+        Err(e) => {
+            eprintln!("Warning: the retrieval index could not be updated: {e}");
+            return String::new();
         }
-        codebase.push('\n');
+    };
+    let ranked = index.rank(query, RANKING_SIZE);
+    if ranked.is_empty() {
+        return String::new();
+    }
+    let mut section = String::from("=== Retrieval Ranking ===\n");
+    for hit in ranked {
+        section.push_str(&format!("{:.2} {}\n", hit.score, hit.path.display()));
     }
+    section.push('\n');
+    section
+}
+
+/// The `=== Git Log ===` and `=== Git Diff ===` sections for `history`, or an
+/// empty string when it asks for neither. Paths that `PathFilter` refuses are
+/// left out.
+pub fn build_history_context(history: &GitHistory) -> Result<String, AppError> {
+    history::history_sections(Path::new("."), history)
+}
+
+/// The codebase summary on its own, for runs in which the model reads the
+/// files it needs with `LocalTools` instead of having them chosen up front.
+pub fn build_codebase_summary() -> Result<String, AppError> {
+    summary_builder::build_summary()
+}
+
+/// Loads the chosen files within `config.context_budget` and reports the
+/// outcome.
+fn load_context_files(
+    file_paths: Vec<PathBuf>,
+    config: &Config,
+    logger: &Logger,
+    log_prefix: &str,
+) -> Result<String, AppError> {
+    let (codebase, report) =
+        load_files_with_root(file_paths, Path::new("."), config.context_budget)?;
+    report_context(&report, logger, log_prefix)?;
     Ok(codebase)
 }
+
+/// Logs every file's outcome to `<log prefix>-context.txt` and prints the
+/// totals along with any file that did not make it in full.
+fn report_context(report: &LoadReport, logger: &Logger, log_prefix: &str) -> Result<(), AppError> {
+    logger.log_text(&format!("{log_prefix}-context.txt"), &report.render(false))?;
+    print!("{}", report.render(true));
+    Ok(())
+}
@@ -0,0 +1,439 @@
+use quote::ToTokens;
+use std::fs;
+use std::path::{Path, PathBuf};
+use syn::{Attribute, Fields, ImplItem, Item, TraitItem, Visibility};
+
+/// Below this share of outline items named in `APISignatures.md`, the
+/// hand-written file counts as terse and the outline is added next to it.
+const TERSE_COVERAGE: f64 = 0.5;
+
+/// One outline line, with the item name used to judge how complete a
+/// hand-written `APISignatures.md` is.
+struct Entry {
+    name: Option<String>,
+    text: String,
+}
+
+/// The generated outline of a module's `.rs` files, or `None` when none of
+/// them declares anything visible outside the file. Files are outlined in the
+/// order given.
+pub(crate) fn module_outline(files: &[PathBuf]) -> Option<String> {
+    let entries = outline_entries(files);
+    if entries.is_empty() {
+        return None;
+    }
+    let mut outline = String::from("--- GENERATED OUTLINE ---\n");
+    for entry in entries {
+        outline.push_str(&entry.text);
+        outline.push('\n');
+    }
+    outline.push_str("--- END GENERATED OUTLINE ---\n\n");
+    Some(outline)
+}
+
+/// The outline of one `.rs` file without the surrounding markers, or `None`
+/// when the file does not parse or declares nothing visible.
+pub(crate) fn file_outline(source: &str) -> Option<String> {
+    let file = syn::parse_file(source).ok()?;
+    let entries = items_outline(&file.items, "");
+    if entries.is_empty() {
+        return None;
+    }
+    let lines: Vec<String> = entries.into_iter().map(|entry| entry.text).collect();
+    Some(lines.join("\n") + "\n")
+}
+
+/// Whether a hand-written `APISignatures.md` names fewer than half of the
+/// items in the module's outline.
+pub(crate) fn is_terse(api_signatures: &str, files: &[PathBuf]) -> bool {
+    let names: Vec<String> = outline_entries(files)
+        .into_iter()
+        .filter_map(|entry| entry.name)
+        .collect();
+    if names.is_empty() {
+        return false;
+    }
+    let named = names
+        .iter()
+        .filter(|name| contains_identifier(api_signatures, name))
+        .count();
+    (named as f64) < names.len() as f64 * TERSE_COVERAGE
+}
+
+fn outline_entries(files: &[PathBuf]) -> Vec<Entry> {
+    let parsed: Vec<(&PathBuf, syn::Result<syn::File>)> = files
+        .iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rs"))
+        .filter_map(|path| {
+            let source = fs::read_to_string(path).ok()?;
+            Some((path, syn::parse_file(&source)))
+        })
+        .collect();
+    // Files declared as `#[cfg(test)] mod name;`, such as `api_test.rs`.
+    let test_modules: Vec<String> = parsed
+        .iter()
+        .filter_map(|(_, file)| file.as_ref().ok())
+        .flat_map(|file| &file.items)
+        .filter_map(|item| match item {
+            Item::Mod(m) if m.content.is_none() && is_test_only(&m.attrs) => {
+                Some(m.ident.to_string())
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut entries = Vec::new();
+    for (path, file) in parsed {
+        let stem = path.file_stem().map(|stem| stem.to_string_lossy());
+        if stem.is_some_and(|stem| test_modules.iter().any(|name| *name == stem)) {
+            continue;
+        }
+        let file_entries = match file {
+            Ok(file) => items_outline(&file.items, ""),
+            Err(e) => vec![Entry {
+                name: None,
+                text: format!("// could not be parsed: {e}"),
+            }],
+        };
+        if !file_entries.is_empty() {
+            entries.push(Entry {
+                name: None,
+                text: format!("// {}", display_path(path)),
+            });
+            entries.extend(file_entries);
+        }
+    }
+    entries
+}
+
+fn display_path(path: &Path) -> String {
+    let text = path.to_string_lossy().replace('\\', "/");
+    text.strip_prefix("./").unwrap_or(&text).to_string()
+}
+
+/// The visible items of a file or inline module, plus every `mod`
+/// declaration outside `#[cfg(test)]`, so that the module tree is complete.
+fn items_outline(items: &[Item], indent: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for item in items {
+        if is_test_only(item_attrs(item)) {
+            continue;
+        }
+        let (name, lines) = match item {
+            Item::Mod(m) => {
+                let head = format!("{}mod {}", vis_prefix(&m.vis), m.ident);
+                let lines = match &m.content {
+                    None => vec![format!("{head};")],
+                    Some((_, inner)) => {
+                        let mut lines = vec![format!("{head} {{")];
+                        lines.extend(
+                            items_outline(inner, "    ")
+                                .into_iter()
+                                .map(|entry| entry.text),
+                        );
+                        lines.push("}".to_string());
+                        lines
+                    }
+                };
+                (Some(m.ident.to_string()), lines)
+            }
+            Item::Fn(f) if is_visible(&f.vis) => (
+                Some(f.sig.ident.to_string()),
+                vec![format!("{}{};", vis_prefix(&f.vis), tokens(&f.sig))],
+            ),
+            Item::Struct(s) if is_visible(&s.vis) => {
+                let head = format!(
+                    "{}struct {}{}",
+                    vis_prefix(&s.vis),
+                    s.ident,
+                    generics(&s.generics)
+                );
+                (Some(s.ident.to_string()), struct_lines(&head, &s.fields))
+            }
+            Item::Enum(e) if is_visible(&e.vis) => {
+                let mut lines = vec![format!(
+                    "{}enum {}{} {{",
+                    vis_prefix(&e.vis),
+                    e.ident,
+                    generics(&e.generics)
+                )];
+                for variant in &e.variants {
+                    let fields = match &variant.fields {
+                        Fields::Unit => String::new(),
+                        Fields::Named(named) => {
+                            let fields: Vec<String> = named
+                                .named
+                                .iter()
+                                .map(|field| {
+                                    let ident = field.ident.as_ref().map(ToString::to_string);
+                                    format!("{}: {}", ident.unwrap_or_default(), tokens(&field.ty))
+                                })
+                                .collect();
+                            format!(" {{ {} }}", fields.join(", "))
+                        }
+                        fields => tokens(&without_attrs(fields)),
+                    };
+                    lines.push(format!("    {}{},", variant.ident, fields));
+                }
+                lines.push("}".to_string());
+                (Some(e.ident.to_string()), lines)
+            }
+            Item::Trait(t) if is_visible(&t.vis) => {
+                let mut lines = vec![format!(
+                    "{}trait {}{} {{",
+                    vis_prefix(&t.vis),
+                    t.ident,
+                    generics(&t.generics)
+                )];
+                for trait_item in &t.items {
+                    match trait_item {
+                        TraitItem::Fn(f) => lines.push(format!("    {};", tokens(&f.sig))),
+                        TraitItem::Type(ty) => lines.push(format!("    type {};", ty.ident)),
+                        TraitItem::Const(c) => {
+                            lines.push(format!("    const {}: {};", c.ident, tokens(&c.ty)))
+                        }
+                        _ => {}
+                    }
+                }
+                lines.push("}".to_string());
+                (Some(t.ident.to_string()), lines)
+            }
+            Item::Impl(i) => {
+                let head = match &i.trait_ {
+                    Some((_, path, _)) => format!(
+                        "impl{} {} for {}",
+                        generics(&i.generics),
+                        tokens(path),
+                        tokens(&i.self_ty)
+                    ),
+                    None => format!("impl{} {}", generics(&i.generics), tokens(&i.self_ty)),
+                };
+                let methods: Vec<String> = i
+                    .items
+                    .iter()
+                    .filter_map(|impl_item| match impl_item {
+                        ImplItem::Fn(f) if i.trait_.is_none() && is_visible(&f.vis) => {
+                            Some(format!("    {}{};", vis_prefix(&f.vis), tokens(&f.sig)))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+                if i.trait_.is_some() {
+                    (None, vec![format!("{head} {{}}")])
+                } else if methods.is_empty() {
+                    continue;
+                } else {
+                    let mut lines = vec![format!("{head} {{")];
+                    lines.extend(methods);
+                    lines.push("}".to_string());
+                    (None, lines)
+                }
+            }
+            Item::Const(c) if is_visible(&c.vis) => (
+                Some(c.ident.to_string()),
+                vec![format!(
+                    "{}const {}: {};",
+                    vis_prefix(&c.vis),
+                    c.ident,
+                    tokens(&c.ty)
+                )],
+            ),
+            Item::Static(s) if is_visible(&s.vis) => (
+                Some(s.ident.to_string()),
+                vec![format!(
+                    "{}static {}: {};",
+                    vis_prefix(&s.vis),
+                    s.ident,
+                    tokens(&s.ty)
+                )],
+            ),
+            Item::Type(t) if is_visible(&t.vis) => (
+                Some(t.ident.to_string()),
+                vec![format!(
+                    "{}type {}{} = {};",
+                    vis_prefix(&t.vis),
+                    t.ident,
+                    generics(&t.generics),
+                    tokens(&t.ty)
+                )],
+            ),
+            Item::Use(u) if is_visible(&u.vis) => (
+                None,
+                vec![format!("{}use {};", vis_prefix(&u.vis), tokens(&u.tree))],
+            ),
+            _ => continue,
+        };
+        if let Some(doc) = first_doc_sentence(item_attrs(item)) {
+            entries.push(Entry {
+                name: None,
+                text: format!("{indent}/// {doc}"),
+            });
+        }
+        let mut lines = lines.into_iter();
+        if let Some(first) = lines.next() {
+            entries.push(Entry {
+                name,
+                text: format!("{indent}{first}"),
+            });
+        }
+        entries.extend(lines.map(|line| Entry {
+            name: None,
+            text: format!("{indent}{line}"),
+        }));
+    }
+    entries
+}
+
+fn struct_lines(head: &str, fields: &Fields) -> Vec<String> {
+    match fields {
+        Fields::Unit => vec![format!("{head};")],
+        Fields::Unnamed(_) => vec![format!("{head}{};", tokens(&without_attrs(fields)))],
+        Fields::Named(named) => {
+            let visible: Vec<String> = named
+                .named
+                .iter()
+                .filter(|field| is_visible(&field.vis))
+                .map(|field| {
+                    let ident = field.ident.as_ref().map(ToString::to_string);
+                    format!(
+                        "    {}{}: {},",
+                        vis_prefix(&field.vis),
+                        ident.unwrap_or_default(),
+                        tokens(&field.ty)
+                    )
+                })
+                .collect();
+            let hidden = visible.len() < named.named.len();
+            if visible.is_empty() {
+                let body = if hidden { " /* private fields */ " } else { "" };
+                return vec![format!("{head} {{{body}}}")];
+            }
+            let mut lines = vec![format!("{head} {{")];
+            lines.extend(visible);
+            if hidden {
+                lines.push("    /* private fields */".to_string());
+            }
+            lines.push("}".to_string());
+            lines
+        }
+    }
+}
+
+fn item_attrs(item: &Item) -> &[Attribute] {
+    match item {
+        Item::Const(i) => &i.attrs,
+        Item::Enum(i) => &i.attrs,
+        Item::Fn(i) => &i.attrs,
+        Item::Impl(i) => &i.attrs,
+        Item::Mod(i) => &i.attrs,
+        Item::Static(i) => &i.attrs,
+        Item::Struct(i) => &i.attrs,
+        Item::Trait(i) => &i.attrs,
+        Item::Type(i) => &i.attrs,
+        Item::Use(i) => &i.attrs,
+        _ => &[],
+    }
+}
+
+fn is_test_only(attrs: &[Attribute]) -> bool {
+    attrs
+        .iter()
+        .any(|attr| attr.path().is_ident("cfg") && tokens(&attr.meta).contains("test"))
+}
+
+/// The first sentence of an item's doc comment.
+fn first_doc_sentence(attrs: &[Attribute]) -> Option<String> {
+    let mut paragraph: Vec<String> = Vec::new();
+    for attr in attrs {
+        let syn::Meta::NameValue(meta) = &attr.meta else {
+            continue;
+        };
+        let syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(text),
+            ..
+        }) = &meta.value
+        else {
+            continue;
+        };
+        if !meta.path.is_ident("doc") {
+            continue;
+        }
+        let line = text.value().trim().to_string();
+        if line.is_empty() {
+            if paragraph.is_empty() {
+                continue;
+            }
+            break;
+        }
+        paragraph.push(line);
+    }
+    if paragraph.is_empty() {
+        return None;
+    }
+    let text = paragraph.join(" ");
+    let sentence = match text.find(". ") {
+        Some(end) => &text[..=end],
+        None => &text,
+    };
+    Some(sentence.to_string())
+}
+
+fn is_visible(vis: &Visibility) -> bool {
+    !matches!(vis, Visibility::Inherited)
+}
+
+fn vis_prefix(vis: &Visibility) -> String {
+    if is_visible(vis) {
+        format!("{} ", tokens(vis))
+    } else {
+        String::new()
+    }
+}
+
+fn generics(generics: &syn::Generics) -> String {
+    tokens(generics)
+}
+
+/// Fields with their attributes, such as `#[from]`, left out.
+fn without_attrs(fields: &Fields) -> Fields {
+    let mut fields = fields.clone();
+    for field in fields.iter_mut() {
+        field.attrs.clear();
+    }
+    fields
+}
+
+/// Source text for a syntax node, spaced the way rustfmt would for the
+/// signatures and types that appear in an outline.
+fn tokens(node: &impl ToTokens) -> String {
+    let raw = node.to_token_stream().to_string();
+    let mut out = String::new();
+    let mut previous = "";
+    for token in raw.split_whitespace() {
+        let joins_previous = token.starts_with([',', ';', ':', '>', ')', ']', '}', '?', '.', '<'])
+            || (token.starts_with('(')
+                && (previous.ends_with(|c: char| c.is_alphanumeric() || c == '_')
+                    || (previous.ends_with('>') && !previous.ends_with("->"))));
+        let previous_joins_next =
+            previous.ends_with(['<', '(', '[', '{', '&', '*']) || previous.ends_with("::");
+        if !out.is_empty() && !joins_previous && !previous_joins_next {
+            out.push(' ');
+        }
+        out.push_str(token);
+        previous = token;
+    }
+    // Trailing commas, as rustfmt leaves them out on one line.
+    for close in [")", "]", ">", "}"] {
+        out = out.replace(&format!(",{close}"), close);
+    }
+    out
+}
+
+fn contains_identifier(text: &str, name: &str) -> bool {
+    let is_ident_char = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices(name).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + name.len()..].chars().next();
+        !before.is_some_and(is_ident_char) && !after.is_some_and(is_ident_char)
+    })
+}
@@ -0,0 +1,143 @@
+use super::outline::{is_terse, module_outline};
+use std::fs;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+const SOURCE: &str = r#"
+mod helpers;
+#[cfg(test)]
+mod helpers_test;
+
+/// Reads settings from disk.
+///
+/// More detail that the outline leaves out.
+pub struct Settings<'a> {
+    pub name: &'a str,
+    limit: u32,
+}
+
+pub(crate) struct Marker;
+
+pub struct Pair(pub u32, String);
+
+#[derive(Debug)]
+pub enum Mode {
+    Fast,
+    Limited(u32),
+    Named { #[allow(dead_code)] label: String },
+}
+
+pub trait Source {
+    type Item;
+    fn next(&mut self) -> Option<Self::Item>;
+}
+
+impl<'a> Settings<'a> {
+    pub fn load(path: &std::path::Path) -> Result<Vec<Settings<'a>>, std::io::Error> {
+        todo!()
+    }
+
+    fn private_helper(&self) {}
+}
+
+impl std::fmt::Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Ok(())
+    }
+}
+
+pub const LIMIT: usize = 10;
+pub(crate) type Lookup<'a> = &'a dyn Fn(&str) -> Option<String>;
+pub use helpers::{first, second};
+
+fn private() {}
+
+#[cfg(test)]
+pub fn test_only() {}
+"#;
+
+fn module(files: &[(&str, &str)]) -> (TempDir, Vec<PathBuf>) {
+    let dir = TempDir::new().unwrap();
+    let mut paths = Vec::new();
+    for (name, content) in files {
+        let path = dir.path().join(name);
+        fs::write(&path, content).unwrap();
+        paths.push(path);
+    }
+    (dir, paths)
+}
+
+#[test]
+fn test_outline_lists_visible_items_and_the_mod_tree() {
+    let (dir, files) = module(&[("mod.rs", SOURCE)]);
+
+    let outline = module_outline(&files).unwrap();
+    let expected = format!(
+        "--- GENERATED OUTLINE ---
+// {}
+mod helpers;
+/// Reads settings from disk.
+pub struct Settings<'a> {{
+    pub name: &'a str,
+    /* private fields */
+}}
+pub(crate) struct Marker;
+pub struct Pair(pub u32, String);
+pub enum Mode {{
+    Fast,
+    Limited(u32),
+    Named {{ label: String }},
+}}
+pub trait Source {{
+    type Item;
+    fn next(&mut self) -> Option<Self::Item>;
+}}
+impl<'a> Settings<'a> {{
+    pub fn load(path: &std::path::Path) -> Result<Vec<Settings<'a>>, std::io::Error>;
+}}
+impl std::fmt::Display for Mode {{}}
+pub const LIMIT: usize;
+pub(crate) type Lookup<'a> = &'a dyn Fn(&str) -> Option<String>;
+pub use helpers::{{first, second}};
+--- END GENERATED OUTLINE ---
+
+",
+        dir.path().join("mod.rs").display()
+    );
+    assert_eq!(outline, expected);
+}
+
+#[test]
+fn test_outline_skips_private_files_and_reports_parse_errors() {
+    let (_dir, files) = module(&[
+        ("notes.md", "pub fn not_rust() {}"),
+        ("private.rs", "fn hidden() {}\nstruct Hidden;\n"),
+        ("broken.rs", "pub fn broken( {"),
+        ("mod.rs", "#[cfg(test)]\nmod helpers_test;\n"),
+        ("helpers_test.rs", "pub fn fixture() {}\n"),
+    ]);
+
+    let outline = module_outline(&files).unwrap();
+    assert!(!outline.contains("not_rust"));
+    assert!(!outline.contains("private.rs"));
+    assert!(outline.contains("broken.rs\n// could not be parsed: "));
+    assert!(!outline.contains("fixture"));
+
+    assert!(module_outline(&files[..2]).is_none());
+}
+
+#[test]
+fn test_terse_when_most_items_are_not_named() {
+    let (_dir, files) = module(&[("mod.rs", SOURCE)]);
+
+    // Outline items: helpers, Settings, Marker, Pair, Mode, Source, LIMIT,
+    // Lookup.
+    assert!(is_terse("pub struct Settings;", &files));
+    assert!(!is_terse(
+        "Settings, Marker, Pair, Mode and helpers",
+        &files
+    ));
+    // "Modes" does not name Mode.
+    assert!(is_terse("Settings, Markers, Pairs, Modes", &files));
+    assert!(!is_terse("", &[]));
+}
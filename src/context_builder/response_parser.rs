@@ -1,7 +1,13 @@
 use crate::app_error::AppError;
+use crate::llm::schema;
 use std::path::PathBuf;
 
+/// Reads the file list from a JSON `{"files": [...]}` reply, falling back to a
+/// `%%%files ... %%%end` block when the reply is free text.
 pub(crate) fn parse_context_llm_response(text: &str) -> Result<Vec<PathBuf>, AppError> {
+    if let Some(files) = schema::parse_file_list(text) {
+        return Ok(files);
+    }
     let mut in_files_block = false;
     let mut files = Vec::new();
     for line in text.lines() {
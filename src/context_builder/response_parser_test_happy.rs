@@ -53,3 +53,29 @@ fn empty_list_is_valid() {
     let files = parse_context_llm_response(input).expect("parser should succeed");
     assert!(files.is_empty());
 }
+
+#[test]
+/// Reads a schema-constrained JSON reply, with or without a code fence.
+fn parses_json_file_list() {
+    for input in [
+        r#"{"files": ["src/main.rs", " Cargo.toml "]}"#,
+        "```json\n{\"files\": [\"src/main.rs\", \"Cargo.toml\"]}\n```",
+    ] {
+        let files = parse_context_llm_response(input).expect("parser should succeed");
+        assert_eq!(
+            files,
+            vec![PathBuf::from("src/main.rs"), PathBuf::from("Cargo.toml")]
+        );
+    }
+}
+
+#[test]
+/// JSON that does not follow the schema falls back to the sentinel block.
+fn falls_back_to_sentinels_for_other_json() {
+    let input = "%%%files\nsrc/lib.rs\n%%%end\n";
+    assert_eq!(
+        parse_context_llm_response(input).unwrap(),
+        vec![PathBuf::from("src/lib.rs")]
+    );
+    assert!(parse_context_llm_response(r#"{"paths": ["src/lib.rs"]}"#).is_err());
+}
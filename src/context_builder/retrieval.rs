@@ -0,0 +1,292 @@
+use crate::app_error::AppError;
+use crate::hash::fnv1a;
+use crate::path_filter::PathFilter;
+use crate::rollup;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Where the index is kept, relative to the project root. `agent-config/` is
+/// always gitignored, so the generated index never shows up as a change.
+pub(crate) const INDEX_PATH: &str = "agent-config/context-index.json";
+
+/// Bumped whenever tokenizing changes, so that old indexes are rebuilt.
+const INDEX_VERSION: u32 = 1;
+
+/// BM25 term-frequency saturation and length normalisation.
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// Words too common in code and prose to say anything about a file.
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "can", "do", "for", "from", "has", "have",
+    "if", "in", "is", "it", "its", "let", "mut", "not", "of", "on", "or", "pub", "self", "so",
+    "that", "the", "this", "to", "use", "was", "we", "when", "which", "will", "with", "fn", "impl",
+    "crate", "super", "mod", "struct", "enum", "match", "return", "some", "none", "ok", "err",
+    "true", "false", "should", "make", "add", "new",
+];
+
+/// A file's entry in the index. The modification time and size decide whether
+/// the file needs to be read again, and the hash whether it needs to be
+/// tokenized again.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct FileEntry {
+    modified_nanos: u128,
+    size: u64,
+    hash: String,
+    length: u64,
+    terms: BTreeMap<String, u32>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoredIndex {
+    version: u32,
+    files: BTreeMap<String, FileEntry>,
+}
+
+/// How much of the index had to be redone.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct UpdateStats {
+    pub(crate) files: usize,
+    /// Files that were new or whose contents changed.
+    pub(crate) tokenized: usize,
+    pub(crate) removed: usize,
+}
+
+/// A file and how well it matches a query.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Ranked {
+    pub(crate) path: PathBuf,
+    pub(crate) score: f64,
+}
+
+/// A BM25 index over the identifiers, doc comments and documentation of the
+/// project's files.
+#[derive(Debug)]
+pub(crate) struct RetrievalIndex {
+    files: BTreeMap<String, FileEntry>,
+}
+
+impl RetrievalIndex {
+    /// Loads the index from `root/agent-config`, brings it up to date with the
+    /// files on disk, and saves it if anything changed. A missing, unreadable
+    /// or outdated index is rebuilt.
+    pub(crate) fn open(root: &Path) -> Result<(Self, UpdateStats), AppError> {
+        let index_path = root.join(INDEX_PATH);
+        let stored = fs::read_to_string(&index_path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<StoredIndex>(&text).ok())
+            .filter(|stored| stored.version == INDEX_VERSION)
+            .unwrap_or_default();
+        let mut old = stored.files;
+        let mut files = BTreeMap::new();
+        let mut stats = UpdateStats::default();
+        let mut changed = false;
+
+        let filter = PathFilter::new_for_base_dir(root)?;
+        for path in rollup::rollup_files(root, false)? {
+            let Ok(rel) = path.strip_prefix(root) else {
+                continue;
+            };
+            if filter.validate(rel).is_err() {
+                continue;
+            }
+            let key = rel.to_string_lossy().replace('\\', "/");
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            let modified_nanos = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_nanos());
+            let size = metadata.len();
+            let previous = old.remove(&key);
+            if let Some(entry) = &previous {
+                if entry.modified_nanos == modified_nanos && entry.size == size {
+                    files.insert(key, entry.clone());
+                    continue;
+                }
+            }
+            let Ok(bytes) = fs::read(&path) else {
+                continue;
+            };
+            let hash = fnv1a(&bytes);
+            changed = true;
+            let entry = match previous {
+                Some(entry) if entry.hash == hash => FileEntry {
+                    modified_nanos,
+                    size,
+                    ..entry
+                },
+                _ => {
+                    stats.tokenized += 1;
+                    let text = std::str::from_utf8(&bytes).unwrap_or("");
+                    let terms = count_terms(file_terms(&key, text));
+                    FileEntry {
+                        modified_nanos,
+                        size,
+                        hash,
+                        length: terms.values().map(|&n| u64::from(n)).sum(),
+                        terms,
+                    }
+                }
+            };
+            files.insert(key, entry);
+        }
+        stats.removed = old.len();
+        stats.files = files.len();
+        changed |= stats.removed > 0;
+
+        if changed {
+            let stored = StoredIndex {
+                version: INDEX_VERSION,
+                files,
+            };
+            if let Some(parent) = index_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let json = serde_json::to_string(&stored)
+                .map_err(|e| AppError::Config(format!("Failed to encode {INDEX_PATH}: {e}")))?;
+            fs::write(&index_path, json)?;
+            return Ok((
+                Self {
+                    files: stored.files,
+                },
+                stats,
+            ));
+        }
+        Ok((Self { files }, stats))
+    }
+
+    /// The `limit` files that best match `query`, best first. Files that share
+    /// no term with the query are left out.
+    pub(crate) fn rank(&self, query: &str, limit: usize) -> Vec<Ranked> {
+        let mut query_terms = text_terms(query);
+        query_terms.sort();
+        query_terms.dedup();
+        let documents: Vec<(&String, &FileEntry)> =
+            self.files.iter().filter(|(_, e)| e.length > 0).collect();
+        if documents.is_empty() || query_terms.is_empty() {
+            return Vec::new();
+        }
+        let count = documents.len() as f64;
+        let average_length = documents.iter().map(|(_, e)| e.length as f64).sum::<f64>() / count;
+        let mut document_frequency: HashMap<&str, f64> = HashMap::new();
+        for term in &query_terms {
+            let df = documents
+                .iter()
+                .filter(|(_, e)| e.terms.contains_key(term))
+                .count();
+            document_frequency.insert(term, df as f64);
+        }
+
+        let mut ranked: Vec<Ranked> = documents
+            .iter()
+            .filter_map(|(path, entry)| {
+                let length_norm = 1.0 - B + B * entry.length as f64 / average_length;
+                let score: f64 = query_terms
+                    .iter()
+                    .filter_map(|term| {
+                        let tf = f64::from(*entry.terms.get(term)?);
+                        let df = document_frequency[term.as_str()];
+                        let idf = ((count - df + 0.5) / (df + 0.5) + 1.0).ln();
+                        Some(idf * tf * (K1 + 1.0) / (tf + K1 * length_norm))
+                    })
+                    .sum();
+                (score > 0.0).then(|| Ranked {
+                    path: PathBuf::from(path.as_str()),
+                    score,
+                })
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.path.cmp(&b.path)));
+        ranked.truncate(limit);
+        ranked
+    }
+}
+
+/// The terms of a file: its path components, and for Rust files the
+/// identifiers and doc comments, leaving out ordinary comments. Other text
+/// files, such as Markdown documentation, contribute every word.
+pub(crate) fn file_terms(path: &str, content: &str) -> Vec<String> {
+    let mut terms = text_terms(&path.replace(['/', '.'], " "));
+    if !path.ends_with(".rs") {
+        terms.extend(text_terms(content));
+        return terms;
+    }
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        if let Some(doc) = trimmed
+            .strip_prefix("///")
+            .or_else(|| trimmed.strip_prefix("//!"))
+        {
+            terms.extend(text_terms(doc));
+        } else {
+            let code = line.find("//").map_or(line, |at| &line[..at]);
+            terms.extend(text_terms(code));
+        }
+    }
+    terms
+}
+
+/// Lowercase terms for every identifier-like word in `text`. An identifier
+/// such as `build_codebase_context` or `RetryPolicy` yields itself and each of
+/// its parts.
+pub(crate) fn text_terms(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    for word in text.split(|c: char| !(c.is_alphanumeric() || c == '_')) {
+        let parts = identifier_parts(word);
+        let whole = word.to_lowercase();
+        if parts.len() > 1 {
+            push_term(&mut terms, whole);
+        }
+        for part in parts {
+            push_term(&mut terms, part);
+        }
+    }
+    terms
+}
+
+fn push_term(terms: &mut Vec<String>, term: String) {
+    if term.chars().count() >= 2
+        && !term.chars().all(|c| c.is_ascii_digit())
+        && !STOP_WORDS.contains(&term.as_str())
+    {
+        terms.push(term);
+    }
+}
+
+/// Splits snake_case and CamelCase into lowercase parts.
+fn identifier_parts(word: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    for piece in word.split('_').filter(|p| !p.is_empty()) {
+        let chars: Vec<char> = piece.chars().collect();
+        let mut current = String::new();
+        for (i, &c) in chars.iter().enumerate() {
+            let starts_hump = c.is_uppercase()
+                && i > 0
+                && (chars[i - 1].is_lowercase()
+                    || chars.get(i + 1).is_some_and(|next| next.is_lowercase()));
+            if starts_hump && !current.is_empty() {
+                parts.push(current.to_lowercase());
+                current.clear();
+            }
+            current.push(c);
+        }
+        if !current.is_empty() {
+            parts.push(current.to_lowercase());
+        }
+    }
+    parts
+}
+
+fn count_terms(terms: Vec<String>) -> BTreeMap<String, u32> {
+    let mut counts = BTreeMap::new();
+    for term in terms {
+        *counts.entry(term).or_insert(0) += 1;
+    }
+    counts
+}
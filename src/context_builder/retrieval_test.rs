@@ -0,0 +1,133 @@
+use super::retrieval::{file_terms, text_terms, RetrievalIndex, INDEX_PATH};
+use super::retrieval_ranking;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+
+fn write(root: &Path, rel: &str, content: &str) {
+    let path = root.join(rel);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+fn project() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path();
+    write(root, ".gitignore", "/agent-config\n");
+    write(
+        root,
+        "src/llm/rate_limit.rs",
+        "/// Holds back requests when the provider reports a rate limit.\npub struct RateLimiter;\nfn acquire_token() {}\n",
+    );
+    write(
+        root,
+        "src/config/settings.rs",
+        "/// Settings read from config.toml.\npub struct Settings { max_cost: f64 }\n",
+    );
+    write(
+        root,
+        "src/logger/mod.rs",
+        "// rate limit is mentioned only in a plain comment\npub fn log_text() {}\n",
+    );
+    write(root, "agent-config/gemini-key.txt", "rate limiter secret\n");
+    dir
+}
+
+#[test]
+fn test_terms_split_identifiers_and_skip_plain_comments() {
+    let terms = text_terms("build_codebase_context RetryPolicy HTTPClient x 42");
+    for expected in [
+        "build_codebase_context",
+        "build",
+        "codebase",
+        "context",
+        "retrypolicy",
+        "retry",
+        "policy",
+        "httpclient",
+        "http",
+        "client",
+    ] {
+        assert!(terms.contains(&expected.to_string()), "{expected}");
+    }
+    assert!(!terms.contains(&"x".to_string()));
+    assert!(!terms.contains(&"42".to_string()));
+
+    let terms = file_terms(
+        "src/a.rs",
+        "// hidden words\n/// shown words\nlet value = 1;\n",
+    );
+    assert!(terms.contains(&"shown".to_string()));
+    assert!(terms.contains(&"value".to_string()));
+    assert!(!terms.contains(&"hidden".to_string()));
+}
+
+#[test]
+fn test_rank_prefers_matching_identifiers_and_docs() {
+    let dir = project();
+    let (index, stats) = RetrievalIndex::open(dir.path()).unwrap();
+    // The three source files; hidden files such as .gitignore are skipped.
+    assert_eq!(stats.files, 3);
+    assert_eq!(stats.tokenized, 3);
+
+    let ranked = index.rank("Make the rate limiter wait longer", 10);
+    assert_eq!(ranked[0].path, PathBuf::from("src/llm/rate_limit.rs"));
+    // Protected folders are never indexed, and plain comments do not count.
+    assert!(ranked.iter().all(|r| !r.path.starts_with("agent-config")));
+    assert!(ranked
+        .iter()
+        .all(|r| r.path != Path::new("src/logger/mod.rs")));
+    assert!(ranked.windows(2).all(|w| w[0].score >= w[1].score));
+
+    assert!(index.rank("the and of", 10).is_empty());
+}
+
+#[test]
+fn test_index_updates_incrementally() {
+    let dir = project();
+    let root = dir.path();
+    RetrievalIndex::open(root).unwrap();
+    assert!(root.join(INDEX_PATH).is_file());
+
+    let (_, stats) = RetrievalIndex::open(root).unwrap();
+    assert_eq!(stats.tokenized, 0);
+
+    // A new modification time with the same contents is not tokenized again.
+    let file = fs::File::options()
+        .write(true)
+        .open(root.join("src/config/settings.rs"))
+        .unwrap();
+    file.set_modified(SystemTime::now() + Duration::from_secs(5))
+        .unwrap();
+    let (_, stats) = RetrievalIndex::open(root).unwrap();
+    assert_eq!(stats.tokenized, 0);
+
+    write(root, "src/config/settings.rs", "pub struct Throttle;\n");
+    fs::remove_file(root.join("src/logger/mod.rs")).unwrap();
+    let (index, stats) = RetrievalIndex::open(root).unwrap();
+    assert_eq!(stats.tokenized, 1);
+    assert_eq!(stats.removed, 1);
+    assert_eq!(
+        index.rank("throttle", 5)[0].path,
+        PathBuf::from("src/config/settings.rs")
+    );
+
+    // A corrupt index is rebuilt.
+    fs::write(root.join(INDEX_PATH), "not json").unwrap();
+    let (_, stats) = RetrievalIndex::open(root).unwrap();
+    assert_eq!(stats.tokenized, stats.files);
+}
+
+#[test]
+fn test_ranking_section_for_the_context_prompt() {
+    let dir = project();
+    let section = retrieval_ranking(dir.path(), "rate limiter");
+    assert!(section.starts_with("=== Retrieval Ranking ===\n"));
+    let first = section.lines().nth(1).unwrap();
+    assert!(first.ends_with(" src/llm/rate_limit.rs"), "{first}");
+    assert!(section.ends_with("\n\n"));
+
+    assert_eq!(retrieval_ranking(dir.path(), "  "), "");
+    assert_eq!(retrieval_ranking(dir.path(), "zebra"), "");
+}
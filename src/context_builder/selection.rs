@@ -0,0 +1,286 @@
+use super::layout::{self, Layout};
+use super::retrieval::RetrievalIndex;
+use crate::app_error::AppError;
+use crate::path_filter::PathFilter;
+use crate::rollup;
+use std::collections::{BTreeSet, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// How many recent commits `auto` looks at for changed files.
+const RECENT_COMMITS: usize = 5;
+
+/// How many of the best-ranked files `ranked` loads.
+const RANKED_FILES: usize = 15;
+
+/// Project files that every deterministic selection starts with, when present.
+const PROJECT_FILES: [&str; 5] = [
+    "Cargo.toml",
+    "UserSpecification.md",
+    "ModuleDependencies.md",
+    "src/main.rs",
+    "src/lib.rs",
+];
+
+/// The documentation of a module that is included when the module is only a
+/// dependency of the selected ones.
+const MODULE_DOCS: [&str; 3] = [
+    "UserSpecification.md",
+    "APISignatures.md",
+    "ModuleDependencies.md",
+];
+
+/// Every file the rollup covers that may be loaded into context.
+pub(crate) fn all_files(root: &Path) -> Result<Vec<PathBuf>, AppError> {
+    let filter = PathFilter::new_for_base_dir(root)?;
+    let files = rollup::rollup_files(root, false)?
+        .into_iter()
+        .filter_map(|path| path.strip_prefix(root).ok().map(Path::to_path_buf))
+        .filter(|rel| filter.validate(rel).is_ok() && is_text_file(&root.join(rel)))
+        .collect();
+    Ok(files)
+}
+
+/// The project files, every file of `names`, and the documentation of the
+/// modules they depend on, directly or indirectly.
+pub(crate) fn module_files(root: &Path, names: &[String]) -> Result<Vec<PathBuf>, AppError> {
+    let mut selection = Selection::new(root)?;
+    let mut modules = Vec::new();
+    for name in names {
+        let Some(module) = resolve_module(&selection.layout, name) else {
+            let known: Vec<String> = module_names(&selection.layout).into_iter().collect();
+            return Err(AppError::Config(format!(
+                "Unknown module '{name}' in --context. Modules: {}",
+                known.join(", ")
+            )));
+        };
+        modules.push(module);
+    }
+    selection.add_modules(&modules);
+    Ok(selection.files)
+}
+
+/// The files `auto` picks for `query`: the modules the query mentions, the
+/// files changed by recent commits or in the working tree, and the
+/// documentation of every module those depend on. Returns `None` when neither
+/// the query nor the history points at anything.
+pub(crate) fn auto_files(root: &Path, query: &str) -> Result<Option<Vec<PathBuf>>, AppError> {
+    let mut selection = Selection::new(root)?;
+    let mentioned = mentioned_modules(query, &module_names(&selection.layout));
+    let changed = recently_changed_files(root);
+    if mentioned.is_empty() && changed.is_empty() {
+        return Ok(None);
+    }
+    selection.add_modules(&mentioned);
+    let changed_modules: Vec<String> = changed
+        .iter()
+        .filter_map(|p| module_of(&selection.layout, p))
+        .collect();
+    for path in changed {
+        selection.add(path);
+    }
+    selection.add_docs_of_closure(&changed_modules);
+    Ok(Some(selection.files))
+}
+
+/// The project files and the best-ranked files for `query`, or `None` when
+/// no file shares a term with it.
+pub(crate) fn ranked_files(root: &Path, query: &str) -> Result<Option<Vec<PathBuf>>, AppError> {
+    let (index, _) = RetrievalIndex::open(root)?;
+    let ranked = index.rank(query, RANKED_FILES);
+    if ranked.is_empty() {
+        return Ok(None);
+    }
+    let mut selection = Selection::new(root)?;
+    for hit in ranked {
+        selection.add(hit.path);
+    }
+    Ok(Some(selection.files))
+}
+
+/// Every module of the project as found by `layout::discover`, by its path
+/// from the project root such as `src/llm` or `crates/core/src/store`.
+fn module_names(layout: &Layout) -> BTreeSet<String> {
+    layout.modules.keys().map(|dir| path_name(dir)).collect()
+}
+
+fn path_name(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/// The module `name` refers to: a module path, or for a module of the root
+/// crate its path under `src/`, so `llm` is `src/llm`.
+fn resolve_module(layout: &Layout, name: &str) -> Option<String> {
+    let name = name.trim().trim_end_matches('/');
+    [PathBuf::from(name), Path::new("src").join(name)]
+        .into_iter()
+        .find(|dir| layout.modules.contains_key(dir))
+        .map(|dir| path_name(&dir))
+}
+
+/// The modules whose name appears in `query` as a whole word, ignoring case.
+/// A module's name is its path after the last `src/`, such as `llm` or
+/// `llm/providers`, or its whole path such as `crates/core` when it has none.
+/// `context_builder` also matches "context builder" and "context-builder".
+pub(crate) fn mentioned_modules(query: &str, modules: &BTreeSet<String>) -> Vec<String> {
+    let query = query.to_lowercase();
+    modules
+        .iter()
+        .filter(|module| {
+            let name = short_name(module).to_lowercase();
+            [name.clone(), name.replace('_', " "), name.replace('_', "-")]
+                .iter()
+                .any(|form| contains_word(&query, form))
+        })
+        .cloned()
+        .collect()
+}
+
+fn short_name(module: &str) -> &str {
+    module
+        .strip_prefix("src/")
+        .or_else(|| module.rsplit_once("/src/").map(|(_, name)| name))
+        .unwrap_or(module)
+}
+
+fn contains_word(text: &str, word: &str) -> bool {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices(word).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + word.len()..].chars().next();
+        !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+    })
+}
+
+/// Files touched by the last few commits or changed in the working tree that
+/// still exist. Outside a git repository there are none.
+fn recently_changed_files(root: &Path) -> Vec<PathBuf> {
+    let log_range = format!("-n{RECENT_COMMITS}");
+    let commands: [&[&str]; 2] = [
+        &["log", &log_range, "--name-only", "--format="],
+        &["diff", "--name-only", "HEAD"],
+    ];
+    let mut files = Vec::new();
+    let mut seen = HashSet::new();
+    for args in commands {
+        let Ok(output) = Command::new("git").args(args).current_dir(root).output() else {
+            continue;
+        };
+        if !output.status.success() {
+            continue;
+        }
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let path = PathBuf::from(line.trim());
+            if !line.trim().is_empty() && root.join(&path).is_file() && seen.insert(path.clone()) {
+                files.push(path);
+            }
+        }
+    }
+    files
+}
+
+/// The nearest module directory that holds `path`, if any.
+fn module_of(layout: &Layout, path: &Path) -> Option<String> {
+    path.ancestors()
+        .skip(1)
+        .find(|dir| layout.modules.contains_key(*dir))
+        .map(path_name)
+}
+
+fn is_text_file(path: &Path) -> bool {
+    fs::read(path).is_ok_and(|bytes| std::str::from_utf8(&bytes).is_ok())
+}
+
+/// Files chosen so far, in order and without duplicates. Only files that exist,
+/// pass the `PathFilter` and are text are kept.
+struct Selection<'a> {
+    root: &'a Path,
+    filter: PathFilter,
+    layout: Layout,
+    files: Vec<PathBuf>,
+    seen: HashSet<PathBuf>,
+}
+
+impl<'a> Selection<'a> {
+    fn new(root: &'a Path) -> Result<Self, AppError> {
+        let filter = PathFilter::new_for_base_dir(root)?;
+        let mut selection = Self {
+            root,
+            layout: layout::discover(root, &filter)?,
+            filter,
+            files: Vec::new(),
+            seen: HashSet::new(),
+        };
+        for file in PROJECT_FILES {
+            selection.add(PathBuf::from(file));
+        }
+        Ok(selection)
+    }
+
+    fn add(&mut self, path: PathBuf) {
+        let full = self.root.join(&path);
+        if full.is_file()
+            && self.filter.validate(&path).is_ok()
+            && is_text_file(&full)
+            && self.seen.insert(path.clone())
+        {
+            self.files.push(path);
+        }
+    }
+
+    /// Every file of each module and of the modules nested in it, then the
+    /// docs of their dependencies.
+    fn add_modules(&mut self, names: &[String]) {
+        for name in names {
+            let dir = Path::new(name);
+            let files: Vec<PathBuf> = self
+                .layout
+                .modules
+                .iter()
+                .filter(|(module, _)| module.starts_with(dir))
+                .flat_map(|(_, files)| files.iter().cloned())
+                .collect();
+            for file in files {
+                self.add(file);
+            }
+        }
+        self.add_docs_of_closure(names);
+    }
+
+    /// The docs of every module that `names` depend on, following each
+    /// `ModuleDependencies.md` transitively.
+    fn add_docs_of_closure(&mut self, names: &[String]) {
+        let mut visited: HashSet<String> = names.iter().cloned().collect();
+        let mut queue: VecDeque<String> = names.iter().cloned().collect();
+        let mut dependencies = Vec::new();
+        while let Some(module) = queue.pop_front() {
+            for dependency in self.dependencies_of(&module) {
+                if visited.insert(dependency.clone()) {
+                    dependencies.push(dependency.clone());
+                    queue.push_back(dependency);
+                }
+            }
+        }
+        for module in names.iter().chain(&dependencies) {
+            for doc in MODULE_DOCS {
+                self.add(Path::new(module).join(doc));
+            }
+        }
+    }
+
+    /// The modules listed in a module's `ModuleDependencies.md`, as paths from
+    /// the project root such as `src/app_error`. Entries that are not modules
+    /// are skipped.
+    fn dependencies_of(&self, module: &str) -> Vec<String> {
+        let path = self.root.join(module).join("ModuleDependencies.md");
+        let Ok(content) = fs::read_to_string(path) else {
+            return Vec::new();
+        };
+        content
+            .lines()
+            .filter_map(|line| resolve_module(&self.layout, line.trim().trim_start_matches("./")))
+            .filter(|name| name != module)
+            .collect()
+    }
+}
@@ -0,0 +1,215 @@
+use super::selection::{all_files, auto_files, mentioned_modules, module_files, ranked_files};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+fn write(root: &Path, rel: &str, content: &str) {
+    let path = root.join(rel);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+/// A project with `app` depending on `store`, which depends on `errors`, and
+/// an unrelated `report_gen` module.
+fn project() -> TempDir {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path();
+    write(root, ".gitignore", "/agent-config\ntarget/\n");
+    write(root, "Cargo.toml", "[package]\n");
+    write(root, "UserSpecification.md", "# Spec\n");
+    write(root, "src/main.rs", "mod app;\n");
+    write(root, "src/app/mod.rs", "// app\n");
+    write(
+        root,
+        "src/app/ModuleDependencies.md",
+        "# Module Dependencies\n\nsrc/store\n",
+    );
+    write(root, "src/store/mod.rs", "// store\n");
+    write(root, "src/store/APISignatures.md", "store api\n");
+    write(root, "src/store/ModuleDependencies.md", "src/errors\n");
+    write(root, "src/errors/mod.rs", "// errors\n");
+    write(root, "src/errors/APISignatures.md", "errors api\n");
+    write(root, "src/report_gen/mod.rs", "// report\n");
+    write(root, "agent-config/gemini-key.txt", "secret\n");
+    write(root, "agent-state/index.json", "{}\n");
+    write(root, "target/debug/out.txt", "build output\n");
+    dir
+}
+
+fn paths(files: &[PathBuf]) -> Vec<String> {
+    files.iter().map(|p| p.display().to_string()).collect()
+}
+
+#[test]
+fn test_all_skips_protected_ignored_and_binary_files() {
+    let dir = project();
+    let root = dir.path();
+    fs::write(root.join("src/logo.png"), [0x89, 0x50, 0xff, 0x00]).unwrap();
+
+    let files = paths(&all_files(root).unwrap());
+
+    assert!(files.contains(&"src/app/mod.rs".to_string()));
+    assert!(files.contains(&"src/report_gen/mod.rs".to_string()));
+    assert!(!files.iter().any(|f| f.starts_with("agent-config")));
+    assert!(!files.iter().any(|f| f.starts_with("agent-state")));
+    assert!(!files.iter().any(|f| f.starts_with("target")));
+    assert!(!files.contains(&"src/logo.png".to_string()));
+}
+
+#[test]
+fn test_modules_include_dependency_docs_transitively() {
+    let dir = project();
+    let root = dir.path();
+
+    let files = paths(&module_files(root, &["app".to_string()]).unwrap());
+
+    assert_eq!(
+        files,
+        vec![
+            "Cargo.toml",
+            "UserSpecification.md",
+            "src/main.rs",
+            "src/app/ModuleDependencies.md",
+            "src/app/mod.rs",
+            "src/store/APISignatures.md",
+            "src/store/ModuleDependencies.md",
+            "src/errors/APISignatures.md",
+        ]
+    );
+
+    let result = module_files(root, &["nope".to_string()]);
+    assert!(result.is_err_and(|e| e
+        .to_string()
+        .contains("src/app, src/errors, src/report_gen, src/store")));
+}
+
+#[test]
+fn test_mentioned_modules_match_whole_words() {
+    let modules: BTreeSet<String> = [
+        "src/app",
+        "src/errors",
+        "src/report_gen",
+        "src/store",
+        "crates/cli",
+        "crates/cli/src/args",
+    ]
+    .map(str::to_string)
+    .into();
+
+    assert_eq!(
+        mentioned_modules("Make the Store retry, and update the report gen", &modules),
+        vec!["src/report_gen".to_string(), "src/store".to_string()]
+    );
+    assert_eq!(
+        mentioned_modules("Parse more args in crates/cli", &modules),
+        vec!["crates/cli".to_string(), "crates/cli/src/args".to_string()]
+    );
+    // "application" and "errorsome" are not the app and errors modules.
+    assert!(mentioned_modules("the application is errorsome", &modules).is_empty());
+}
+
+#[test]
+fn test_auto_uses_the_query_and_recent_commits() {
+    let dir = project();
+    let root = dir.path();
+    let git = |args: &[&str]| {
+        let status = Command::new("git")
+            .args([
+                "-c",
+                "user.name=t",
+                "-c",
+                "user.email=t@t",
+                "-c",
+                "commit.gpgsign=false",
+            ])
+            .args(args)
+            .current_dir(root)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {args:?}");
+    };
+
+    // Outside a repository only the query counts.
+    assert!(auto_files(root, "Tidy things up").unwrap().is_none());
+    let files = paths(&auto_files(root, "Fix the errors module").unwrap().unwrap());
+    assert!(files.contains(&"src/errors/mod.rs".to_string()));
+    assert!(!files.contains(&"src/app/mod.rs".to_string()));
+
+    git(&["init", "-q"]);
+    git(&["add", "."]);
+    git(&["commit", "-q", "-m", "initial"]);
+    write(root, "src/report_gen/mod.rs", "// report v2\n");
+    git(&["commit", "-q", "-am", "report change"]);
+
+    // Every file of the first commit is recent, so use a query that adds
+    // nothing and check that the changed file and its module docs are in.
+    let files = paths(&auto_files(root, "Tidy things up").unwrap().unwrap());
+    assert!(files.contains(&"src/report_gen/mod.rs".to_string()));
+    assert!(!files.iter().any(|f| f.starts_with("agent-config")));
+}
+
+#[test]
+fn test_ranked_adds_the_best_matches_to_the_project_files() {
+    let dir = project();
+    let root = dir.path();
+    write(
+        root,
+        "src/store/cache.rs",
+        "/// Evicts stale cache entries.\nfn evict_stale() {}\n",
+    );
+
+    let files = paths(
+        &ranked_files(root, "Evict stale entries sooner")
+            .unwrap()
+            .unwrap(),
+    );
+    assert_eq!(
+        &files[..3],
+        ["Cargo.toml", "UserSpecification.md", "src/main.rs"]
+    );
+    assert_eq!(files[3], "src/store/cache.rs");
+
+    assert!(ranked_files(root, "zebra").unwrap().is_none());
+}
+
+#[test]
+fn test_modules_outside_the_root_src_can_be_selected() {
+    let dir = project();
+    let root = dir.path();
+    write(
+        root,
+        "Cargo.toml",
+        "[package]\n\n[workspace]\nmembers = [\"crates/*\"]\n",
+    );
+    write(root, "crates/core/Cargo.toml", "[package]\n");
+    write(root, "crates/core/src/lib.rs", "mod db;\n");
+    write(root, "crates/core/src/db/mod.rs", "// db\n");
+    write(
+        root,
+        "crates/core/src/db/ModuleDependencies.md",
+        "src/errors\ntools/gen\n",
+    );
+    write(root, "tests/cli/main.rs", "// cli test\n");
+    write(root, "tools/gen/UserSpecification.md", "# Gen\n");
+    write(root, "tools/gen/gen.py", "print()\n");
+
+    let files = paths(&module_files(root, &["crates/core/src/db".to_string()]).unwrap());
+    assert!(files.contains(&"crates/core/src/db/mod.rs".to_string()));
+    assert!(files.contains(&"src/errors/APISignatures.md".to_string()));
+    assert!(files.contains(&"tools/gen/UserSpecification.md".to_string()));
+    assert!(!files.contains(&"tools/gen/gen.py".to_string()));
+
+    let files = paths(&module_files(root, &["crates/core".to_string()]).unwrap());
+    assert!(files.contains(&"crates/core/Cargo.toml".to_string()));
+    assert!(files.contains(&"crates/core/src/db/mod.rs".to_string()));
+
+    let files = paths(&module_files(root, &["tests/cli/".to_string()]).unwrap());
+    assert!(files.contains(&"tests/cli/main.rs".to_string()));
+
+    let files = paths(&auto_files(root, "Speed up the db").unwrap().unwrap());
+    assert!(files.contains(&"crates/core/src/db/mod.rs".to_string()));
+    assert!(files.contains(&"tools/gen/UserSpecification.md".to_string()));
+}
@@ -1,11 +1,12 @@
 use crate::app_error::AppError;
+use crate::path_filter::PathFilter;
 use ignore::WalkBuilder;
 use path_clean::PathClean;
-use std::collections::BTreeMap;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 
-use super::path_filter::PathFilter;
+use super::layout;
+use super::outline;
 
 fn read_and_format_file(path: &Path) -> Result<String, AppError> {
     let content = fs::read_to_string(path).map_err(AppError::Io)?;
@@ -33,6 +34,9 @@ pub(crate) fn build_summary() -> Result<String, AppError> {
         ".gitignore",
         "build.sh",
         "Cargo.toml",
+        "package.json",
+        "pyproject.toml",
+        "go.mod",
         "ModuleDependencies.md",
         "UserSpecification.md",
     ];
@@ -65,8 +69,6 @@ pub(crate) fn build_summary() -> Result<String, AppError> {
     }
 
     let mut top_level_filenames: Vec<String> = Vec::new();
-    let mut src_top_level_filenames: Vec<String> = Vec::new();
-    let mut modules: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
 
     for result in WalkBuilder::new("./")
         .max_depth(Some(1))
@@ -85,78 +87,57 @@ pub(crate) fn build_summary() -> Result<String, AppError> {
     }
     top_level_filenames.sort();
 
-    let src_path = Path::new("src");
-    if src_path.is_dir() {
-        // Recursively walk src to catch all sub-modules
-        for result in WalkBuilder::new(src_path)
-            .git_ignore(true)
-            .parents(true)
-            .ignore(false)
-            .git_global(false)
-            .build()
-        {
-            let entry =
-                result.map_err(|e| AppError::Config(format!("Error walking directory: {e}")))?;
-            let path = entry.path().clean();
-
-            // We only care about files
-            if !path.is_file() {
-                continue;
-            }
-
-            // Skip src root itself (depth 0)
-            if entry.depth() == 0 {
-                continue;
-            }
-
-            // Depth 1 relative to src_path means src/file.rs
-            if entry.depth() == 1 {
-                let rel = to_relative_string(&path);
-                src_top_level_filenames.push(rel);
-            } else {
-                // Depth >= 2 means src/mod/file.rs or src/mod/sub/file.rs
-                // We group by the directory path relative to src
-                if let Some(parent) = path.parent() {
-                    if let Ok(relative_parent) = parent.strip_prefix(src_path) {
-                        let module_name = relative_parent.to_string_lossy().into_owned();
-                        modules.entry(module_name).or_default().push(path);
-                    }
-                }
-            }
-        }
-    }
-    src_top_level_filenames.sort();
+    let layout = layout::discover(Path::new("."), &filter)?;
 
     summary.push_str("--- FILENAMES ---\n");
     for name in top_level_filenames {
         summary.push_str(&name);
         summary.push('\n');
     }
-    for name in src_top_level_filenames {
-        summary.push_str(&name);
+    for file in &layout.src_files {
+        summary.push_str(&to_relative_string(file));
         summary.push('\n');
     }
     summary.push_str("--- END FILENAMES ---\n\n");
 
-    for (module_name, mut files) in modules {
-        let module_path = src_path.join(&module_name);
+    for (module_path, files) in layout.modules {
         summary.push_str(&format!("=== {} ===\n\n", module_path.display()));
 
+        // A workspace member's manifest says what the crate is and what it
+        // depends on.
+        if layout.members.contains(&module_path) {
+            let manifest_path = module_path.join("Cargo.toml");
+            if manifest_path.exists() && filter.validate(&manifest_path).is_ok() {
+                summary.push_str(&read_and_format_file(&manifest_path)?);
+            }
+        }
+
         // Check for documentation files and validate they are not ignored
         let module_deps_path = module_path.join("ModuleDependencies.md");
         if module_deps_path.exists() && filter.validate(&module_deps_path).is_ok() {
             summary.push_str(&read_and_format_file(&module_deps_path)?);
         }
 
+        // The generated outline stands in for a missing APISignatures.md and
+        // backs up a terse one.
         let api_signatures_path = module_path.join("APISignatures.md");
-        if api_signatures_path.exists() && filter.validate(&api_signatures_path).is_ok() {
-            summary.push_str(&read_and_format_file(&api_signatures_path)?);
+        let include_outline =
+            if api_signatures_path.exists() && filter.validate(&api_signatures_path).is_ok() {
+                summary.push_str(&read_and_format_file(&api_signatures_path)?);
+                let api_signatures = fs::read_to_string(&api_signatures_path)?;
+                outline::is_terse(&api_signatures, &files)
+            } else {
+                true
+            };
+        if include_outline {
+            if let Some(module_outline) = outline::module_outline(&files) {
+                summary.push_str(&module_outline);
+            }
         }
 
         summary.push_str("--- FILENAMES ---\n");
-        files.sort();
-        for file in files {
-            summary.push_str(&format!("{}\n", to_relative_string(&file)));
+        for file in &files {
+            summary.push_str(&format!("{}\n", to_relative_string(file)));
         }
         summary.push_str("--- END FILENAMES ---\n\n");
     }
@@ -170,3 +170,68 @@ fn test_nested_module_structure() {
     assert!(summary.contains("=== src/parent/child ==="));
     assert!(summary.contains("src/parent/child/mod.rs"));
 }
+
+#[test]
+fn test_outline_replaces_missing_api_signatures() {
+    let _guard = CWD_LOCK.lock().unwrap();
+    let env = TestEnv::new();
+
+    env.create_file(".gitignore", "");
+    env.create_file("Cargo.toml", "[package]");
+    env.create_file("src/bare/mod.rs", "pub fn run(count: u32) -> bool { true }");
+
+    let summary = summary_builder::build_summary().unwrap();
+
+    assert!(summary.contains(
+        "=== src/bare ===\n\n--- GENERATED OUTLINE ---\n// src/bare/mod.rs\npub fn run(count: u32) -> bool;\n--- END GENERATED OUTLINE ---\n\n--- FILENAMES ---"
+    ));
+}
+
+#[test]
+fn test_outline_backs_up_terse_api_signatures() {
+    let _guard = CWD_LOCK.lock().unwrap();
+    let env = TestEnv::new();
+
+    env.create_file(".gitignore", "");
+    env.create_file("Cargo.toml", "[package]");
+    env.create_file("src/terse/APISignatures.md", "See the code.");
+    env.create_file("src/terse/mod.rs", "pub fn one() {}\npub fn two() {}");
+    env.create_file("src/full/APISignatures.md", "pub fn one();");
+    env.create_file("src/full/mod.rs", "pub fn one() {}");
+
+    let summary = summary_builder::build_summary().unwrap();
+    let terse = summary.split("=== src/terse ===").nth(1).unwrap();
+    let full = summary
+        .split("=== src/full ===")
+        .nth(1)
+        .unwrap()
+        .split("=== src/terse ===")
+        .next()
+        .unwrap();
+
+    assert!(terse.contains("See the code.\n\n--- GENERATED OUTLINE ---"));
+    assert!(terse.contains("pub fn two();"));
+    assert!(!full.contains("GENERATED OUTLINE"));
+}
+
+#[test]
+fn test_workspace_members_are_summarized() {
+    let _guard = CWD_LOCK.lock().unwrap();
+    let env = TestEnv::new();
+
+    env.create_file(".gitignore", "");
+    env.create_file("Cargo.toml", "[workspace]\nmembers = [\"crates/*\"]\n");
+    env.create_file("crates/core/Cargo.toml", "[package]\nname = \"core\"\n");
+    env.create_file("crates/core/src/lib.rs", "pub fn run() {}");
+    env.create_file("crates/core/src/APISignatures.md", "pub fn run();");
+
+    let summary = summary_builder::build_summary().unwrap();
+
+    assert!(summary.contains(
+        "=== crates/core ===\n\n--- crates/core/Cargo.toml ---\n[package]\nname = \"core\"\n"
+    ));
+    assert!(summary.contains(
+        "=== crates/core/src ===\n\n--- crates/core/src/APISignatures.md ---\npub fn run();"
+    ));
+    assert!(summary.contains("crates/core/src/lib.rs\n--- END FILENAMES ---"));
+}
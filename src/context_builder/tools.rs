@@ -0,0 +1,204 @@
+use crate::app_error::AppError;
+use crate::llm::tools::{ToolCall, ToolHandler, ToolSpec};
+use crate::path_filter::PathFilter;
+use ignore::WalkBuilder;
+use path_clean::PathClean;
+use serde_json::json;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Files longer than this are cut off, so that one generated file cannot fill
+/// the context window on its own.
+const MAX_READ_BYTES: usize = 200_000;
+const MAX_GREP_MATCHES: usize = 100;
+const MAX_GREP_LINE_CHARS: usize = 300;
+
+/// The `read_file`, `list_dir` and `grep` tools, run against the project in
+/// `root`. Every path goes through the same `PathFilter` as context files, so
+/// the model can never see ignored or protected files, and nothing outside
+/// `root` can be reached.
+pub struct LocalTools {
+    root: PathBuf,
+    filter: PathFilter,
+}
+
+impl LocalTools {
+    pub fn new(root: &Path) -> Result<Self, AppError> {
+        Ok(Self {
+            root: root.canonicalize()?,
+            filter: PathFilter::new_for_base_dir(root)?,
+        })
+    }
+
+    fn read_file(&self, path: &str) -> Result<String, String> {
+        let full = self.resolve(path)?;
+        let bytes = fs::read(&full).map_err(|e| format!("cannot read '{path}': {e}"))?;
+        let text = String::from_utf8(bytes).map_err(|_| format!("'{path}' is not a text file"))?;
+        if text.len() <= MAX_READ_BYTES {
+            return Ok(text);
+        }
+        let mut end = MAX_READ_BYTES;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        Ok(format!(
+            "{}\n[truncated: showing the first {end} of {} bytes]",
+            &text[..end],
+            text.len()
+        ))
+    }
+
+    fn list_dir(&self, path: &str) -> Result<String, String> {
+        let full = self.resolve(path)?;
+        let entries = fs::read_dir(&full).map_err(|e| format!("cannot list '{path}': {e}"))?;
+        let mut names: Vec<String> = entries
+            .filter_map(|e| e.ok())
+            .filter_map(|entry| {
+                let relative = Path::new(path).join(entry.file_name());
+                let is_dir = entry.file_type().ok()?.is_dir();
+                self.check(&relative, is_dir).ok()?;
+                let name = entry.file_name().to_string_lossy().to_string();
+                Some(if is_dir { format!("{name}/") } else { name })
+            })
+            .collect();
+        names.sort();
+        if names.is_empty() {
+            return Ok(format!("'{path}' is empty"));
+        }
+        Ok(names.join("\n"))
+    }
+
+    /// Searches for `pattern` as a literal, case-sensitive string in every
+    /// allowed text file under `path`.
+    fn grep(&self, pattern: &str, path: &str) -> Result<String, String> {
+        if pattern.is_empty() {
+            return Err("the pattern must not be empty".to_string());
+        }
+        let start = self.resolve(path)?;
+        let mut matches = Vec::new();
+        let walker = WalkBuilder::new(&start)
+            .hidden(false)
+            .filter_entry(|e| e.file_name() != ".git")
+            .sort_by_file_path(|a, b| a.cmp(b))
+            .build();
+        for entry in walker.filter_map(|e| e.ok()) {
+            if !entry.file_type().is_some_and(|t| t.is_file()) {
+                continue;
+            }
+            let Ok(relative) = entry.path().strip_prefix(&self.root) else {
+                continue;
+            };
+            if self.check(relative, false).is_err() {
+                continue;
+            }
+            let Ok(content) = fs::read_to_string(entry.path()) else {
+                continue;
+            };
+            for (number, line) in content.lines().enumerate() {
+                if !line.contains(pattern) {
+                    continue;
+                }
+                if matches.len() == MAX_GREP_MATCHES {
+                    matches.push(format!(
+                        "[stopped after {MAX_GREP_MATCHES} matches; narrow the pattern or the path]"
+                    ));
+                    return Ok(matches.join("\n"));
+                }
+                let line: String = line.trim().chars().take(MAX_GREP_LINE_CHARS).collect();
+                matches.push(format!("{}:{}: {line}", relative.display(), number + 1));
+            }
+        }
+        if matches.is_empty() {
+            return Ok(format!("no matches for '{pattern}' in '{path}'"));
+        }
+        Ok(matches.join("\n"))
+    }
+
+    /// Checks a path relative to the root against the filter and the `.git`
+    /// directory, which `.gitignore` does not cover.
+    fn check(&self, relative: &Path, is_dir: bool) -> Result<(), String> {
+        if relative.clean().components().next() == Some(Component::Normal(".git".as_ref())) {
+            return Err(format!("'{}' is not accessible", relative.display()));
+        }
+        self.filter
+            .validate_entry(relative, is_dir)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Validates `path` and resolves it inside the root, which is kept in
+    /// canonical form. Symbolic links that lead out of the root are rejected.
+    fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        let relative = Path::new(path);
+        let full = self.root.join(relative);
+        if path != "." {
+            self.check(relative, full.is_dir())?;
+        }
+        let resolved = full
+            .canonicalize()
+            .map_err(|_| format!("'{path}' does not exist"))?;
+        if !resolved.starts_with(&self.root) {
+            return Err(format!("'{path}' is outside the project"));
+        }
+        Ok(resolved)
+    }
+}
+
+fn string_argument<'a>(call: &'a ToolCall, name: &str) -> Option<&'a str> {
+    call.arguments.get(name).and_then(|v| v.as_str())
+}
+
+impl ToolHandler for LocalTools {
+    fn specs(&self) -> Vec<ToolSpec> {
+        let path = |description: &str| json!({ "type": "string", "description": description });
+        vec![
+            ToolSpec {
+                name: "read_file",
+                description: "Returns the full contents of a text file in the project.",
+                parameters: json!({
+                    "type": "object",
+                    "properties": { "path": path("Path relative to the project root.") },
+                    "required": ["path"],
+                }),
+            },
+            ToolSpec {
+                name: "list_dir",
+                description: "Lists a directory in the project. Directories end with '/'.",
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "path": path("Path relative to the project root; '.' for the root.")
+                    },
+                    "required": ["path"],
+                }),
+            },
+            ToolSpec {
+                name: "grep",
+                description: "Finds lines containing a literal, case-sensitive string in the project's text files. Returns 'path:line: text' for each match.",
+                parameters: json!({
+                    "type": "object",
+                    "properties": {
+                        "pattern": { "type": "string", "description": "The text to search for." },
+                        "path": path("Directory or file to search, relative to the project root. Defaults to '.'.")
+                    },
+                    "required": ["pattern"],
+                }),
+            },
+        ]
+    }
+
+    fn call(&self, call: &ToolCall) -> String {
+        let result = match call.name.as_str() {
+            "read_file" => match string_argument(call, "path") {
+                Some(path) => self.read_file(path),
+                None => Err("missing the 'path' argument".to_string()),
+            },
+            "list_dir" => self.list_dir(string_argument(call, "path").unwrap_or(".")),
+            "grep" => match string_argument(call, "pattern") {
+                Some(pattern) => self.grep(pattern, string_argument(call, "path").unwrap_or(".")),
+                None => Err("missing the 'pattern' argument".to_string()),
+            },
+            other => Err(format!("unknown tool '{other}'")),
+        };
+        result.unwrap_or_else(|e| format!("Error: {e}"))
+    }
+}
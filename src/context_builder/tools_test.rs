@@ -0,0 +1,133 @@
+use super::tools::LocalTools;
+use crate::llm::tools::{ToolCall, ToolHandler};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn project() -> TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    fs::create_dir_all(root.join("src/llm")).unwrap();
+    fs::create_dir_all(root.join("agent-config")).unwrap();
+    fs::create_dir_all(root.join("target")).unwrap();
+    fs::create_dir_all(root.join(".git")).unwrap();
+    fs::write(root.join(".gitignore"), "target/\n").unwrap();
+    fs::write(root.join("src/main.rs"), "fn main() {\n    run();\n}\n").unwrap();
+    fs::write(root.join("src/llm/mod.rs"), "pub fn run() {}\n").unwrap();
+    fs::write(root.join("agent-config/gemini-key.txt"), "secret run()").unwrap();
+    fs::write(root.join("target/out.rs"), "fn run() {}").unwrap();
+    fs::write(root.join(".git/config"), "run()").unwrap();
+    dir
+}
+
+fn call(tools: &LocalTools, name: &str, arguments: Value) -> String {
+    tools.call(&ToolCall {
+        id: "call-1".to_string(),
+        name: name.to_string(),
+        arguments,
+    })
+}
+
+#[test]
+fn test_specs_name_the_three_tools() {
+    let dir = project();
+    let tools = LocalTools::new(dir.path()).unwrap();
+    let names: Vec<&str> = tools.specs().iter().map(|s| s.name).collect();
+    assert_eq!(names, vec!["read_file", "list_dir", "grep"]);
+}
+
+#[test]
+fn test_read_file_is_sandboxed() {
+    let dir = project();
+    let tools = LocalTools::new(dir.path()).unwrap();
+    assert_eq!(
+        call(&tools, "read_file", json!({ "path": "src/llm/mod.rs" })),
+        "pub fn run() {}\n"
+    );
+    for path in [
+        "agent-config/gemini-key.txt",
+        "target/out.rs",
+        ".git/config",
+        "../outside.txt",
+        "/etc/passwd",
+        "src/missing.rs",
+    ] {
+        let result = call(&tools, "read_file", json!({ "path": path }));
+        assert!(result.starts_with("Error: "), "{path}: {result}");
+    }
+    assert!(call(&tools, "read_file", json!({})).starts_with("Error: "));
+    assert!(call(&tools, "delete_file", json!({ "path": "src/main.rs" })).starts_with("Error: "));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_read_file_rejects_symlinks_out_of_the_project() {
+    let dir = project();
+    let outside = tempfile::tempdir().unwrap();
+    fs::write(outside.path().join("secret.txt"), "secret").unwrap();
+    std::os::unix::fs::symlink(
+        outside.path().join("secret.txt"),
+        dir.path().join("src/link.txt"),
+    )
+    .unwrap();
+    let tools = LocalTools::new(dir.path()).unwrap();
+    let result = call(&tools, "read_file", json!({ "path": "src/link.txt" }));
+    assert!(result.contains("outside the project"), "{result}");
+}
+
+#[test]
+fn test_list_dir_hides_filtered_entries() {
+    let dir = project();
+    let tools = LocalTools::new(dir.path()).unwrap();
+    assert_eq!(
+        call(&tools, "list_dir", json!({ "path": "." })),
+        ".gitignore\nsrc/"
+    );
+    assert_eq!(
+        call(&tools, "list_dir", json!({ "path": "src" })),
+        "llm/\nmain.rs"
+    );
+    assert!(call(&tools, "list_dir", json!({ "path": "agent-config" })).starts_with("Error: "));
+}
+
+#[test]
+fn test_grep_searches_only_allowed_files() {
+    let dir = project();
+    let tools = LocalTools::new(dir.path()).unwrap();
+    let result = call(&tools, "grep", json!({ "pattern": "run()" }));
+    assert_eq!(
+        result,
+        "src/llm/mod.rs:1: pub fn run() {}\nsrc/main.rs:2: run();"
+    );
+
+    let result = call(
+        &tools,
+        "grep",
+        json!({ "pattern": "run()", "path": "src/llm" }),
+    );
+    assert_eq!(result, "src/llm/mod.rs:1: pub fn run() {}");
+
+    let result = call(&tools, "grep", json!({ "pattern": "nowhere" }));
+    assert!(result.starts_with("no matches"), "{result}");
+    assert!(call(&tools, "grep", json!({ "pattern": "" })).starts_with("Error: "));
+}
+
+#[test]
+fn test_grep_stops_after_too_many_matches() {
+    let dir = project();
+    let lines = "hit\n".repeat(150);
+    fs::write(Path::new(dir.path()).join("src/many.txt"), lines).unwrap();
+    let tools = LocalTools::new(dir.path()).unwrap();
+    let result = call(&tools, "grep", json!({ "pattern": "hit" }));
+    assert_eq!(result.lines().count(), 101);
+    assert!(result.ends_with("narrow the pattern or the path]"));
+}
+
+#[test]
+fn test_relative_root_is_resolved() {
+    let dir = project();
+    let tools = LocalTools::new(&dir.path().join("src/..")).unwrap();
+    let result = call(&tools, "grep", json!({ "pattern": "pub fn run" }));
+    assert_eq!(result, "src/llm/mod.rs:1: pub fn run() {}");
+}
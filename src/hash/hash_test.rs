@@ -0,0 +1,8 @@
+use super::fnv1a;
+
+#[test]
+fn test_fnv1a_matches_the_reference_values() {
+    assert_eq!(fnv1a(b""), "cbf29ce484222325");
+    assert_eq!(fnv1a(b"a"), "af63dc4c8601ec8c");
+    assert_eq!(fnv1a(b"foobar"), "85944171f73967e8");
+}
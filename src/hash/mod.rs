@@ -0,0 +1,13 @@
+#[cfg(test)]
+mod hash_test;
+
+/// FNV-1a as 16 hex digits. Unlike the standard library's hashers it is
+/// stable across builds and Rust releases, so it can key data on disk.
+pub(crate) fn fnv1a(bytes: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{hash:016x}")
+}
@@ -0,0 +1,140 @@
+use crate::app_error::AppError;
+use crate::cli::{self, CliArgs, Model, QuerySource, Workflow};
+use crate::config::STDIN_ATTACHMENT;
+use crate::logger::{self, Logger, QUERY_FILE};
+use std::fmt::Write;
+use std::path::Path;
+
+/// Longest query line shown before it is cut short.
+const MAX_QUERY_CHARS: usize = 80;
+
+pub fn run_history_command() -> Result<(), AppError> {
+    print!("{}", list_runs(&Logger::default_root()));
+    Ok(())
+}
+
+/// Describes every run logged under `root`, newest first.
+pub(crate) fn list_runs(root: &Path) -> String {
+    let runs = logger::runs(root);
+    if runs.is_empty() {
+        return format!("No runs have been logged in {} yet.\n", root.display());
+    }
+    let mut out = String::new();
+    for run in runs {
+        let outcome = match (&run.outcome, &run.error) {
+            (Some(outcome), Some(error)) => {
+                format!("{outcome} ({})", error.lines().next().unwrap_or_default())
+            }
+            (Some(outcome), None) => outcome.clone(),
+            (None, _) => "-".to_string(),
+        };
+        let cost = run
+            .cost
+            .map_or("-".to_string(), |cost| format!("${cost:.4}"));
+        let query = run.query.as_deref().map_or("-".to_string(), first_line);
+        let _ = writeln!(out, "{}", run.run_id);
+        let _ = writeln!(out, "  workflow: {}", run.workflow);
+        let _ = writeln!(out, "  model:    {}", run.model.as_deref().unwrap_or("-"));
+        let _ = writeln!(out, "  outcome:  {outcome}");
+        let _ = writeln!(out, "  cost:     {cost}");
+        let _ = writeln!(out, "  query:    {query}");
+    }
+    out
+}
+
+fn first_line(query: &str) -> String {
+    let line = query.lines().next().unwrap_or_default();
+    if line.chars().count() <= MAX_QUERY_CHARS {
+        return line.to_string();
+    }
+    let cut: String = line.chars().take(MAX_QUERY_CHARS).collect();
+    format!("{cut}...")
+}
+
+/// Turns `rerun <run-id>` into the workflow that run used, reading the query
+/// from its `query.md` and using its model unless `--model` was given. The
+/// run's recorded options are restored, and options given to `rerun` are
+/// applied after them. Any other workflow is returned unchanged.
+pub(crate) fn rerun_args(root: &Path, args: CliArgs) -> Result<CliArgs, AppError> {
+    let Workflow::Rerun { run_id, model } = &args.workflow else {
+        return Ok(args);
+    };
+    let run = logger::find_run(root, run_id)?;
+    let (workflow, workflow_flag) = match run.workflow.as_str() {
+        "committing-code" => (Workflow::CommitCode, "--commit"),
+        "consistency" => (Workflow::ConsistencyCheck, "--cc"),
+        other => {
+            return Err(AppError::Config(format!(
+            "Run {} was a '{other}' run. Only committing-code and consistency runs can be rerun.",
+            run.run_id
+        )))
+        }
+    };
+    if args.force && workflow != Workflow::CommitCode {
+        return Err(AppError::Config(
+            "The --force or --f flag can only be used with the 'committing-code' workflow."
+                .to_string(),
+        ));
+    }
+    if run.query.is_none() {
+        return Err(AppError::Config(format!(
+            "Run {} has no saved query to rerun.",
+            run.run_id
+        )));
+    }
+    let model = match (model, &run.model) {
+        (Some(model), _) => *model,
+        (None, Some(name)) => Model::from_str(name)?,
+        (None, None) => {
+            println!(
+                "Run {} did not record its model; using the default model.",
+                run.run_id
+            );
+            Model::default()
+        }
+    };
+    println!(
+        "Rerunning {} ({}) with {} against the current tree.",
+        run.run_id,
+        run.workflow,
+        model.name()
+    );
+
+    // Piped input is gone once the run ends, so it cannot be attached again.
+    let mut restored = Vec::new();
+    let mut recorded = run.options.iter();
+    while let Some(option) = recorded.next() {
+        if option == "--attach" {
+            match recorded.next() {
+                Some(path) if path == STDIN_ATTACHMENT => {
+                    println!("Its standard input attachment (--attach -) is not carried over.");
+                }
+                Some(path) => restored.extend([option.clone(), path.clone()]),
+                None => {}
+            }
+        } else {
+            restored.push(option.clone());
+        }
+    }
+    if !restored.is_empty() {
+        println!("Restoring its options: {}", restored.join(" "));
+    }
+
+    let mut argv = vec![workflow_flag.to_string()];
+    argv.extend(restored);
+    argv.extend(args.run_options());
+    let options = cli::parse_args(argv.into_iter()).map_err(|e| {
+        AppError::Config(format!(
+            "The options of run {} cannot be combined with the ones given to rerun: {e}",
+            run.run_id
+        ))
+    })?;
+    Ok(CliArgs {
+        model,
+        force: args.force,
+        record: args.record,
+        replay: args.replay,
+        query: Some(QuerySource::File(root.join(&run.run_id).join(QUERY_FILE))),
+        ..options
+    })
+}
@@ -0,0 +1,256 @@
+use crate::cli::{CliArgs, ContextMode, DiffSpec, Model, QuerySource, Workflow};
+use crate::history::{list_runs, rerun_args};
+use crate::logger::{self, Logger};
+use std::fs;
+use std::path::Path;
+use tempfile::{tempdir, TempDir};
+
+fn write(root: &Path, rel: &str, content: &str) {
+    let path = root.join(rel);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+/// A log folder with a failed committing-code run, an older successful
+/// consistency run, a rollup, and a run logged before runs were recorded.
+fn logs() -> TempDir {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    write(
+        root,
+        "2026-10-18-09-00-00-committing-code/run.json",
+        r#"{"workflow": "committing-code", "model": "gpt-5.2", "outcome": "failed", "error": "The build did not pass after the maximum number of attempts.\nmore"}"#,
+    );
+    write(
+        root,
+        "2026-10-18-09-00-00-committing-code/query.md",
+        "Fix the parser\n\nIt drops trailing commas.\n",
+    );
+    write(
+        root,
+        "2026-10-18-09-00-00-committing-code/1-initial-response.json",
+        r#"{"callUsage": {"cost": 0.0125}}"#,
+    );
+    write(
+        root,
+        "2026-10-18-09-00-00-committing-code/2-repair-response.json",
+        r#"{"callUsage": {"cost": 0.0025}}"#,
+    );
+    write(
+        root,
+        "2026-10-17-09-00-00-consistency/run.json",
+        r#"{"workflow": "consistency", "model": "gemini-2.5-pro", "outcome": "succeeded"}"#,
+    );
+    write(
+        root,
+        "2026-10-17-09-00-00-consistency/query.md",
+        &format!("Check {}\n", "the docs ".repeat(12)),
+    );
+    write(
+        root,
+        "2026-10-16-09-00-00-rollup/run.json",
+        r#"{"workflow": "rollup", "outcome": "succeeded"}"#,
+    );
+    write(
+        root,
+        "2025-09-23-19-51-35-committing-code/query.md",
+        "Add logging\n",
+    );
+    dir
+}
+
+#[test]
+fn test_history_lists_runs_newest_first() {
+    let dir = logs();
+
+    let listing = list_runs(dir.path());
+    let expected = format!(
+        "2026-10-18-09-00-00-committing-code
+  workflow: committing-code
+  model:    gpt-5.2
+  outcome:  failed (The build did not pass after the maximum number of attempts.)
+  cost:     $0.0150
+  query:    Fix the parser
+2026-10-17-09-00-00-consistency
+  workflow: consistency
+  model:    gemini-2.5-pro
+  outcome:  succeeded
+  cost:     -
+  query:    Check {}...
+2026-10-16-09-00-00-rollup
+  workflow: rollup
+  model:    -
+  outcome:  succeeded
+  cost:     -
+  query:    -
+2025-09-23-19-51-35-committing-code
+  workflow: committing-code
+  model:    -
+  outcome:  -
+  cost:     -
+  query:    Add logging
+",
+        "the docs ".repeat(12).chars().take(74).collect::<String>()
+    );
+    assert_eq!(listing, expected);
+
+    let empty = tempdir().unwrap();
+    assert!(list_runs(&empty.path().join("logs")).starts_with("No runs have been logged in "));
+}
+
+#[test]
+fn test_rerun_repeats_the_recorded_workflow_query_and_model() {
+    let dir = logs();
+    let root = dir.path();
+    let rerun = |run_id: &str, model: Option<Model>| CliArgs {
+        workflow: Workflow::Rerun {
+            run_id: run_id.to_string(),
+            model,
+        },
+        max_cost: Some(1.0),
+        ..CliArgs::default()
+    };
+
+    let args = rerun_args(root, rerun("2026-10-18-09-00-00-committing-code", None)).unwrap();
+    assert_eq!(args.workflow, Workflow::CommitCode);
+    assert_eq!(args.model.name(), "gpt-5.2");
+    assert_eq!(
+        args.query,
+        Some(QuerySource::File(
+            root.join("2026-10-18-09-00-00-committing-code/query.md")
+        ))
+    );
+    assert_eq!(args.max_cost, Some(1.0));
+
+    // A unique prefix is enough, and --model replaces the recorded model.
+    let other = Model::from_str("gpt-5.2").unwrap();
+    let args = rerun_args(root, rerun("2026-10-17", Some(other))).unwrap();
+    assert_eq!(args.workflow, Workflow::ConsistencyCheck);
+    assert_eq!(args.model, other);
+
+    let args = rerun_args(root, rerun("2025-09-23-19-51-35-committing-code", None)).unwrap();
+    assert_eq!(args.model, Model::default());
+
+    for (run_id, message) in [
+        (
+            "2026-10-16-09-00-00-rollup",
+            "Only committing-code and consistency",
+        ),
+        ("2026-10-20", "No run"),
+        ("2026-10-1", "matches 3 runs"),
+        ("", "No run"),
+    ] {
+        let err = rerun_args(root, rerun(run_id, None))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains(message), "{run_id}: {err}");
+    }
+
+    write(root, "2026-10-19-09-00-00-committing-code/run.json", "{}");
+    let err = rerun_args(root, rerun("2026-10-19-09-00-00-committing-code", None))
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("has no saved query"), "{err}");
+
+    let forced = CliArgs {
+        force: true,
+        ..rerun("2026-10-17-09-00-00-consistency", None)
+    };
+    assert!(rerun_args(root, forced).is_err());
+
+    let args = CliArgs::default();
+    assert_eq!(rerun_args(root, args).unwrap(), CliArgs::default());
+}
+
+#[test]
+fn test_logged_runs_are_listed() {
+    let dir = tempdir().unwrap();
+    let logger = Logger::new_with_root(dir.path(), "consistency").unwrap();
+    logger
+        .log_run("consistency", Some("gpt-5.2"), &[], "unfinished", None)
+        .unwrap();
+    assert!(list_runs(dir.path()).contains("  outcome:  unfinished\n"));
+
+    logger
+        .log_run(
+            "consistency",
+            Some("gpt-5.2"),
+            &[],
+            "failed",
+            Some("Interrupted"),
+        )
+        .unwrap();
+    let listing = list_runs(dir.path());
+    assert!(listing.contains(
+        "  workflow: consistency\n  model:    gpt-5.2\n  outcome:  failed (Interrupted)\n"
+    ));
+}
+
+#[test]
+fn test_rerun_restores_the_recorded_options() {
+    let dir = tempdir().unwrap();
+    let logger = Logger::new_with_root(dir.path(), "committing-code").unwrap();
+    logger
+        .log_text(logger::QUERY_FILE, "Fix the parser")
+        .unwrap();
+    let original = CliArgs {
+        max_cost: Some(2.5),
+        stream: Some(false),
+        context: Some(ContextMode::Modules(vec![
+            "llm".to_string(),
+            "config".to_string(),
+        ])),
+        with_log: Some(3),
+        with_diff: Some(DiffSpec::Range("HEAD~3..HEAD".to_string())),
+        attach: vec!["notes.md".into(), "-".into()],
+        allow_external_attachments: true,
+        ..CliArgs::default()
+    };
+    logger
+        .log_run(
+            "committing-code",
+            Some("gpt-5.2"),
+            &original.run_options(),
+            "succeeded",
+            None,
+        )
+        .unwrap();
+    let run_id = logger::runs(dir.path())[0].run_id.clone();
+
+    // Options given to rerun replace the recorded ones; piped input is gone.
+    let rerun = CliArgs {
+        workflow: Workflow::Rerun {
+            run_id,
+            model: None,
+        },
+        max_cost: Some(1.0),
+        with_log: Some(5),
+        ..CliArgs::default()
+    };
+    let args = rerun_args(dir.path(), rerun).unwrap();
+    assert_eq!(args.workflow, Workflow::CommitCode);
+    assert_eq!(args.model.name(), "gpt-5.2");
+    assert_eq!(args.max_cost, Some(1.0));
+    assert_eq!(args.stream, Some(false));
+    assert_eq!(args.context, original.context);
+    assert_eq!(args.with_log, Some(5));
+    assert_eq!(args.with_diff, original.with_diff);
+    assert_eq!(args.attach, vec![std::path::PathBuf::from("notes.md")]);
+    assert!(args.allow_external_attachments);
+    assert!(matches!(args.query, Some(QuerySource::File(_))));
+}
+
+#[test]
+fn test_rerun_refuses_options_its_workflow_does_not_take() {
+    let dir = logs();
+    let rerun = CliArgs {
+        workflow: Workflow::Rerun {
+            run_id: "2026-10-17-09-00-00-consistency".to_string(),
+            model: None,
+        },
+        tools: true,
+        ..CliArgs::default()
+    };
+    let err = rerun_args(dir.path(), rerun).unwrap_err().to_string();
+    assert!(err.contains("cannot be combined"), "{err}");
+}
@@ -1,15 +1,36 @@
+use super::budget::Usage;
+use super::conversation::{Conversation, Message, Role};
+use super::generation::{GenerationParams, ReasoningEffort};
+use super::models::{self, Provider, MODELS};
+use super::rate_limit::{self, RateLimitInfo, RateLimiter};
+use super::registry::ProviderPool;
+use super::stream::{Progress, SseEvent, SseParser};
+use super::tokens::estimate_tokens;
+use super::tools::ToolCall;
 use crate::app_error::AppError;
-use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
-use reqwest::{Client, StatusCode};
+use crate::hash::fnv1a;
+use reqwest::header::{HeaderMap, HeaderValue};
+use reqwest::StatusCode;
 use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
 use std::future::Future;
 use std::pin::Pin;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 const GPT_API_URL: &str = "https://api.openai.com/v1/chat/completions";
-const GPT_MODEL_NAME: &str = "gpt-5.2";
 const GEMINI_INTERACTIONS_URL: &str =
     "https://generativelanguage.googleapis.com/v1beta/interactions";
+const GEMINI_CACHED_CONTENTS_URL: &str =
+    "https://generativelanguage.googleapis.com/v1beta/cachedContents";
+
+/// The smallest prefix Gemini accepts as cached content.
+const MIN_CACHED_TOKENS: u64 = 4_096;
+
+/// How long a Gemini cached content lives. Entries are treated as expired a
+/// little early so that a request is not sent just as the cache disappears.
+const CACHE_TTL: Duration = Duration::from_secs(600);
+const CACHE_EXPIRY_MARGIN: Duration = Duration::from_secs(30);
 
 #[derive(Debug)]
 pub(crate) enum QueryError {
@@ -27,58 +48,232 @@ pub(crate) enum QueryError {
         body: String,
         parse_error: String,
     },
+    Stream {
+        message: String,
+    },
 }
 
 pub(crate) struct GeminiClient {
-    client: Client,
+    pool: Arc<ProviderPool>,
     api_key: String,
     model_name: &'static str,
     api_url: String,
+    cache_url: String,
     polling_interval: Duration,
+    min_cached_tokens: u64,
+    /// The interaction currently being generated, so that it can be cancelled.
+    in_flight: Mutex<Option<String>>,
+    /// Cached contents created by this client, by a hash of their prefix.
+    caches: Mutex<HashMap<String, CachedPrefix>>,
+}
+
+struct CachedPrefix {
+    name: String,
+    expires: Instant,
 }
 
 impl GeminiClient {
-    pub(crate) fn new(api_key: String, model_name: &'static str) -> Self {
+    pub(crate) fn new(api_key: String, model_name: &'static str, pool: Arc<ProviderPool>) -> Self {
         Self::create(
+            pool,
             api_key,
             model_name,
             GEMINI_INTERACTIONS_URL.to_string(),
+            GEMINI_CACHED_CONTENTS_URL.to_string(),
             Duration::from_secs(2),
+            MIN_CACHED_TOKENS,
         )
     }
 
     #[cfg(test)]
     pub(crate) fn new_test(api_key: String, model_name: &'static str, api_url: String) -> Self {
-        Self::create(api_key, model_name, api_url, Duration::from_millis(10))
+        let cache_url = format!("{api_url}/cachedContents");
+        Self::create(
+            ProviderPool::new(),
+            api_key,
+            model_name,
+            api_url,
+            cache_url,
+            Duration::from_millis(10),
+            1,
+        )
     }
 
     fn create(
+        pool: Arc<ProviderPool>,
         api_key: String,
         model_name: &'static str,
         api_url: String,
+        cache_url: String,
         polling_interval: Duration,
+        min_cached_tokens: u64,
     ) -> Self {
-        let client = Client::builder()
-            .connect_timeout(Duration::from_secs(15))
-            .tcp_keepalive(Some(Duration::from_secs(30)))
-            .pool_idle_timeout(Duration::from_secs(90))
-            .pool_max_idle_per_host(8)
-            .build()
-            .unwrap_or_else(|_| Client::new());
         Self {
-            client,
+            pool,
             api_key,
             model_name,
             api_url,
+            cache_url,
             polling_interval,
+            min_cached_tokens,
+            in_flight: Mutex::new(None),
+            caches: Mutex::new(HashMap::new()),
         }
     }
 
-    async fn query_once(&self, request_body: &Value) -> Result<Value, QueryError> {
+    /// Sends an interaction, serving its stable prefix from cached content
+    /// when it has one. When the server no longer has the cached content, the
+    /// cache is forgotten and the request is sent again in full. The name of
+    /// the cached content used is added to the interaction as
+    /// `"cached_content"`.
+    async fn query_once(
+        &self,
+        request_body: &Value,
+        progress: &Progress<'_>,
+    ) -> Result<Value, QueryError> {
+        let Some((cached_body, name)) = self.with_cached_prefix(request_body).await else {
+            return self.query_interaction(request_body, progress).await;
+        };
+        match self.query_interaction(&cached_body, progress).await {
+            Ok(mut interaction) => {
+                if let Some(obj) = interaction.as_object_mut() {
+                    obj.insert("cached_content".to_string(), json!(name));
+                }
+                Ok(interaction)
+            }
+            Err(QueryError::Http { status, body, .. }) if is_missing_cache(status, &body) => {
+                println!("Gemini cached content {name} has expired; sending the full prompt");
+                self.forget_cache(&name);
+                self.query_interaction(request_body, progress).await
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// `request_body` with its system instruction and first input turn
+    /// replaced by a reference to cached content holding them, and the cached
+    /// content's name. The cache is created on first use and reused until it
+    /// expires. Returns `None` when the request continues a stored
+    /// interaction, declares tools, has nothing after the first turn, has a
+    /// prefix too small to cache, or the cache cannot be created.
+    async fn with_cached_prefix(&self, request_body: &Value) -> Option<(Value, String)> {
+        if request_body.get("previous_interaction_id").is_some()
+            || request_body.get("tools").is_some()
+        {
+            return None;
+        }
+        let input = request_body.get("input")?.as_array()?;
+        let (first, rest) = input.split_first()?;
+        if rest.is_empty() || first.get("role")?.as_str()? != "user" {
+            return None;
+        }
+        let prompt = first.get("content")?.as_str()?;
+        let system = request_body
+            .get("system_instruction")
+            .and_then(|s| s.as_str());
+        let prefix_tokens = estimate_tokens(prompt) + system.map_or(0, estimate_tokens);
+        if prefix_tokens < self.min_cached_tokens {
+            return None;
+        }
+
+        let key = fnv1a(
+            json!([self.model_name, system, prompt])
+                .to_string()
+                .as_bytes(),
+        );
+        let name = match self.cached(&key) {
+            Some(name) => name,
+            None => self.create_cache(&key, system, prompt).await?,
+        };
+        let mut body = request_body.clone();
+        if let Some(obj) = body.as_object_mut() {
+            obj.remove("system_instruction");
+            obj.insert("input".to_string(), Value::Array(rest.to_vec()));
+            obj.insert("cached_content".to_string(), json!(name));
+        }
+        Some((body, name))
+    }
+
+    fn cached(&self, key: &str) -> Option<String> {
+        let caches = self.caches.lock().unwrap_or_else(|e| e.into_inner());
+        caches
+            .get(key)
+            .filter(|cache| cache.expires > Instant::now())
+            .map(|cache| cache.name.clone())
+    }
+
+    fn forget_cache(&self, name: &str) {
+        let mut caches = self.caches.lock().unwrap_or_else(|e| e.into_inner());
+        caches.retain(|_, cache| cache.name != name);
+    }
+
+    /// Stores the prefix as cached content with a `CACHE_TTL` lifetime. A
+    /// failure is reported but not returned, since the request can still be
+    /// sent without the cache.
+    async fn create_cache(&self, key: &str, system: Option<&str>, prompt: &str) -> Option<String> {
+        let mut body = json!({
+            "model": format!("models/{}", self.model_name),
+            "contents": [{ "role": "user", "parts": [{ "text": prompt }] }],
+            "ttl": format!("{}s", CACHE_TTL.as_secs()),
+        });
+        if let Some(system) = system {
+            body["system_instruction"] = json!({ "parts": [{ "text": system }] });
+        }
+        let created_at = Instant::now();
+        self.pool.limiter.acquire().await;
+        let resp_res = self
+            .pool
+            .http
+            .post(&self.cache_url)
+            .header("x-goog-api-key", &self.api_key)
+            .header("Content-Type", "application/json")
+            .json(&body)
+            .send()
+            .await;
+        let name = match self.handle_response(resp_res).await {
+            Ok(cache) => cache
+                .get("name")
+                .and_then(|n| n.as_str())
+                .map(str::to_string),
+            Err(e) => {
+                eprintln!(
+                    "Warning: creating Gemini cached content failed: {}",
+                    map_query_error_to_app_error(e)
+                );
+                return None;
+            }
+        };
+        let Some(name) = name else {
+            eprintln!("Warning: Gemini cached content was created without a name");
+            return None;
+        };
+        println!("Created Gemini cached content {name}");
+        self.caches
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(
+                key.to_string(),
+                CachedPrefix {
+                    name: name.clone(),
+                    expires: created_at + CACHE_TTL - CACHE_EXPIRY_MARGIN,
+                },
+            );
+        Some(name)
+    }
+
+    async fn query_interaction(
+        &self,
+        request_body: &Value,
+        progress: &Progress<'_>,
+    ) -> Result<Value, QueryError> {
+        if is_streaming_request(request_body) {
+            return self.query_streaming(request_body, progress).await;
+        }
         let mut resp = self.post_interaction(request_body).await?;
         loop {
             let status = resp.get("status").and_then(|s| s.as_str()).unwrap_or("");
             if status == "completed" || status == "failed" || status == "cancelled" {
+                self.set_in_flight(None);
                 return Ok(resp);
             }
             let id =
@@ -89,14 +284,137 @@ impl GeminiClient {
                         parse_error: "Missing 'id' in non-terminal Interaction response"
                             .to_string(),
                     })?;
+            self.set_in_flight(Some(id));
+            progress.on_wait();
             tokio::time::sleep(self.polling_interval).await;
             resp = self.get_interaction(id).await?;
         }
     }
 
+    /// Streams an interaction as server-sent events. Text deltas are passed to
+    /// `progress` as they arrive, and the result is assembled into the same
+    /// shape as a polled interaction.
+    async fn query_streaming(
+        &self,
+        request_body: &Value,
+        progress: &Progress<'_>,
+    ) -> Result<Value, QueryError> {
+        self.pool.limiter.acquire().await;
+        let resp_res = self
+            .pool
+            .http
+            .post(&self.api_url)
+            .header("x-goog-api-key", &self.api_key)
+            .header("Content-Type", "application/json")
+            .header("Accept", "text/event-stream")
+            .json(request_body)
+            .send()
+            .await;
+        let resp = check_stream_response(resp_res, &self.api_key, &self.pool.limiter).await?;
+
+        let mut text = String::new();
+        let mut interaction: Option<Value> = None;
+        read_event_stream(resp, &self.api_key, |event| {
+            let data = parse_event_data(&event)?;
+            if let Some(id) = data.pointer("/interaction/id").and_then(|id| id.as_str()) {
+                self.set_in_flight(Some(id));
+            }
+            let event_type = event
+                .event
+                .as_deref()
+                .or_else(|| data.get("event_type").and_then(|t| t.as_str()))
+                .unwrap_or("");
+            match event_type {
+                "content.delta" => {
+                    let delta = data.get("delta").unwrap_or(&Value::Null);
+                    if delta.get("type").and_then(|t| t.as_str()) == Some("text") {
+                        let fragment = delta.get("text").and_then(|t| t.as_str()).unwrap_or("");
+                        text.push_str(fragment);
+                        progress.on_delta(fragment);
+                    }
+                    Ok(false)
+                }
+                "interaction.complete" => {
+                    interaction = Some(data.get("interaction").cloned().unwrap_or(json!({})));
+                    Ok(true)
+                }
+                "error" => Err(QueryError::Stream {
+                    message: censor_api_key(&event.data, &self.api_key),
+                }),
+                _ => Ok(false),
+            }
+        })
+        .await?;
+
+        let mut interaction = interaction.ok_or_else(|| QueryError::Stream {
+            message: "stream ended before the interaction completed".to_string(),
+        })?;
+        self.set_in_flight(None);
+        if let Some(obj) = interaction.as_object_mut() {
+            obj.entry("status").or_insert(json!("completed"));
+            let has_outputs = obj
+                .get("outputs")
+                .and_then(|o| o.as_array())
+                .is_some_and(|o| !o.is_empty());
+            if !has_outputs {
+                obj.insert(
+                    "outputs".to_string(),
+                    json!([{ "type": "text", "text": text }]),
+                );
+            }
+        }
+        Ok(interaction)
+    }
+
+    fn set_in_flight(&self, id: Option<&str>) {
+        *self.in_flight.lock().unwrap_or_else(|e| e.into_inner()) = id.map(str::to_string);
+    }
+
+    /// Asks the server to stop generating the interaction that is in flight,
+    /// if its id is known. Failures are reported but not returned, since the
+    /// call is being abandoned either way.
+    async fn cancel_in_flight(&self) {
+        let id = self
+            .in_flight
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        let Some(id) = id else {
+            return;
+        };
+        let url = format!("{}/{}/cancel", self.api_url, id);
+        let result = self
+            .pool
+            .http
+            .post(&url)
+            .header("x-goog-api-key", &self.api_key)
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await;
+        match result {
+            Ok(resp) if resp.status().is_success() => {
+                println!("Cancelled Gemini interaction {id}");
+            }
+            Ok(resp) => {
+                eprintln!(
+                    "Warning: cancelling Gemini interaction {id} failed with HTTP {}",
+                    resp.status()
+                );
+            }
+            Err(e) => {
+                eprintln!(
+                    "Warning: cancelling Gemini interaction {id} failed: {}",
+                    censor_api_key_in_error_string(e, &self.api_key)
+                );
+            }
+        }
+    }
+
     async fn post_interaction(&self, body: &Value) -> Result<Value, QueryError> {
+        self.pool.limiter.acquire().await;
         let resp_res = self
-            .client
+            .pool
+            .http
             .post(&self.api_url)
             .header("x-goog-api-key", &self.api_key)
             .header("Content-Type", "application/json")
@@ -108,8 +426,10 @@ impl GeminiClient {
 
     async fn get_interaction(&self, id: &str) -> Result<Value, QueryError> {
         let url = format!("{}/{}", self.api_url, id);
+        self.pool.limiter.acquire().await;
         let resp_res = self
-            .client
+            .pool
+            .http
             .get(&url)
             .header("x-goog-api-key", &self.api_key)
             .send()
@@ -131,37 +451,37 @@ impl GeminiClient {
                 });
             }
         };
-        handle_response_to_json(resp, &self.api_key).await
+        handle_response_to_json(resp, &self.api_key, &self.pool.limiter).await
     }
 }
 
 pub(crate) struct GptClient {
-    client: Client,
+    pool: Arc<ProviderPool>,
     api_key: String,
+    model_name: &'static str,
     api_url: String,
 }
 
 impl GptClient {
-    pub(crate) fn new(api_key: String) -> Self {
-        Self::create(api_key, GPT_API_URL.to_string())
+    pub(crate) fn new(api_key: String, model_name: &'static str, pool: Arc<ProviderPool>) -> Self {
+        Self::create(pool, api_key, model_name, GPT_API_URL.to_string())
     }
 
     #[cfg(test)]
     pub(crate) fn new_test(api_key: String, api_url: String) -> Self {
-        Self::create(api_key, api_url)
+        Self::create(ProviderPool::new(), api_key, "gpt-5.2", api_url)
     }
 
-    fn create(api_key: String, api_url: String) -> Self {
-        let client = Client::builder()
-            .connect_timeout(Duration::from_secs(15))
-            .tcp_keepalive(Some(Duration::from_secs(30)))
-            .pool_idle_timeout(Duration::from_secs(90))
-            .pool_max_idle_per_host(8)
-            .build()
-            .unwrap_or_else(|_| Client::new());
+    fn create(
+        pool: Arc<ProviderPool>,
+        api_key: String,
+        model_name: &'static str,
+        api_url: String,
+    ) -> Self {
         Self {
-            client,
+            pool,
             api_key,
+            model_name,
             api_url,
         }
     }
@@ -170,7 +490,9 @@ impl GptClient {
         &self,
         request_body: &Value,
         idempotency_key: &str,
+        progress: &Progress<'_>,
     ) -> Result<Value, QueryError> {
+        let streaming = is_streaming_request(request_body);
         let mut headers = HeaderMap::new();
         headers.insert("Content-Type", HeaderValue::from_static("application/json"));
         if !idempotency_key.is_empty() {
@@ -178,14 +500,23 @@ impl GptClient {
                 headers.insert("Idempotency-Key", hv);
             }
         }
+        if streaming {
+            headers.insert("Accept", HeaderValue::from_static("text/event-stream"));
+        }
+        self.pool.limiter.acquire().await;
         let resp_res = self
-            .client
+            .pool
+            .http
             .post(&self.api_url)
             .bearer_auth(&self.api_key)
             .headers(headers)
             .json(request_body)
             .send()
             .await;
+        if streaming {
+            let resp = check_stream_response(resp_res, &self.api_key, &self.pool.limiter).await?;
+            return self.read_streamed_completion(resp, progress).await;
+        }
         let resp = match resp_res {
             Ok(r) => r,
             Err(e) => {
@@ -196,7 +527,56 @@ impl GptClient {
                 });
             }
         };
-        handle_response_to_json(resp, &self.api_key).await
+        handle_response_to_json(resp, &self.api_key, &self.pool.limiter).await
+    }
+
+    /// Reads a streamed chat completion and assembles it into the shape of a
+    /// non-streamed one, so that text and usage extraction work unchanged.
+    async fn read_streamed_completion(
+        &self,
+        resp: reqwest::Response,
+        progress: &Progress<'_>,
+    ) -> Result<Value, QueryError> {
+        let mut text = String::new();
+        let mut usage = Value::Null;
+        let mut finished = false;
+        read_event_stream(resp, &self.api_key, |event| {
+            if event.data.trim() == "[DONE]" {
+                finished = true;
+                return Ok(true);
+            }
+            let data = parse_event_data(&event)?;
+            if data.get("error").is_some() {
+                return Err(QueryError::Stream {
+                    message: censor_api_key(&event.data, &self.api_key),
+                });
+            }
+            if let Some(fragment) = data
+                .pointer("/choices/0/delta/content")
+                .and_then(|c| c.as_str())
+            {
+                text.push_str(fragment);
+                progress.on_delta(fragment);
+            }
+            if let Some(u) = data.get("usage").filter(|u| !u.is_null()) {
+                usage = u.clone();
+            }
+            Ok(false)
+        })
+        .await?;
+
+        if !finished {
+            return Err(QueryError::Stream {
+                message: "stream ended before the completion finished".to_string(),
+            });
+        }
+        let mut completion = json!({
+            "choices": [{ "message": { "role": "assistant", "content": text } }],
+        });
+        if !usage.is_null() {
+            completion["usage"] = usage;
+        }
+        Ok(completion)
     }
 }
 
@@ -209,7 +589,14 @@ impl LlmApiClient {
     pub(crate) fn get_model_name(&self) -> &'static str {
         match self {
             LlmApiClient::Gemini(c) => c.model_name,
-            LlmApiClient::Gpt(_) => GPT_MODEL_NAME,
+            LlmApiClient::Gpt(c) => c.model_name,
+        }
+    }
+
+    fn limiter(&self) -> &RateLimiter {
+        match self {
+            LlmApiClient::Gemini(c) => &c.pool.limiter,
+            LlmApiClient::Gpt(c) => &c.pool.limiter,
         }
     }
 
@@ -220,18 +607,41 @@ impl LlmApiClient {
         }
     }
 
-    pub(crate) fn build_request_body(&self, prompt: &str) -> Value {
+    pub(crate) fn build_request_body(&self, conversation: &Conversation) -> Value {
+        match self {
+            LlmApiClient::Gemini(c) => build_gemini_request_body(c.model_name, conversation),
+            LlmApiClient::Gpt(c) => build_gpt_request_body(c.model_name, conversation),
+        }
+    }
+
+    /// The id under which the provider stored this exchange, if it keeps
+    /// conversation state server-side.
+    pub(crate) fn extract_conversation_id(&self, response: &Value) -> Option<String> {
+        match self {
+            LlmApiClient::Gemini(_) => response
+                .get("id")
+                .and_then(|id| id.as_str())
+                .map(|id| id.to_string()),
+            LlmApiClient::Gpt(_) => None,
+        }
+    }
+
+    pub(crate) fn enable_streaming(&self, request_body: &mut Value) {
+        match self {
+            LlmApiClient::Gemini(_) => {
+                request_body["stream"] = json!(true);
+            }
+            LlmApiClient::Gpt(_) => {
+                request_body["stream"] = json!(true);
+                request_body["stream_options"] = json!({ "include_usage": true });
+            }
+        }
+    }
+
+    pub(crate) fn apply_generation(&self, request_body: &mut Value, params: &GenerationParams) {
         match self {
-            LlmApiClient::Gemini(c) => json!({
-                "model": c.model_name,
-                "input": prompt,
-            }),
-            LlmApiClient::Gpt(_) => json!({
-                "model": GPT_MODEL_NAME,
-                "messages": [
-                    { "role": "user", "content": prompt }
-                ],
-            }),
+            LlmApiClient::Gemini(c) => apply_gemini_generation(c.model_name, request_body, params),
+            LlmApiClient::Gpt(c) => apply_gpt_generation(c.model_name, request_body, params),
         }
     }
 
@@ -239,14 +649,18 @@ impl LlmApiClient {
         &self,
         request_body: &Value,
         idempotency_key: Option<&str>,
+        progress: &Progress<'_>,
     ) -> Result<Value, AppError> {
         let policy = RetryPolicy::for_model(self);
         let mut attempt: u32 = 1;
         loop {
+            if attempt > 1 {
+                progress.on_retry(attempt);
+            }
             let result = match self {
-                LlmApiClient::Gemini(c) => c.query_once(request_body).await,
+                LlmApiClient::Gemini(c) => c.query_once(request_body, progress).await,
                 LlmApiClient::Gpt(c) => {
-                    c.query_once(request_body, idempotency_key.unwrap_or_default())
+                    c.query_once(request_body, idempotency_key.unwrap_or_default(), progress)
                         .await
                 }
             };
@@ -262,11 +676,14 @@ impl LlmApiClient {
                         ..
                     } = e
                     {
+                        // Hold back every call to this provider, not just
+                        // this one, until the server is ready again.
+                        self.limiter().pause(ra, Instant::now());
                         if ra > delay {
                             delay = ra;
                         }
                     }
-                    tokio::time::sleep(delay).await;
+                    tokio::time::sleep(delay.min(rate_limit::MAX_WAIT)).await;
                     attempt += 1;
                 }
             }
@@ -280,22 +697,60 @@ impl LlmApiClient {
         }
     }
 
+    pub(crate) fn extract_usage(&self, response: &Value) -> Option<Usage> {
+        match self {
+            LlmApiClient::Gemini(_) => extract_usage_from_gemini_response(response),
+            LlmApiClient::Gpt(_) => extract_usage_from_gpt_response(response),
+        }
+    }
+
+    pub(crate) fn extract_tool_calls(&self, response: &Value) -> Vec<ToolCall> {
+        match self {
+            LlmApiClient::Gemini(_) => extract_tool_calls_from_gemini_response(response),
+            LlmApiClient::Gpt(_) => extract_tool_calls_from_gpt_response(response),
+        }
+    }
+
     pub(crate) fn supports_idempotency(&self) -> bool {
         matches!(self, LlmApiClient::Gpt(_))
     }
+
+    /// Stops the server-side work of an abandoned call. Only Gemini keeps
+    /// generating after the connection is dropped; a GPT completion stops
+    /// with its connection, so there is nothing to do.
+    pub(crate) async fn cancel_in_flight(&self) {
+        if let LlmApiClient::Gemini(c) = self {
+            c.cancel_in_flight().await;
+        }
+    }
 }
 
 pub(crate) trait LlmApi: Send + Sync {
     fn get_model_name(&self) -> &'static str;
     fn get_url(&self) -> &str;
-    fn build_request_body(&self, prompt: &str) -> Value;
+    fn build_request_body(&self, conversation: &Conversation) -> Value;
+    fn extract_conversation_id(&self, response: &Value) -> Option<String>;
+    fn enable_streaming(&self, request_body: &mut Value);
+    /// Adds the temperature, output limit and reasoning effort in `params` to
+    /// the request in the provider's format.
+    fn apply_generation(&self, _request_body: &mut Value, _params: &GenerationParams) {}
     fn query_with_retries<'a>(
         &'a self,
         request_body: &'a Value,
         idempotency_key: Option<&'a str>,
+        progress: &'a Progress<'a>,
     ) -> Pin<Box<dyn Future<Output = Result<Value, AppError>> + Send + 'a>>;
     fn extract_text_from_response(&self, response: &Value) -> Result<String, AppError>;
+    fn extract_usage(&self, response: &Value) -> Option<Usage>;
+    /// The tool calls requested by the response, if any.
+    fn extract_tool_calls(&self, _response: &Value) -> Vec<ToolCall> {
+        Vec::new()
+    }
     fn supports_idempotency(&self) -> bool;
+    /// Called when a call is abandoned because of a deadline or Ctrl-C.
+    fn cancel_in_flight<'a>(&'a self) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        Box::pin(async {})
+    }
 }
 
 impl LlmApi for LlmApiClient {
@@ -305,28 +760,307 @@ impl LlmApi for LlmApiClient {
     fn get_url(&self) -> &str {
         LlmApiClient::get_url(self)
     }
-    fn build_request_body(&self, prompt: &str) -> Value {
-        LlmApiClient::build_request_body(self, prompt)
+    fn build_request_body(&self, conversation: &Conversation) -> Value {
+        LlmApiClient::build_request_body(self, conversation)
+    }
+    fn extract_conversation_id(&self, response: &Value) -> Option<String> {
+        LlmApiClient::extract_conversation_id(self, response)
+    }
+    fn enable_streaming(&self, request_body: &mut Value) {
+        LlmApiClient::enable_streaming(self, request_body)
+    }
+    fn apply_generation(&self, request_body: &mut Value, params: &GenerationParams) {
+        LlmApiClient::apply_generation(self, request_body, params)
     }
     fn query_with_retries<'a>(
         &'a self,
         request_body: &'a Value,
         idempotency_key: Option<&'a str>,
+        progress: &'a Progress<'a>,
     ) -> Pin<Box<dyn Future<Output = Result<Value, AppError>> + Send + 'a>> {
         Box::pin(LlmApiClient::query_with_retries(
             self,
             request_body,
             idempotency_key,
+            progress,
         ))
     }
     fn extract_text_from_response(&self, response: &Value) -> Result<String, AppError> {
         LlmApiClient::extract_text_from_response(self, response)
     }
+    fn extract_usage(&self, response: &Value) -> Option<Usage> {
+        LlmApiClient::extract_usage(self, response)
+    }
+    fn extract_tool_calls(&self, response: &Value) -> Vec<ToolCall> {
+        LlmApiClient::extract_tool_calls(self, response)
+    }
     fn supports_idempotency(&self) -> bool {
         LlmApiClient::supports_idempotency(self)
     }
+    fn cancel_in_flight<'a>(&'a self) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        Box::pin(LlmApiClient::cancel_in_flight(self))
+    }
+}
+
+/// Builds an Interactions request. System messages become the
+/// `system_instruction`, which is sent on every turn. When the conversation
+/// continues an interaction stored by this model, only the new turns are sent
+/// along with `previous_interaction_id`; otherwise the whole history is sent.
+pub(crate) fn build_gemini_request_body(model_name: &str, conversation: &Conversation) -> Value {
+    let mut body = json!({ "model": model_name });
+
+    let system: Vec<&str> = conversation
+        .messages()
+        .iter()
+        .filter(|m| m.role == Role::System)
+        .map(|m| m.content.as_str())
+        .collect();
+    if !system.is_empty() {
+        body["system_instruction"] = json!(system.join("\n\n"));
+    }
+
+    let continuation = conversation
+        .continuation(model_name)
+        .filter(|(_, new)| new.iter().any(|m| m.role != Role::System));
+    let turns = match continuation {
+        Some((id, new_messages)) => {
+            body["previous_interaction_id"] = json!(id);
+            new_messages
+        }
+        None => conversation.messages(),
+    };
+    let turns: Vec<&Message> = turns.iter().filter(|m| m.role != Role::System).collect();
+    body["input"] = match turns.as_slice() {
+        [only] if only.role == Role::User => json!(only.content),
+        _ => Value::Array(gemini_turns(&turns)),
+    };
+    if !conversation.tools().is_empty() {
+        body["tools"] = conversation
+            .tools()
+            .iter()
+            .map(|t| {
+                json!({
+                    "type": "function",
+                    "name": t.name,
+                    "description": t.description,
+                    "parameters": t.parameters,
+                })
+            })
+            .collect();
+    }
+    if let Some(schema) = conversation
+        .response_schema()
+        .filter(|_| models::supports(model_name, |f| f.json_mode))
+    {
+        body["response_mime_type"] = json!("application/json");
+        body["response_format"] = schema.schema.clone();
+    }
+    body
+}
+
+/// Converts messages to Interactions turns. Tool calls become `function_call`
+/// parts of the model turn, and consecutive tool results are sent together as
+/// `function_result` parts of one user turn.
+fn gemini_turns(messages: &[&Message]) -> Vec<Value> {
+    let mut turns: Vec<Value> = Vec::new();
+    let mut previous_was_result = false;
+    for m in messages {
+        if let Some(call) = &m.answers {
+            let part = json!({
+                "type": "function_result",
+                "call_id": call.id,
+                "name": call.name,
+                "result": m.content,
+            });
+            match turns.last_mut() {
+                Some(turn) if previous_was_result => {
+                    if let Some(parts) = turn["content"].as_array_mut() {
+                        parts.push(part);
+                    }
+                }
+                _ => turns.push(json!({ "role": "user", "content": [part] })),
+            }
+            previous_was_result = true;
+            continue;
+        }
+        previous_was_result = false;
+        let role = if m.role == Role::Assistant {
+            "model"
+        } else {
+            "user"
+        };
+        if m.tool_calls.is_empty() {
+            turns.push(json!({ "role": role, "content": m.content }));
+            continue;
+        }
+        let mut parts = Vec::new();
+        if !m.content.is_empty() {
+            parts.push(json!({ "type": "text", "text": m.content }));
+        }
+        parts.extend(m.tool_calls.iter().map(|call| {
+            json!({
+                "type": "function_call",
+                "id": call.id,
+                "name": call.name,
+                "arguments": call.arguments,
+            })
+        }));
+        turns.push(json!({ "role": role, "content": parts }));
+    }
+    turns
+}
+
+/// Sets the Interactions `generation_config`. Models with the
+/// `reasoning_effort` feature take a thinking level, and only "low" or "high",
+/// so lower efforts map to "low" and higher ones to "high". Models with the
+/// `thinking_budget` feature get the effort as a budget in tokens instead.
+/// Any other model drops the effort with a warning.
+pub(crate) fn apply_gemini_generation(
+    model_name: &str,
+    request_body: &mut Value,
+    params: &GenerationParams,
+) {
+    let mut config = json!({});
+    if let Some(t) = params.temperature {
+        config["temperature"] = json!(t);
+    }
+    if let Some(m) = params.max_output_tokens {
+        config["max_output_tokens"] = json!(m);
+    }
+    if let Some(effort) = params.reasoning_effort {
+        if models::supports(model_name, |f| f.reasoning_effort) {
+            let level = match effort {
+                ReasoningEffort::Minimal | ReasoningEffort::Low => "low",
+                ReasoningEffort::Medium | ReasoningEffort::High => "high",
+            };
+            config["thinking_level"] = json!(level);
+        } else if models::supports(model_name, |f| f.thinking_budget) {
+            config["thinking_config"] = json!({ "thinking_budget": thinking_budget(effort) });
+        } else {
+            warn_dropped(model_name, "reasoning_effort");
+        }
+    }
+    if config.as_object().is_some_and(|c| !c.is_empty()) {
+        request_body["generation_config"] = config;
+    }
+}
+
+/// The Gemini 2.5 thinking budget for an effort. Gemini 2.5 Pro cannot turn
+/// thinking off, so `minimal` is its smallest budget and `high` its largest.
+pub(crate) fn thinking_budget(effort: ReasoningEffort) -> u64 {
+    match effort {
+        ReasoningEffort::Minimal => 128,
+        ReasoningEffort::Low => 2_048,
+        ReasoningEffort::Medium => 8_192,
+        ReasoningEffort::High => 32_768,
+    }
 }
 
+/// Warns, once per model and parameter, that a configured generation
+/// parameter is not sent because the model does not support it.
+fn warn_dropped(model_name: &str, parameter: &'static str) {
+    static WARNED: Mutex<BTreeSet<(String, &str)>> = Mutex::new(BTreeSet::new());
+    let mut warned = WARNED.lock().unwrap_or_else(|e| e.into_inner());
+    if warned.insert((model_name.to_string(), parameter)) {
+        eprintln!(
+            "Warning: {model_name} does not support {parameter}; the configured value is not sent"
+        );
+    }
+}
+
+/// Sets the chat completions sampling fields. The output limit covers the
+/// reasoning tokens as well as the answer.
+pub(crate) fn apply_gpt_generation(
+    model_name: &str,
+    request_body: &mut Value,
+    params: &GenerationParams,
+) {
+    if let Some(t) = params.temperature {
+        request_body["temperature"] = json!(t);
+    }
+    if let Some(m) = params.max_output_tokens {
+        request_body["max_completion_tokens"] = json!(m);
+    }
+    if let Some(effort) = params.reasoning_effort {
+        if models::supports(model_name, |f| f.reasoning_effort) {
+            request_body["reasoning_effort"] = json!(effort.as_str());
+        } else {
+            warn_dropped(model_name, "reasoning_effort");
+        }
+    }
+}
+
+/// Builds a chat completions request. The API is stateless, so the whole
+/// conversation is sent as the `messages` array on every turn.
+pub(crate) fn build_gpt_request_body(model_name: &str, conversation: &Conversation) -> Value {
+    let messages: Vec<Value> = conversation
+        .messages()
+        .iter()
+        .map(|m| {
+            let role = match m.role {
+                Role::System => "system",
+                Role::User => "user",
+                Role::Assistant => "assistant",
+                Role::Tool => "tool",
+            };
+            let mut message = json!({ "role": role, "content": m.content });
+            if let Some(call) = &m.answers {
+                message["tool_call_id"] = json!(call.id);
+            }
+            if !m.tool_calls.is_empty() {
+                message["tool_calls"] = m
+                    .tool_calls
+                    .iter()
+                    .map(|call| {
+                        json!({
+                            "id": call.id,
+                            "type": "function",
+                            "function": { "name": call.name, "arguments": call.arguments.to_string() },
+                        })
+                    })
+                    .collect();
+            }
+            message
+        })
+        .collect();
+    let mut body = json!({
+        "model": model_name,
+        "messages": messages,
+    });
+    // OpenAI caches long prompt prefixes automatically; a stable key routes
+    // repeats of the same prefix to the same cache.
+    if let Some(key) = conversation.cache_key() {
+        body["prompt_cache_key"] = json!(key);
+    }
+    if !conversation.tools().is_empty() {
+        body["tools"] = conversation
+            .tools()
+            .iter()
+            .map(|t| {
+                json!({
+                    "type": "function",
+                    "function": {
+                        "name": t.name,
+                        "description": t.description,
+                        "parameters": t.parameters,
+                    },
+                })
+            })
+            .collect();
+    }
+    if let Some(schema) = conversation
+        .response_schema()
+        .filter(|_| models::supports(model_name, |f| f.json_mode))
+    {
+        body["response_format"] = json!({
+            "type": "json_schema",
+            "json_schema": { "name": schema.name, "strict": true, "schema": schema.schema },
+        });
+    }
+    body
+}
+
+#[derive(Debug, Clone, Copy)]
 pub(crate) struct RetryPolicy {
     pub(crate) max_attempts: u32,
     pub(crate) base_delay: Duration,
@@ -334,19 +1068,21 @@ pub(crate) struct RetryPolicy {
 }
 
 impl RetryPolicy {
+    /// The model's policy from the registry. A model missing from it gets the
+    /// policy of its provider's first registered model.
     pub(crate) fn for_model(model: &LlmApiClient) -> Self {
-        match model {
-            LlmApiClient::Gemini(_) => RetryPolicy {
-                max_attempts: 4,
-                base_delay: Duration::from_millis(400),
-                max_delay: Duration::from_secs(8),
-            },
-            LlmApiClient::Gpt(_) => RetryPolicy {
-                max_attempts: 6,
-                base_delay: Duration::from_millis(300),
-                max_delay: Duration::from_secs(10),
-            },
+        if let Some(spec) = models::lookup(model.get_model_name()) {
+            return spec.retry;
         }
+        let provider = match model {
+            LlmApiClient::Gemini(_) => Provider::Gemini,
+            LlmApiClient::Gpt(_) => Provider::Gpt,
+        };
+        MODELS
+            .iter()
+            .find(|spec| spec.provider == provider)
+            .map(|spec| spec.retry)
+            .expect("every provider has a registered model")
     }
     pub(crate) fn is_retryable(&self, model: &LlmApiClient, err: &QueryError) -> bool {
         match err {
@@ -362,6 +1098,9 @@ impl RetryPolicy {
                 matches!(status.as_u16(), 408 | 429 | 500 | 502 | 503 | 504)
             }
             QueryError::InvalidJson { .. } => false,
+            // A dropped or aborted stream is transient; the call is simply
+            // started again from the beginning.
+            QueryError::Stream { .. } => true,
         }
     }
     pub(crate) fn backoff_delay(&self, attempt: u32) -> Duration {
@@ -401,6 +1140,9 @@ fn map_query_error_to_app_error(e: QueryError) -> AppError {
         QueryError::InvalidJson { body, parse_error } => AppError::Network(format!(
             "Invalid JSON in success response: {parse_error}; raw body:\n{body}"
         )),
+        QueryError::Stream { message } => {
+            AppError::Network(format!("Streaming response failed: {message}"))
+        }
     }
 }
 
@@ -408,8 +1150,9 @@ pub(crate) fn censor_api_key(text: &str, api_key: &str) -> String {
     if api_key.is_empty() {
         return text.to_string();
     }
-    let censored_key = if api_key.len() > 8 {
-        format!("...{}", &api_key[api_key.len() - 4..])
+    let chars: Vec<char> = api_key.chars().collect();
+    let censored_key = if chars.len() > 8 {
+        format!("...{}", chars[chars.len() - 4..].iter().collect::<String>())
     } else {
         "...".to_string()
     };
@@ -423,9 +1166,10 @@ fn censor_api_key_in_error_string(e: reqwest::Error, api_key: &str) -> String {
 async fn handle_response_to_json(
     resp: reqwest::Response,
     api_key: &str,
+    limiter: &RateLimiter,
 ) -> Result<Value, QueryError> {
     let status = resp.status();
-    let retry_after = parse_retry_after(resp.headers());
+    let retry_after = observe_headers(resp.headers(), limiter);
     let text = match resp.text().await {
         Ok(t) => t,
         Err(e) => {
@@ -452,15 +1196,81 @@ async fn handle_response_to_json(
     }
 }
 
-fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
-    if let Some(val) = headers.get(RETRY_AFTER) {
-        if let Ok(s) = val.to_str() {
-            if let Ok(secs) = s.trim().parse::<u64>() {
-                return Some(Duration::from_secs(secs));
+/// Whether a failed interaction was refused because its cached content no
+/// longer exists: a 404, or a 400 or 403 whose message is about the cache.
+fn is_missing_cache(status: StatusCode, body: &str) -> bool {
+    status == StatusCode::NOT_FOUND
+        || (matches!(status, StatusCode::BAD_REQUEST | StatusCode::FORBIDDEN)
+            && body.to_lowercase().contains("cached"))
+}
+
+fn is_streaming_request(request_body: &Value) -> bool {
+    request_body.get("stream").and_then(|s| s.as_bool()) == Some(true)
+}
+
+/// Turns a failed send or a non-success status into a `QueryError`, and
+/// otherwise hands back the response so its event stream can be read.
+async fn check_stream_response(
+    resp_res: Result<reqwest::Response, reqwest::Error>,
+    api_key: &str,
+    limiter: &RateLimiter,
+) -> Result<reqwest::Response, QueryError> {
+    let resp = resp_res.map_err(|e| QueryError::Transport {
+        is_connect: e.is_connect(),
+        is_timeout: e.is_timeout(),
+        message: censor_api_key_in_error_string(e, api_key),
+    })?;
+    let retry_after = observe_headers(resp.headers(), limiter);
+    if resp.status().is_success() {
+        return Ok(resp);
+    }
+    let status = resp.status();
+    let body = resp.text().await.unwrap_or_default();
+    Err(QueryError::Http {
+        status,
+        body: censor_api_key(&body, api_key),
+        retry_after,
+    })
+}
+
+/// Feeds the response body through an SSE parser, calling `on_event` for each
+/// event until it returns `Ok(true)` or the body ends.
+async fn read_event_stream<F>(
+    mut resp: reqwest::Response,
+    api_key: &str,
+    mut on_event: F,
+) -> Result<(), QueryError>
+where
+    F: FnMut(SseEvent) -> Result<bool, QueryError>,
+{
+    let mut parser = SseParser::default();
+    loop {
+        let chunk = resp.chunk().await.map_err(|e| QueryError::Stream {
+            message: censor_api_key_in_error_string(e, api_key),
+        })?;
+        let Some(chunk) = chunk else {
+            return Ok(());
+        };
+        for event in parser.feed(&chunk) {
+            if on_event(event)? {
+                return Ok(());
             }
         }
     }
-    None
+}
+
+fn parse_event_data(event: &SseEvent) -> Result<Value, QueryError> {
+    serde_json::from_str(&event.data).map_err(|e| QueryError::InvalidJson {
+        body: event.data.clone(),
+        parse_error: e.to_string(),
+    })
+}
+
+/// Feeds a response's rate-limit headers to the provider's limiter and
+/// returns its `Retry-After`, if any.
+fn observe_headers(headers: &HeaderMap, limiter: &RateLimiter) -> Option<Duration> {
+    limiter.observe(&RateLimitInfo::from_headers(headers), Instant::now());
+    rate_limit::parse_retry_after(headers, SystemTime::now())
 }
 
 pub(crate) fn extract_text_from_gemini_response(response: &Value) -> Result<String, AppError> {
@@ -500,3 +1310,84 @@ pub(crate) fn extract_text_from_gpt_response(response: &Value) -> Result<String,
         })?;
     Ok(content.to_string())
 }
+
+pub(crate) fn extract_tool_calls_from_gemini_response(response: &Value) -> Vec<ToolCall> {
+    let Some(outputs) = response.get("outputs").and_then(|o| o.as_array()) else {
+        return Vec::new();
+    };
+    outputs
+        .iter()
+        .filter(|part| part.get("type").and_then(|t| t.as_str()) == Some("function_call"))
+        .filter_map(|part| {
+            Some(ToolCall {
+                id: part.get("id")?.as_str()?.to_string(),
+                name: part.get("name")?.as_str()?.to_string(),
+                arguments: part.get("arguments").cloned().unwrap_or(json!({})),
+            })
+        })
+        .collect()
+}
+
+/// GPT sends the arguments as a JSON string. Arguments that do not parse are
+/// kept as the raw string so that the tool can report the problem.
+pub(crate) fn extract_tool_calls_from_gpt_response(response: &Value) -> Vec<ToolCall> {
+    let Some(calls) = response
+        .get("choices")
+        .and_then(|c| c.as_array())
+        .and_then(|a| a.first())
+        .and_then(|choice| choice.get("message"))
+        .and_then(|msg| msg.get("tool_calls"))
+        .and_then(|c| c.as_array())
+    else {
+        return Vec::new();
+    };
+    calls
+        .iter()
+        .filter_map(|call| {
+            let function = call.get("function")?;
+            let raw = function
+                .get("arguments")
+                .and_then(|a| a.as_str())
+                .unwrap_or("{}");
+            Some(ToolCall {
+                id: call.get("id")?.as_str()?.to_string(),
+                name: function.get("name")?.as_str()?.to_string(),
+                arguments: serde_json::from_str(raw).unwrap_or_else(|_| json!(raw)),
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn extract_usage_from_gemini_response(response: &Value) -> Option<Usage> {
+    let usage = response.get("usage")?;
+    Some(Usage {
+        input_tokens: usage.get("total_input_tokens")?.as_u64()?,
+        output_tokens: usage
+            .get("total_output_tokens")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0)
+            + usage
+                .get("total_thought_tokens")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0),
+        cached_input_tokens: usage
+            .get("total_cached_tokens")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0),
+    })
+}
+
+pub(crate) fn extract_usage_from_gpt_response(response: &Value) -> Option<Usage> {
+    let usage = response.get("usage")?;
+    Some(Usage {
+        input_tokens: usage.get("prompt_tokens")?.as_u64()?,
+        output_tokens: usage
+            .get("completion_tokens")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0),
+        cached_input_tokens: usage
+            .pointer("/prompt_tokens_details/cached_tokens")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0),
+    })
+}
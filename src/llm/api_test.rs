@@ -1,7 +1,17 @@
 use super::api::{
-    self, extract_text_from_gemini_response, extract_text_from_gpt_response, GeminiClient,
-    GptClient, LlmApiClient, QueryError,
+    self, apply_gemini_generation, apply_gpt_generation, build_gemini_request_body,
+    build_gpt_request_body, extract_text_from_gemini_response, extract_text_from_gpt_response,
+    extract_tool_calls_from_gemini_response, extract_tool_calls_from_gpt_response,
+    extract_usage_from_gemini_response, extract_usage_from_gpt_response, thinking_budget,
+    GeminiClient, GptClient, LlmApiClient, QueryError,
 };
+use super::budget::Usage;
+use super::conversation::Conversation;
+use super::generation::{GenerationParams, ReasoningEffort};
+use super::registry::ProviderPool;
+use super::schema::ResponseSchema;
+use super::stream::Progress;
+use super::tools::{ToolCall, ToolSpec};
 use crate::app_error::AppError;
 use reqwest::StatusCode;
 use serde_json::json;
@@ -33,6 +43,7 @@ async fn start_mock_server_with_capture(
             if let Some((status, body)) = resp_iter.next() {
                 let status_line = match status {
                     200 => "200 OK",
+                    404 => "404 Not Found",
                     500 => "500 Internal Server Error",
                     _ => "200 OK",
                 };
@@ -68,7 +79,7 @@ async fn test_gemini_happy_path_immediate() {
     let api_client = LlmApiClient::Gemini(client);
 
     let res = api_client
-        .query_with_retries(&json!({"input": "hi"}), None)
+        .query_with_retries(&json!({"input": "hi"}), None, &Progress::disabled())
         .await
         .unwrap();
 
@@ -98,7 +109,7 @@ async fn test_gemini_happy_path_polling() {
     let api_client = LlmApiClient::Gemini(client);
 
     let res = api_client
-        .query_with_retries(&json!({"input": "hi"}), None)
+        .query_with_retries(&json!({"input": "hi"}), None, &Progress::disabled())
         .await
         .unwrap();
 
@@ -128,7 +139,7 @@ async fn test_gemini_polling_url_verification() {
     let api_client = LlmApiClient::Gemini(client);
 
     let _ = api_client
-        .query_with_retries(&json!({"input": "hi"}), None)
+        .query_with_retries(&json!({"input": "hi"}), None, &Progress::disabled())
         .await
         .unwrap();
 
@@ -176,7 +187,7 @@ async fn test_gemini_happy_path_polling_multiple() {
     let api_client = LlmApiClient::Gemini(client);
 
     let res = api_client
-        .query_with_retries(&json!({"input": "hi"}), None)
+        .query_with_retries(&json!({"input": "hi"}), None, &Progress::disabled())
         .await
         .unwrap();
 
@@ -203,7 +214,7 @@ async fn test_gemini_retry_flow() {
     let api_client = LlmApiClient::Gemini(client);
 
     let res = api_client
-        .query_with_retries(&json!({"input": "hi"}), None)
+        .query_with_retries(&json!({"input": "hi"}), None, &Progress::disabled())
         .await
         .unwrap();
 
@@ -225,7 +236,11 @@ async fn test_gpt_happy_path_immediate() {
     let api_client = LlmApiClient::Gpt(client);
 
     let res = api_client
-        .query_with_retries(&json!({"msg": "hi"}), Some("uuid-123"))
+        .query_with_retries(
+            &json!({"msg": "hi"}),
+            Some("uuid-123"),
+            &Progress::disabled(),
+        )
         .await
         .unwrap();
 
@@ -252,7 +267,7 @@ async fn test_gpt_retry_flow_and_idempotency() {
 
     let uuid = "uuid-123";
     let res = api_client
-        .query_with_retries(&json!({"msg": "hi"}), Some(uuid))
+        .query_with_retries(&json!({"msg": "hi"}), Some(uuid), &Progress::disabled())
         .await
         .unwrap();
 
@@ -287,7 +302,7 @@ async fn test_gemini_request_structure_and_auth() {
     let api_client = LlmApiClient::Gemini(client);
 
     let _ = api_client
-        .query_with_retries(&json!({"input": "hi"}), None)
+        .query_with_retries(&json!({"input": "hi"}), None, &Progress::disabled())
         .await
         .unwrap();
 
@@ -298,6 +313,130 @@ async fn test_gemini_request_structure_and_auth() {
     );
 }
 
+/// A fresh request whose first turn is the stable prefix and whose second
+/// turn is the build output.
+fn cacheable_request() -> serde_json::Value {
+    json!({
+        "model": "model",
+        "system_instruction": "You write code.",
+        "input": [
+            { "role": "user", "content": "[codebase]\nfn main() {}" },
+            { "role": "user", "content": "[build.sh output]\nerror" },
+        ],
+    })
+}
+
+fn request_json(request: &str) -> serde_json::Value {
+    let (_, body) = request.split_once("\r\n\r\n").unwrap();
+    serde_json::from_str(body).unwrap()
+}
+
+#[tokio::test]
+async fn test_gemini_caches_the_stable_prefix_and_reuses_it() {
+    let cache = json!({ "name": "cachedContents/abc" }).to_string();
+    let done = json!({
+        "status": "completed",
+        "outputs": [{ "type": "text", "text": "ok" }]
+    })
+    .to_string();
+    let (url, mut rx) =
+        start_mock_server_with_capture(vec![(200, cache), (200, done.clone()), (200, done)]).await;
+    let api_client = LlmApiClient::Gemini(GeminiClient::new_test("key".to_string(), "model", url));
+
+    let res = api_client
+        .query_with_retries(&cacheable_request(), None, &Progress::disabled())
+        .await
+        .unwrap();
+    assert_eq!(res["cached_content"], "cachedContents/abc");
+
+    let create = rx.recv().await.unwrap();
+    assert!(create.starts_with("POST /cachedContents HTTP/1.1"));
+    let create = request_json(&create);
+    assert_eq!(create["model"], "models/model");
+    assert_eq!(create["ttl"], "600s");
+    assert_eq!(
+        create["system_instruction"]["parts"][0]["text"],
+        "You write code."
+    );
+    assert_eq!(
+        create["contents"][0]["parts"][0]["text"],
+        "[codebase]\nfn main() {}"
+    );
+
+    let interaction = request_json(&rx.recv().await.unwrap());
+    assert_eq!(interaction["cached_content"], "cachedContents/abc");
+    assert!(interaction.get("system_instruction").is_none());
+    assert_eq!(
+        interaction["input"],
+        json!([{ "role": "user", "content": "[build.sh output]\nerror" }])
+    );
+
+    // The second request reuses the cache without creating another.
+    api_client
+        .query_with_retries(&cacheable_request(), None, &Progress::disabled())
+        .await
+        .unwrap();
+    let interaction = rx.recv().await.unwrap();
+    assert!(interaction.starts_with("POST / HTTP/1.1"));
+    assert_eq!(
+        request_json(&interaction)["cached_content"],
+        "cachedContents/abc"
+    );
+}
+
+#[tokio::test]
+async fn test_gemini_sends_the_full_prompt_when_the_cache_is_gone() {
+    let cache = json!({ "name": "cachedContents/old" }).to_string();
+    let missing = json!({
+        "error": { "code": 404, "message": "CachedContent not found" }
+    })
+    .to_string();
+    let done = json!({
+        "status": "completed",
+        "outputs": [{ "type": "text", "text": "ok" }]
+    })
+    .to_string();
+    let (url, mut rx) =
+        start_mock_server_with_capture(vec![(200, cache), (404, missing), (200, done)]).await;
+    let api_client = LlmApiClient::Gemini(GeminiClient::new_test("key".to_string(), "model", url));
+
+    let res = api_client
+        .query_with_retries(&cacheable_request(), None, &Progress::disabled())
+        .await
+        .unwrap();
+    assert!(res.get("cached_content").is_none());
+
+    rx.recv().await.unwrap();
+    rx.recv().await.unwrap();
+    let full = request_json(&rx.recv().await.unwrap());
+    assert_eq!(full, cacheable_request());
+}
+
+#[tokio::test]
+async fn test_gemini_does_not_cache_continuations_or_single_turns() {
+    let done = json!({
+        "status": "completed",
+        "outputs": [{ "type": "text", "text": "ok" }]
+    })
+    .to_string();
+    let (url, mut rx) =
+        start_mock_server_with_capture(vec![(200, done.clone()), (200, done)]).await;
+    let api_client = LlmApiClient::Gemini(GeminiClient::new_test("key".to_string(), "model", url));
+
+    let mut continuation = cacheable_request();
+    continuation["previous_interaction_id"] = json!("earlier");
+    let single = json!({ "system_instruction": "s", "input": "[codebase]" });
+    for request in [continuation, single] {
+        api_client
+            .query_with_retries(&request, None, &Progress::disabled())
+            .await
+            .unwrap();
+        let sent = rx.recv().await.unwrap();
+        assert!(sent.starts_with("POST / HTTP/1.1"));
+        assert_eq!(request_json(&sent), request);
+    }
+}
+
 #[tokio::test]
 async fn test_gpt_auth_header() {
     let success_body = json!({
@@ -310,7 +449,7 @@ async fn test_gpt_auth_header() {
     let api_client = LlmApiClient::Gpt(client);
 
     let _ = api_client
-        .query_with_retries(&json!({"msg": "hi"}), Some("u"))
+        .query_with_retries(&json!({"msg": "hi"}), Some("u"), &Progress::disabled())
         .await
         .unwrap();
 
@@ -324,9 +463,9 @@ async fn test_gpt_auth_header() {
 
 #[test]
 fn test_build_request_body_gemini() {
-    let client = GeminiClient::new("k".into(), "gemini-model-x");
+    let client = GeminiClient::new("k".into(), "gemini-model-x", ProviderPool::new());
     let api_client = LlmApiClient::Gemini(client);
-    let body = api_client.build_request_body("test prompt");
+    let body = api_client.build_request_body(&Conversation::from_user("test prompt"));
 
     assert_eq!(body["model"], "gemini-model-x");
     assert_eq!(body["input"], "test prompt");
@@ -342,9 +481,9 @@ fn test_build_request_body_gemini() {
 
 #[test]
 fn test_build_request_body_gpt() {
-    let client = GptClient::new("k".into());
+    let client = GptClient::new("k".into(), "gpt-5.2", ProviderPool::new());
     let api_client = LlmApiClient::Gpt(client);
-    let body = api_client.build_request_body("test prompt");
+    let body = api_client.build_request_body(&Conversation::from_user("test prompt"));
 
     assert_eq!(body["model"], "gpt-5.2");
     let msgs = body["messages"].as_array().unwrap();
@@ -353,6 +492,300 @@ fn test_build_request_body_gpt() {
     assert_eq!(msgs[0]["content"], "test prompt");
 }
 
+#[test]
+fn test_build_request_body_requests_schema_output() {
+    let mut conversation = Conversation::from_user("which files?");
+    let gemini = LlmApiClient::Gemini(GeminiClient::new(
+        "k".into(),
+        "gemini-model-x",
+        ProviderPool::new(),
+    ));
+    let gpt = LlmApiClient::Gpt(GptClient::new("k".into(), "gpt-5.2", ProviderPool::new()));
+    for client in [&gemini, &gpt] {
+        let body = client.build_request_body(&conversation);
+        assert!(body.get("response_format").is_none());
+        assert!(body.get("response_mime_type").is_none());
+    }
+
+    let schema = ResponseSchema::file_list();
+    conversation.set_response_schema(schema.clone());
+    let body = gemini.build_request_body(&conversation);
+    assert_eq!(body["response_mime_type"], "application/json");
+    assert_eq!(body["response_format"], schema.schema);
+
+    let body = gpt.build_request_body(&conversation);
+    assert_eq!(body["response_format"]["type"], "json_schema");
+    assert_eq!(body["response_format"]["json_schema"]["name"], "file_list");
+    assert_eq!(body["response_format"]["json_schema"]["strict"], true);
+    assert_eq!(
+        body["response_format"]["json_schema"]["schema"],
+        schema.schema
+    );
+}
+
+#[test]
+fn test_apply_generation_uses_each_providers_fields() {
+    let params = GenerationParams {
+        temperature: Some(0.3),
+        max_output_tokens: Some(4096),
+        reasoning_effort: Some(ReasoningEffort::Medium),
+    };
+
+    let mut body = json!({ "model": "gemini-3-pro-preview" });
+    apply_gemini_generation("gemini-3-pro-preview", &mut body, &params);
+    assert_eq!(
+        body["generation_config"],
+        json!({ "temperature": 0.3, "max_output_tokens": 4096, "thinking_level": "high" })
+    );
+
+    // Gemini 2.5 has no thinking level and gets a budget instead.
+    let mut body = json!({});
+    apply_gemini_generation("gemini-2.5-pro", &mut body, &params);
+    assert_eq!(
+        body["generation_config"],
+        json!({
+            "temperature": 0.3,
+            "max_output_tokens": 4096,
+            "thinking_config": { "thinking_budget": 8192 }
+        })
+    );
+
+    let mut body = json!({});
+    apply_gemini_generation("gemini-2.5-pro", &mut body, &GenerationParams::default());
+    assert!(body.get("generation_config").is_none());
+
+    let mut body = json!({ "model": "gpt-5.2" });
+    apply_gpt_generation("gpt-5.2", &mut body, &params);
+    assert_eq!(body["temperature"], 0.3);
+    assert_eq!(body["max_completion_tokens"], 4096);
+    assert_eq!(body["reasoning_effort"], "medium");
+}
+
+#[test]
+fn test_gemini_3_thinking_level_is_low_or_high() {
+    for (effort, level) in [
+        (ReasoningEffort::Minimal, "low"),
+        (ReasoningEffort::Low, "low"),
+        (ReasoningEffort::Medium, "high"),
+        (ReasoningEffort::High, "high"),
+    ] {
+        let params = GenerationParams {
+            reasoning_effort: Some(effort),
+            ..GenerationParams::default()
+        };
+        let mut body = json!({});
+        apply_gemini_generation("gemini-3-pro-preview", &mut body, &params);
+        assert_eq!(body["generation_config"]["thinking_level"], level);
+    }
+}
+
+#[test]
+fn test_gemini_2_5_thinking_budget_grows_with_the_effort() {
+    let mut budgets = Vec::new();
+    for effort in [
+        ReasoningEffort::Minimal,
+        ReasoningEffort::Low,
+        ReasoningEffort::Medium,
+        ReasoningEffort::High,
+    ] {
+        let params = GenerationParams {
+            reasoning_effort: Some(effort),
+            ..GenerationParams::default()
+        };
+        let mut body = json!({});
+        apply_gemini_generation("gemini-2.5-pro", &mut body, &params);
+        assert!(body["generation_config"].get("thinking_level").is_none());
+        let budget = body["generation_config"]["thinking_config"]["thinking_budget"]
+            .as_u64()
+            .unwrap();
+        assert_eq!(budget, thinking_budget(effort));
+        budgets.push(budget);
+    }
+    assert_eq!(budgets, vec![128, 2_048, 8_192, 32_768]);
+}
+
+fn tool_conversation() -> Conversation {
+    let mut conversation = Conversation::from_user("fix it");
+    conversation.set_tools(vec![ToolSpec {
+        name: "read_file",
+        description: "Reads a file.",
+        parameters: json!({ "type": "object" }),
+    }]);
+    let calls = vec![
+        ToolCall {
+            id: "c1".to_string(),
+            name: "read_file".to_string(),
+            arguments: json!({ "path": "a.rs" }),
+        },
+        ToolCall {
+            id: "c2".to_string(),
+            name: "read_file".to_string(),
+            arguments: json!({ "path": "b.rs" }),
+        },
+    ];
+    conversation.push_tool_calls("Reading.".to_string(), calls.clone());
+    conversation.push_tool_result(&calls[0], "A".to_string());
+    conversation.push_tool_result(&calls[1], "B".to_string());
+    conversation
+}
+
+#[test]
+fn test_build_request_body_gemini_tools() {
+    let body = build_gemini_request_body("gemini-model-x", &tool_conversation());
+    assert_eq!(
+        body["tools"],
+        json!([{
+            "type": "function",
+            "name": "read_file",
+            "description": "Reads a file.",
+            "parameters": { "type": "object" }
+        }])
+    );
+    let turns = body["input"].as_array().unwrap();
+    assert_eq!(turns.len(), 3);
+    assert_eq!(turns[1]["role"], "model");
+    assert_eq!(
+        turns[1]["content"],
+        json!([
+            { "type": "text", "text": "Reading." },
+            { "type": "function_call", "id": "c1", "name": "read_file", "arguments": { "path": "a.rs" } },
+            { "type": "function_call", "id": "c2", "name": "read_file", "arguments": { "path": "b.rs" } }
+        ])
+    );
+    // Both results go back in one user turn.
+    assert_eq!(turns[2]["role"], "user");
+    assert_eq!(
+        turns[2]["content"],
+        json!([
+            { "type": "function_result", "call_id": "c1", "name": "read_file", "result": "A" },
+            { "type": "function_result", "call_id": "c2", "name": "read_file", "result": "B" }
+        ])
+    );
+}
+
+#[test]
+fn test_build_request_body_gpt_tools() {
+    let body = build_gpt_request_body("gpt-5.2", &tool_conversation());
+    assert_eq!(body["tools"][0]["type"], "function");
+    assert_eq!(body["tools"][0]["function"]["name"], "read_file");
+    let messages = body["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 4);
+    assert_eq!(messages[1]["role"], "assistant");
+    assert_eq!(messages[1]["content"], "Reading.");
+    assert_eq!(
+        messages[1]["tool_calls"][1],
+        json!({
+            "id": "c2",
+            "type": "function",
+            "function": { "name": "read_file", "arguments": "{\"path\":\"b.rs\"}" }
+        })
+    );
+    assert_eq!(
+        messages[3],
+        json!({ "role": "tool", "content": "B", "tool_call_id": "c2" })
+    );
+}
+
+#[test]
+fn test_extract_tool_calls() {
+    let gemini = json!({ "outputs": [
+        { "type": "thought", "summary": "..." },
+        { "type": "function_call", "id": "g1", "name": "grep", "arguments": { "pattern": "fn" } }
+    ]});
+    assert_eq!(
+        extract_tool_calls_from_gemini_response(&gemini),
+        vec![ToolCall {
+            id: "g1".to_string(),
+            name: "grep".to_string(),
+            arguments: json!({ "pattern": "fn" }),
+        }]
+    );
+    assert!(extract_tool_calls_from_gemini_response(&json!({ "outputs": [] })).is_empty());
+
+    let gpt = json!({ "choices": [{ "message": { "content": null, "tool_calls": [
+        { "id": "o1", "type": "function", "function": { "name": "read_file", "arguments": "{\"path\":\"a.rs\"}" } },
+        { "id": "o2", "type": "function", "function": { "name": "read_file", "arguments": "not json" } }
+    ]}}]});
+    let calls = extract_tool_calls_from_gpt_response(&gpt);
+    assert_eq!(calls.len(), 2);
+    assert_eq!(calls[0].arguments, json!({ "path": "a.rs" }));
+    assert_eq!(calls[1].arguments, json!("not json"));
+    assert!(extract_tool_calls_from_gpt_response(&json!({ "choices": [] })).is_empty());
+}
+
+#[test]
+fn test_build_request_body_gemini_multi_turn() {
+    let api_client = LlmApiClient::Gemini(GeminiClient::new(
+        "k".into(),
+        "gemini-model-x",
+        ProviderPool::new(),
+    ));
+    let mut conversation = Conversation::new();
+    conversation.push_system("rules");
+    conversation.push_user("question");
+    conversation.push_assistant("answer");
+    conversation.push_user("follow-up");
+
+    // Without stored state the full history is sent as turns.
+    let body = api_client.build_request_body(&conversation);
+    assert_eq!(body["system_instruction"], "rules");
+    assert!(body.get("previous_interaction_id").is_none());
+    let turns = body["input"].as_array().unwrap();
+    assert_eq!(turns.len(), 3);
+    assert_eq!(turns[1]["role"], "model");
+    assert_eq!(turns[1]["content"], "answer");
+
+    // With stored state only the new turn is sent.
+    let mut conversation = Conversation::new();
+    conversation.push_system("rules");
+    conversation.push_user("question");
+    conversation.push_assistant("answer");
+    conversation.set_provider_state("gemini-model-x", "int-1".to_string());
+    conversation.push_user("follow-up");
+    let body = api_client.build_request_body(&conversation);
+    assert_eq!(body["previous_interaction_id"], "int-1");
+    assert_eq!(body["input"], "follow-up");
+    assert_eq!(body["system_instruction"], "rules");
+
+    // State stored by another model is ignored.
+    let other = LlmApiClient::Gemini(GeminiClient::new(
+        "k".into(),
+        "gemini-model-y",
+        ProviderPool::new(),
+    ));
+    let body = other.build_request_body(&conversation);
+    assert!(body.get("previous_interaction_id").is_none());
+    assert_eq!(body["input"].as_array().unwrap().len(), 3);
+}
+
+#[test]
+fn test_build_request_body_gpt_multi_turn() {
+    let api_client = LlmApiClient::Gpt(GptClient::new("k".into(), "gpt-5.2", ProviderPool::new()));
+    let mut conversation = Conversation::new();
+    conversation.push_system("rules");
+    conversation.push_user("question");
+    conversation.push_assistant("answer");
+    conversation.push_user("follow-up");
+
+    let body = api_client.build_request_body(&conversation);
+    let roles: Vec<&str> = body["messages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["role"].as_str().unwrap())
+        .collect();
+    assert_eq!(roles, vec!["system", "user", "assistant", "user"]);
+
+    // Later turns keep the cache key of the prefix they share with the first.
+    let first = api_client.build_request_body(&Conversation::with_system("rules", "question"));
+    assert!(first["prompt_cache_key"].is_string());
+    assert_eq!(body["prompt_cache_key"], first["prompt_cache_key"]);
+    assert_eq!(
+        api_client.extract_conversation_id(&json!({"id": "chatcmpl-1"})),
+        None
+    );
+}
+
 // --- Original Unit Tests ---
 
 #[test]
@@ -564,6 +997,14 @@ fn test_censor_api_key_short_key() {
     assert_eq!(censored, "The key is ...");
 }
 
+#[test]
+fn test_censor_api_key_non_ascii_key() {
+    // Keys from commands or environment variables are not always ASCII.
+    let key = "clé-secrète-très-longue";
+    let censored = api::censor_api_key("key: clé-secrète-très-longue", key);
+    assert_eq!(censored, "key: ...ngue");
+}
+
 #[test]
 fn test_censor_api_key_empty_key() {
     let text = "The key is ";
@@ -581,11 +1022,220 @@ fn test_censor_api_key_no_match() {
 }
 
 fn fake_gpt_client() -> LlmApiClient {
-    let inner = GptClient::new(String::new());
+    let inner = GptClient::new(String::new(), "gpt-5.2", ProviderPool::new());
     LlmApiClient::Gpt(inner)
 }
 
 fn fake_gemini_client() -> LlmApiClient {
-    let inner = GeminiClient::new(String::new(), "gemini-test-model");
+    let inner = GeminiClient::new(String::new(), "gemini-test-model", ProviderPool::new());
     LlmApiClient::Gemini(inner)
 }
+
+#[test]
+fn test_extract_gemini_usage() {
+    let response = json!({
+        "status": "completed",
+        "usage": {
+            "total_input_tokens": 1200,
+            "total_output_tokens": 300,
+            "total_thought_tokens": 50,
+            "total_cached_tokens": 1000,
+            "total_tokens": 1550
+        }
+    });
+    assert_eq!(
+        extract_usage_from_gemini_response(&response),
+        Some(Usage {
+            input_tokens: 1200,
+            output_tokens: 350,
+            cached_input_tokens: 1000,
+        })
+    );
+    assert_eq!(extract_usage_from_gemini_response(&json!({})), None);
+}
+
+#[test]
+fn test_extract_gpt_usage() {
+    let response = json!({
+        "choices": [],
+        "usage": {
+            "prompt_tokens": 42,
+            "completion_tokens": 7,
+            "total_tokens": 49,
+            "prompt_tokens_details": { "cached_tokens": 32 }
+        }
+    });
+    assert_eq!(
+        extract_usage_from_gpt_response(&response),
+        Some(Usage {
+            input_tokens: 42,
+            output_tokens: 7,
+            cached_input_tokens: 32,
+        })
+    );
+    assert_eq!(extract_usage_from_gpt_response(&json!({"usage": {}})), None);
+}
+
+fn sse_body(events: &[(&str, serde_json::Value)]) -> String {
+    events
+        .iter()
+        .map(|(event, data)| {
+            if event.is_empty() {
+                format!("data: {data}\n\n")
+            } else {
+                format!("event: {event}\ndata: {data}\n\n")
+            }
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_gpt_streaming_assembles_completion() {
+    let mut body = sse_body(&[
+        ("", json!({"choices": [{"delta": {"role": "assistant"}}]})),
+        ("", json!({"choices": [{"delta": {"content": "Hello"}}]})),
+        ("", json!({"choices": [{"delta": {"content": " world"}}]})),
+        (
+            "",
+            json!({"choices": [], "usage": {"prompt_tokens": 12, "completion_tokens": 2}}),
+        ),
+    ]);
+    body.push_str("data: [DONE]\n\n");
+    let (url, mut rx) = start_mock_server_with_capture(vec![(200, body)]).await;
+    let api_client = LlmApiClient::Gpt(GptClient::new_test("key".to_string(), url));
+
+    let mut request = api_client.build_request_body(&Conversation::from_user("hi"));
+    api_client.enable_streaming(&mut request);
+    let result = api_client
+        .query_with_retries(&request, None, &Progress::disabled())
+        .await
+        .unwrap();
+
+    let sent = rx.recv().await.unwrap();
+    assert!(sent.contains("\"stream\":true"));
+    assert!(sent.contains("\"include_usage\":true"));
+    assert_eq!(
+        api_client.extract_text_from_response(&result).unwrap(),
+        "Hello world"
+    );
+    assert_eq!(
+        api_client.extract_usage(&result),
+        Some(Usage {
+            input_tokens: 12,
+            output_tokens: 2,
+            cached_input_tokens: 0,
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_gpt_streaming_retries_truncated_stream() {
+    let truncated = sse_body(&[("", json!({"choices": [{"delta": {"content": "Hel"}}]}))]);
+    let mut complete = sse_body(&[("", json!({"choices": [{"delta": {"content": "Hello"}}]}))]);
+    complete.push_str("data: [DONE]\n\n");
+    let url = start_mock_server(vec![(200, truncated), (200, complete)]).await;
+    let api_client = LlmApiClient::Gpt(GptClient::new_test("key".to_string(), url));
+
+    let mut request = api_client.build_request_body(&Conversation::from_user("hi"));
+    api_client.enable_streaming(&mut request);
+    let result = api_client
+        .query_with_retries(&request, None, &Progress::disabled())
+        .await
+        .unwrap();
+    assert_eq!(
+        api_client.extract_text_from_response(&result).unwrap(),
+        "Hello"
+    );
+}
+
+#[tokio::test]
+async fn test_gemini_streaming_assembles_interaction() {
+    let body = sse_body(&[
+        ("interaction.start", json!({"interaction": {"id": "abc"}})),
+        (
+            "content.delta",
+            json!({"delta": {"type": "thought", "text": "thinking"}}),
+        ),
+        (
+            "content.delta",
+            json!({"delta": {"type": "text", "text": "Streamed "}}),
+        ),
+        (
+            "content.delta",
+            json!({"delta": {"type": "text", "text": "answer"}}),
+        ),
+        (
+            "interaction.complete",
+            json!({"interaction": {"id": "abc", "status": "completed", "usage": {"total_input_tokens": 5, "total_output_tokens": 2}}}),
+        ),
+    ]);
+    let url = start_mock_server(vec![(200, body)]).await;
+    let api_client = LlmApiClient::Gemini(GeminiClient::new_test("key".to_string(), "model", url));
+
+    let mut request = api_client.build_request_body(&Conversation::from_user("hi"));
+    api_client.enable_streaming(&mut request);
+    assert_eq!(request["stream"], true);
+    let result = api_client
+        .query_with_retries(&request, None, &Progress::disabled())
+        .await
+        .unwrap();
+    assert_eq!(
+        api_client.extract_text_from_response(&result).unwrap(),
+        "Streamed answer"
+    );
+    assert_eq!(api_client.extract_usage(&result).unwrap().input_tokens, 5);
+}
+
+#[tokio::test]
+async fn test_gemini_streaming_error_event_is_reported() {
+    let error_event = sse_body(&[("error", json!({"error": {"message": "overloaded"}}))]);
+    let url = start_mock_server(vec![
+        (200, error_event.clone()),
+        (200, error_event.clone()),
+        (200, error_event.clone()),
+        (200, error_event),
+    ])
+    .await;
+    let api_client = LlmApiClient::Gemini(GeminiClient::new_test("key".to_string(), "model", url));
+
+    let mut request = api_client.build_request_body(&Conversation::from_user("hi"));
+    api_client.enable_streaming(&mut request);
+    let result = api_client
+        .query_with_retries(&request, None, &Progress::disabled())
+        .await;
+    match result {
+        Err(AppError::Network(msg)) => assert!(msg.contains("overloaded")),
+        other => panic!("expected a network error, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn test_gemini_cancel_in_flight_cancels_polled_interaction() {
+    let in_progress = json!({ "id": "int-9", "status": "in_progress" }).to_string();
+    let (url, mut rx) = start_mock_server_with_capture(vec![(200, in_progress); 500]).await;
+    let api_client = LlmApiClient::Gemini(GeminiClient::new_test(
+        "key".to_string(),
+        "gemini-3-pro-preview",
+        url,
+    ));
+
+    let request = api_client.build_request_body(&Conversation::from_user("hi"));
+    let progress = Progress::disabled();
+    let abandoned = tokio::time::timeout(
+        std::time::Duration::from_millis(100),
+        api_client.query_with_retries(&request, None, &progress),
+    )
+    .await;
+    assert!(abandoned.is_err());
+
+    api_client.cancel_in_flight().await;
+    let mut saw_cancel = false;
+    while let Ok(request) = rx.try_recv() {
+        saw_cancel |= request.starts_with("POST /int-9/cancel ");
+    }
+    assert!(saw_cancel);
+
+    // The id is forgotten once cancelled, so a second cancel sends nothing.
+    api_client.cancel_in_flight().await;
+    assert!(rx.try_recv().is_err());
+}
@@ -0,0 +1,153 @@
+use super::models::{lookup, MODELS};
+use crate::app_error::AppError;
+use std::sync::{Arc, Mutex};
+
+/// Token counts reported by a provider for a single call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// The part of `input_tokens` served from the provider's prompt cache.
+    pub cached_input_tokens: u64,
+}
+
+/// Price in US dollars per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Pricing {
+    pub(crate) input_per_million: f64,
+    pub(crate) cached_input_per_million: f64,
+    pub(crate) output_per_million: f64,
+}
+
+impl Pricing {
+    pub(crate) fn cost(&self, usage: &Usage) -> f64 {
+        let cached = usage.cached_input_tokens.min(usage.input_tokens);
+        ((usage.input_tokens - cached) as f64 * self.input_per_million
+            + cached as f64 * self.cached_input_per_million
+            + usage.output_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+}
+
+pub(crate) fn pricing_for_model(model_name: &str) -> Pricing {
+    if let Some(spec) = lookup(model_name) {
+        return spec.pricing;
+    }
+    // Unknown models are priced at the highest registered rates so that a cost
+    // limit errs on the side of stopping early.
+    MODELS.iter().fold(
+        Pricing {
+            input_per_million: 0.0,
+            cached_input_per_million: 0.0,
+            output_per_million: 0.0,
+        },
+        |max, spec| Pricing {
+            input_per_million: max.input_per_million.max(spec.pricing.input_per_million),
+            cached_input_per_million: max
+                .cached_input_per_million
+                .max(spec.pricing.cached_input_per_million),
+            output_per_million: max.output_per_million.max(spec.pricing.output_per_million),
+        },
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BudgetLimits {
+    pub max_cost: Option<f64>,
+    pub max_tokens: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct Spent {
+    calls: u32,
+    input_tokens: u64,
+    cached_input_tokens: u64,
+    output_tokens: u64,
+    cost: f64,
+}
+
+/// Spending limits for a whole workflow run. Clones share the same running
+/// totals, so every call made during a run is counted against one budget.
+#[derive(Debug, Clone, Default)]
+pub struct Budget {
+    limits: BudgetLimits,
+    spent: Arc<Mutex<Spent>>,
+}
+
+impl Budget {
+    pub fn new(limits: BudgetLimits) -> Self {
+        Self {
+            limits,
+            spent: Arc::new(Mutex::new(Spent::default())),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn limits(&self) -> BudgetLimits {
+        self.limits
+    }
+
+    /// Returns an error if sending a prompt of `estimated_input_tokens` to
+    /// `model_name` could take the run past either limit.
+    pub(crate) fn check(
+        &self,
+        model_name: &str,
+        estimated_input_tokens: u64,
+    ) -> Result<(), AppError> {
+        let spent = self.spent.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(max_tokens) = self.limits.max_tokens {
+            let used = spent.input_tokens + spent.output_tokens;
+            if used + estimated_input_tokens > max_tokens {
+                return Err(AppError::BudgetExceeded(format!(
+                    "the next call needs about {estimated_input_tokens} input tokens, but {used} of the {max_tokens} token limit have already been used across {} calls.",
+                    spent.calls
+                )));
+            }
+        }
+
+        if let Some(max_cost) = self.limits.max_cost {
+            let estimate = pricing_for_model(model_name).cost(&Usage {
+                input_tokens: estimated_input_tokens,
+                ..Usage::default()
+            });
+            if spent.cost + estimate > max_cost {
+                return Err(AppError::BudgetExceeded(format!(
+                    "the next call's input alone is estimated at ${estimate:.4}, but ${:.4} of the ${max_cost:.2} cost limit has already been spent across {} calls.",
+                    spent.cost, spent.calls
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Adds a completed call to the running totals and returns its cost.
+    pub(crate) fn record(&self, model_name: &str, usage: &Usage) -> f64 {
+        let cost = pricing_for_model(model_name).cost(usage);
+        let mut spent = self.spent.lock().unwrap_or_else(|e| e.into_inner());
+        spent.calls += 1;
+        spent.input_tokens += usage.input_tokens;
+        spent.cached_input_tokens += usage.cached_input_tokens;
+        spent.output_tokens += usage.output_tokens;
+        spent.cost += cost;
+        cost
+    }
+
+    pub fn total_cost(&self) -> f64 {
+        self.spent.lock().unwrap_or_else(|e| e.into_inner()).cost
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        let spent = self.spent.lock().unwrap_or_else(|e| e.into_inner());
+        spent.input_tokens + spent.output_tokens
+    }
+
+    /// Input tokens served from a prompt cache across the whole run.
+    pub fn total_cached_tokens(&self) -> u64 {
+        self.spent
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .cached_input_tokens
+    }
+}
@@ -0,0 +1,116 @@
+use super::budget::{pricing_for_model, Budget, BudgetLimits, Usage};
+use crate::app_error::AppError;
+
+#[test]
+fn test_unlimited_budget_never_blocks() {
+    let budget = Budget::default();
+    budget.record(
+        "gpt-5.2",
+        &Usage {
+            input_tokens: 10_000_000,
+            output_tokens: 10_000_000,
+            cached_input_tokens: 0,
+        },
+    );
+    assert!(budget.check("gpt-5.2", 10_000_000).is_ok());
+}
+
+#[test]
+fn test_token_limit_blocks_next_call() {
+    let budget = Budget::new(BudgetLimits {
+        max_cost: None,
+        max_tokens: Some(1_000),
+    });
+    assert!(budget.check("gpt-5.2", 1_000).is_ok());
+    assert!(matches!(
+        budget.check("gpt-5.2", 1_001),
+        Err(AppError::BudgetExceeded(_))
+    ));
+
+    budget.record(
+        "gpt-5.2",
+        &Usage {
+            input_tokens: 500,
+            output_tokens: 400,
+            cached_input_tokens: 0,
+        },
+    );
+    assert_eq!(budget.total_tokens(), 900);
+    assert!(budget.check("gpt-5.2", 100).is_ok());
+    assert!(budget.check("gpt-5.2", 101).is_err());
+}
+
+#[test]
+fn test_cost_limit_blocks_next_call() {
+    let budget = Budget::new(BudgetLimits {
+        max_cost: Some(1.0),
+        max_tokens: None,
+    });
+    // 400k input tokens of gemini-3-pro-preview cost $0.80.
+    assert!(budget.check("gemini-3-pro-preview", 400_000).is_ok());
+    budget.record(
+        "gemini-3-pro-preview",
+        &Usage {
+            input_tokens: 400_000,
+            output_tokens: 0,
+            cached_input_tokens: 0,
+        },
+    );
+    assert!((budget.total_cost() - 0.8).abs() < 1e-9);
+
+    let err = budget.check("gemini-3-pro-preview", 200_000).unwrap_err();
+    assert!(err.to_string().contains("cost limit"));
+}
+
+#[test]
+fn test_clones_share_totals() {
+    let budget = Budget::new(BudgetLimits {
+        max_cost: None,
+        max_tokens: Some(100),
+    });
+    let clone = budget.clone();
+    clone.record(
+        "gpt-5.2",
+        &Usage {
+            input_tokens: 60,
+            output_tokens: 40,
+            cached_input_tokens: 0,
+        },
+    );
+    assert_eq!(budget.total_tokens(), 100);
+    assert!(budget.check("gpt-5.2", 1).is_err());
+}
+
+#[test]
+fn test_pricing() {
+    let pricing = pricing_for_model("gemini-2.5-pro");
+    let cost = pricing.cost(&Usage {
+        input_tokens: 1_000_000,
+        output_tokens: 1_000_000,
+        cached_input_tokens: 0,
+    });
+    assert!((cost - 11.25).abs() < 1e-9);
+}
+
+#[test]
+fn test_cached_input_is_priced_at_the_cache_rate() {
+    let pricing = pricing_for_model("gpt-5.2");
+    let cost = pricing.cost(&Usage {
+        input_tokens: 1_000_000,
+        output_tokens: 0,
+        cached_input_tokens: 800_000,
+    });
+    assert!((cost - (0.2 * 1.75 + 0.8 * 0.175)).abs() < 1e-9);
+
+    let budget = Budget::new(BudgetLimits::default());
+    budget.record(
+        "gpt-5.2",
+        &Usage {
+            input_tokens: 100,
+            output_tokens: 10,
+            cached_input_tokens: 60,
+        },
+    );
+    assert_eq!(budget.total_cached_tokens(), 60);
+    assert_eq!(budget.total_tokens(), 110);
+}
@@ -0,0 +1,300 @@
+use super::api::LlmApi;
+use super::budget::Usage;
+use super::conversation::Conversation;
+use super::generation::GenerationParams;
+use super::stream::Progress;
+use super::tools::ToolCall;
+use crate::app_error::AppError;
+use crate::hash::fnv1a;
+use crate::logger::QUERY_FILE;
+use serde_json::{json, Value};
+use std::fs;
+use std::future::Future;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+
+const CASSETTE_FILE: &str = "cassette.jsonl";
+const LOG_QUERY_SUFFIX: &str = "-query.json";
+const LOG_RESPONSE_SUFFIX: &str = "-response.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+#[derive(Debug)]
+struct Entry {
+    key: String,
+    response: Value,
+    used: bool,
+}
+
+/// A set of recorded request and response pairs, keyed by a hash of the
+/// request body. In record mode every successful call is appended to
+/// `[dir]/cassette.jsonl`; in replay mode calls are answered from the recorded
+/// pairs and nothing is sent. Clones share the same entries.
+#[derive(Debug, Clone)]
+pub struct Cassette {
+    mode: Mode,
+    dir: PathBuf,
+    entries: Arc<Mutex<Vec<Entry>>>,
+}
+
+impl Cassette {
+    /// Starts a new recording in `dir`, replacing any earlier cassette there.
+    pub fn record(dir: &Path) -> Result<Self, AppError> {
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(CASSETTE_FILE), "")?;
+        Ok(Self {
+            mode: Mode::Record,
+            dir: dir.to_path_buf(),
+            entries: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+
+    /// Loads the pairs to replay from `dir`, which is either a directory
+    /// written by `record` or the log directory of an earlier run.
+    pub fn replay(dir: &Path) -> Result<Self, AppError> {
+        let cassette_path = dir.join(CASSETTE_FILE);
+        let entries = if cassette_path.is_file() {
+            load_cassette_file(&cassette_path)?
+        } else if dir.is_dir() {
+            load_log_dir(dir)?
+        } else {
+            return Err(AppError::Config(format!(
+                "Replay directory '{}' does not exist.",
+                dir.display()
+            )));
+        };
+        if entries.is_empty() {
+            return Err(AppError::Config(format!(
+                "Replay directory '{}' contains no recorded LLM responses.",
+                dir.display()
+            )));
+        }
+        Ok(Self {
+            mode: Mode::Replay,
+            dir: dir.to_path_buf(),
+            entries: Arc::new(Mutex::new(entries)),
+        })
+    }
+
+    pub fn is_replay(&self) -> bool {
+        self.mode == Mode::Replay
+    }
+
+    /// Saves the supervisor query next to the recording so that a replay can
+    /// reuse it instead of asking for it again.
+    pub fn save_query(&self, query: &str) -> Result<(), AppError> {
+        fs::write(self.dir.join(QUERY_FILE), query)?;
+        Ok(())
+    }
+
+    /// The supervisor query stored in `dir` by a recording or a run log.
+    pub fn recorded_query(dir: &Path) -> Option<String> {
+        fs::read_to_string(dir.join(QUERY_FILE))
+            .ok()
+            .map(|q| q.trim().to_string())
+            .filter(|q| !q.is_empty())
+    }
+
+    fn append(&self, key: &str, request_body: &Value, response: &Value) -> Result<(), AppError> {
+        let line = json!({ "key": key, "request": request_body, "response": response });
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(CASSETTE_FILE))?;
+        writeln!(file, "{line}")?;
+        Ok(())
+    }
+
+    /// Takes the first unused response recorded for `key`. Identical requests
+    /// are answered in the order they were recorded.
+    fn take(&self, key: &str) -> Option<Value> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = entries.iter_mut().find(|e| !e.used && e.key == key)?;
+        entry.used = true;
+        Some(entry.response.clone())
+    }
+}
+
+/// A stable hash of a request body. Streaming options are left out so that a
+/// run recorded with streaming can be replayed without it and vice versa.
+pub(crate) fn request_key(request_body: &Value) -> String {
+    let mut body = request_body.clone();
+    if let Some(obj) = body.as_object_mut() {
+        obj.remove("stream");
+        obj.remove("stream_options");
+    }
+    fnv1a(body.to_string().as_bytes())
+}
+
+fn load_cassette_file(path: &Path) -> Result<Vec<Entry>, AppError> {
+    let content = fs::read_to_string(path)?;
+    let mut entries = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value: Value = serde_json::from_str(line).map_err(|e| {
+            AppError::Config(format!(
+                "Invalid cassette entry on line {} of '{}': {e}",
+                index + 1,
+                path.display()
+            ))
+        })?;
+        let (Some(key), Some(response)) = (value["key"].as_str(), value.get("response")) else {
+            return Err(AppError::Config(format!(
+                "Cassette entry on line {} of '{}' needs a 'key' and a 'response'.",
+                index + 1,
+                path.display()
+            )));
+        };
+        entries.push(Entry {
+            key: key.to_string(),
+            response: response.clone(),
+            used: false,
+        });
+    }
+    Ok(entries)
+}
+
+/// Rebuilds pairs from the `[prefix]-query.json` and `[prefix]-response.json`
+/// files of a run log. Calls that failed or were never sent are skipped.
+fn load_log_dir(dir: &Path) -> Result<Vec<Entry>, AppError> {
+    let mut prefixes: Vec<String> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            e.file_name()
+                .to_str()?
+                .strip_suffix(LOG_QUERY_SUFFIX)
+                .map(str::to_string)
+        })
+        .collect();
+    prefixes.sort_by_key(|p| (log_prefix_number(p), p.clone()));
+
+    let mut entries = Vec::new();
+    for prefix in prefixes {
+        let Ok(response_text) =
+            fs::read_to_string(dir.join(format!("{prefix}{LOG_RESPONSE_SUFFIX}")))
+        else {
+            continue;
+        };
+        let query: Value = serde_json::from_str(&fs::read_to_string(
+            dir.join(format!("{prefix}{LOG_QUERY_SUFFIX}")),
+        )?)?;
+        let mut response: Value = serde_json::from_str(&response_text)?;
+        // Only successful calls have their usage recorded.
+        let Some(obj) = response.as_object_mut() else {
+            continue;
+        };
+        if obj.remove("callUsage").is_none() {
+            continue;
+        }
+        obj.remove("totalResponseTime");
+        obj.remove("modelFallback");
+        if obj.len() == 1 {
+            if let Some(payload) = obj.remove("response_payload") {
+                response = payload;
+            }
+        }
+        entries.push(Entry {
+            key: request_key(&query["body"]),
+            response,
+            used: false,
+        });
+    }
+    Ok(entries)
+}
+
+fn log_prefix_number(prefix: &str) -> u64 {
+    prefix
+        .split('-')
+        .next()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(u64::MAX)
+}
+
+/// Wraps a client so that its calls are recorded into, or answered from, a
+/// cassette. Everything except sending the request is left to the wrapped
+/// client, so replayed responses are parsed exactly as live ones.
+pub(crate) struct CassetteClient<'c> {
+    inner: &'c dyn LlmApi,
+    cassette: &'c Cassette,
+}
+
+impl<'c> CassetteClient<'c> {
+    pub(crate) fn new(inner: &'c dyn LlmApi, cassette: &'c Cassette) -> Self {
+        Self { inner, cassette }
+    }
+
+    async fn query(
+        &self,
+        request_body: &Value,
+        idempotency_key: Option<&str>,
+        progress: &Progress<'_>,
+    ) -> Result<Value, AppError> {
+        let key = request_key(request_body);
+        if self.cassette.is_replay() {
+            return self.cassette.take(&key).ok_or_else(|| {
+                AppError::ReplayMiss(format!(
+                    "no unused response for request {key} in '{}'. The prompt differs from the recorded run; check that the tree and the query match it.",
+                    self.cassette.dir.display()
+                ))
+            });
+        }
+        let response = self
+            .inner
+            .query_with_retries(request_body, idempotency_key, progress)
+            .await?;
+        self.cassette.append(&key, request_body, &response)?;
+        Ok(response)
+    }
+}
+
+impl LlmApi for CassetteClient<'_> {
+    fn get_model_name(&self) -> &'static str {
+        self.inner.get_model_name()
+    }
+    fn get_url(&self) -> &str {
+        self.inner.get_url()
+    }
+    fn build_request_body(&self, conversation: &Conversation) -> Value {
+        self.inner.build_request_body(conversation)
+    }
+    fn extract_conversation_id(&self, response: &Value) -> Option<String> {
+        self.inner.extract_conversation_id(response)
+    }
+    fn enable_streaming(&self, request_body: &mut Value) {
+        self.inner.enable_streaming(request_body)
+    }
+    fn apply_generation(&self, request_body: &mut Value, params: &GenerationParams) {
+        self.inner.apply_generation(request_body, params)
+    }
+    fn query_with_retries<'a>(
+        &'a self,
+        request_body: &'a Value,
+        idempotency_key: Option<&'a str>,
+        progress: &'a Progress<'a>,
+    ) -> Pin<Box<dyn Future<Output = Result<Value, AppError>> + Send + 'a>> {
+        Box::pin(self.query(request_body, idempotency_key, progress))
+    }
+    fn extract_text_from_response(&self, response: &Value) -> Result<String, AppError> {
+        self.inner.extract_text_from_response(response)
+    }
+    fn extract_usage(&self, response: &Value) -> Option<Usage> {
+        self.inner.extract_usage(response)
+    }
+    fn extract_tool_calls(&self, response: &Value) -> Vec<ToolCall> {
+        self.inner.extract_tool_calls(response)
+    }
+    fn supports_idempotency(&self) -> bool {
+        self.inner.supports_idempotency()
+    }
+    fn cancel_in_flight<'a>(&'a self) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        self.inner.cancel_in_flight()
+    }
+}
@@ -0,0 +1,179 @@
+use super::api::LlmApi;
+use super::budget::Usage;
+use super::cassette::{request_key, Cassette, CassetteClient};
+use super::conversation::Conversation;
+use super::stream::Progress;
+use super::{converse_internal, Session};
+use crate::app_error::AppError;
+use crate::logger::Logger;
+use serde_json::{json, Value};
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, Ordering};
+use tempfile::tempdir;
+
+/// Answers every prompt with an echo of it and counts the calls it receives.
+#[derive(Default)]
+struct EchoApi {
+    calls: AtomicU32,
+}
+
+impl LlmApi for EchoApi {
+    fn get_model_name(&self) -> &'static str {
+        "echo-model"
+    }
+    fn get_url(&self) -> &str {
+        "http://echo.url"
+    }
+    fn build_request_body(&self, conversation: &Conversation) -> Value {
+        json!({ "prompt": conversation.transcript() })
+    }
+    fn extract_conversation_id(&self, _response: &Value) -> Option<String> {
+        None
+    }
+    fn enable_streaming(&self, request_body: &mut Value) {
+        request_body["stream"] = json!(true);
+    }
+    fn query_with_retries<'a>(
+        &'a self,
+        request_body: &'a Value,
+        _idempotency_key: Option<&'a str>,
+        _progress: &'a Progress<'a>,
+    ) -> Pin<Box<dyn Future<Output = Result<Value, AppError>> + Send + 'a>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        let text = format!("echo: {}", request_body["prompt"].as_str().unwrap_or(""));
+        Box::pin(async move { Ok(json!({ "text": text })) })
+    }
+    fn extract_text_from_response(&self, response: &Value) -> Result<String, AppError> {
+        response["text"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| AppError::ResponseParsing("missing text".to_string()))
+    }
+    fn extract_usage(&self, _response: &Value) -> Option<Usage> {
+        None
+    }
+    fn supports_idempotency(&self) -> bool {
+        false
+    }
+}
+
+async fn ask(
+    api: &EchoApi,
+    cassette: &Cassette,
+    prompt: &str,
+    logger: &Logger,
+    log_prefix: &str,
+) -> Result<String, AppError> {
+    let client = CassetteClient::new(api, cassette);
+    let mut conversation = Conversation::from_user(prompt);
+    converse_internal(
+        &[&client],
+        &Session::default(),
+        &mut conversation,
+        logger,
+        log_prefix,
+    )
+    .await
+}
+
+fn only_subdir(root: &Path) -> std::path::PathBuf {
+    std::fs::read_dir(root)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path()
+}
+
+#[tokio::test]
+async fn test_recorded_calls_replay_from_cassette_and_from_logs() {
+    let dir = tempdir().unwrap();
+    let cassette_dir = dir.path().join("cassette");
+    let log_root = dir.path().join("logs");
+
+    let recorder = Cassette::record(&cassette_dir).unwrap();
+    let live = EchoApi::default();
+    let logger = Logger::new_with_root(&log_root, "record").unwrap();
+    assert_eq!(
+        ask(&live, &recorder, "first", &logger, "1-test")
+            .await
+            .unwrap(),
+        "echo: first"
+    );
+    assert_eq!(
+        ask(&live, &recorder, "second", &logger, "2-test")
+            .await
+            .unwrap(),
+        "echo: second"
+    );
+    assert_eq!(live.calls.load(Ordering::SeqCst), 2);
+
+    let replay_logs = tempdir().unwrap();
+    let logger = Logger::new_with_root(replay_logs.path(), "replay").unwrap();
+    for source in [cassette_dir.clone(), only_subdir(&log_root)] {
+        let cassette = Cassette::replay(&source).unwrap();
+        let offline = EchoApi::default();
+        assert_eq!(
+            ask(&offline, &cassette, "second", &logger, "1-replay")
+                .await
+                .unwrap(),
+            "echo: second"
+        );
+        assert_eq!(
+            ask(&offline, &cassette, "first", &logger, "2-replay")
+                .await
+                .unwrap(),
+            "echo: first"
+        );
+        assert_eq!(offline.calls.load(Ordering::SeqCst), 0);
+
+        // Each recorded response is used once, and unknown prompts are misses.
+        let result = ask(&offline, &cassette, "first", &logger, "3-replay").await;
+        assert!(matches!(result, Err(AppError::ReplayMiss(_))));
+        let result = ask(&offline, &cassette, "third", &logger, "4-replay").await;
+        assert!(matches!(result, Err(AppError::ReplayMiss(_))));
+    }
+}
+
+#[test]
+fn test_saved_query_is_read_back() {
+    let dir = tempdir().unwrap();
+    let cassette = Cassette::record(dir.path()).unwrap();
+    assert_eq!(Cassette::recorded_query(dir.path()), None);
+    cassette.save_query("Fix the bug\n").unwrap();
+    assert_eq!(
+        Cassette::recorded_query(dir.path()),
+        Some("Fix the bug".to_string())
+    );
+}
+
+#[test]
+fn test_replay_rejects_missing_or_empty_directories() {
+    let dir = tempdir().unwrap();
+    assert!(matches!(
+        Cassette::replay(&dir.path().join("missing")),
+        Err(AppError::Config(_))
+    ));
+    assert!(matches!(
+        Cassette::replay(dir.path()),
+        Err(AppError::Config(_))
+    ));
+}
+
+#[test]
+fn test_request_key_ignores_streaming_options() {
+    let plain = json!({ "model": "m", "input": "hi" });
+    let streamed = json!({
+        "model": "m",
+        "input": "hi",
+        "stream": true,
+        "stream_options": { "include_usage": true }
+    });
+    assert_eq!(request_key(&plain), request_key(&streamed));
+    assert_ne!(
+        request_key(&plain),
+        request_key(&json!({ "model": "m", "input": "bye" }))
+    );
+}
@@ -0,0 +1,243 @@
+use super::generation::CallRole;
+use super::schema::ResponseSchema;
+use super::tokens::estimate_tokens;
+use super::tools::{describe_call, ToolCall, ToolSpec};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+    /// The result of a tool call, sent back on the user's side.
+    Tool,
+}
+
+impl Role {
+    fn label(self) -> &'static str {
+        match self {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+            Role::Tool => "tool",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub role: Role,
+    pub content: String,
+    /// The calls requested by an assistant message.
+    pub tool_calls: Vec<ToolCall>,
+    /// The call that a tool message answers.
+    pub answers: Option<ToolCall>,
+}
+
+/// Server-side state left behind by the last reply, such as a Gemini
+/// interaction id, and how many messages it already covers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ProviderState {
+    model_name: &'static str,
+    id: String,
+    covered: usize,
+}
+
+/// An ordered list of system, user and assistant messages. Each client
+/// serializes the conversation in its provider's native multi-turn format, and
+/// `llm::converse` appends the reply so that the next user turn can follow on.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Conversation {
+    messages: Vec<Message>,
+    provider_state: Option<ProviderState>,
+    response_schema: Option<ResponseSchema>,
+    tools: Vec<ToolSpec>,
+    call_role: Option<CallRole>,
+}
+
+impl Conversation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A single-turn conversation holding only `prompt`.
+    #[cfg(test)]
+    pub fn from_user(prompt: impl Into<String>) -> Self {
+        let mut conversation = Self::new();
+        conversation.push_user(prompt);
+        conversation
+    }
+
+    /// A single-turn conversation with a system instruction, which providers
+    /// give priority over the user turn. An empty instruction is left out.
+    pub fn with_system(system: impl Into<String>, prompt: impl Into<String>) -> Self {
+        let mut conversation = Self::new();
+        let system = system.into();
+        if !system.is_empty() {
+            conversation.push_system(system);
+        }
+        conversation.push_user(prompt);
+        conversation
+    }
+
+    pub fn push_system(&mut self, content: impl Into<String>) {
+        self.push(Role::System, content.into());
+    }
+
+    pub fn push_user(&mut self, content: impl Into<String>) {
+        self.push(Role::User, content.into());
+    }
+
+    pub fn push_assistant(&mut self, content: impl Into<String>) {
+        self.push(Role::Assistant, content.into());
+    }
+
+    fn push(&mut self, role: Role, content: String) {
+        self.messages.push(Message {
+            role,
+            content,
+            tool_calls: Vec::new(),
+            answers: None,
+        });
+    }
+
+    /// Asks for replies that follow `schema`. Providers with schema-constrained
+    /// output enforce it; the reply may still be free text, for example when it
+    /// comes from an older recording.
+    pub fn set_response_schema(&mut self, schema: ResponseSchema) {
+        self.response_schema = Some(schema);
+    }
+
+    pub(crate) fn response_schema(&self) -> Option<&ResponseSchema> {
+        self.response_schema.as_ref()
+    }
+
+    /// Offers `tools` to the model on every following call. Use
+    /// `llm::converse_with_tools` to send a conversation with tools, so that
+    /// the calls the model makes are run.
+    pub fn set_tools(&mut self, tools: Vec<ToolSpec>) {
+        self.tools = tools;
+    }
+
+    pub(crate) fn tools(&self) -> &[ToolSpec] {
+        &self.tools
+    }
+
+    /// Sends every following call with the generation parameters configured
+    /// for `role` in `Session::generation`.
+    pub fn set_call_role(&mut self, role: CallRole) {
+        self.call_role = Some(role);
+    }
+
+    pub(crate) fn call_role(&self) -> Option<CallRole> {
+        self.call_role
+    }
+
+    /// An assistant reply that asks for tool calls. `content` holds any text
+    /// the model wrote alongside them.
+    pub(crate) fn push_tool_calls(&mut self, content: String, tool_calls: Vec<ToolCall>) {
+        self.messages.push(Message {
+            role: Role::Assistant,
+            content,
+            tool_calls,
+            answers: None,
+        });
+    }
+
+    pub(crate) fn push_tool_result(&mut self, call: &ToolCall, result: String) {
+        self.messages.push(Message {
+            role: Role::Tool,
+            content: result,
+            tool_calls: Vec::new(),
+            answers: Some(call.clone()),
+        });
+    }
+
+    /// The tool calls requested by the last message, if it is an assistant
+    /// reply that has not been answered yet.
+    pub(crate) fn pending_tool_calls(&self) -> &[ToolCall] {
+        match self.messages.last() {
+            Some(m) if m.role == Role::Assistant => &m.tool_calls,
+            _ => &[],
+        }
+    }
+
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    /// The full conversation as plain text. A conversation holding a single
+    /// user message renders as just that message.
+    pub fn transcript(&self) -> String {
+        if let [only] = self.messages.as_slice() {
+            if only.role == Role::User {
+                return only.content.clone();
+            }
+        }
+        let mut out = String::new();
+        for message in &self.messages {
+            out.push_str(&format!("=== {} ===\n", message.role.label()));
+            if let Some(call) = &message.answers {
+                out.push_str(&format!("[result of {}]\n", describe_call(call)));
+            }
+            out.push_str(&message.content);
+            let only_calls = message.content.is_empty() && !message.tool_calls.is_empty();
+            if !only_calls && !message.content.ends_with('\n') {
+                out.push('\n');
+            }
+            for call in &message.tool_calls {
+                out.push_str(&format!("[tool call] {}\n", describe_call(call)));
+            }
+        }
+        out
+    }
+
+    /// Providers bill the whole history as input on every turn, even when the
+    /// history is stored server-side, so this covers every message.
+    pub fn estimated_tokens(&self) -> u64 {
+        self.messages
+            .iter()
+            .map(|m| {
+                estimate_tokens(&m.content)
+                    + m.tool_calls
+                        .iter()
+                        .map(|c| estimate_tokens(&describe_call(c)))
+                        .sum::<u64>()
+            })
+            .sum()
+    }
+
+    /// A key for the prefix that every call in this conversation resends
+    /// unchanged: the system instruction and the first user message. Calls
+    /// that share the key can be served from the same provider prompt cache.
+    pub(crate) fn cache_key(&self) -> Option<String> {
+        let first_user = self.messages.iter().position(|m| m.role == Role::User)?;
+        let mut hasher = DefaultHasher::new();
+        for message in &self.messages[..=first_user] {
+            message.role.label().hash(&mut hasher);
+            message.content.hash(&mut hasher);
+        }
+        Some(format!("crate-{:016x}", hasher.finish()))
+    }
+
+    /// Remembers a provider-side id for everything in the conversation so far.
+    pub(crate) fn set_provider_state(&mut self, model_name: &'static str, id: String) {
+        self.provider_state = Some(ProviderState {
+            model_name,
+            id,
+            covered: self.messages.len(),
+        });
+    }
+
+    /// The provider-side id stored for `model_name` and the messages added
+    /// since it was stored. Returns `None` when the stored state belongs to a
+    /// different model or when nothing new has been added.
+    pub(crate) fn continuation(&self, model_name: &str) -> Option<(&str, &[Message])> {
+        let state = self.provider_state.as_ref()?;
+        if state.model_name != model_name || state.covered >= self.messages.len() {
+            return None;
+        }
+        Some((&state.id, &self.messages[state.covered..]))
+    }
+}
@@ -0,0 +1,61 @@
+use super::conversation::{Conversation, Role};
+
+#[test]
+fn test_transcript_of_single_prompt_is_the_prompt() {
+    let conversation = Conversation::from_user("just this");
+    assert_eq!(conversation.transcript(), "just this");
+}
+
+#[test]
+fn test_transcript_labels_each_turn() {
+    let mut conversation = Conversation::new();
+    conversation.push_system("rules");
+    conversation.push_user("question");
+    conversation.push_assistant("answer\n");
+    assert_eq!(
+        conversation.transcript(),
+        "=== system ===\nrules\n=== user ===\nquestion\n=== assistant ===\nanswer\n"
+    );
+    assert_eq!(conversation.messages()[0].role, Role::System);
+}
+
+#[test]
+fn test_continuation_requires_new_messages() {
+    let mut conversation = Conversation::from_user("question");
+    assert!(conversation.continuation("m").is_none());
+
+    conversation.push_assistant("answer");
+    conversation.set_provider_state("m", "id-1".to_string());
+    assert!(conversation.continuation("m").is_none());
+
+    conversation.push_user("follow-up");
+    let (id, new_messages) = conversation.continuation("m").unwrap();
+    assert_eq!(id, "id-1");
+    assert_eq!(new_messages[0].content, "follow-up");
+}
+
+#[test]
+fn test_estimated_tokens_covers_whole_history() {
+    let mut conversation = Conversation::from_user("abcd");
+    let one = conversation.estimated_tokens();
+    conversation.push_assistant("abcd");
+    assert_eq!(conversation.estimated_tokens(), one * 2);
+}
+
+#[test]
+fn test_cache_key_covers_system_and_first_user_message() {
+    let base = Conversation::with_system("rules", "codebase");
+    let key = base.cache_key().unwrap();
+
+    let mut continued = base.clone();
+    continued.push_assistant("answer");
+    continued.push_user("build failed");
+    assert_eq!(continued.cache_key().unwrap(), key);
+
+    let other_prompt = Conversation::with_system("rules", "other codebase");
+    assert_ne!(other_prompt.cache_key().unwrap(), key);
+    let other_system = Conversation::with_system("other rules", "codebase");
+    assert_ne!(other_system.cache_key().unwrap(), key);
+
+    assert!(Conversation::new().cache_key().is_none());
+}
@@ -0,0 +1,140 @@
+use super::models::Provider;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// What a call is for. Generation parameters can be set for each role, for
+/// example low reasoning effort for choosing context files and high effort
+/// for reviewing specifications.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CallRole {
+    /// Choosing the files to include as context.
+    Context,
+    /// Choosing extra files after a failed build.
+    ExtraCode,
+    /// Writing and repairing code, including auto-workflow code stages.
+    Commit,
+    /// The consistency check workflow.
+    Consistency,
+    /// The auto-workflow self-consistent stage.
+    SelfConsistent,
+}
+
+impl CallRole {
+    /// The name used for the role in `agent-config/config.toml` and the logs.
+    pub fn name(self) -> &'static str {
+        match self {
+            CallRole::Context => "context",
+            CallRole::ExtraCode => "extra_code",
+            CallRole::Commit => "commit",
+            CallRole::Consistency => "consistency",
+            CallRole::SelfConsistent => "self_consistent",
+        }
+    }
+}
+
+/// How much the model should think before answering. Sent to GPT as
+/// `reasoning_effort`, to Gemini 3 models as `thinking_level` and to Gemini
+/// 2.5 models as a thinking budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReasoningEffort {
+    Minimal,
+    Low,
+    Medium,
+    High,
+}
+
+impl ReasoningEffort {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReasoningEffort::Minimal => "minimal",
+            ReasoningEffort::Low => "low",
+            ReasoningEffort::Medium => "medium",
+            ReasoningEffort::High => "high",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "minimal" => Some(ReasoningEffort::Minimal),
+            "low" => Some(ReasoningEffort::Low),
+            "medium" => Some(ReasoningEffort::Medium),
+            "high" => Some(ReasoningEffort::High),
+            _ => None,
+        }
+    }
+}
+
+/// Sampling and reasoning settings for a call. Unset fields are left to the
+/// provider's defaults.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GenerationParams {
+    pub temperature: Option<f64>,
+    pub max_output_tokens: Option<u64>,
+    pub reasoning_effort: Option<ReasoningEffort>,
+}
+
+impl GenerationParams {
+    /// `self` with every field that `over` sets replaced by its value.
+    pub fn overlay(self, over: &GenerationParams) -> Self {
+        Self {
+            temperature: over.temperature.or(self.temperature),
+            max_output_tokens: over.max_output_tokens.or(self.max_output_tokens),
+            reasoning_effort: over.reasoning_effort.or(self.reasoning_effort),
+        }
+    }
+
+    /// The fields that are set, for the request log.
+    pub(crate) fn to_log_json(self) -> Value {
+        let mut log = json!({});
+        if let Some(t) = self.temperature {
+            log["temperature"] = json!(t);
+        }
+        if let Some(m) = self.max_output_tokens {
+            log["maxOutputTokens"] = json!(m);
+        }
+        if let Some(e) = self.reasoning_effort {
+            log["reasoningEffort"] = json!(e.as_str());
+        }
+        log
+    }
+}
+
+/// Generation parameters in layers. A call with role `R` to provider `P`
+/// uses, from lowest to highest precedence: the settings for every call, the
+/// settings for `P`, the settings for `R`, and the settings for `R` on `P`.
+#[derive(Debug, Clone, Default)]
+pub struct GenerationConfig {
+    layers: HashMap<(Option<CallRole>, Option<Provider>), GenerationParams>,
+}
+
+impl GenerationConfig {
+    /// Sets the parameters for a layer; `None` stands for every role or every
+    /// provider. Fields left unset in `params` keep the layer's earlier values.
+    pub fn set(
+        &mut self,
+        role: Option<CallRole>,
+        provider: Option<Provider>,
+        params: GenerationParams,
+    ) {
+        let layer = self.layers.entry((role, provider)).or_default();
+        *layer = layer.overlay(&params);
+    }
+
+    pub fn resolve(&self, role: Option<CallRole>, provider: Option<Provider>) -> GenerationParams {
+        let mut keys = vec![(None, None)];
+        if provider.is_some() {
+            keys.push((None, provider));
+        }
+        if role.is_some() {
+            keys.push((role, None));
+            if provider.is_some() {
+                keys.push((role, provider));
+            }
+        }
+        keys.iter()
+            .filter_map(|key| self.layers.get(key))
+            .fold(GenerationParams::default(), |params, layer| {
+                params.overlay(layer)
+            })
+    }
+}
@@ -0,0 +1,113 @@
+use super::generation::{CallRole, GenerationConfig, GenerationParams, ReasoningEffort};
+use super::models::Provider;
+use serde_json::json;
+
+fn effort(effort: ReasoningEffort) -> GenerationParams {
+    GenerationParams {
+        reasoning_effort: Some(effort),
+        ..GenerationParams::default()
+    }
+}
+
+#[test]
+fn test_resolve_layers_role_over_provider_over_default() {
+    let mut config = GenerationConfig::default();
+    config.set(
+        None,
+        None,
+        GenerationParams {
+            temperature: Some(1.0),
+            max_output_tokens: Some(8000),
+            reasoning_effort: Some(ReasoningEffort::Medium),
+        },
+    );
+    config.set(None, Some(Provider::Gpt), effort(ReasoningEffort::Minimal));
+    config.set(Some(CallRole::Context), None, effort(ReasoningEffort::Low));
+    config.set(
+        Some(CallRole::Context),
+        Some(Provider::Gemini),
+        GenerationParams {
+            temperature: Some(0.2),
+            ..GenerationParams::default()
+        },
+    );
+
+    let params = config.resolve(None, None);
+    assert_eq!(params.reasoning_effort, Some(ReasoningEffort::Medium));
+
+    let params = config.resolve(Some(CallRole::Commit), Some(Provider::Gpt));
+    assert_eq!(params.reasoning_effort, Some(ReasoningEffort::Minimal));
+    assert_eq!(params.max_output_tokens, Some(8000));
+
+    // The role beats the provider, even the provider-wide setting.
+    let params = config.resolve(Some(CallRole::Context), Some(Provider::Gpt));
+    assert_eq!(params.reasoning_effort, Some(ReasoningEffort::Low));
+    assert_eq!(params.temperature, Some(1.0));
+
+    let params = config.resolve(Some(CallRole::Context), Some(Provider::Gemini));
+    assert_eq!(
+        params,
+        GenerationParams {
+            temperature: Some(0.2),
+            max_output_tokens: Some(8000),
+            reasoning_effort: Some(ReasoningEffort::Low),
+        }
+    );
+
+    // Unknown providers get the provider-independent layers only.
+    let params = config.resolve(Some(CallRole::Context), None);
+    assert_eq!(params.temperature, Some(1.0));
+}
+
+#[test]
+fn test_set_keeps_fields_the_new_params_leave_unset() {
+    let mut config = GenerationConfig::default();
+    config.set(None, None, effort(ReasoningEffort::High));
+    config.set(
+        None,
+        None,
+        GenerationParams {
+            temperature: Some(0.0),
+            ..GenerationParams::default()
+        },
+    );
+    let params = config.resolve(None, None);
+    assert_eq!(params.reasoning_effort, Some(ReasoningEffort::High));
+    assert_eq!(params.temperature, Some(0.0));
+}
+
+#[test]
+fn test_provider_for_model_name() {
+    assert_eq!(
+        Provider::for_model_name("gemini-3-pro-preview"),
+        Some(Provider::Gemini)
+    );
+    assert_eq!(Provider::for_model_name("gpt-5.2"), Some(Provider::Gpt));
+    assert_eq!(Provider::for_model_name("mock-model"), None);
+}
+
+#[test]
+fn test_reasoning_effort_names_round_trip() {
+    for effort in [
+        ReasoningEffort::Minimal,
+        ReasoningEffort::Low,
+        ReasoningEffort::Medium,
+        ReasoningEffort::High,
+    ] {
+        assert_eq!(ReasoningEffort::parse(effort.as_str()), Some(effort));
+    }
+    assert_eq!(ReasoningEffort::parse("extreme"), None);
+}
+
+#[test]
+fn test_log_json_lists_only_set_fields() {
+    assert_eq!(GenerationParams::default().to_log_json(), json!({}));
+    let params = GenerationParams {
+        max_output_tokens: Some(100),
+        ..effort(ReasoningEffort::Low)
+    };
+    assert_eq!(
+        params.to_log_json(),
+        json!({ "maxOutputTokens": 100, "reasoningEffort": "low" })
+    );
+}
@@ -0,0 +1,57 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use tokio::sync::Notify;
+
+/// Exit code for a process stopped by Ctrl-C (128 + SIGINT).
+pub const INTERRUPTED_EXIT_CODE: u8 = 130;
+
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+static CANCELLING: AtomicBool = AtomicBool::new(false);
+
+fn interrupts() -> &'static Notify {
+    static NOTIFY: OnceLock<Notify> = OnceLock::new();
+    NOTIFY.get_or_init(Notify::new)
+}
+
+/// Takes over Ctrl-C for the rest of the process. While an LLM call is in
+/// flight, the first Ctrl-C cancels it so that the remote request can be
+/// stopped cleanly; at any other time, or on a second Ctrl-C, the process
+/// exits right away as it would without the handler.
+pub fn install_ctrl_c_handler() {
+    tokio::spawn(async {
+        loop {
+            if tokio::signal::ctrl_c().await.is_err() {
+                return;
+            }
+            if IN_FLIGHT.load(Ordering::SeqCst) == 0 || CANCELLING.swap(true, Ordering::SeqCst) {
+                eprintln!("\nInterrupted.");
+                std::process::exit(i32::from(INTERRUPTED_EXIT_CODE));
+            }
+            eprintln!("\nCancelling the in-flight LLM call... (press Ctrl-C again to exit now)");
+            interrupts().notify_waiters();
+        }
+    });
+}
+
+/// Marks an LLM call as in flight for as long as it is alive.
+pub(crate) struct InFlight(());
+
+impl InFlight {
+    pub(crate) fn start() -> Self {
+        IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+        Self(())
+    }
+
+    /// Resolves when Ctrl-C is pressed while this call is in flight.
+    pub(crate) async fn interrupted(&self) {
+        interrupts().notified().await;
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if IN_FLIGHT.fetch_sub(1, Ordering::SeqCst) == 1 {
+            CANCELLING.store(false, Ordering::SeqCst);
+        }
+    }
+}
@@ -1,94 +1,309 @@
 pub mod api;
+pub mod budget;
+pub mod cassette;
+pub mod conversation;
+pub mod generation;
+pub mod interrupt;
+pub mod models;
+mod rate_limit;
+pub mod registry;
+pub mod schema;
+pub mod session;
+mod stream;
+pub mod tokens;
+pub mod tools;
 
 #[cfg(test)]
 mod api_test;
 #[cfg(test)]
+mod budget_test;
+#[cfg(test)]
+mod cassette_test;
+#[cfg(test)]
+mod conversation_test;
+#[cfg(test)]
+mod generation_test;
+#[cfg(test)]
 mod mod_test;
+#[cfg(test)]
+mod models_test;
+#[cfg(test)]
+mod rate_limit_test;
+#[cfg(test)]
+mod schema_test;
+#[cfg(test)]
+mod stream_test;
+#[cfg(test)]
+mod tokens_test;
+#[cfg(test)]
+mod tools_test;
 
 use crate::app_error::AppError;
-use crate::cli::Model;
 use crate::logger::Logger;
 use api::{LlmApi, LlmApiClient};
-use serde_json::json;
-use std::time::Instant;
+use budget::Usage;
+use cassette::CassetteClient;
+pub use conversation::Conversation;
+use interrupt::InFlight;
+use models::{Model, Provider};
+use registry::ClientRegistry;
+use serde_json::{json, Value};
+pub use session::Session;
+use std::time::{Duration, Instant};
+use stream::Progress;
+use tokens::input_token_limit_for_model_name;
+use tools::{describe_call, ToolHandler};
 use uuid::Uuid;
 
 pub(crate) fn generate_request_id() -> String {
     Uuid::new_v4().to_string()
 }
 
-pub(crate) fn create_client(model: Model, api_key: String) -> LlmApiClient {
-    match model {
-        Model::Gemini3Pro => {
-            LlmApiClient::Gemini(api::GeminiClient::new(api_key, "gemini-3-pro-preview"))
-        }
-        Model::Gemini2_5Pro => {
-            LlmApiClient::Gemini(api::GeminiClient::new(api_key, "gemini-2.5-pro"))
+/// A client for one call. The HTTP connections and the rate limiter come from
+/// `registry`, so they outlive the call and are shared with every other call
+/// to the same provider.
+pub(crate) fn create_client(
+    model: Model,
+    api_key: String,
+    registry: &ClientRegistry,
+) -> LlmApiClient {
+    let pool = registry.pool(model.provider());
+    match model.provider() {
+        Provider::Gemini => {
+            LlmApiClient::Gemini(api::GeminiClient::new(api_key, model.name(), pool))
         }
-        Model::Gpt5 => LlmApiClient::Gpt(api::GptClient::new(api_key)),
+        Provider::Gpt => LlmApiClient::Gpt(api::GptClient::new(api_key, model.name(), pool)),
     }
 }
 
-pub async fn query(
+/// Sends `conversation` and appends the reply to it as an assistant message,
+/// so that a follow-up user turn can be added and sent with another call.
+pub async fn converse(
     model: Model,
     api_key: String,
-    prompt: &str,
+    session: &Session,
+    conversation: &mut Conversation,
     logger: &Logger,
     log_prefix: &str,
 ) -> Result<String, AppError> {
-    let api_client = create_client(model, api_key);
-    query_internal(&api_client, prompt, logger, log_prefix).await
+    dispatch(
+        model,
+        api_key,
+        session,
+        conversation,
+        None,
+        logger,
+        log_prefix,
+    )
+    .await
 }
 
-async fn query_internal(
-    api_client: &dyn LlmApi,
-    prompt: &str,
+/// Like `converse`, but offers the model `tools`. Each call the model makes is
+/// run locally and its result sent back, until the model answers with text or
+/// runs past `session.max_tool_calls`. Calls beyond the limit are not run; the
+/// model is told to answer with what it has, and asking for more after that
+/// ends the conversation with `AppError::ToolCallLimit`.
+pub async fn converse_with_tools(
+    model: Model,
+    api_key: String,
+    session: &Session,
+    conversation: &mut Conversation,
+    tools: &dyn ToolHandler,
     logger: &Logger,
     log_prefix: &str,
 ) -> Result<String, AppError> {
-    logger.log_text(&format!("{log_prefix}-query.txt"), prompt)?;
+    dispatch(
+        model,
+        api_key,
+        session,
+        conversation,
+        Some(tools),
+        logger,
+        log_prefix,
+    )
+    .await
+}
 
-    let request_body = api_client.build_request_body(prompt);
-    let url = api_client.get_url();
-    let request_id = generate_request_id();
+async fn dispatch(
+    model: Model,
+    api_key: String,
+    session: &Session,
+    conversation: &mut Conversation,
+    tools: Option<&dyn ToolHandler>,
+    logger: &Logger,
+    log_prefix: &str,
+) -> Result<String, AppError> {
+    let mut clients = vec![create_client(model, api_key, &session.clients)];
+    clients.extend(
+        session
+            .fallbacks
+            .iter()
+            .filter(|fallback| fallback.model != model)
+            .map(|fallback| {
+                create_client(fallback.model, fallback.api_key.clone(), &session.clients)
+            }),
+    );
+    let recorded: Vec<CassetteClient>;
+    let api_clients: Vec<&dyn LlmApi> = match &session.cassette {
+        Some(cassette) => {
+            recorded = clients
+                .iter()
+                .map(|client| CassetteClient::new(client, cassette))
+                .collect();
+            recorded.iter().map(|c| c as &dyn LlmApi).collect()
+        }
+        None => clients.iter().map(|c| c as &dyn LlmApi).collect(),
+    };
+    match tools {
+        Some(tools) => {
+            converse_with_tools_internal(
+                &api_clients,
+                session,
+                conversation,
+                tools,
+                logger,
+                log_prefix,
+            )
+            .await
+        }
+        None => converse_internal(&api_clients, session, conversation, logger, log_prefix).await,
+    }
+}
 
-    let log_body = json!({
-        "url": url,
-        "body": &request_body,
-        "requestId": request_id
-    });
-    logger.log_json(&format!("{log_prefix}-query.json"), &log_body)?;
+/// Runs the tool loop described on `converse_with_tools`. Each round is logged
+/// under its own prefix: `[prefix]`, then `[prefix]-tools-1`, and so on.
+async fn converse_with_tools_internal(
+    api_clients: &[&dyn LlmApi],
+    session: &Session,
+    conversation: &mut Conversation,
+    tools: &dyn ToolHandler,
+    logger: &Logger,
+    log_prefix: &str,
+) -> Result<String, AppError> {
+    conversation.set_tools(tools.specs());
+    let limit = session.max_tool_calls;
+    let mut calls_run: u32 = 0;
+    let mut round = 0;
+    loop {
+        let prefix = if round == 0 {
+            log_prefix.to_string()
+        } else {
+            format!("{log_prefix}-tools-{round}")
+        };
+        let text = converse_internal(api_clients, session, conversation, logger, &prefix).await?;
+        let calls = conversation.pending_tool_calls().to_vec();
+        if calls.is_empty() {
+            return Ok(text);
+        }
+        if calls_run >= limit {
+            return Err(AppError::ToolCallLimit(format!(
+                "the model kept calling tools after using all {limit} calls allowed for this query."
+            )));
+        }
+        for call in &calls {
+            let result = if calls_run < limit {
+                calls_run += 1;
+                println!("Tool call {calls_run}/{limit}: {}", describe_call(call));
+                tools.call(call)
+            } else {
+                format!(
+                    "Not run: the limit of {limit} tool calls for this query has been reached. Answer now with the information you already have."
+                )
+            };
+            conversation.push_tool_result(call, result);
+        }
+        round += 1;
+    }
+}
 
-    let start_time = Instant::now();
-    let idempotency_key = if api_client.supports_idempotency() {
-        Some(request_id.as_str())
-    } else {
-        None
-    };
+/// Sends `conversation` to the first client and, each time a client fails
+/// with a network error after exhausting its retries, to the next one.
+async fn converse_internal(
+    api_clients: &[&dyn LlmApi],
+    session: &Session,
+    conversation: &mut Conversation,
+    logger: &Logger,
+    log_prefix: &str,
+) -> Result<String, AppError> {
+    logger.log_text(
+        &format!("{log_prefix}-query.txt"),
+        &conversation.transcript(),
+    )?;
 
-    let response_result = api_client
-        .query_with_retries(&request_body, idempotency_key)
-        .await;
-    let duration = start_time.elapsed();
+    let estimated_input_tokens = conversation.estimated_tokens();
+    let mut fallbacks: Vec<Value> = Vec::new();
+    let mut last_error = None;
+    let mut answer = None;
+    for (index, api_client) in api_clients.iter().copied().enumerate() {
+        let model_name = api_client.get_model_name();
+        if let Err(e) = check_call(api_client, session, estimated_input_tokens) {
+            if index == 0 {
+                logger.log_text(
+                    &format!("{log_prefix}-response.txt"),
+                    &format!("NOT SENT\n{e}"),
+                )?;
+                return Err(e);
+            }
+            println!("Skipping fallback model {model_name}: {e}");
+            fallbacks.push(json!({ "model": model_name, "skipped": e.to_string() }));
+            continue;
+        }
+        if index > 0 {
+            println!("Falling back to {model_name}");
+        }
 
-    println!(
-        "LLM call to {} took {:.3}s",
-        api_client.get_model_name(),
-        duration.as_secs_f64()
-    );
+        let (response_result, duration) =
+            send(api_client, session, conversation, logger, log_prefix).await?;
+        match response_result {
+            Ok(json) => {
+                answer = Some((api_client, json, duration));
+                break;
+            }
+            Err(e @ AppError::Network(_)) if index + 1 < api_clients.len() => {
+                println!("{model_name} is unavailable: {e}");
+                fallbacks.push(json!({
+                    "model": model_name,
+                    "error": e.to_string(),
+                    "totalResponseTime": duration.as_millis(),
+                }));
+                last_error = Some((e, duration));
+            }
+            Err(e) => {
+                last_error = Some((e, duration));
+                break;
+            }
+        }
+    }
 
-    let response_json = match response_result {
-        Ok(json) => json,
-        Err(e) => {
-            let error_json =
-                json!({ "error": e.to_string(), "totalResponseTime": duration.as_millis() });
-            logger.log_json(&format!("{log_prefix}-response.json"), &error_json)?;
-            let error_msg = format!("ERROR\n{e}");
-            logger.log_text(&format!("{log_prefix}-response.txt"), &error_msg)?;
-            return Err(e);
+    let Some((api_client, response_json, duration)) = answer else {
+        let (e, duration) = last_error.expect("the first client is always tried");
+        let mut error_json =
+            json!({ "error": e.to_string(), "totalResponseTime": duration.as_millis() });
+        if !fallbacks.is_empty() {
+            error_json["modelFallback"] = json!({ "failed": fallbacks });
         }
+        logger.log_json(&format!("{log_prefix}-response.json"), &error_json)?;
+        let error_msg = format!("ERROR\n{e}");
+        logger.log_text(&format!("{log_prefix}-response.txt"), &error_msg)?;
+        return Err(e);
     };
 
+    // Providers that do not report usage are charged the estimated input size
+    // so that the budget still moves.
+    let usage = api_client.extract_usage(&response_json).unwrap_or(Usage {
+        input_tokens: estimated_input_tokens,
+        ..Usage::default()
+    });
+    let cost = session.budget.record(api_client.get_model_name(), &usage);
+    println!(
+        "Used {} input ({} cached) and {} output tokens (${:.4}; ${:.4} this run)",
+        usage.input_tokens,
+        usage.cached_input_tokens,
+        usage.output_tokens,
+        cost,
+        session.budget.total_cost()
+    );
+
     let mut logged_response = response_json.clone();
     if let Some(obj) = logged_response.as_object_mut() {
         obj.insert("totalResponseTime".to_string(), json!(duration.as_millis()));
@@ -98,17 +313,161 @@ async fn query_internal(
             "totalResponseTime": duration.as_millis(),
         });
     }
+    if let Some(obj) = logged_response.as_object_mut() {
+        obj.insert(
+            "callUsage".to_string(),
+            json!({
+                "inputTokens": usage.input_tokens,
+                "outputTokens": usage.output_tokens,
+                "cache": {
+                    "hitTokens": usage.cached_input_tokens,
+                    "missTokens": usage.input_tokens.saturating_sub(usage.cached_input_tokens),
+                },
+                "cost": cost,
+                "runTotalTokens": session.budget.total_tokens(),
+                "runTotalCachedTokens": session.budget.total_cached_tokens(),
+                "runTotalCost": session.budget.total_cost(),
+            }),
+        );
+        if !fallbacks.is_empty() {
+            obj.insert(
+                "modelFallback".to_string(),
+                json!({ "failed": fallbacks, "answeredBy": api_client.get_model_name() }),
+            );
+        }
+    }
     logger.log_json(&format!("{log_prefix}-response.json"), &logged_response)?;
 
+    // A reply that only calls tools has no text.
+    let tool_calls = api_client.extract_tool_calls(&response_json);
     let response_text = match api_client.extract_text_from_response(&response_json) {
         Ok(text) => text,
+        Err(_) if !tool_calls.is_empty() => String::new(),
         Err(e) => {
             let error_msg = format!("ERROR\n{e}");
             logger.log_text(&format!("{log_prefix}-response.txt"), &error_msg)?;
             return Err(e);
         }
     };
-    logger.log_text(&format!("{log_prefix}-response.txt"), &response_text)?;
+    let mut logged_text = response_text.clone();
+    for call in &tool_calls {
+        if !logged_text.is_empty() {
+            logged_text.push('\n');
+        }
+        logged_text.push_str(&format!("[tool call] {}", describe_call(call)));
+    }
+    logger.log_text(&format!("{log_prefix}-response.txt"), &logged_text)?;
+
+    if tool_calls.is_empty() {
+        conversation.push_assistant(response_text.clone());
+    } else {
+        conversation.push_tool_calls(response_text.clone(), tool_calls);
+    }
+    if let Some(id) = api_client.extract_conversation_id(&response_json) {
+        conversation.set_provider_state(api_client.get_model_name(), id);
+    }
 
     Ok(response_text)
 }
+
+/// Checks that the conversation fits `api_client`'s input limit and that
+/// sending it stays within the run's budget.
+fn check_call(
+    api_client: &dyn LlmApi,
+    session: &Session,
+    estimated_input_tokens: u64,
+) -> Result<(), AppError> {
+    let input_limit = input_token_limit_for_model_name(api_client.get_model_name());
+    if estimated_input_tokens > input_limit {
+        return Err(AppError::PromptTooLarge(format!(
+            "the prompt is estimated at {estimated_input_tokens} tokens, but {} accepts at most {input_limit} input tokens.",
+            api_client.get_model_name()
+        )));
+    }
+    session
+        .budget
+        .check(api_client.get_model_name(), estimated_input_tokens)
+}
+
+/// Sends one request and logs it to `[prefix]-query.json`. A fallback
+/// overwrites the log, so it always holds the request that was sent last.
+async fn send(
+    api_client: &dyn LlmApi,
+    session: &Session,
+    conversation: &Conversation,
+    logger: &Logger,
+    log_prefix: &str,
+) -> Result<(Result<Value, AppError>, Duration), AppError> {
+    let mut request_body = api_client.build_request_body(conversation);
+    // Streamed tool calls arrive in fragments, so calls that offer tools wait
+    // for the complete response, as do models that cannot stream.
+    if session.stream
+        && conversation.tools().is_empty()
+        && models::supports(api_client.get_model_name(), |f| f.streaming)
+    {
+        api_client.enable_streaming(&mut request_body);
+    }
+    let role = conversation.call_role();
+    let generation = session
+        .generation
+        .resolve(role, Provider::for_model_name(api_client.get_model_name()));
+    api_client.apply_generation(&mut request_body, &generation);
+    let url = api_client.get_url();
+    let request_id = generate_request_id();
+
+    let mut generation_log = generation.to_log_json();
+    generation_log["role"] = json!(role.map(|r| r.name()));
+    let log_body = json!({
+        "url": url,
+        "body": &request_body,
+        "requestId": request_id,
+        "generation": generation_log
+    });
+    logger.log_json(&format!("{log_prefix}-query.json"), &log_body)?;
+
+    let start_time = Instant::now();
+    let idempotency_key = if api_client.supports_idempotency() {
+        Some(request_id.as_str())
+    } else {
+        None
+    };
+
+    let progress = Progress::new(api_client.get_model_name(), logger, log_prefix);
+    let in_flight = InFlight::start();
+    let deadline = async {
+        match session.call_timeout {
+            Some(timeout) => tokio::time::sleep(timeout).await,
+            None => std::future::pending().await,
+        }
+    };
+    let response_result = tokio::select! {
+        result = api_client.query_with_retries(&request_body, idempotency_key, &progress) => result,
+        _ = deadline => {
+            progress.finish();
+            api_client.cancel_in_flight().await;
+            Err(AppError::DeadlineExceeded(format!(
+                "{} did not answer within {}s.",
+                api_client.get_model_name(),
+                session.call_timeout.unwrap_or_default().as_secs()
+            )))
+        }
+        _ = in_flight.interrupted() => {
+            progress.finish();
+            api_client.cancel_in_flight().await;
+            Err(AppError::Interrupted(format!(
+                "the call to {} was cancelled.",
+                api_client.get_model_name()
+            )))
+        }
+    };
+    drop(in_flight);
+    progress.finish();
+    let duration = start_time.elapsed();
+
+    println!(
+        "LLM call to {} took {:.3}s",
+        api_client.get_model_name(),
+        duration.as_secs_f64()
+    );
+    Ok((response_result, duration))
+}
@@ -1,7 +1,13 @@
 use super::api::LlmApi;
-use super::{create_client, generate_request_id, query_internal};
+use super::budget::{Budget, BudgetLimits, Usage};
+use super::conversation::Conversation;
+use super::generation::{CallRole, GenerationParams, ReasoningEffort};
+use super::models::Model;
+use super::models::Provider;
+use super::registry::ClientRegistry;
+use super::stream::Progress;
+use super::{converse_internal, create_client, generate_request_id, Session};
 use crate::app_error::AppError;
-use crate::cli::Model;
 use crate::logger::Logger;
 use serde_json::{json, Value};
 use std::future::Future;
@@ -10,10 +16,14 @@ use std::sync::{Arc, Mutex};
 use tempfile::tempdir;
 
 struct MockLlmApiClient {
+    model_name: &'static str,
+    hang: bool,
+    cancelled: Arc<Mutex<bool>>,
     response: Result<Value, String>,
     extracted_text: Result<String, String>,
     supports_idempotency: bool,
     last_idempotency_key: Arc<Mutex<Option<String>>>,
+    last_request_body: Arc<Mutex<Option<Value>>>,
 }
 
 impl MockLlmApiClient {
@@ -23,44 +33,109 @@ impl MockLlmApiClient {
         supports_idempotency: bool,
     ) -> Self {
         Self {
+            model_name: "mock-model",
+            hang: false,
+            cancelled: Arc::new(Mutex::new(false)),
             response,
             extracted_text,
             supports_idempotency,
             last_idempotency_key: Arc::new(Mutex::new(None)),
+            last_request_body: Arc::new(Mutex::new(None)),
         }
     }
 }
 
+impl MockLlmApiClient {
+    fn named(mut self, model_name: &'static str) -> Self {
+        self.model_name = model_name;
+        self
+    }
+}
+
 impl LlmApi for MockLlmApiClient {
     fn get_model_name(&self) -> &'static str {
-        "mock-model"
+        self.model_name
     }
     fn get_url(&self) -> &'static str {
         "http://mock.url"
     }
-    fn build_request_body(&self, prompt: &str) -> Value {
-        json!({ "prompt": prompt })
+    fn build_request_body(&self, conversation: &Conversation) -> Value {
+        json!({ "prompt": conversation.transcript() })
+    }
+    fn extract_conversation_id(&self, response: &Value) -> Option<String> {
+        response
+            .get("id")
+            .and_then(|id| id.as_str())
+            .map(|id| id.to_string())
+    }
+    fn enable_streaming(&self, request_body: &mut Value) {
+        request_body["stream"] = json!(true);
+    }
+    fn apply_generation(&self, request_body: &mut Value, params: &GenerationParams) {
+        request_body["generation"] = params.to_log_json();
     }
     fn query_with_retries<'a>(
         &'a self,
-        _request_body: &'a Value,
+        request_body: &'a Value,
         idempotency_key: Option<&'a str>,
+        progress: &'a Progress<'a>,
     ) -> Pin<Box<dyn Future<Output = Result<Value, AppError>> + Send + 'a>> {
         let mut lock = self.last_idempotency_key.lock().unwrap();
         *lock = idempotency_key.map(|s| s.to_string());
+        *self.last_request_body.lock().unwrap() = Some(request_body.clone());
+        if request_body["stream"] == json!(true) {
+            if let Ok(text) = &self.extracted_text {
+                progress.on_delta(text);
+            }
+        }
+        if self.hang {
+            return Box::pin(std::future::pending());
+        }
         let resp = self.response.clone().map_err(AppError::Network);
         Box::pin(async { resp })
     }
+    fn cancel_in_flight<'a>(&'a self) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+        *self.cancelled.lock().unwrap() = true;
+        Box::pin(async {})
+    }
     fn extract_text_from_response(&self, _response: &Value) -> Result<String, AppError> {
         self.extracted_text
             .clone()
             .map_err(AppError::ResponseParsing)
     }
+    fn extract_usage(&self, response: &Value) -> Option<Usage> {
+        Some(Usage {
+            input_tokens: response.get("input_tokens")?.as_u64()?,
+            output_tokens: response.get("output_tokens")?.as_u64()?,
+            cached_input_tokens: response
+                .get("cached_input_tokens")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0),
+        })
+    }
     fn supports_idempotency(&self) -> bool {
         self.supports_idempotency
     }
 }
 
+async fn query_internal(
+    api_client: &MockLlmApiClient,
+    session: &Session,
+    prompt: &str,
+    logger: &Logger,
+    log_prefix: &str,
+) -> Result<String, AppError> {
+    let mut conversation = Conversation::from_user(prompt);
+    converse_internal(
+        &[api_client],
+        session,
+        &mut conversation,
+        logger,
+        log_prefix,
+    )
+    .await
+}
+
 #[tokio::test]
 async fn test_query_internal_happy_path() {
     let dir = tempdir().unwrap();
@@ -84,7 +159,7 @@ async fn test_query_internal_happy_path() {
         true,
     );
 
-    let result = query_internal(&client, "my prompt", &logger, "1-test").await;
+    let result = query_internal(&client, &Session::default(), "my prompt", &logger, "1-test").await;
 
     assert!(result.is_ok());
     assert_eq!(result.unwrap(), "llm says hi");
@@ -131,7 +206,7 @@ async fn test_query_internal_no_idempotency() {
         false, // No idempotency
     );
 
-    let _ = query_internal(&client, "prompt", &logger, "4-test").await;
+    let _ = query_internal(&client, &Session::default(), "prompt", &logger, "4-test").await;
 
     let used_key = client.last_idempotency_key.lock().unwrap().clone();
     assert!(used_key.is_none());
@@ -149,7 +224,7 @@ async fn test_query_internal_api_error() {
     let logger = Logger::new("test-run").unwrap();
     let client = MockLlmApiClient::new(Err("API failed".to_string()), Ok("...".to_string()), false);
 
-    let result = query_internal(&client, "prompt", &logger, "2-test").await;
+    let result = query_internal(&client, &Session::default(), "prompt", &logger, "2-test").await;
     assert!(result.is_err());
     assert!(matches!(result, Err(AppError::Network(_))));
 
@@ -170,13 +245,254 @@ async fn test_query_internal_parsing_error() {
         false,
     );
 
-    let result = query_internal(&client, "prompt", &logger, "3-test").await;
+    let result = query_internal(&client, &Session::default(), "prompt", &logger, "3-test").await;
     assert!(result.is_err());
     assert!(matches!(result, Err(AppError::ResponseParsing(_))));
 
     std::env::set_current_dir(original_dir).unwrap();
 }
 
+#[tokio::test]
+async fn test_query_internal_records_usage_against_budget() {
+    let dir = tempdir().unwrap();
+    let logger = Logger::new_with_root(dir.path(), "test-run").unwrap();
+    let session = Session::new(
+        Budget::new(BudgetLimits {
+            max_cost: None,
+            max_tokens: Some(1_000),
+        }),
+        false,
+    );
+    let client = MockLlmApiClient::new(
+        Ok(json!({"input_tokens": 600, "output_tokens": 300})),
+        Ok("hi".to_string()),
+        false,
+    );
+
+    query_internal(&client, &session, "prompt", &logger, "1-test")
+        .await
+        .unwrap();
+    assert_eq!(session.budget.total_tokens(), 900);
+
+    let log_dir = std::fs::read_dir(dir.path())
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let response_json: Value = serde_json::from_str(
+        &std::fs::read_to_string(log_dir.join("1-test-response.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(response_json["callUsage"]["inputTokens"], 600);
+    assert_eq!(response_json["callUsage"]["runTotalTokens"], 900);
+
+    // The second prompt no longer fits in the remaining 100 tokens.
+    let long_prompt = "x".repeat(1_000);
+    let result = query_internal(&client, &session, &long_prompt, &logger, "2-test").await;
+    assert!(matches!(result, Err(AppError::BudgetExceeded(_))));
+    let response_txt = std::fs::read_to_string(log_dir.join("2-test-response.txt")).unwrap();
+    assert!(response_txt.starts_with("NOT SENT"));
+}
+
+#[tokio::test]
+async fn test_query_internal_streaming_writes_partial_log() {
+    let dir = tempdir().unwrap();
+    let logger = Logger::new_with_root(dir.path(), "test-run").unwrap();
+    let client = MockLlmApiClient::new(
+        Ok(json!({"input_tokens": 1, "output_tokens": 1})),
+        Ok("streamed text".to_string()),
+        false,
+    );
+    let log_dir = std::fs::read_dir(dir.path())
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+
+    query_internal(&client, &Session::default(), "prompt", &logger, "1-test")
+        .await
+        .unwrap();
+    let body = client.last_request_body.lock().unwrap().clone().unwrap();
+    assert!(body.get("stream").is_none());
+    assert!(!log_dir.join("1-test-response.partial.txt").exists());
+
+    let session = Session::new(Budget::default(), true);
+    query_internal(&client, &session, "prompt", &logger, "2-test")
+        .await
+        .unwrap();
+    let body = client.last_request_body.lock().unwrap().clone().unwrap();
+    assert_eq!(body["stream"], true);
+    let partial = std::fs::read_to_string(log_dir.join("2-test-response.partial.txt")).unwrap();
+    assert_eq!(partial, "streamed text");
+    let query_json: Value =
+        serde_json::from_str(&std::fs::read_to_string(log_dir.join("2-test-query.json")).unwrap())
+            .unwrap();
+    assert_eq!(query_json["body"]["stream"], true);
+}
+
+#[tokio::test]
+async fn test_converse_internal_appends_reply_and_provider_state() {
+    let dir = tempdir().unwrap();
+    let logger = Logger::new_with_root(dir.path(), "test-run").unwrap();
+    let client = MockLlmApiClient::new(
+        Ok(json!({"id": "interaction-1"})),
+        Ok("first answer".to_string()),
+        false,
+    );
+
+    let mut conversation = Conversation::new();
+    conversation.push_system("be brief");
+    conversation.push_user("first question");
+    let text = converse_internal(
+        &[&client],
+        &Session::default(),
+        &mut conversation,
+        &logger,
+        "1-test",
+    )
+    .await
+    .unwrap();
+    assert_eq!(text, "first answer");
+    assert_eq!(conversation.messages().len(), 3);
+    assert_eq!(conversation.messages()[2].content, "first answer");
+
+    conversation.push_user("follow-up");
+    let (id, new_messages) = conversation.continuation("mock-model").unwrap();
+    assert_eq!(id, "interaction-1");
+    assert_eq!(new_messages.len(), 1);
+    assert!(conversation.continuation("other-model").is_none());
+}
+
+#[tokio::test]
+async fn test_converse_internal_failure_leaves_conversation_unchanged() {
+    let dir = tempdir().unwrap();
+    let logger = Logger::new_with_root(dir.path(), "test-run").unwrap();
+    let client = MockLlmApiClient::new(Err("boom".to_string()), Ok(String::new()), false);
+
+    let mut conversation = Conversation::from_user("question");
+    let result = converse_internal(
+        &[&client],
+        &Session::default(),
+        &mut conversation,
+        &logger,
+        "1-test",
+    )
+    .await;
+    assert!(result.is_err());
+    assert_eq!(conversation, Conversation::from_user("question"));
+}
+
+fn read_log_json(root: &std::path::Path, file_name: &str) -> Value {
+    let log_dir = std::fs::read_dir(root)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    serde_json::from_str(&std::fs::read_to_string(log_dir.join(file_name)).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn test_converse_internal_falls_back_after_network_failure() {
+    let dir = tempdir().unwrap();
+    let logger = Logger::new_with_root(dir.path(), "test-run").unwrap();
+    let primary = MockLlmApiClient::new(Err("HTTP 503".to_string()), Ok(String::new()), false)
+        .named("primary-model");
+    let fallback = MockLlmApiClient::new(
+        Ok(json!({"id": "fallback-1"})),
+        Ok("fallback answer".to_string()),
+        false,
+    )
+    .named("fallback-model");
+
+    let mut conversation = Conversation::from_user("question");
+    let text = converse_internal(
+        &[&primary, &fallback],
+        &Session::default(),
+        &mut conversation,
+        &logger,
+        "1-test",
+    )
+    .await
+    .unwrap();
+    assert_eq!(text, "fallback answer");
+    assert!(conversation.continuation("fallback-model").is_none());
+    conversation.push_user("follow-up");
+    assert!(conversation.continuation("fallback-model").is_some());
+
+    let response_json = read_log_json(dir.path(), "1-test-response.json");
+    let fallback_log = &response_json["modelFallback"];
+    assert_eq!(fallback_log["answeredBy"], "fallback-model");
+    assert_eq!(fallback_log["failed"][0]["model"], "primary-model");
+    assert!(fallback_log["failed"][0]["error"]
+        .as_str()
+        .unwrap()
+        .contains("HTTP 503"));
+}
+
+#[tokio::test]
+async fn test_converse_internal_reports_last_error_when_every_model_fails() {
+    let dir = tempdir().unwrap();
+    let logger = Logger::new_with_root(dir.path(), "test-run").unwrap();
+    let primary = MockLlmApiClient::new(Err("first down".to_string()), Ok(String::new()), false)
+        .named("primary-model");
+    let fallback = MockLlmApiClient::new(Err("second down".to_string()), Ok(String::new()), false)
+        .named("fallback-model");
+
+    let mut conversation = Conversation::from_user("question");
+    let result = converse_internal(
+        &[&primary, &fallback],
+        &Session::default(),
+        &mut conversation,
+        &logger,
+        "1-test",
+    )
+    .await;
+    assert!(matches!(result, Err(AppError::Network(msg)) if msg == "second down"));
+    assert_eq!(conversation, Conversation::from_user("question"));
+
+    let response_json = read_log_json(dir.path(), "1-test-response.json");
+    assert_eq!(
+        response_json["modelFallback"]["failed"][0]["model"],
+        "primary-model"
+    );
+}
+
+#[tokio::test]
+async fn test_converse_internal_deadline_cancels_the_call() {
+    let dir = tempdir().unwrap();
+    let logger = Logger::new_with_root(dir.path(), "test-run").unwrap();
+    let mut client = MockLlmApiClient::new(Ok(json!({})), Ok("late".to_string()), false);
+    client.hang = true;
+    let fallback = MockLlmApiClient::new(Ok(json!({})), Ok("fallback".to_string()), false)
+        .named("fallback-model");
+    let session = Session {
+        call_timeout: Some(std::time::Duration::from_millis(20)),
+        ..Default::default()
+    };
+
+    let mut conversation = Conversation::from_user("question");
+    let result = converse_internal(
+        &[&client, &fallback],
+        &session,
+        &mut conversation,
+        &logger,
+        "1-test",
+    )
+    .await;
+    // A deadline ends the call rather than moving on to a fallback model.
+    assert!(matches!(result, Err(AppError::DeadlineExceeded(_))));
+    assert!(*client.cancelled.lock().unwrap());
+    assert_eq!(conversation, Conversation::from_user("question"));
+    let response_json = read_log_json(dir.path(), "1-test-response.json");
+    assert!(response_json["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("LLM call deadline exceeded"));
+}
+
 #[test]
 fn test_generate_request_id() {
     let id1 = generate_request_id();
@@ -189,7 +505,12 @@ fn test_generate_request_id() {
 
 #[test]
 fn test_create_client_config() {
-    let client = create_client(Model::Gemini3Pro, "key".into());
+    let registry = ClientRegistry::default();
+    let client = create_client(
+        Model::from_str("gemini-3-pro-preview").unwrap(),
+        "key".into(),
+        &registry,
+    );
     assert_eq!(client.get_model_name(), "gemini-3-pro-preview");
     assert_eq!(
         client.get_url(),
@@ -197,7 +518,11 @@ fn test_create_client_config() {
     );
     assert!(!client.supports_idempotency());
 
-    let client = create_client(Model::Gemini2_5Pro, "key".into());
+    let client = create_client(
+        Model::from_str("gemini-2.5-pro").unwrap(),
+        "key".into(),
+        &registry,
+    );
     assert_eq!(client.get_model_name(), "gemini-2.5-pro");
     assert_eq!(
         client.get_url(),
@@ -205,7 +530,7 @@ fn test_create_client_config() {
     );
     assert!(!client.supports_idempotency());
 
-    let client = create_client(Model::Gpt5, "key".into());
+    let client = create_client(Model::from_str("gpt-5.2").unwrap(), "key".into(), &registry);
     assert_eq!(client.get_model_name(), "gpt-5.2");
     assert_eq!(
         client.get_url(),
@@ -213,3 +538,90 @@ fn test_create_client_config() {
     );
     assert!(client.supports_idempotency());
 }
+
+#[tokio::test]
+async fn test_cache_statistics_are_logged() {
+    let dir = tempdir().unwrap();
+    let logger = Logger::new_with_root(dir.path(), "test-run").unwrap();
+    let session = Session::default();
+    let client = MockLlmApiClient::new(
+        Ok(json!({"input_tokens": 1000, "output_tokens": 10, "cached_input_tokens": 800})),
+        Ok("hi".to_string()),
+        false,
+    );
+
+    query_internal(&client, &session, "prompt", &logger, "1-test")
+        .await
+        .unwrap();
+    assert_eq!(session.budget.total_cached_tokens(), 800);
+
+    let log_dir = std::fs::read_dir(dir.path())
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let response_json: Value = serde_json::from_str(
+        &std::fs::read_to_string(log_dir.join("1-test-response.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(response_json["callUsage"]["cache"]["hitTokens"], 800);
+    assert_eq!(response_json["callUsage"]["cache"]["missTokens"], 200);
+    assert_eq!(response_json["callUsage"]["runTotalCachedTokens"], 800);
+}
+
+#[tokio::test]
+async fn test_generation_parameters_follow_the_role_and_are_logged() {
+    let dir = tempdir().unwrap();
+    let logger = Logger::new_with_root(dir.path(), "test-run").unwrap();
+    let mut session = Session::default();
+    session.generation.set(
+        None,
+        None,
+        GenerationParams {
+            temperature: Some(0.5),
+            ..GenerationParams::default()
+        },
+    );
+    session.generation.set(
+        Some(CallRole::SelfConsistent),
+        Some(Provider::Gpt),
+        GenerationParams {
+            reasoning_effort: Some(ReasoningEffort::High),
+            ..GenerationParams::default()
+        },
+    );
+    let client = MockLlmApiClient::new(Ok(json!({})), Ok("hi".to_string()), false).named("gpt-5.2");
+
+    let mut conversation = Conversation::from_user("prompt");
+    conversation.set_call_role(CallRole::SelfConsistent);
+    converse_internal(&[&client], &session, &mut conversation, &logger, "1-test")
+        .await
+        .unwrap();
+
+    let expected = json!({ "temperature": 0.5, "reasoningEffort": "high" });
+    let body = client.last_request_body.lock().unwrap().clone().unwrap();
+    assert_eq!(body["generation"], expected);
+
+    let log_dir = std::fs::read_dir(dir.path())
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    let query_json: Value =
+        serde_json::from_str(&std::fs::read_to_string(log_dir.join("1-test-query.json")).unwrap())
+            .unwrap();
+    assert_eq!(
+        query_json["generation"],
+        json!({ "role": "self_consistent", "temperature": 0.5, "reasoningEffort": "high" })
+    );
+
+    // Without a role only the settings for every call apply.
+    let mut conversation = Conversation::from_user("prompt");
+    converse_internal(&[&client], &session, &mut conversation, &logger, "2-test")
+        .await
+        .unwrap();
+    let body = client.last_request_body.lock().unwrap().clone().unwrap();
+    assert_eq!(body["generation"], json!({ "temperature": 0.5 }));
+}
@@ -0,0 +1,214 @@
+use super::api::RetryPolicy;
+use super::budget::Pricing;
+use crate::app_error::AppError;
+use std::fmt;
+use std::time::Duration;
+
+/// The API families that models belong to. Each has its own client, request
+/// format and API key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Provider {
+    Gemini,
+    Gpt,
+}
+
+impl Provider {
+    pub fn name(self) -> &'static str {
+        match self {
+            Provider::Gemini => "gemini",
+            Provider::Gpt => "gpt",
+        }
+    }
+
+    pub(crate) fn for_model_name(model_name: &str) -> Option<Self> {
+        lookup(model_name).map(|spec| spec.provider)
+    }
+}
+
+/// Optional API features. Requests leave out what a model does not support.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Features {
+    /// Responses can be streamed as server-sent events.
+    pub streaming: bool,
+    /// Replies can be constrained to a JSON schema.
+    pub json_mode: bool,
+    /// The model can call tools.
+    pub tools: bool,
+    /// The model accepts a reasoning effort (Gemini's thinking level).
+    pub reasoning_effort: bool,
+    /// The model takes a thinking budget in tokens instead of a level, so the
+    /// reasoning effort is sent as a budget (Gemini 2.5).
+    pub thinking_budget: bool,
+}
+
+/// Everything code-commit needs to know about one model.
+#[derive(Debug)]
+pub(crate) struct ModelSpec {
+    /// The `--model` name, which is also the model id sent to the API.
+    pub(crate) id: &'static str,
+    /// Other names accepted by `--model`.
+    pub(crate) aliases: &'static [&'static str],
+    pub(crate) provider: Provider,
+    /// The key file's name inside `agent-config/`.
+    pub(crate) key_file: &'static str,
+    /// The environment variable that can hold the key instead.
+    pub(crate) key_env: &'static str,
+    /// Input plus output tokens the model accepts in one call.
+    pub(crate) context_window: u64,
+    pub(crate) max_output_tokens: u64,
+    pub(crate) pricing: Pricing,
+    pub(crate) retry: RetryPolicy,
+    pub(crate) features: Features,
+}
+
+impl ModelSpec {
+    /// The largest prompt that still leaves room for the maximum output.
+    pub(crate) fn input_token_limit(&self) -> u64 {
+        self.context_window - self.max_output_tokens
+    }
+}
+
+const GEMINI_RETRY: RetryPolicy = RetryPolicy {
+    max_attempts: 4,
+    base_delay: Duration::from_millis(400),
+    max_delay: Duration::from_secs(8),
+};
+
+const GPT_RETRY: RetryPolicy = RetryPolicy {
+    max_attempts: 6,
+    base_delay: Duration::from_millis(300),
+    max_delay: Duration::from_secs(10),
+};
+
+const ALL_FEATURES: Features = Features {
+    streaming: true,
+    json_mode: true,
+    tools: true,
+    reasoning_effort: true,
+    thinking_budget: false,
+};
+
+/// Every supported model. The first entry is the default. Adding a model
+/// means adding an entry here.
+pub(crate) const MODELS: &[ModelSpec] = &[
+    ModelSpec {
+        id: "gemini-3-pro-preview",
+        aliases: &[],
+        provider: Provider::Gemini,
+        key_file: "gemini-key.txt",
+        key_env: "GEMINI_API_KEY",
+        context_window: 1_048_576,
+        max_output_tokens: 65_536,
+        pricing: Pricing {
+            input_per_million: 2.0,
+            cached_input_per_million: 0.20,
+            output_per_million: 12.0,
+        },
+        retry: GEMINI_RETRY,
+        features: ALL_FEATURES,
+    },
+    ModelSpec {
+        id: "gemini-2.5-pro",
+        aliases: &[],
+        provider: Provider::Gemini,
+        key_file: "gemini-key.txt",
+        key_env: "GEMINI_API_KEY",
+        context_window: 1_048_576,
+        max_output_tokens: 65_536,
+        pricing: Pricing {
+            input_per_million: 1.25,
+            cached_input_per_million: 0.125,
+            output_per_million: 10.0,
+        },
+        retry: GEMINI_RETRY,
+        // Gemini 2.5 takes a thinking budget rather than a thinking level.
+        features: Features {
+            reasoning_effort: false,
+            thinking_budget: true,
+            ..ALL_FEATURES
+        },
+    },
+    ModelSpec {
+        id: "gpt-5.2",
+        aliases: &["gpt-5"],
+        provider: Provider::Gpt,
+        key_file: "openai-key.txt",
+        key_env: "OPENAI_API_KEY",
+        context_window: 400_000,
+        max_output_tokens: 128_000,
+        pricing: Pricing {
+            input_per_million: 1.75,
+            cached_input_per_million: 0.175,
+            output_per_million: 14.0,
+        },
+        retry: GPT_RETRY,
+        features: ALL_FEATURES,
+    },
+];
+
+/// The registry entry for an API model id or `--model` alias.
+pub(crate) fn lookup(name: &str) -> Option<&'static ModelSpec> {
+    MODELS
+        .iter()
+        .find(|spec| spec.id == name || spec.aliases.contains(&name))
+}
+
+/// Whether the model called `model_name` has a feature. Models that are not
+/// in the registry, such as test doubles, are assumed to support everything.
+pub(crate) fn supports(model_name: &str, feature: impl Fn(&Features) -> bool) -> bool {
+    lookup(model_name).is_none_or(|spec| feature(&spec.features))
+}
+
+/// A model from the registry.
+#[derive(Clone, Copy)]
+pub struct Model(&'static ModelSpec);
+
+impl Model {
+    pub(crate) fn from_str(s: &str) -> Result<Self, AppError> {
+        lookup(s)
+            .map(Model)
+            .ok_or_else(|| AppError::Config(format!("Unsupported model: {s}")))
+    }
+
+    /// Every model in the registry, the default first.
+    pub fn all() -> impl Iterator<Item = Model> {
+        MODELS.iter().map(Model)
+    }
+
+    /// The API model id, which `--model` also accepts.
+    pub fn name(self) -> &'static str {
+        self.0.id
+    }
+
+    pub fn provider(self) -> Provider {
+        self.0.provider
+    }
+
+    pub fn features(self) -> Features {
+        self.0.features
+    }
+
+    pub(crate) fn spec(self) -> &'static ModelSpec {
+        self.0
+    }
+}
+
+impl Default for Model {
+    fn default() -> Self {
+        Model(&MODELS[0])
+    }
+}
+
+impl PartialEq for Model {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.id == other.0.id
+    }
+}
+
+impl Eq for Model {}
+
+impl fmt::Debug for Model {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Model({})", self.0.id)
+    }
+}
@@ -0,0 +1,79 @@
+use super::api::RetryPolicy;
+use super::budget::pricing_for_model;
+use super::create_client;
+use super::models::{lookup, supports, Model, Provider, MODELS};
+use super::registry::ClientRegistry;
+use super::tokens::input_token_limit_for_model_name;
+use std::collections::HashSet;
+
+#[test]
+fn test_ids_and_aliases_are_unique() {
+    let mut names = HashSet::new();
+    for spec in MODELS {
+        assert!(names.insert(spec.id), "duplicate name {}", spec.id);
+        for alias in spec.aliases {
+            assert!(names.insert(alias), "duplicate name {alias}");
+        }
+    }
+}
+
+#[test]
+fn test_every_model_leaves_room_for_input() {
+    for spec in MODELS {
+        assert!(spec.max_output_tokens < spec.context_window, "{}", spec.id);
+        assert!(spec.retry.max_attempts >= 1, "{}", spec.id);
+    }
+}
+
+#[test]
+fn test_from_str_accepts_ids_and_aliases() {
+    assert_eq!(
+        Model::from_str("gpt-5").unwrap(),
+        Model::from_str("gpt-5.2").unwrap()
+    );
+    assert_eq!(Model::from_str("gpt-5").unwrap().name(), "gpt-5.2");
+    assert_eq!(Model::default().name(), MODELS[0].id);
+    assert!(Model::from_str("gpt-4").is_err());
+}
+
+#[test]
+fn test_clients_use_the_registry_entry() {
+    let registry = ClientRegistry::default();
+    for model in Model::all() {
+        let client = create_client(model, "k".to_string(), &registry);
+        assert_eq!(client.get_model_name(), model.name());
+        assert_eq!(
+            Provider::for_model_name(client.get_model_name()),
+            Some(model.provider())
+        );
+        assert_eq!(
+            RetryPolicy::for_model(&client).max_attempts,
+            model.spec().retry.max_attempts
+        );
+    }
+}
+
+#[test]
+fn test_limits_and_pricing_come_from_the_registry() {
+    let spec = lookup("gpt-5.2").unwrap();
+    assert_eq!(input_token_limit_for_model_name("gpt-5.2"), 272_000);
+    assert_eq!(pricing_for_model("gpt-5.2"), spec.pricing);
+
+    // Unknown models get the highest rate of each kind.
+    let unknown = pricing_for_model("some-future-model");
+    assert_eq!(unknown.input_per_million, 2.0);
+    assert_eq!(unknown.output_per_million, 14.0);
+    assert_eq!(
+        input_token_limit_for_model_name("some-future-model"),
+        128_000
+    );
+}
+
+#[test]
+fn test_supports_reads_features() {
+    assert!(supports("gemini-3-pro-preview", |f| f.reasoning_effort));
+    assert!(!supports("gemini-2.5-pro", |f| f.reasoning_effort));
+    assert!(supports("gemini-2.5-pro", |f| f.json_mode));
+    // Test doubles are not registered and get every feature.
+    assert!(supports("mock-model", |f| f.reasoning_effort));
+}
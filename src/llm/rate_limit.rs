@@ -0,0 +1,211 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Providers count request limits per minute; used to estimate the refill
+/// rate when the headers give no reset time.
+const LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// The longest a server can make us wait. Longer `Retry-After` and reset
+/// times are cut down to this.
+pub(crate) const MAX_WAIT: Duration = Duration::from_secs(60 * 60);
+
+/// The `x-ratelimit-*` headers of a response. Every field is optional, since
+/// providers send different subsets and Gemini usually sends none.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct RateLimitInfo {
+    pub(crate) limit_requests: Option<f64>,
+    pub(crate) remaining_requests: Option<f64>,
+    pub(crate) reset_requests: Option<Duration>,
+    pub(crate) remaining_tokens: Option<f64>,
+    pub(crate) reset_tokens: Option<Duration>,
+}
+
+impl RateLimitInfo {
+    pub(crate) fn from_headers(headers: &HeaderMap) -> Self {
+        let number = |name: &str| header_str(headers, name)?.trim().parse::<f64>().ok();
+        let reset = |name: &str| parse_reset_duration(header_str(headers, name)?);
+        Self {
+            limit_requests: number("x-ratelimit-limit-requests"),
+            remaining_requests: number("x-ratelimit-remaining-requests"),
+            reset_requests: reset("x-ratelimit-reset-requests"),
+            remaining_tokens: number("x-ratelimit-remaining-tokens"),
+            reset_tokens: reset("x-ratelimit-reset-tokens"),
+        }
+    }
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name)?.to_str().ok()
+}
+
+/// How long the server asked us to wait. OpenAI's `retry-after-ms` is the
+/// most precise and wins; `Retry-After` may be a number of seconds or an
+/// HTTP date, which is measured from `now` and treated as zero once past.
+pub(crate) fn parse_retry_after(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    if let Some(wait) = header_str(headers, "retry-after-ms")
+        .and_then(|s| s.trim().parse::<f64>().ok())
+        .and_then(|ms| seconds(ms / 1000.0))
+    {
+        return Some(wait);
+    }
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<f64>() {
+        return seconds(secs);
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let at = SystemTime::from(date);
+    Some(at.duration_since(now).unwrap_or_default().min(MAX_WAIT))
+}
+
+/// Parses reset times such as `1s`, `6m0s`, `20ms` or `1h2m3.5s`, as sent by
+/// OpenAI, or a bare number of seconds.
+pub(crate) fn parse_reset_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    if let Ok(secs) = text.parse::<f64>() {
+        return seconds(secs);
+    }
+    let mut total = 0.0;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let number_end = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .filter(|&end| end > 0)?;
+        let number: f64 = rest[..number_end].parse().ok()?;
+        rest = &rest[number_end..];
+        let unit_end = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let seconds = match &rest[..unit_end] {
+            "h" => 3600.0,
+            "m" => 60.0,
+            "s" => 1.0,
+            "ms" => 0.001,
+            _ => return None,
+        };
+        total += number * seconds;
+        rest = &rest[unit_end..];
+    }
+    seconds(total)
+}
+
+/// `secs` as a `Duration` of at most `MAX_WAIT`, or `None` when it is
+/// negative or not a number.
+fn seconds(secs: f64) -> Option<Duration> {
+    if secs.is_nan() || secs < 0.0 {
+        return None;
+    }
+    Duration::try_from_secs_f64(secs.min(MAX_WAIT.as_secs_f64())).ok()
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// `None` until the provider reports a limit; until then requests are
+    /// not held back.
+    capacity: Option<f64>,
+    tokens: f64,
+    refill_per_sec: f64,
+    updated: Instant,
+    paused_until: Option<Instant>,
+}
+
+/// A token bucket of requests for one provider, shared by every call to it.
+/// It starts unlimited and learns the limit from the rate-limit headers; a
+/// `Retry-After` or an exhausted limit pauses every call until the reset.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    bucket: Mutex<Bucket>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self {
+            bucket: Mutex::new(Bucket {
+                capacity: None,
+                tokens: 0.0,
+                refill_per_sec: 0.0,
+                updated: Instant::now(),
+                paused_until: None,
+            }),
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Waits until a request may be sent and takes a token for it.
+    pub(crate) async fn acquire(&self) {
+        while let Some(wait) = self.try_acquire(Instant::now()) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Takes a token and returns `None`, or returns how long to wait before
+    /// trying again.
+    pub(crate) fn try_acquire(&self, now: Instant) -> Option<Duration> {
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(until) = bucket.paused_until {
+            if until > now {
+                return Some(until - now);
+            }
+            bucket.paused_until = None;
+        }
+        let capacity = bucket.capacity?;
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * bucket.refill_per_sec).min(capacity);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return None;
+        }
+        if bucket.refill_per_sec <= 0.0 {
+            return Some(Duration::from_secs(1));
+        }
+        Some(seconds((1.0 - bucket.tokens) / bucket.refill_per_sec).unwrap_or(LIMIT_WINDOW))
+    }
+
+    /// Updates the bucket from a response's rate-limit headers. The server's
+    /// count of remaining requests replaces ours, and an exhausted request or
+    /// token limit pauses every call until its reset time.
+    pub(crate) fn observe(&self, info: &RateLimitInfo, now: Instant) {
+        {
+            let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(limit) = info.limit_requests.filter(|l| *l > 0.0) {
+                let remaining = info.remaining_requests.unwrap_or(limit).clamp(0.0, limit);
+                let window = info
+                    .reset_requests
+                    .filter(|r| !r.is_zero() && remaining < limit)
+                    .map(|r| (limit - remaining, r))
+                    .unwrap_or((limit, LIMIT_WINDOW));
+                bucket.capacity = Some(limit);
+                bucket.tokens = remaining;
+                bucket.refill_per_sec = window.0 / window.1.as_secs_f64();
+                bucket.updated = now;
+            }
+        }
+        if info.remaining_requests == Some(0.0) {
+            if let Some(reset) = info.reset_requests {
+                self.pause(reset, now);
+            }
+        }
+        if info.remaining_tokens == Some(0.0) {
+            if let Some(reset) = info.reset_tokens {
+                self.pause(reset, now);
+            }
+        }
+    }
+
+    /// Holds back every request until `now + wait`, or longer if a pause is
+    /// already in place. `wait` is cut down to `MAX_WAIT`.
+    pub(crate) fn pause(&self, wait: Duration, now: Instant) {
+        let Some(until) = now.checked_add(wait.min(MAX_WAIT)) else {
+            return;
+        };
+        let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        if bucket.paused_until.is_none_or(|p| p < until) {
+            bucket.paused_until = Some(until);
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use super::models::Provider;
+use super::rate_limit::{
+    parse_reset_duration, parse_retry_after, RateLimitInfo, RateLimiter, MAX_WAIT,
+};
+use super::registry::ClientRegistry;
+use reqwest::header::{HeaderMap, HeaderValue};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for (name, value) in pairs {
+        map.insert(*name, HeaderValue::from_str(value).unwrap());
+    }
+    map
+}
+
+#[test]
+fn test_retry_after_seconds_and_milliseconds() {
+    let now = SystemTime::now();
+    assert_eq!(
+        parse_retry_after(&headers(&[("retry-after", "7")]), now),
+        Some(Duration::from_secs(7))
+    );
+    assert_eq!(
+        parse_retry_after(&headers(&[("retry-after", "1.5")]), now),
+        Some(Duration::from_millis(1500))
+    );
+    // The millisecond header is more precise and wins.
+    assert_eq!(
+        parse_retry_after(
+            &headers(&[("retry-after", "2"), ("retry-after-ms", "1250")]),
+            now
+        ),
+        Some(Duration::from_millis(1250))
+    );
+    assert_eq!(parse_retry_after(&headers(&[]), now), None);
+    assert_eq!(
+        parse_retry_after(&headers(&[("retry-after", "soon")]), now),
+        None
+    );
+    assert_eq!(
+        parse_retry_after(&headers(&[("retry-after", "-3")]), now),
+        None
+    );
+}
+
+#[test]
+fn test_retry_after_http_date() {
+    // Wed, 21 Oct 2015 07:28:00 GMT
+    let date = UNIX_EPOCH + Duration::from_secs(1_445_412_480);
+    let header = headers(&[("retry-after", "Wed, 21 Oct 2015 07:28:00 GMT")]);
+    assert_eq!(
+        parse_retry_after(&header, date - Duration::from_secs(30)),
+        Some(Duration::from_secs(30))
+    );
+    // A date in the past means the request can be retried straight away.
+    assert_eq!(
+        parse_retry_after(&header, date + Duration::from_secs(30)),
+        Some(Duration::ZERO)
+    );
+}
+
+#[test]
+fn test_reset_durations() {
+    assert_eq!(parse_reset_duration("1s"), Some(Duration::from_secs(1)));
+    assert_eq!(parse_reset_duration("6m0s"), Some(Duration::from_secs(360)));
+    assert_eq!(
+        parse_reset_duration("20ms"),
+        Some(Duration::from_millis(20))
+    );
+    assert_eq!(
+        parse_reset_duration("0.5h2m3.5s"),
+        Some(Duration::from_millis(1_923_500))
+    );
+    assert_eq!(
+        parse_reset_duration("0.5"),
+        Some(Duration::from_millis(500))
+    );
+    assert_eq!(parse_reset_duration(""), None);
+    assert_eq!(parse_reset_duration("3d"), None);
+    assert_eq!(parse_reset_duration("s"), None);
+}
+
+#[test]
+fn test_oversized_waits_are_capped() {
+    let mut headers = HeaderMap::new();
+    headers.insert("retry-after", HeaderValue::from_static("1e300"));
+    assert_eq!(
+        parse_retry_after(&headers, SystemTime::now()),
+        Some(MAX_WAIT)
+    );
+    headers.insert(
+        "retry-after",
+        HeaderValue::from_static("Fri, 31 Dec 9999 23:59:59 GMT"),
+    );
+    assert_eq!(
+        parse_retry_after(&headers, SystemTime::now()),
+        Some(MAX_WAIT)
+    );
+    headers.insert("retry-after-ms", HeaderValue::from_static("-5"));
+    headers.insert("retry-after", HeaderValue::from_static("2"));
+    assert_eq!(
+        parse_retry_after(&headers, SystemTime::now()),
+        Some(Duration::from_secs(2))
+    );
+
+    assert_eq!(parse_reset_duration("1e300"), Some(MAX_WAIT));
+    assert_eq!(
+        parse_reset_duration("99999999999999999999h"),
+        Some(MAX_WAIT)
+    );
+    assert_eq!(parse_reset_duration("-1"), None);
+}
+
+#[test]
+fn test_limiter_survives_a_huge_retry_after() {
+    let limiter = RateLimiter::default();
+    let now = Instant::now();
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "retry-after",
+        HeaderValue::from_static("10000000000000000000"),
+    );
+    let wait = parse_retry_after(&headers, SystemTime::now()).unwrap();
+    limiter.pause(wait, now);
+    limiter.pause(Duration::MAX, now);
+    limiter.observe(
+        &RateLimitInfo {
+            remaining_tokens: Some(0.0),
+            reset_tokens: parse_reset_duration("10000000000000000000"),
+            ..RateLimitInfo::default()
+        },
+        now,
+    );
+    assert_eq!(limiter.try_acquire(now), Some(MAX_WAIT));
+    assert_eq!(limiter.try_acquire(now + MAX_WAIT), None);
+}
+
+#[test]
+fn test_rate_limit_info_from_headers() {
+    let info = RateLimitInfo::from_headers(&headers(&[
+        ("x-ratelimit-limit-requests", "500"),
+        ("x-ratelimit-remaining-requests", "499"),
+        ("x-ratelimit-reset-requests", "120ms"),
+        ("x-ratelimit-remaining-tokens", "0"),
+        ("x-ratelimit-reset-tokens", "6m0s"),
+    ]));
+    assert_eq!(
+        info,
+        RateLimitInfo {
+            limit_requests: Some(500.0),
+            remaining_requests: Some(499.0),
+            reset_requests: Some(Duration::from_millis(120)),
+            remaining_tokens: Some(0.0),
+            reset_tokens: Some(Duration::from_secs(360)),
+        }
+    );
+    assert_eq!(
+        RateLimitInfo::from_headers(&HeaderMap::new()),
+        RateLimitInfo::default()
+    );
+}
+
+#[test]
+fn test_limiter_is_unlimited_until_it_learns_a_limit() {
+    let limiter = RateLimiter::default();
+    let now = Instant::now();
+    for _ in 0..100 {
+        assert_eq!(limiter.try_acquire(now), None);
+    }
+}
+
+#[test]
+fn test_limiter_spends_and_refills_tokens() {
+    let limiter = RateLimiter::default();
+    let now = Instant::now();
+    limiter.observe(
+        &RateLimitInfo {
+            limit_requests: Some(60.0),
+            remaining_requests: Some(2.0),
+            ..RateLimitInfo::default()
+        },
+        now,
+    );
+    assert_eq!(limiter.try_acquire(now), None);
+    assert_eq!(limiter.try_acquire(now), None);
+    // 60 a minute refills one token a second.
+    assert_eq!(limiter.try_acquire(now), Some(Duration::from_secs(1)));
+    assert_eq!(limiter.try_acquire(now + Duration::from_secs(1)), None);
+}
+
+#[test]
+fn test_limiter_pauses_when_a_limit_is_exhausted() {
+    let limiter = RateLimiter::default();
+    let now = Instant::now();
+    limiter.observe(
+        &RateLimitInfo {
+            remaining_tokens: Some(0.0),
+            reset_tokens: Some(Duration::from_secs(5)),
+            ..RateLimitInfo::default()
+        },
+        now,
+    );
+    assert_eq!(limiter.try_acquire(now), Some(Duration::from_secs(5)));
+    assert_eq!(limiter.try_acquire(now + Duration::from_secs(5)), None);
+
+    // A shorter pause does not cut an existing one short.
+    limiter.pause(Duration::from_secs(10), now);
+    limiter.pause(Duration::from_secs(2), now);
+    assert_eq!(
+        limiter.try_acquire(now + Duration::from_secs(4)),
+        Some(Duration::from_secs(6))
+    );
+}
+
+#[test]
+fn test_registry_shares_one_pool_per_provider() {
+    let registry = ClientRegistry::default();
+    let shared = registry.clone();
+    let gemini = registry.pool(Provider::Gemini);
+    assert!(Arc::ptr_eq(&gemini, &shared.pool(Provider::Gemini)));
+    assert!(!Arc::ptr_eq(&gemini, &registry.pool(Provider::Gpt)));
+
+    // A pause seen by one call holds back the next call to the provider.
+    let now = Instant::now();
+    gemini.limiter.pause(Duration::from_secs(3), now);
+    assert_eq!(
+        shared.pool(Provider::Gemini).limiter.try_acquire(now),
+        Some(Duration::from_secs(3))
+    );
+    assert_eq!(registry.pool(Provider::Gpt).limiter.try_acquire(now), None);
+}
@@ -0,0 +1,49 @@
+use super::models::Provider;
+use super::rate_limit::RateLimiter;
+use reqwest::Client;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// The HTTP client and rate limiter for one provider. Calls share them, so
+/// connections are reused and every call sees the provider's limits.
+#[derive(Debug)]
+pub(crate) struct ProviderPool {
+    pub(crate) http: Client,
+    pub(crate) limiter: RateLimiter,
+}
+
+impl ProviderPool {
+    pub(crate) fn new() -> Arc<Self> {
+        let http = Client::builder()
+            .connect_timeout(Duration::from_secs(15))
+            .tcp_keepalive(Some(Duration::from_secs(30)))
+            .pool_idle_timeout(Duration::from_secs(90))
+            .pool_max_idle_per_host(8)
+            .build()
+            .unwrap_or_else(|_| Client::new());
+        Arc::new(Self {
+            http,
+            limiter: RateLimiter::default(),
+        })
+    }
+}
+
+/// One `ProviderPool` per provider for a whole run. Clones share the same
+/// pools, so every call in a workflow, concurrent or not, goes through the
+/// same connections and limiter.
+#[derive(Debug, Clone, Default)]
+pub struct ClientRegistry {
+    pools: Arc<Mutex<HashMap<Provider, Arc<ProviderPool>>>>,
+}
+
+impl ClientRegistry {
+    pub(crate) fn pool(&self, provider: Provider) -> Arc<ProviderPool> {
+        self.pools
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(provider)
+            .or_insert_with(ProviderPool::new)
+            .clone()
+    }
+}
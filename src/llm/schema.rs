@@ -0,0 +1,60 @@
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+/// A JSON schema that the reply must follow. Providers that support
+/// schema-constrained output are asked to enforce it, so callers can parse the
+/// reply as JSON instead of scraping sentinels out of free text. Callers must
+/// still accept a reply that is not valid JSON, since a provider or a replayed
+/// recording may ignore the schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseSchema {
+    /// A short identifier, which OpenAI requires alongside the schema.
+    pub name: &'static str,
+    pub schema: Value,
+}
+
+impl ResponseSchema {
+    /// `{"files": ["path", ...]}`, for replies that list files to load.
+    pub fn file_list() -> Self {
+        Self {
+            name: "file_list",
+            schema: json!({
+                "type": "object",
+                "properties": {
+                    "files": {
+                        "type": "array",
+                        "description": "Paths relative to the project root, one per file.",
+                        "items": { "type": "string" }
+                    }
+                },
+                "required": ["files"],
+                "additionalProperties": false
+            }),
+        }
+    }
+}
+
+/// Parses a reply as a JSON value. Surrounding whitespace and a Markdown code
+/// fence are tolerated; anything else makes the reply count as free text.
+pub fn parse_json_reply(text: &str) -> Option<Value> {
+    let trimmed = text.trim();
+    let unfenced = trimmed
+        .strip_prefix("```json")
+        .or_else(|| trimmed.strip_prefix("```"))
+        .and_then(|rest| rest.strip_suffix("```"))
+        .unwrap_or(trimmed);
+    serde_json::from_str(unfenced.trim()).ok()
+}
+
+/// The files of a `file_list` reply, or `None` when the reply does not follow
+/// the schema.
+pub fn parse_file_list(text: &str) -> Option<Vec<PathBuf>> {
+    let value = parse_json_reply(text)?;
+    value
+        .get("files")?
+        .as_array()?
+        .iter()
+        .map(|f| f.as_str().map(|s| PathBuf::from(s.trim())))
+        .filter(|f| f.as_ref().is_none_or(|p| !p.as_os_str().is_empty()))
+        .collect()
+}
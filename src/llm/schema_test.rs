@@ -0,0 +1,35 @@
+use super::schema::{parse_file_list, parse_json_reply, ResponseSchema};
+use serde_json::json;
+use std::path::PathBuf;
+
+#[test]
+fn test_parse_json_reply_accepts_fences_and_rejects_text() {
+    assert_eq!(parse_json_reply(" {\"a\": 1}\n"), Some(json!({ "a": 1 })));
+    assert_eq!(
+        parse_json_reply("```json\n{\"a\": 1}\n```"),
+        Some(json!({ "a": 1 }))
+    );
+    assert_eq!(parse_json_reply("```\n[1]\n```"), Some(json!([1])));
+    assert_eq!(parse_json_reply("%%%files\nsrc/a.rs\n%%%end"), None);
+    assert_eq!(parse_json_reply("Here you go: {\"a\": 1}"), None);
+}
+
+#[test]
+fn test_parse_file_list() {
+    assert_eq!(
+        parse_file_list(r#"{"files": ["src/a.rs", "  ", " src/b.rs "]}"#),
+        Some(vec![PathBuf::from("src/a.rs"), PathBuf::from("src/b.rs")])
+    );
+    assert_eq!(parse_file_list(r#"{"files": []}"#), Some(Vec::new()));
+    assert_eq!(parse_file_list(r#"{"files": [1]}"#), None);
+    assert_eq!(parse_file_list(r#"{"paths": ["src/a.rs"]}"#), None);
+    assert_eq!(parse_file_list("%%%files\nsrc/a.rs\n%%%end"), None);
+}
+
+#[test]
+fn test_file_list_schema_is_strict() {
+    // OpenAI strict mode needs every property required and no extras.
+    let schema = ResponseSchema::file_list().schema;
+    assert_eq!(schema["required"], json!(["files"]));
+    assert_eq!(schema["additionalProperties"], false);
+}
@@ -0,0 +1,52 @@
+use super::budget::Budget;
+use super::cassette::Cassette;
+use super::generation::GenerationConfig;
+use super::models::Model;
+use super::registry::ClientRegistry;
+use std::time::Duration;
+
+/// State shared by every LLM call made during one workflow run.
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    pub budget: Budget,
+    /// Stream responses as they are generated instead of waiting for the
+    /// complete response.
+    pub stream: bool,
+    /// Records every call into, or answers every call from, a cassette.
+    pub cassette: Option<Cassette>,
+    /// Models to try in order when a call to the requested model fails with a
+    /// network error after exhausting its retries.
+    pub fallbacks: Vec<Fallback>,
+    /// The longest a single call may take, retries included, before it is
+    /// abandoned and cancelled on the server.
+    pub call_timeout: Option<Duration>,
+    /// How many tool calls one `converse_with_tools` query may run.
+    pub max_tool_calls: u32,
+    /// Temperature, output limit and reasoning effort for each call role and
+    /// provider.
+    pub generation: GenerationConfig,
+    /// HTTP connections and rate limiters shared by every call of the run.
+    pub clients: ClientRegistry,
+}
+
+/// A fallback model and the API key for its provider.
+#[derive(Debug, Clone)]
+pub struct Fallback {
+    pub model: Model,
+    pub api_key: String,
+}
+
+impl Session {
+    pub fn new(budget: Budget, stream: bool) -> Self {
+        Self {
+            budget,
+            stream,
+            cassette: None,
+            fallbacks: Vec::new(),
+            call_timeout: None,
+            max_tool_calls: 0,
+            generation: GenerationConfig::default(),
+            clients: ClientRegistry::default(),
+        }
+    }
+}
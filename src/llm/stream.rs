@@ -0,0 +1,207 @@
+use super::tokens::estimate_tokens;
+use crate::logger::Logger;
+use std::io::{IsTerminal, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(250);
+
+/// One server-sent event. `event` is `None` when the server only sent data.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SseEvent {
+    pub(crate) event: Option<String>,
+    pub(crate) data: String,
+}
+
+/// Incremental parser for a `text/event-stream` body. Bytes can be fed in
+/// arbitrary chunks; complete events are returned as soon as their
+/// terminating blank line arrives. Bytes are only decoded once an event is
+/// complete, so a character split across chunks arrives intact.
+#[derive(Default)]
+pub(crate) struct SseParser {
+    buffer: Vec<u8>,
+}
+
+impl SseParser {
+    pub(crate) fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+        if self.buffer.contains(&b'\r') {
+            self.buffer = normalize_line_endings(&self.buffer);
+        }
+
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.windows(2).position(|pair| pair == b"\n\n") {
+            let block: Vec<u8> = self.buffer.drain(..end + 2).collect();
+            if let Some(event) = parse_event_block(&String::from_utf8_lossy(&block)) {
+                events.push(event);
+            }
+        }
+        events
+    }
+}
+
+/// `bytes` with every `\r\n` turned into `\n`. A trailing `\r` is kept in
+/// case its `\n` is in the next chunk.
+fn normalize_line_endings(bytes: &[u8]) -> Vec<u8> {
+    let mut normalized = Vec::with_capacity(bytes.len());
+    for (i, &byte) in bytes.iter().enumerate() {
+        if byte == b'\r' && bytes.get(i + 1) == Some(&b'\n') {
+            continue;
+        }
+        normalized.push(byte);
+    }
+    normalized
+}
+
+fn parse_event_block(block: &str) -> Option<SseEvent> {
+    let mut event = None;
+    let mut data_lines = Vec::new();
+    for line in block.lines() {
+        if line.starts_with(':') {
+            continue;
+        }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => event = Some(value.to_string()),
+            "data" => data_lines.push(value),
+            _ => {}
+        }
+    }
+    if event.is_none() && data_lines.is_empty() {
+        return None;
+    }
+    Some(SseEvent {
+        event,
+        data: data_lines.join("\n"),
+    })
+}
+
+struct ProgressState {
+    text_len: usize,
+    estimated_tokens: u64,
+    last_draw: Option<Instant>,
+    drew_line: bool,
+}
+
+/// Live progress for one LLM call. Streamed text is appended to
+/// `[prefix]-response.partial.txt` as it arrives so that a crash mid-generation
+/// still leaves the partial response on disk, and a status line with elapsed
+/// time and tokens received is redrawn on stdout.
+pub(crate) struct Progress<'a> {
+    model_name: &'a str,
+    logger: Option<&'a Logger>,
+    partial_file: String,
+    start: Instant,
+    live: bool,
+    state: Mutex<ProgressState>,
+}
+
+impl<'a> Progress<'a> {
+    pub(crate) fn new(model_name: &'a str, logger: &'a Logger, log_prefix: &str) -> Self {
+        Self {
+            model_name,
+            logger: Some(logger),
+            partial_file: format!("{log_prefix}-response.partial.txt"),
+            start: Instant::now(),
+            live: std::io::stdout().is_terminal(),
+            state: Mutex::new(ProgressState {
+                text_len: 0,
+                estimated_tokens: 0,
+                last_draw: None,
+                drew_line: false,
+            }),
+        }
+    }
+
+    /// A progress tracker that neither logs nor prints.
+    #[cfg(test)]
+    pub(crate) fn disabled() -> Self {
+        Self {
+            model_name: "",
+            logger: None,
+            partial_file: String::new(),
+            start: Instant::now(),
+            live: false,
+            state: Mutex::new(ProgressState {
+                text_len: 0,
+                estimated_tokens: 0,
+                last_draw: None,
+                drew_line: false,
+            }),
+        }
+    }
+
+    /// Records a fragment of streamed text.
+    pub(crate) fn on_delta(&self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        if let Some(logger) = self.logger {
+            // Losing a partial log line must never fail the call itself.
+            let _ = logger.append_text(&self.partial_file, text);
+        }
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.text_len += text.len();
+        state.estimated_tokens += estimate_tokens(text);
+        self.redraw(&mut state, false);
+    }
+
+    /// Called while waiting on a call that produces no incremental text, such
+    /// as a Gemini interaction that is still being polled.
+    pub(crate) fn on_wait(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        self.redraw(&mut state, false);
+    }
+
+    /// Notes in the partial log that the call is being retried, since the
+    /// text streamed so far will be generated again.
+    pub(crate) fn on_retry(&self, attempt: u32) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.text_len > 0 {
+            if let Some(logger) = self.logger {
+                let _ = logger.append_text(
+                    &self.partial_file,
+                    &format!("\n[stream interrupted, starting attempt {attempt}]\n"),
+                );
+            }
+            state.text_len = 0;
+            state.estimated_tokens = 0;
+        }
+        self.redraw(&mut state, true);
+    }
+
+    /// Ends the status line so that later output starts on a fresh line.
+    pub(crate) fn finish(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if state.drew_line {
+            self.redraw(&mut state, true);
+            println!();
+            state.drew_line = false;
+        }
+    }
+
+    fn redraw(&self, state: &mut ProgressState, force: bool) {
+        if !self.live {
+            return;
+        }
+        let now = Instant::now();
+        if !force && state.last_draw.is_some_and(|t| now - t < REDRAW_INTERVAL) {
+            return;
+        }
+        state.last_draw = Some(now);
+        state.drew_line = true;
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let line = if state.text_len == 0 {
+            format!("Waiting for {}... {elapsed:.1}s", self.model_name)
+        } else {
+            format!(
+                "Receiving from {}... ~{} tokens, {elapsed:.1}s",
+                self.model_name, state.estimated_tokens
+            )
+        };
+        let mut stdout = std::io::stdout();
+        let _ = write!(stdout, "\r\x1b[2K{line}");
+        let _ = stdout.flush();
+    }
+}
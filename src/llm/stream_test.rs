@@ -0,0 +1,54 @@
+use super::stream::{SseEvent, SseParser};
+
+#[test]
+fn test_sse_parser_handles_split_chunks() {
+    let mut parser = SseParser::default();
+    assert!(parser.feed(b"event: content.del").is_empty());
+    assert!(parser.feed(b"ta\ndata: {\"a\":").is_empty());
+    let events = parser.feed(b"1}\n\ndata: second\n\n");
+    assert_eq!(
+        events,
+        vec![
+            SseEvent {
+                event: Some("content.delta".to_string()),
+                data: "{\"a\":1}".to_string(),
+            },
+            SseEvent {
+                event: None,
+                data: "second".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_sse_parser_handles_crlf_comments_and_multiline_data() {
+    let mut parser = SseParser::default();
+    let events = parser.feed(b": keep-alive\r\n\r\ndata: line one\r\ndata: line two\r\n\r\n");
+    assert_eq!(
+        events,
+        vec![SseEvent {
+            event: None,
+            data: "line one\nline two".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn test_sse_parser_keeps_a_character_split_across_chunks() {
+    let mut parser = SseParser::default();
+    let bytes = "data: caf\u{e9} \u{1f600}\r\n\r\n".as_bytes();
+    // Split inside the two-byte `é` and again inside the CRLF pair.
+    let (first, rest) = bytes.split_at(10);
+    let (second, third) = rest.split_at(rest.len() - 3);
+    assert!(parser.feed(first).is_empty());
+    assert!(parser.feed(second).is_empty());
+    let events = parser.feed(third);
+    assert_eq!(
+        events,
+        vec![SseEvent {
+            event: None,
+            data: "caf\u{e9} \u{1f600}".to_string(),
+        }]
+    );
+}
@@ -0,0 +1,42 @@
+use super::models::{lookup, Model};
+
+/// Estimates how many tokens a provider will count for `text`.
+///
+/// Providers only report exact counts after a call, so this is a heuristic
+/// tuned for source code: runs of ASCII letters and digits cost roughly one
+/// token per four characters, every punctuation character costs one token,
+/// whitespace is mostly free, and non-ASCII characters cost one token each.
+pub fn estimate_tokens(text: &str) -> u64 {
+    let mut tokens: u64 = 0;
+    let mut word_len: u64 = 0;
+    let mut newlines: u64 = 0;
+
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            word_len += 1;
+            continue;
+        }
+        tokens += word_len.div_ceil(4);
+        word_len = 0;
+        if c == '\n' {
+            newlines += 1;
+        } else if c.is_ascii_punctuation() || !c.is_ascii() {
+            tokens += 1;
+        }
+    }
+    tokens += word_len.div_ceil(4);
+
+    // Tokenizers usually merge consecutive newlines and indentation, so only
+    // charge for every other line break.
+    tokens + newlines.div_ceil(2)
+}
+
+/// The largest prompt, in tokens, that can be sent to `model_name` while
+/// leaving room for the model's maximum output.
+pub(crate) fn input_token_limit_for_model_name(model_name: &str) -> u64 {
+    lookup(model_name).map_or(128_000, |spec| spec.input_token_limit())
+}
+
+pub fn input_token_limit(model: Model) -> u64 {
+    model.spec().input_token_limit()
+}
@@ -0,0 +1,50 @@
+use super::models::Model;
+use super::tokens::{estimate_tokens, input_token_limit, input_token_limit_for_model_name};
+
+#[test]
+fn test_estimate_tokens_basic() {
+    assert_eq!(estimate_tokens(""), 0);
+    assert_eq!(estimate_tokens("abcd"), 1);
+    assert_eq!(estimate_tokens("abcde"), 2);
+    assert_eq!(estimate_tokens("   "), 0);
+}
+
+#[test]
+fn test_estimate_tokens_counts_punctuation_and_newlines() {
+    // "fn" (1) + "main" (1) + "(" + ")" + "{" + "}" (4) + one newline (1)
+    assert_eq!(estimate_tokens("fn main() {}\n"), 7);
+    // Non-ASCII characters each count as a token.
+    assert_eq!(estimate_tokens("héllo"), 3);
+}
+
+#[test]
+fn test_estimate_tokens_is_in_a_sensible_range_for_code() {
+    let code = include_str!("api.rs");
+    let estimate = estimate_tokens(code);
+    let bytes = code.len() as u64;
+    assert!(
+        estimate > bytes / 8,
+        "estimate {estimate} for {bytes} bytes"
+    );
+    assert!(
+        estimate < bytes / 2,
+        "estimate {estimate} for {bytes} bytes"
+    );
+}
+
+#[test]
+fn test_input_token_limits() {
+    assert_eq!(
+        input_token_limit(Model::from_str("gpt-5.2").unwrap()),
+        272_000
+    );
+    assert_eq!(
+        input_token_limit(Model::from_str("gemini-3-pro-preview").unwrap()),
+        983_040
+    );
+    assert_eq!(
+        input_token_limit(Model::from_str("gemini-2.5-pro").unwrap()),
+        983_040
+    );
+    assert_eq!(input_token_limit_for_model_name("unknown"), 128_000);
+}
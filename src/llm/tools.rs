@@ -0,0 +1,32 @@
+use serde_json::Value;
+
+/// A function the model may call instead of answering. `parameters` is a JSON
+/// schema for the call's arguments object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolSpec {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub parameters: Value,
+}
+
+/// A call requested by the model. `id` is the provider's id for the call and
+/// is echoed back with the result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub arguments: Value,
+}
+
+/// Runs the tools offered to the model. Calls run locally and must not fail:
+/// problems such as a missing file are reported to the model as the result
+/// text so that it can correct itself.
+pub trait ToolHandler: Send + Sync {
+    fn specs(&self) -> Vec<ToolSpec>;
+    fn call(&self, call: &ToolCall) -> String;
+}
+
+/// A one-line description of a call for stdout and the response log.
+pub(crate) fn describe_call(call: &ToolCall) -> String {
+    format!("{}({})", call.name, call.arguments)
+}
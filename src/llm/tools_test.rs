@@ -0,0 +1,215 @@
+use super::api::LlmApi;
+use super::budget::Usage;
+use super::conversation::{Conversation, Role};
+use super::stream::Progress;
+use super::tools::{ToolCall, ToolHandler, ToolSpec};
+use super::{converse_with_tools_internal, Session};
+use crate::app_error::AppError;
+use crate::logger::Logger;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use tempfile::tempdir;
+
+/// Answers with the scripted responses in order. A response is either
+/// `{"text": ...}` or `{"calls": [[id, name, path], ...]}`.
+struct ScriptedApi {
+    responses: Mutex<VecDeque<Value>>,
+    requests: Mutex<Vec<Value>>,
+}
+
+impl ScriptedApi {
+    fn new(responses: Vec<Value>) -> Self {
+        Self {
+            responses: Mutex::new(responses.into()),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl LlmApi for ScriptedApi {
+    fn get_model_name(&self) -> &'static str {
+        "scripted-model"
+    }
+    fn get_url(&self) -> &str {
+        "http://scripted.url"
+    }
+    fn build_request_body(&self, conversation: &Conversation) -> Value {
+        let tools: Vec<&str> = conversation.tools().iter().map(|t| t.name).collect();
+        json!({ "prompt": conversation.transcript(), "tools": tools })
+    }
+    fn extract_conversation_id(&self, _response: &Value) -> Option<String> {
+        None
+    }
+    fn enable_streaming(&self, request_body: &mut Value) {
+        request_body["stream"] = json!(true);
+    }
+    fn query_with_retries<'a>(
+        &'a self,
+        request_body: &'a Value,
+        _idempotency_key: Option<&'a str>,
+        _progress: &'a Progress<'a>,
+    ) -> Pin<Box<dyn Future<Output = Result<Value, AppError>> + Send + 'a>> {
+        self.requests.lock().unwrap().push(request_body.clone());
+        let response = self
+            .responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .expect("no scripted response left");
+        Box::pin(async move { Ok(response) })
+    }
+    fn extract_text_from_response(&self, response: &Value) -> Result<String, AppError> {
+        response["text"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| AppError::ResponseParsing("no text".to_string()))
+    }
+    fn extract_usage(&self, _response: &Value) -> Option<Usage> {
+        None
+    }
+    fn extract_tool_calls(&self, response: &Value) -> Vec<ToolCall> {
+        response["calls"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|call| ToolCall {
+                id: call[0].as_str().unwrap().to_string(),
+                name: call[1].as_str().unwrap().to_string(),
+                arguments: json!({ "path": call[2] }),
+            })
+            .collect()
+    }
+    fn supports_idempotency(&self) -> bool {
+        false
+    }
+}
+
+/// Answers `read_file` with a fixed text and remembers the paths it was asked
+/// for.
+#[derive(Default)]
+struct FakeFiles {
+    read: Mutex<Vec<String>>,
+}
+
+impl ToolHandler for FakeFiles {
+    fn specs(&self) -> Vec<ToolSpec> {
+        vec![ToolSpec {
+            name: "read_file",
+            description: "Reads a file.",
+            parameters: json!({ "type": "object" }),
+        }]
+    }
+    fn call(&self, call: &ToolCall) -> String {
+        let path = call.arguments["path"].as_str().unwrap_or("").to_string();
+        self.read.lock().unwrap().push(path.clone());
+        format!("contents of {path}")
+    }
+}
+
+fn session(max_tool_calls: u32) -> Session {
+    Session {
+        stream: true,
+        max_tool_calls,
+        ..Session::default()
+    }
+}
+
+#[tokio::test]
+async fn test_tool_calls_are_run_until_the_model_answers() {
+    let dir = tempdir().unwrap();
+    let logger = Logger::new_with_root(dir.path(), "test-run").unwrap();
+    let api = ScriptedApi::new(vec![
+        json!({ "calls": [["c1", "read_file", "src/main.rs"], ["c2", "read_file", "src/lib.rs"]] }),
+        json!({ "text": "done" }),
+    ]);
+    let files = FakeFiles::default();
+
+    let mut conversation = Conversation::from_user("fix it");
+    let text = converse_with_tools_internal(
+        &[&api],
+        &session(10),
+        &mut conversation,
+        &files,
+        &logger,
+        "1-initial-query",
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(text, "done");
+    assert_eq!(
+        *files.read.lock().unwrap(),
+        vec!["src/main.rs", "src/lib.rs"]
+    );
+    let roles: Vec<Role> = conversation.messages().iter().map(|m| m.role).collect();
+    assert_eq!(
+        roles,
+        vec![
+            Role::User,
+            Role::Assistant,
+            Role::Tool,
+            Role::Tool,
+            Role::Assistant
+        ]
+    );
+    assert_eq!(conversation.messages()[3].content, "contents of src/lib.rs");
+    assert_eq!(
+        conversation.messages()[3].answers.as_ref().unwrap().id,
+        "c2"
+    );
+
+    let requests = api.requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0]["tools"], json!(["read_file"]));
+    // Calls that offer tools are never streamed.
+    assert!(requests[0].get("stream").is_none());
+    assert!(requests[1]["prompt"]
+        .as_str()
+        .unwrap()
+        .contains("contents of src/main.rs"));
+
+    let run_dir = std::fs::read_dir(dir.path())
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    assert_eq!(
+        std::fs::read_to_string(run_dir.join("1-initial-query-response.txt")).unwrap(),
+        "[tool call] read_file({\"path\":\"src/main.rs\"})\n[tool call] read_file({\"path\":\"src/lib.rs\"})"
+    );
+    assert_eq!(
+        std::fs::read_to_string(run_dir.join("1-initial-query-tools-1-response.txt")).unwrap(),
+        "done"
+    );
+}
+
+#[tokio::test]
+async fn test_tool_calls_stop_at_the_limit() {
+    let dir = tempdir().unwrap();
+    let logger = Logger::new_with_root(dir.path(), "test-run").unwrap();
+    let api = ScriptedApi::new(vec![
+        json!({ "calls": [["c1", "read_file", "a.rs"], ["c2", "read_file", "b.rs"]] }),
+        json!({ "calls": [["c3", "read_file", "c.rs"]] }),
+    ]);
+    let files = FakeFiles::default();
+
+    let mut conversation = Conversation::from_user("fix it");
+    let result = converse_with_tools_internal(
+        &[&api],
+        &session(1),
+        &mut conversation,
+        &files,
+        &logger,
+        "1-initial-query",
+    )
+    .await;
+
+    assert!(matches!(result, Err(AppError::ToolCallLimit(_))));
+    // Only the first call ran; the second was answered with a note.
+    assert_eq!(*files.read.lock().unwrap(), vec!["a.rs"]);
+    assert!(conversation.messages()[3].content.starts_with("Not run"));
+}
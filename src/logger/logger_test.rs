@@ -1,4 +1,4 @@
-use super::Logger;
+use super::{recent_queries, Logger, PastQuery};
 use serde_json::json;
 use std::fs;
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -66,3 +66,70 @@ fn test_logger_happy_path() {
     let expected_json = serde_json::to_string_pretty(&json_content).unwrap();
     assert_eq!(stored_json, expected_json);
 }
+
+#[test]
+fn test_append_text_creates_and_extends_file() {
+    let temp_dir = tempfile::tempdir().expect("Failed to create temp dir");
+    let logger = Logger::new_with_root(temp_dir.path(), &generate_test_suffix())
+        .expect("Failed to create Logger");
+
+    logger.append_text("partial.txt", "first ").unwrap();
+    logger.append_text("partial.txt", "second").unwrap();
+
+    let dir = fs::read_dir(temp_dir.path())
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap();
+    let stored = fs::read_to_string(dir.path().join("partial.txt")).unwrap();
+    assert_eq!(stored, "first second");
+}
+
+#[test]
+fn test_recent_queries_newest_first() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path();
+    for (run_id, query) in [
+        (
+            "2026-10-15-08-00-00-committing-code",
+            Some("Add the parser"),
+        ),
+        (
+            "2026-10-16-08-00-00-committing-code",
+            Some("Fix the parser\n"),
+        ),
+        ("2026-10-16-09-00-00-rollup", None),
+        ("2026-10-17-08-00-00-consistency", Some("  ")),
+        (
+            "2026-10-17-09-00-00-committing-code",
+            Some("Fix the parser"),
+        ),
+        (
+            "2026-10-18-08-00-00-committing-code",
+            Some("Document the flags"),
+        ),
+    ] {
+        fs::create_dir_all(root.join(run_id)).unwrap();
+        if let Some(query) = query {
+            fs::write(root.join(run_id).join("query.md"), query).unwrap();
+        }
+    }
+    fs::write(root.join("stray.txt"), "not a run").unwrap();
+
+    let queries = recent_queries(root, 2);
+    assert_eq!(
+        queries,
+        vec![
+            PastQuery {
+                run_id: "2026-10-18-08-00-00-committing-code".to_string(),
+                query: "Document the flags".to_string(),
+            },
+            PastQuery {
+                run_id: "2026-10-17-09-00-00-committing-code".to_string(),
+                query: "Fix the parser".to_string(),
+            },
+        ]
+    );
+    assert_eq!(recent_queries(root, 10).len(), 3);
+    assert!(recent_queries(&root.join("missing"), 5).is_empty());
+}
@@ -1,20 +1,63 @@
 use crate::app_error::AppError;
 use chrono::Utc;
-use serde_json::Value;
+use serde_json::{json, Value};
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
 #[cfg(test)]
 mod logger_test;
 
+/// The file in a run's log folder that holds its supervisor query.
+pub const QUERY_FILE: &str = "query.md";
+
+/// The file in a run's log folder that records its workflow, model and
+/// outcome.
+pub const RUN_FILE: &str = "run.json";
+
+/// The outcome of a run that has not finished, or that was killed before it
+/// could record one.
+pub const UNFINISHED: &str = "unfinished";
+
 pub struct Logger {
     log_dir: PathBuf,
 }
 
+/// The query of an earlier run, read back from its log folder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PastQuery {
+    /// The name of the run's log folder, such as
+    /// `2025-09-23-19-51-35-committing-code`.
+    pub run_id: String,
+    pub query: String,
+}
+
+/// What the log folder of an earlier run says about it. Folders written
+/// before runs were recorded only have a workflow and perhaps a query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunSummary {
+    pub run_id: String,
+    pub workflow: String,
+    pub model: Option<String>,
+    /// `succeeded`, `failed` or `unfinished`.
+    pub outcome: Option<String>,
+    pub error: Option<String>,
+    /// The summed cost of every LLM call the run logged, in US dollars.
+    pub cost: Option<f64>,
+    pub query: Option<String>,
+    /// The command-line flags the run was started with that `rerun`
+    /// restores, such as `--context auto`.
+    pub options: Vec<String>,
+}
+
 impl Logger {
     pub fn new(suffix: &str) -> Result<Self, AppError> {
-        let root = PathBuf::from("agent-config").join("logs");
-        Self::new_with_root(&root, suffix)
+        Self::new_with_root(&Self::default_root(), suffix)
+    }
+
+    /// `agent-config/logs`, where every run's log folder is created.
+    pub fn default_root() -> PathBuf {
+        PathBuf::from("agent-config").join("logs")
     }
 
     pub fn new_with_root(root: &Path, suffix: &str) -> Result<Self, AppError> {
@@ -45,4 +88,167 @@ impl Logger {
         fs::write(path, pretty_json)?;
         Ok(())
     }
+
+    /// Writes `run.json`. A run records itself as `unfinished` when it starts
+    /// and records its real outcome, with the error if it failed, when it
+    /// ends. Workflows that do not call an LLM have no model and no options.
+    pub fn log_run(
+        &self,
+        workflow: &str,
+        model: Option<&str>,
+        options: &[String],
+        outcome: &str,
+        error: Option<&str>,
+    ) -> Result<(), AppError> {
+        let mut record = json!({
+            "workflow": workflow,
+            "outcome": outcome,
+        });
+        if let Some(model) = model {
+            record["model"] = json!(model);
+        }
+        if !options.is_empty() {
+            record["options"] = json!(options);
+        }
+        if let Some(error) = error {
+            record["error"] = json!(error);
+        }
+        self.log_json(RUN_FILE, &record)
+    }
+
+    /// Appends to a text log, creating it if needed. Used for output that
+    /// arrives piece by piece and must survive a crash part way through.
+    pub fn append_text(&self, file_name: &str, content: &str) -> Result<(), AppError> {
+        let path = self.path_for(file_name);
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        file.write_all(content.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// The queries of the most recent runs under `root`, newest first, up to
+/// `count`. Runs without a query are skipped, and a query repeated by a later
+/// run is only listed once.
+pub fn recent_queries(root: &Path, count: usize) -> Vec<PastQuery> {
+    let mut queries: Vec<PastQuery> = Vec::new();
+    for run_id in run_ids(root) {
+        if queries.len() == count {
+            break;
+        }
+        let Some(query) = read_query(&root.join(&run_id)) else {
+            continue;
+        };
+        if queries.iter().any(|past| past.query == query) {
+            continue;
+        }
+        queries.push(PastQuery { run_id, query });
+    }
+    queries
+}
+
+/// Every run under `root`, newest first.
+pub fn runs(root: &Path) -> Vec<RunSummary> {
+    run_ids(root)
+        .into_iter()
+        .map(|run_id| read_run(root, run_id))
+        .collect()
+}
+
+/// The run under `root` whose folder is named `run_id`, or, failing that,
+/// the only one whose name starts with it, such as its timestamp.
+pub fn find_run(root: &Path, run_id: &str) -> Result<RunSummary, AppError> {
+    let ids = run_ids(root);
+    if ids.iter().any(|id| id == run_id) {
+        return Ok(read_run(root, run_id.to_string()));
+    }
+    let matches: Vec<String> = ids
+        .into_iter()
+        .filter(|id| !run_id.is_empty() && id.starts_with(run_id))
+        .collect();
+    match matches.as_slice() {
+        [id] => Ok(read_run(root, id.clone())),
+        [] => Err(AppError::Config(format!(
+            "No run '{run_id}' in {}. List the runs with 'code-commit history'.",
+            root.display()
+        ))),
+        _ => Err(AppError::Config(format!(
+            "'{run_id}' matches {} runs: {}",
+            matches.len(),
+            matches.join(", ")
+        ))),
+    }
+}
+
+/// The names of the run folders under `root`, newest first. Folder names
+/// start with the timestamp, so they sort by age.
+fn run_ids(root: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut run_ids: Vec<String> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect();
+    run_ids.sort_by(|a, b| b.cmp(a));
+    run_ids
+}
+
+fn read_query(dir: &Path) -> Option<String> {
+    let query = fs::read_to_string(dir.join(QUERY_FILE)).ok()?;
+    let query = query.trim().to_string();
+    (!query.is_empty()).then_some(query)
+}
+
+fn read_run(root: &Path, run_id: String) -> RunSummary {
+    let dir = root.join(&run_id);
+    let record: Value = fs::read_to_string(dir.join(RUN_FILE))
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+        .unwrap_or(Value::Null);
+    let field = |name: &str| record.get(name).and_then(Value::as_str).map(str::to_string);
+
+    // `yyyy-mm-dd-hh-mm-ss-` is 20 characters.
+    let suffix = run_id.get(20..).unwrap_or_default().to_string();
+    let cost = fs::read_dir(&dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .ends_with("-response.json")
+        })
+        .filter_map(|entry| {
+            let text = fs::read_to_string(entry.path()).ok()?;
+            let response: Value = serde_json::from_str(&text).ok()?;
+            response.get("callUsage")?.get("cost")?.as_f64()
+        })
+        .fold(None, |total: Option<f64>, cost| {
+            Some(total.unwrap_or(0.0) + cost)
+        });
+
+    RunSummary {
+        workflow: field("workflow").unwrap_or(suffix),
+        model: field("model"),
+        outcome: field("outcome"),
+        error: field("error"),
+        cost,
+        query: read_query(&dir),
+        options: record
+            .get("options")
+            .and_then(Value::as_array)
+            .map(|options| {
+                options
+                    .iter()
+                    .filter_map(|option| option.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        run_id,
+    }
 }
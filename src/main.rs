@@ -5,20 +5,35 @@ mod committing_code;
 mod config;
 mod consistency;
 mod context_builder;
+mod hash;
+mod history;
 mod init;
 mod llm;
 mod logger;
+mod models;
+mod path_filter;
 mod rollup;
+mod specifications;
 mod system_prompts;
 
+#[cfg(test)]
+mod history_test;
+#[cfg(test)]
+mod models_test;
+
 use app_error::AppError;
 use logger::Logger;
 use std::process::ExitCode;
 
 #[tokio::main]
 async fn main() -> ExitCode {
+    llm::interrupt::install_ctrl_c_handler();
     match run().await {
         Ok(_) => ExitCode::SUCCESS,
+        Err(e @ AppError::Interrupted(_)) => {
+            eprintln!("Error: {e}");
+            ExitCode::from(llm::interrupt::INTERRUPTED_EXIT_CODE)
+        }
         Err(e) => {
             eprintln!("Error: {e}");
             ExitCode::FAILURE
@@ -27,35 +42,55 @@ async fn main() -> ExitCode {
 }
 
 async fn run() -> Result<(), AppError> {
-    let args = cli::parse_cli_args()?;
-
-    let suffix = match &args.workflow {
-        cli::Workflow::CommitCode => "committing-code",
-        cli::Workflow::ConsistencyCheck => "consistency",
-        cli::Workflow::Rollup => "rollup",
-        cli::Workflow::Auto => "auto-workflow",
-        cli::Workflow::Init(_) => "init",
+    let mut args = cli::parse_cli_args()?;
+
+    // Listing the runs or the models is not a run of its own.
+    match args.workflow {
+        cli::Workflow::History => return history::run_history_command(),
+        cli::Workflow::Models => return models::run_models_command(),
+        _ => {}
+    }
+    if let cli::Workflow::Rerun { .. } = args.workflow {
+        args = history::rerun_args(&Logger::default_root(), args)?;
+    }
+
+    let (suffix, calls_llm) = match &args.workflow {
+        cli::Workflow::CommitCode => ("committing-code", true),
+        cli::Workflow::ConsistencyCheck => ("consistency", true),
+        cli::Workflow::Rollup => ("rollup", false),
+        cli::Workflow::Auto => ("auto-workflow", true),
+        cli::Workflow::Init(_) => ("init", false),
+        cli::Workflow::History | cli::Workflow::Models | cli::Workflow::Rerun { .. } => {
+            unreachable!("history and models return early and a rerun is resolved above")
+        }
     };
 
     let logger = Logger::new(suffix)?;
+    let model = calls_llm.then(|| args.model.name());
+    let options = if calls_llm {
+        args.run_options()
+    } else {
+        Vec::new()
+    };
+    logger.log_run(suffix, model, &options, logger::UNFINISHED, None)?;
 
-    match args.workflow {
-        cli::Workflow::CommitCode => {
-            committing_code::run(&logger, args).await?;
-        }
-        cli::Workflow::ConsistencyCheck => {
-            consistency::run(&logger, args).await?;
-        }
-        cli::Workflow::Rollup => {
-            rollup::run(&logger, args).await?;
-        }
-        cli::Workflow::Auto => {
-            auto_workflow::run(&logger, args).await?;
-        }
-        cli::Workflow::Init(ref name) => {
-            init::run_init_command(name)?;
+    let result = match args.workflow {
+        cli::Workflow::CommitCode => committing_code::run(&logger, args).await,
+        cli::Workflow::ConsistencyCheck => consistency::run(&logger, args).await,
+        cli::Workflow::Rollup => rollup::run(&logger, args).await,
+        cli::Workflow::Auto => auto_workflow::run(&logger, args).await,
+        cli::Workflow::Init(ref name) => init::run_init_command(name),
+        cli::Workflow::History | cli::Workflow::Models | cli::Workflow::Rerun { .. } => {
+            unreachable!()
         }
-    }
+    };
 
-    Ok(())
+    let recorded = match &result {
+        Ok(()) => logger.log_run(suffix, model, &options, "succeeded", None),
+        Err(e) => logger.log_run(suffix, model, &options, "failed", Some(&e.to_string())),
+    };
+    if let Err(e) = recorded {
+        eprintln!("Warning: the run's outcome could not be logged: {e}");
+    }
+    result
 }
@@ -0,0 +1,72 @@
+use crate::app_error::AppError;
+use crate::config::{self, is_readable_by_others, EnvLookup, KeySource};
+use crate::llm::models::Model;
+use std::fmt::Write;
+use std::path::Path;
+
+pub fn run_models_command() -> Result<(), AppError> {
+    let env = |name: &str| std::env::var(name).ok();
+    print!("{}", list_in_dir(Path::new("."), &env)?);
+    Ok(())
+}
+
+/// Describes every registered model and where its API key would come from.
+/// Keys are not read and key commands are not run.
+pub(crate) fn list_in_dir(base_dir: &Path, env: EnvLookup) -> Result<String, AppError> {
+    let mut out = String::new();
+    for (i, model) in Model::all().enumerate() {
+        let spec = model.spec();
+        let default = if i == 0 { " (default)" } else { "" };
+        let _ = writeln!(out, "{}{default}", spec.id);
+        if !spec.aliases.is_empty() {
+            let _ = writeln!(out, "  aliases:        {}", spec.aliases.join(", "));
+        }
+        let _ = writeln!(out, "  provider:       {}", spec.provider.name());
+        let _ = writeln!(
+            out,
+            "  context window: {} tokens, up to {} output",
+            spec.context_window, spec.max_output_tokens
+        );
+        let _ = writeln!(
+            out,
+            "  price:          ${:.2} input, ${:.3} cached input, ${:.2} output per million tokens",
+            spec.pricing.input_per_million,
+            spec.pricing.cached_input_per_million,
+            spec.pricing.output_per_million
+        );
+        let _ = writeln!(out, "  features:       {}", feature_names(model).join(", "));
+        let key_state = match config::key_source(base_dir, model, env)? {
+            Some(source) => {
+                let exposed = matches!(&source, KeySource::File(path)
+                    if is_readable_by_others(&base_dir.join(path)));
+                let warning = if exposed {
+                    ", readable by other users"
+                } else {
+                    ""
+                };
+                format!("present ({}{warning})", source.describe())
+            }
+            None => format!(
+                "missing (set {}, key_command.{} or agent-config/{})",
+                spec.key_env,
+                spec.provider.name(),
+                spec.key_file
+            ),
+        };
+        let _ = writeln!(out, "  key:            {key_state}");
+    }
+    Ok(out)
+}
+
+fn feature_names(model: Model) -> Vec<&'static str> {
+    let features = model.features();
+    [
+        (features.streaming, "streaming"),
+        (features.json_mode, "json-mode"),
+        (features.tools, "tools"),
+        (features.reasoning_effort, "reasoning-effort"),
+    ]
+    .into_iter()
+    .filter_map(|(supported, name)| supported.then_some(name))
+    .collect()
+}
@@ -0,0 +1,54 @@
+use crate::models::list_in_dir;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn test_list_shows_every_model_and_its_key_state() {
+    let dir = tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("agent-config")).unwrap();
+    fs::write(dir.path().join("agent-config/gemini-key.txt"), "secret\n").unwrap();
+
+    let listing = list_in_dir(dir.path(), &|_| None).unwrap();
+
+    assert!(listing.starts_with("gemini-3-pro-preview (default)\n"));
+    assert!(listing.contains("\ngemini-2.5-pro\n"));
+    assert!(listing.contains("\ngpt-5.2\n  aliases:        gpt-5\n"));
+    assert!(listing.contains("key:            present (agent-config/gemini-key.txt"));
+    assert!(listing.contains(
+        "key:            missing (set OPENAI_API_KEY, key_command.gpt or agent-config/openai-key.txt)"
+    ));
+    assert!(!listing.contains("secret"));
+}
+
+#[test]
+fn test_list_names_the_key_source_without_reading_it() {
+    let dir = tempdir().unwrap();
+    fs::create_dir_all(dir.path().join("agent-config")).unwrap();
+    fs::write(
+        dir.path().join("agent-config/config.toml"),
+        "[key_command]\ngemini = \"exit 1\"\n",
+    )
+    .unwrap();
+    let env = |name: &str| (name == "OPENAI_API_KEY").then(|| "sk-secret".to_string());
+
+    let listing = list_in_dir(dir.path(), &env).unwrap();
+
+    assert!(listing.contains("present (key_command.gemini in agent-config/config.toml)"));
+    assert!(listing.contains("present (the OPENAI_API_KEY environment variable)"));
+    assert!(!listing.contains("sk-secret"));
+}
+
+#[test]
+fn test_list_shows_features_and_limits() {
+    let dir = tempdir().unwrap();
+    let listing = list_in_dir(dir.path(), &|_| None).unwrap();
+
+    let gemini_2_5 = listing
+        .split("\ngemini-2.5-pro\n")
+        .nth(1)
+        .and_then(|rest| rest.split("\ngpt-5.2").next())
+        .unwrap();
+    assert!(gemini_2_5.contains("features:       streaming, json-mode, tools\n"));
+    assert!(gemini_2_5.contains("context window: 1048576 tokens, up to 65536 output"));
+    assert!(gemini_2_5.contains("$1.25 input, $0.125 cached input, $10.00 output"));
+}
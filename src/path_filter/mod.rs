@@ -3,6 +3,9 @@ use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use path_clean::PathClean;
 use std::path::{Component, Path};
 
+#[cfg(test)]
+mod path_filter_test;
+
 pub(crate) struct PathFilter {
     gitignore_matcher: Gitignore,
 }
@@ -23,6 +26,12 @@ impl PathFilter {
     }
 
     pub(crate) fn validate(&self, path: &Path) -> Result<(), AppError> {
+        self.validate_entry(path, false)
+    }
+
+    /// Like `validate`, but also applies directory-only `.gitignore` rules
+    /// such as `target/` when `is_dir` is set.
+    pub(crate) fn validate_entry(&self, path: &Path, is_dir: bool) -> Result<(), AppError> {
         let cleaned = path.clean();
 
         // Detect traversal/absolute on the original input
@@ -56,7 +65,7 @@ impl PathFilter {
 
         match self
             .gitignore_matcher
-            .matched_path_or_any_parents(&cleaned, false)
+            .matched_path_or_any_parents(&cleaned, is_dir)
         {
             ignore::Match::Ignore(_) => Err(AppError::FileUpdate(format!(
                 "File '{}' matches a rule in .gitignore and cannot be loaded into context.",
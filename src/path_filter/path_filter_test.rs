@@ -1,4 +1,4 @@
-use super::path_filter::PathFilter;
+use super::PathFilter;
 use crate::app_error::AppError;
 use std::fs;
 use std::path::PathBuf;
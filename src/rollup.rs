@@ -32,7 +32,14 @@ fn to_relative_string(base_dir: &Path, path: &Path) -> String {
 }
 
 fn validate_path(path: &Path, base_dir: &Path, ignore: &Gitignore) -> Result<bool, AppError> {
-    for c in path.components() {
+    // Checked relative to `base_dir`, which may itself be absolute.
+    let rel = if let Ok(s) = path.clean().strip_prefix(base_dir.clean()) {
+        s.to_path_buf()
+    } else {
+        path.clean()
+    };
+
+    for c in rel.components() {
         match c {
             Component::RootDir => {
                 return Err(AppError::FileUpdate(
@@ -48,12 +55,6 @@ fn validate_path(path: &Path, base_dir: &Path, ignore: &Gitignore) -> Result<boo
         }
     }
 
-    let rel = if let Ok(s) = path.clean().strip_prefix(base_dir) {
-        s.to_path_buf()
-    } else {
-        path.clean()
-    };
-
     if rel.components().any(|c| c.as_os_str() == ".git") {
         return Ok(false);
     }
@@ -73,10 +74,11 @@ fn validate_path(path: &Path, base_dir: &Path, ignore: &Gitignore) -> Result<boo
     Ok(true)
 }
 
-pub(crate) fn build_rollup_for_base_dir(
+/// Every file the rollup covers, sorted by path relative to `base_dir`.
+pub(crate) fn rollup_files(
     base_dir: &Path,
     include_cargo_lock: bool,
-) -> Result<String, AppError> {
+) -> Result<Vec<PathBuf>, AppError> {
     let matcher = build_gitignore_matcher(base_dir)?;
     let mut files: Vec<PathBuf> = Vec::new();
 
@@ -108,9 +110,15 @@ pub(crate) fn build_rollup_for_base_dir(
         let rb = to_relative_string(base_dir, b);
         ra.cmp(&rb)
     });
+    Ok(files)
+}
 
+pub(crate) fn build_rollup_for_base_dir(
+    base_dir: &Path,
+    include_cargo_lock: bool,
+) -> Result<String, AppError> {
     let mut out = String::new();
-    for file in files {
+    for file in rollup_files(base_dir, include_cargo_lock)? {
         let rel = to_relative_string(base_dir, &file);
         match fs::read_to_string(&file) {
             Ok(content) => {
@@ -0,0 +1,28 @@
+use crate::app_error::AppError;
+use ignore::WalkBuilder;
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+mod specifications_test;
+
+/// The file that marks a directory as a module.
+pub(crate) const USER_SPECIFICATION: &str = "UserSpecification.md";
+
+/// Every `UserSpecification.md` under `root`, skipping gitignored paths.
+pub(crate) fn find_all_user_specifications(root: &Path) -> Result<Vec<PathBuf>, AppError> {
+    let mut specs = Vec::new();
+    let walker = WalkBuilder::new(root)
+        .hidden(false)
+        .git_ignore(true)
+        .build();
+
+    for result in walker {
+        let entry = result.map_err(|e| AppError::Config(format!("Failed to walk dir: {e}")))?;
+        if entry.file_type().map(|ft| ft.is_file()).unwrap_or(false)
+            && entry.file_name() == USER_SPECIFICATION
+        {
+            specs.push(entry.path().to_path_buf());
+        }
+    }
+    Ok(specs)
+}
@@ -0,0 +1,33 @@
+use super::*;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn finds_specifications_and_skips_gitignored_ones() {
+    let dir = tempdir().unwrap();
+    let root = dir.path();
+    fs::create_dir(root.join(".git")).unwrap();
+    fs::write(root.join(".gitignore"), "ignored/\n").unwrap();
+    fs::write(root.join(USER_SPECIFICATION), "root").unwrap();
+    for module in ["src/a", "src/a/b", "ignored"] {
+        fs::create_dir_all(root.join(module)).unwrap();
+        fs::write(root.join(module).join(USER_SPECIFICATION), module).unwrap();
+    }
+    fs::write(root.join("src/a/notes.md"), "not a spec").unwrap();
+
+    let mut specs: Vec<PathBuf> = find_all_user_specifications(root)
+        .unwrap()
+        .into_iter()
+        .map(|p| p.strip_prefix(root).unwrap().to_path_buf())
+        .collect();
+    specs.sort();
+
+    assert_eq!(
+        specs,
+        vec![
+            PathBuf::from("UserSpecification.md"),
+            PathBuf::from("src/a/UserSpecification.md"),
+            PathBuf::from("src/a/b/UserSpecification.md"),
+        ]
+    );
+}
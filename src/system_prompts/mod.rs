@@ -2,8 +2,11 @@ pub const CODE_MODIFICATION_INSTRUCTIONS: &str = include_str!("code_modification
 pub const CONTEXT_BUILDER_CONTEXT_QUERY: &str = include_str!("context_builder_context_query.txt");
 pub const COMMITTING_CODE_INITIAL_QUERY: &str = include_str!("committing_code_initial_query.txt");
 pub const COMMITTING_CODE_REPAIR_QUERY: &str = include_str!("committing_code_repair_query.txt");
+pub const COMMITTING_CODE_REPAIR_FOLLOW_UP: &str =
+    include_str!("committing_code_repair_follow_up.txt");
 pub const COMMITTING_CODE_EXTRA_CODE_QUERY: &str =
     include_str!("committing_code_extra_code_query.txt");
+pub const COMMITTING_CODE_TOOLS: &str = include_str!("committing_code_tools.txt");
 #[allow(dead_code)]
 pub const COMMITTING_CODE_REFACTOR_QUERY: &str = include_str!("committing_code_refactor_query.txt");
 pub const CONSISTENCY_CHECK: &str = include_str!("consistency_check.txt");